@@ -1,6 +1,7 @@
 const loginLink = document.getElementById("login-link");
 const logoutLink = document.getElementById("logout-link");
 const protectImg = document.getElementById("protected-img");
+const greeting = document.getElementById("greeting");
 
 logoutLink.addEventListener("click", (e) => {
     e.preventDefault();
@@ -15,6 +16,7 @@ logoutLink.addEventListener("click", (e) => {
             loginLink.style.display = "block";
             logoutLink.style.display = "none";
             protectImg.src = "/assets/default.jpg";
+            greeting.style.display = "none";
         } else {
             alert("Failed to logout");
         }
@@ -34,6 +36,11 @@ logoutLink.addEventListener("click", (e) => {
                 } else {
                     protectImg.src = "/assets/default.jpg";
                 }
+
+                if (data.greeting) {
+                    greeting.textContent = data.greeting;
+                    greeting.style.display = "block";
+                }
             });
         } else {
             loginLink.style.display = "block";
@@ -8,7 +8,7 @@ use axum::{
     Json, Router,
 };
 use axum_extra::extract::CookieJar;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use tower_http::services::ServeDir;
 
 #[tokio::main]
@@ -56,33 +56,55 @@ async fn protected(jar: CookieJar) -> impl IntoResponse {
 
     let api_client = reqwest::Client::builder().build().unwrap();
 
-    let verify_token_body = serde_json::json!({
-        "token": &jwt_cookie.value(),
-    });
-
     let auth_hostname = env::var("AUTH_SERVICE_HOST_NAME").unwrap_or("0.0.0.0".to_owned());
-    let url = format!("http://{}:3000/verify-token", auth_hostname);
+    let url = format!("http://{}:3000/introspect", auth_hostname);
 
-    let response = match api_client.post(&url).json(&verify_token_body).send().await {
-        Ok(response) => response,
-        Err(_) => {
+    // app-service authenticates as an introspection client registered with the auth service
+    let client_id = env::var("INTROSPECTION_CLIENT_ID").unwrap_or("app-service".to_owned());
+    let client_secret = env::var("INTROSPECTION_CLIENT_SECRET").unwrap_or_default();
+
+    let response = match api_client
+        .post(&url)
+        .basic_auth(client_id, Some(client_secret))
+        .form(&[("token", jwt_cookie.value())])
+        .send()
+        .await
+    {
+        Ok(response) if response.status() == reqwest::StatusCode::OK => response,
+        _ => {
             return StatusCode::INTERNAL_SERVER_ERROR.into_response();
         }
     };
 
-    match response.status() {
-        reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::BAD_REQUEST => {
-            StatusCode::UNAUTHORIZED.into_response()
+    let introspection = match response.json::<IntrospectionResponse>().await {
+        Ok(introspection) => introspection,
+        Err(_) => {
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
         }
-        reqwest::StatusCode::OK => Json(ProtectedRouteResponse {
+    };
+
+    match introspection {
+        IntrospectionResponse {
+            active: true,
+            sub: Some(email),
+        } => Json(ProtectedRouteResponse {
             img_url: "https://i.ibb.co/YP90j68/Light-Live-Bootcamp-Certificate.png".to_owned(),
+            greeting: format!("Welcome back, {}!", email),
         })
         .into_response(),
-        _ => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        _ => StatusCode::UNAUTHORIZED.into_response(),
     }
 }
 
+// The fields of the RFC 7662 introspection response app-service uses
+#[derive(Deserialize)]
+struct IntrospectionResponse {
+    active: bool,
+    sub: Option<String>,
+}
+
 #[derive(Serialize)]
 pub struct ProtectedRouteResponse {
     pub img_url: String,
+    pub greeting: String,
 }
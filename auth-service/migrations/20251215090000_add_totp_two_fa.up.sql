@@ -0,0 +1,15 @@
+-- Add up migration script here
+ALTER TABLE users ADD COLUMN IF NOT EXISTS two_fa_method TEXT NOT NULL DEFAULT 'none';
+
+-- Users that had 2FA enabled were all using email codes
+UPDATE users SET two_fa_method = 'email' WHERE requires_2fa;
+
+ALTER TABLE users DROP COLUMN IF EXISTS requires_2fa;
+
+CREATE TABLE IF NOT EXISTS totp_secrets(
+   email TEXT NOT NULL PRIMARY KEY REFERENCES users(email) ON DELETE CASCADE,
+   -- AES-256-GCM nonce followed by the ciphertext of the secret
+   encrypted_secret BYTEA NOT NULL,
+   confirmed BOOLEAN NOT NULL DEFAULT FALSE,
+   last_used_time_step BIGINT
+);
@@ -0,0 +1,40 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS roles(
+   name TEXT NOT NULL PRIMARY KEY,
+   description TEXT NOT NULL DEFAULT ''
+);
+
+-- Permissions are written as `resource:action`
+CREATE TABLE IF NOT EXISTS permissions(
+   name TEXT NOT NULL PRIMARY KEY,
+   description TEXT NOT NULL DEFAULT ''
+);
+
+CREATE TABLE IF NOT EXISTS role_permissions(
+   role TEXT NOT NULL REFERENCES roles(name) ON DELETE CASCADE,
+   permission TEXT NOT NULL REFERENCES permissions(name) ON DELETE CASCADE,
+   PRIMARY KEY (role, permission)
+);
+
+CREATE TABLE IF NOT EXISTS user_roles(
+   email TEXT NOT NULL REFERENCES users(email) ON DELETE CASCADE,
+   role TEXT NOT NULL REFERENCES roles(name) ON DELETE CASCADE,
+   created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+   PRIMARY KEY (email, role)
+);
+
+INSERT INTO permissions (name, description) VALUES
+   ('roles:read', 'List roles and the roles assigned to users'),
+   ('roles:manage', 'Assign roles to and revoke roles from users'),
+   ('jwt-keys:rotate', 'Rotate the JWT signing key')
+ON CONFLICT DO NOTHING;
+
+INSERT INTO roles (name, description) VALUES
+   ('admin', 'Full access to the admin API')
+ON CONFLICT DO NOTHING;
+
+INSERT INTO role_permissions (role, permission) VALUES
+   ('admin', 'roles:read'),
+   ('admin', 'roles:manage'),
+   ('admin', 'jwt-keys:rotate')
+ON CONFLICT DO NOTHING;
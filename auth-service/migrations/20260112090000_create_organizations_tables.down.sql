@@ -0,0 +1,5 @@
+-- Add down migration script here
+DROP TABLE IF EXISTS organization_invitations;
+DROP TABLE IF EXISTS selected_organizations;
+DROP TABLE IF EXISTS organization_memberships;
+DROP TABLE IF EXISTS organizations;
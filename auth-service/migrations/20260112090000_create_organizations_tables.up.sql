@@ -0,0 +1,31 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS organizations(
+   id UUID NOT NULL PRIMARY KEY,
+   name TEXT NOT NULL,
+   created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE IF NOT EXISTS organization_memberships(
+   organization_id UUID NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+   email TEXT NOT NULL REFERENCES users(email) ON DELETE CASCADE,
+   role TEXT NOT NULL CHECK (role IN ('owner', 'admin', 'member')),
+   created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+   PRIMARY KEY (organization_id, email)
+);
+
+-- The organization each user works in, dropped together with the membership
+CREATE TABLE IF NOT EXISTS selected_organizations(
+   email TEXT NOT NULL PRIMARY KEY,
+   organization_id UUID NOT NULL,
+   FOREIGN KEY (organization_id, email)
+      REFERENCES organization_memberships(organization_id, email) ON DELETE CASCADE
+);
+
+-- Invitations may be sent to addresses that have no account yet, so email is not a foreign key
+CREATE TABLE IF NOT EXISTS organization_invitations(
+   token_hash TEXT NOT NULL PRIMARY KEY,
+   organization_id UUID NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+   email TEXT NOT NULL,
+   role TEXT NOT NULL CHECK (role IN ('owner', 'admin', 'member')),
+   expires_at TIMESTAMPTZ NOT NULL
+);
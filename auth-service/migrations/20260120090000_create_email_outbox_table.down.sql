@@ -0,0 +1,3 @@
+-- Add down migration script here
+DELETE FROM permissions WHERE name = 'email-outbox:read';
+DROP TABLE IF EXISTS email_outbox;
@@ -0,0 +1,30 @@
+-- Add up migration script here
+-- Emails waiting to be delivered by the outbox worker. Bodies are cleared once an email is sent,
+-- they may hold 2FA codes and one-time links.
+CREATE TABLE IF NOT EXISTS email_outbox(
+   id UUID NOT NULL PRIMARY KEY,
+   -- SHA-256 of the key the email was queued with, so the same email is never queued twice
+   idempotency_key TEXT NOT NULL UNIQUE,
+   recipient TEXT NOT NULL,
+   subject TEXT NOT NULL,
+   html_body TEXT NOT NULL,
+   text_body TEXT NOT NULL,
+   status TEXT NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'sent', 'dead')),
+   attempts INTEGER NOT NULL DEFAULT 0,
+   last_error TEXT,
+   next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+   created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+   sent_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS email_outbox_due_idx
+   ON email_outbox (next_attempt_at)
+   WHERE status = 'pending';
+
+INSERT INTO permissions (name, description) VALUES
+   ('email-outbox:read', 'List emails that could not be delivered')
+ON CONFLICT DO NOTHING;
+
+INSERT INTO role_permissions (role, permission) VALUES
+   ('admin', 'email-outbox:read')
+ON CONFLICT DO NOTHING;
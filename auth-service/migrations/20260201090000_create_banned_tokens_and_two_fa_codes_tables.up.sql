@@ -0,0 +1,35 @@
+-- Add up migration script here
+-- Postgres alternative to the Redis stores for revoked tokens and pending 2FA logins. Rows past
+-- `expires_at` are ignored straight away and deleted by a periodic purge.
+CREATE TABLE IF NOT EXISTS banned_tokens(
+   -- `jti` claim of the revoked token
+   jti TEXT NOT NULL PRIMARY KEY,
+   expires_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS banned_tokens_expires_at_idx ON banned_tokens (expires_at);
+
+CREATE TABLE IF NOT EXISTS banned_users(
+   email TEXT NOT NULL PRIMARY KEY,
+   -- tokens of the user issued at or before this unix timestamp are banned
+   issued_before BIGINT NOT NULL,
+   expires_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS banned_users_expires_at_idx ON banned_users (expires_at);
+
+CREATE TABLE IF NOT EXISTS two_fa_codes(
+   login_attempt_id TEXT NOT NULL PRIMARY KEY,
+   email TEXT NOT NULL,
+   code TEXT NOT NULL,
+   failed_attempts INTEGER NOT NULL DEFAULT 0,
+   resends INTEGER NOT NULL DEFAULT 0,
+   -- when the current code was sent, resends wait for a cooldown after it
+   sent_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+   -- orders the pending attempts of a user, so the oldest are discarded beyond the cap
+   created_at TIMESTAMPTZ NOT NULL DEFAULT clock_timestamp(),
+   expires_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS two_fa_codes_email_idx ON two_fa_codes (email, created_at);
+CREATE INDEX IF NOT EXISTS two_fa_codes_expires_at_idx ON two_fa_codes (expires_at);
@@ -0,0 +1,2 @@
+-- Add down migration script here
+UPDATE banned_users SET issued_before = issued_before / 1000;
@@ -0,0 +1,2 @@
+-- Add down migration script here
+ALTER TABLE two_fa_codes DROP COLUMN organization_id;
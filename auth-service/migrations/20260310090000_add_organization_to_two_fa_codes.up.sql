@@ -0,0 +1,3 @@
+-- Add up migration script here
+-- the organization the login asked to work in, selected once the second factor completes the login
+ALTER TABLE two_fa_codes ADD COLUMN organization_id UUID;
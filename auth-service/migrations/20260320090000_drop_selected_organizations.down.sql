@@ -0,0 +1,7 @@
+-- Add down migration script here
+CREATE TABLE IF NOT EXISTS selected_organizations(
+   email TEXT NOT NULL PRIMARY KEY,
+   organization_id UUID NOT NULL,
+   FOREIGN KEY (organization_id, email)
+      REFERENCES organization_memberships(organization_id, email) ON DELETE CASCADE
+);
@@ -0,0 +1,3 @@
+-- Add up migration script here
+-- The organization a user works in is kept with each session's refresh tokens instead
+DROP TABLE IF EXISTS selected_organizations;
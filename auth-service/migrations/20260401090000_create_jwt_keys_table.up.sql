@@ -0,0 +1,11 @@
+-- Add up migration script here
+-- Signing keys promoted by rotation, loaded by every instance of the service
+CREATE TABLE IF NOT EXISTS jwt_keys(
+   kid TEXT NOT NULL PRIMARY KEY,
+   algorithm TEXT NOT NULL,
+   -- AES-256-GCM nonce followed by the ciphertext of the PEM or HS256 secret
+   encrypted_private_key BYTEA NOT NULL,
+   rotated_at TIMESTAMPTZ NOT NULL DEFAULT clock_timestamp(),
+   -- NULL for the active key
+   retire_at TIMESTAMPTZ
+);
@@ -0,0 +1,8 @@
+-- Add down migration script here
+DROP TABLE IF EXISTS webauthn_challenges;
+DROP TABLE IF EXISTS rate_limit_buckets;
+DROP TABLE IF EXISTS login_locks;
+DROP TABLE IF EXISTS failed_logins;
+DROP TABLE IF EXISTS one_time_tokens;
+DROP TABLE IF EXISTS refresh_tokens;
+DROP TABLE IF EXISTS refresh_token_families;
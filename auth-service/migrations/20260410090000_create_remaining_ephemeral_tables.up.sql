@@ -0,0 +1,77 @@
+-- Add up migration script here
+-- Postgres alternative to the remaining Redis stores, so EPHEMERAL_STORE=postgres needs no Redis.
+-- Rows past `expires_at` are ignored straight away and deleted by a periodic purge.
+CREATE TABLE IF NOT EXISTS refresh_token_families(
+   family_id UUID NOT NULL PRIMARY KEY,
+   email TEXT NOT NULL,
+   -- the organization the session works in
+   organization_id UUID,
+   expires_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS refresh_token_families_email_idx ON refresh_token_families (email);
+CREATE INDEX IF NOT EXISTS refresh_token_families_expires_at_idx ON refresh_token_families (expires_at);
+
+CREATE TABLE IF NOT EXISTS refresh_tokens(
+   -- SHA-256 of the token
+   token_hash TEXT NOT NULL PRIMARY KEY,
+   family_id UUID NOT NULL REFERENCES refresh_token_families(family_id) ON DELETE CASCADE,
+   -- exchanged for a new token already, presenting it again revokes the family
+   used BOOLEAN NOT NULL DEFAULT FALSE,
+   expires_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS refresh_tokens_family_id_idx ON refresh_tokens (family_id);
+CREATE INDEX IF NOT EXISTS refresh_tokens_expires_at_idx ON refresh_tokens (expires_at);
+
+CREATE TABLE IF NOT EXISTS one_time_tokens(
+   -- password_reset or email_verification
+   purpose TEXT NOT NULL,
+   -- SHA-256 of the token
+   token_hash TEXT NOT NULL,
+   email TEXT NOT NULL,
+   expires_at TIMESTAMPTZ NOT NULL,
+   PRIMARY KEY (purpose, token_hash)
+);
+
+CREATE INDEX IF NOT EXISTS one_time_tokens_expires_at_idx ON one_time_tokens (expires_at);
+
+CREATE TABLE IF NOT EXISTS failed_logins(
+   -- an email or client address, see FailedLoginKey
+   key TEXT NOT NULL PRIMARY KEY,
+   failures INTEGER NOT NULL,
+   expires_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS failed_logins_expires_at_idx ON failed_logins (expires_at);
+
+CREATE TABLE IF NOT EXISTS login_locks(
+   key TEXT NOT NULL PRIMARY KEY,
+   -- logins are refused until then
+   expires_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS login_locks_expires_at_idx ON login_locks (expires_at);
+
+CREATE TABLE IF NOT EXISTS rate_limit_buckets(
+   key TEXT NOT NULL PRIMARY KEY,
+   tokens DOUBLE PRECISION NOT NULL,
+   -- unix timestamp in milliseconds the tokens were last counted at
+   updated_at BIGINT NOT NULL,
+   -- when the bucket is full again and carries no information
+   expires_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS rate_limit_buckets_expires_at_idx ON rate_limit_buckets (expires_at);
+
+CREATE TABLE IF NOT EXISTS webauthn_challenges(
+   challenge TEXT NOT NULL PRIMARY KEY,
+   -- registration or authentication
+   ceremony TEXT NOT NULL,
+   email TEXT NOT NULL,
+   -- the password login an authentication completes, if any
+   login_attempt_id TEXT,
+   expires_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS webauthn_challenges_expires_at_idx ON webauthn_challenges (expires_at);
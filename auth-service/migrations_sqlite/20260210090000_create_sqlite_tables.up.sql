@@ -0,0 +1,43 @@
+-- Add up migration script here
+-- SQLite counterpart of the users, banned token and 2FA code tables in `migrations`. Times are unix
+-- timestamps in seconds, and rows past `expires_at` are ignored straight away and deleted by a
+-- periodic purge.
+CREATE TABLE IF NOT EXISTS users(
+   email TEXT NOT NULL PRIMARY KEY,
+   password_hash TEXT NOT NULL,
+   two_fa_method TEXT NOT NULL DEFAULT 'none',
+   email_verified BOOLEAN NOT NULL DEFAULT FALSE
+);
+
+CREATE TABLE IF NOT EXISTS banned_tokens(
+   -- `jti` claim of the revoked token
+   jti TEXT NOT NULL PRIMARY KEY,
+   expires_at INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS banned_tokens_expires_at_idx ON banned_tokens (expires_at);
+
+CREATE TABLE IF NOT EXISTS banned_users(
+   email TEXT NOT NULL PRIMARY KEY,
+   -- tokens of the user issued at or before this unix timestamp are banned
+   issued_before INTEGER NOT NULL,
+   expires_at INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS banned_users_expires_at_idx ON banned_users (expires_at);
+
+CREATE TABLE IF NOT EXISTS two_fa_codes(
+   -- orders the pending attempts of a user, so the oldest are discarded beyond the cap
+   id INTEGER PRIMARY KEY AUTOINCREMENT,
+   login_attempt_id TEXT NOT NULL UNIQUE,
+   email TEXT NOT NULL,
+   code TEXT NOT NULL,
+   failed_attempts INTEGER NOT NULL DEFAULT 0,
+   resends INTEGER NOT NULL DEFAULT 0,
+   -- when the current code was sent, resends wait for a cooldown after it
+   sent_at INTEGER NOT NULL DEFAULT (unixepoch()),
+   expires_at INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS two_fa_codes_email_idx ON two_fa_codes (email, id);
+CREATE INDEX IF NOT EXISTS two_fa_codes_expires_at_idx ON two_fa_codes (expires_at);
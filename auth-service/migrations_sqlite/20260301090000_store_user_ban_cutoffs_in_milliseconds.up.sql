@@ -0,0 +1,3 @@
+-- Add up migration script here
+-- `issued_before` now holds milliseconds, tokens of the user issued strictly before it are banned
+UPDATE banned_users SET issued_before = issued_before * 1000;
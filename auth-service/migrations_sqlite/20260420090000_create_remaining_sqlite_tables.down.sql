@@ -0,0 +1,20 @@
+-- Add down migration script here
+DROP TABLE IF EXISTS webauthn_challenges;
+DROP TABLE IF EXISTS rate_limit_buckets;
+DROP TABLE IF EXISTS login_locks;
+DROP TABLE IF EXISTS failed_logins;
+DROP TABLE IF EXISTS one_time_tokens;
+DROP TABLE IF EXISTS refresh_tokens;
+DROP TABLE IF EXISTS refresh_token_families;
+DROP TABLE IF EXISTS jwt_keys;
+DROP TABLE IF EXISTS email_outbox;
+DROP TABLE IF EXISTS organization_invitations;
+DROP TABLE IF EXISTS organization_memberships;
+DROP TABLE IF EXISTS organizations;
+DROP TABLE IF EXISTS user_roles;
+DROP TABLE IF EXISTS role_permissions;
+DROP TABLE IF EXISTS permissions;
+DROP TABLE IF EXISTS roles;
+DROP TABLE IF EXISTS recovery_codes;
+DROP TABLE IF EXISTS webauthn_credentials;
+DROP TABLE IF EXISTS totp_secrets;
@@ -0,0 +1,217 @@
+-- Add up migration script here
+-- SQLite counterpart of the remaining tables in `migrations`, so a service on SQLite needs neither
+-- PostgreSQL nor Redis. The tables of a user reference `users` in this same file. Times are unix
+-- timestamps in seconds unless noted, rows past `expires_at` are ignored straight away and deleted
+-- by a periodic purge.
+CREATE TABLE IF NOT EXISTS totp_secrets(
+   email TEXT NOT NULL PRIMARY KEY REFERENCES users(email) ON DELETE CASCADE,
+   -- AES-256-GCM nonce followed by the ciphertext of the secret
+   encrypted_secret BLOB NOT NULL,
+   confirmed BOOLEAN NOT NULL DEFAULT FALSE,
+   last_used_time_step INTEGER
+);
+
+CREATE TABLE IF NOT EXISTS webauthn_credentials(
+   credential_id BLOB NOT NULL PRIMARY KEY,
+   email TEXT NOT NULL REFERENCES users(email) ON DELETE CASCADE,
+   -- COSE encoded public key
+   public_key BLOB NOT NULL,
+   sign_count INTEGER NOT NULL DEFAULT 0,
+   created_at INTEGER NOT NULL DEFAULT (unixepoch())
+);
+
+CREATE INDEX IF NOT EXISTS webauthn_credentials_email_idx ON webauthn_credentials (email);
+
+CREATE TABLE IF NOT EXISTS recovery_codes(
+   id INTEGER PRIMARY KEY AUTOINCREMENT,
+   email TEXT NOT NULL REFERENCES users(email) ON DELETE CASCADE,
+   -- Argon2 hash, the codes themselves are only shown to the user once
+   code_hash TEXT NOT NULL,
+   created_at INTEGER NOT NULL DEFAULT (unixepoch())
+);
+
+CREATE INDEX IF NOT EXISTS recovery_codes_email_idx ON recovery_codes (email);
+
+CREATE TABLE IF NOT EXISTS roles(
+   name TEXT NOT NULL PRIMARY KEY,
+   description TEXT NOT NULL DEFAULT ''
+);
+
+-- Permissions are written as `resource:action`
+CREATE TABLE IF NOT EXISTS permissions(
+   name TEXT NOT NULL PRIMARY KEY,
+   description TEXT NOT NULL DEFAULT ''
+);
+
+CREATE TABLE IF NOT EXISTS role_permissions(
+   role TEXT NOT NULL REFERENCES roles(name) ON DELETE CASCADE,
+   permission TEXT NOT NULL REFERENCES permissions(name) ON DELETE CASCADE,
+   PRIMARY KEY (role, permission)
+);
+
+CREATE TABLE IF NOT EXISTS user_roles(
+   email TEXT NOT NULL REFERENCES users(email) ON DELETE CASCADE,
+   role TEXT NOT NULL REFERENCES roles(name) ON DELETE CASCADE,
+   created_at INTEGER NOT NULL DEFAULT (unixepoch()),
+   PRIMARY KEY (email, role)
+);
+
+INSERT INTO permissions (name, description) VALUES
+   ('roles:read', 'List roles and the roles assigned to users'),
+   ('roles:manage', 'Assign roles to and revoke roles from users'),
+   ('jwt-keys:rotate', 'Rotate the JWT signing key'),
+   ('email-outbox:read', 'List emails that could not be delivered')
+ON CONFLICT DO NOTHING;
+
+INSERT INTO roles (name, description) VALUES
+   ('admin', 'Full access to the admin API')
+ON CONFLICT DO NOTHING;
+
+INSERT INTO role_permissions (role, permission) VALUES
+   ('admin', 'roles:read'),
+   ('admin', 'roles:manage'),
+   ('admin', 'jwt-keys:rotate'),
+   ('admin', 'email-outbox:read')
+ON CONFLICT DO NOTHING;
+
+CREATE TABLE IF NOT EXISTS organizations(
+   -- UUID
+   id TEXT NOT NULL PRIMARY KEY,
+   name TEXT NOT NULL,
+   created_at INTEGER NOT NULL DEFAULT (unixepoch())
+);
+
+CREATE TABLE IF NOT EXISTS organization_memberships(
+   organization_id TEXT NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+   email TEXT NOT NULL REFERENCES users(email) ON DELETE CASCADE,
+   role TEXT NOT NULL CHECK (role IN ('owner', 'admin', 'member')),
+   created_at INTEGER NOT NULL DEFAULT (unixepoch()),
+   PRIMARY KEY (organization_id, email)
+);
+
+CREATE INDEX IF NOT EXISTS organization_memberships_email_idx ON organization_memberships (email);
+
+-- Invitations may be sent to addresses that have no account yet, so email is not a foreign key
+CREATE TABLE IF NOT EXISTS organization_invitations(
+   token_hash TEXT NOT NULL PRIMARY KEY,
+   organization_id TEXT NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+   email TEXT NOT NULL,
+   role TEXT NOT NULL CHECK (role IN ('owner', 'admin', 'member')),
+   expires_at INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS organization_invitations_expires_at_idx
+   ON organization_invitations (expires_at);
+
+-- Emails waiting to be delivered by the outbox worker. Bodies are cleared once an email is sent,
+-- they may hold 2FA codes and one-time links.
+CREATE TABLE IF NOT EXISTS email_outbox(
+   -- UUID
+   id TEXT NOT NULL PRIMARY KEY,
+   -- SHA-256 of the key the email was queued with, so the same email is never queued twice
+   idempotency_key TEXT NOT NULL UNIQUE,
+   recipient TEXT NOT NULL,
+   subject TEXT NOT NULL,
+   html_body TEXT NOT NULL,
+   text_body TEXT NOT NULL,
+   status TEXT NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'sent', 'dead')),
+   attempts INTEGER NOT NULL DEFAULT 0,
+   last_error TEXT,
+   -- with fractions of a second, retries can come sooner than a second apart
+   next_attempt_at REAL NOT NULL DEFAULT (unixepoch('subsec')),
+   created_at REAL NOT NULL DEFAULT (unixepoch('subsec')),
+   sent_at REAL
+);
+
+CREATE INDEX IF NOT EXISTS email_outbox_due_idx
+   ON email_outbox (next_attempt_at)
+   WHERE status = 'pending';
+
+-- Signing keys promoted by rotation, loaded by every instance of the service
+CREATE TABLE IF NOT EXISTS jwt_keys(
+   kid TEXT NOT NULL PRIMARY KEY,
+   algorithm TEXT NOT NULL,
+   -- AES-256-GCM nonce followed by the ciphertext of the PEM or HS256 secret
+   encrypted_private_key BLOB NOT NULL,
+   -- counts the rotations, the most recent key has the highest number
+   rotation INTEGER NOT NULL,
+   -- NULL for the active key
+   retire_at INTEGER
+);
+
+CREATE TABLE IF NOT EXISTS refresh_token_families(
+   -- UUID
+   family_id TEXT NOT NULL PRIMARY KEY,
+   email TEXT NOT NULL,
+   -- the organization the session works in
+   organization_id TEXT,
+   expires_at INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS refresh_token_families_email_idx ON refresh_token_families (email);
+CREATE INDEX IF NOT EXISTS refresh_token_families_expires_at_idx
+   ON refresh_token_families (expires_at);
+
+CREATE TABLE IF NOT EXISTS refresh_tokens(
+   -- SHA-256 of the token
+   token_hash TEXT NOT NULL PRIMARY KEY,
+   family_id TEXT NOT NULL REFERENCES refresh_token_families(family_id) ON DELETE CASCADE,
+   -- exchanged for a new token already, presenting it again revokes the family
+   used BOOLEAN NOT NULL DEFAULT FALSE,
+   expires_at INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS refresh_tokens_family_id_idx ON refresh_tokens (family_id);
+CREATE INDEX IF NOT EXISTS refresh_tokens_expires_at_idx ON refresh_tokens (expires_at);
+
+CREATE TABLE IF NOT EXISTS one_time_tokens(
+   -- password_reset or email_verification
+   purpose TEXT NOT NULL,
+   -- SHA-256 of the token
+   token_hash TEXT NOT NULL,
+   email TEXT NOT NULL,
+   expires_at INTEGER NOT NULL,
+   PRIMARY KEY (purpose, token_hash)
+);
+
+CREATE INDEX IF NOT EXISTS one_time_tokens_expires_at_idx ON one_time_tokens (expires_at);
+
+CREATE TABLE IF NOT EXISTS failed_logins(
+   -- an email or client address, see FailedLoginKey
+   key TEXT NOT NULL PRIMARY KEY,
+   failures INTEGER NOT NULL,
+   expires_at INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS failed_logins_expires_at_idx ON failed_logins (expires_at);
+
+CREATE TABLE IF NOT EXISTS login_locks(
+   key TEXT NOT NULL PRIMARY KEY,
+   -- logins are refused until then
+   expires_at INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS login_locks_expires_at_idx ON login_locks (expires_at);
+
+CREATE TABLE IF NOT EXISTS rate_limit_buckets(
+   key TEXT NOT NULL PRIMARY KEY,
+   tokens REAL NOT NULL,
+   -- unix timestamp in milliseconds the tokens were last counted at
+   updated_at INTEGER NOT NULL,
+   -- when the bucket is full again and carries no information
+   expires_at INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS rate_limit_buckets_expires_at_idx ON rate_limit_buckets (expires_at);
+
+CREATE TABLE IF NOT EXISTS webauthn_challenges(
+   challenge TEXT NOT NULL PRIMARY KEY,
+   -- registration or authentication
+   ceremony TEXT NOT NULL,
+   email TEXT NOT NULL,
+   -- the password login an authentication completes, if any
+   login_attempt_id TEXT,
+   expires_at INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS webauthn_challenges_expires_at_idx ON webauthn_challenges (expires_at);
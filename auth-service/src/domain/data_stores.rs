@@ -1,20 +1,71 @@
-use rand::Rng;
+use std::{net::IpAddr, time::Duration};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use rand::{distributions::Alphanumeric, Rng};
+use sha2::{Digest, Sha256};
 use uuid::Uuid;
 
-use super::{Email, Password, User};
+use super::{
+    Email, EmailMessage, Invitation, InvitationToken, Membership, Organization, OrganizationId,
+    OrganizationName, Password, RateLimit, RateLimitDecision, Role, RoleDefinition, TwoFAMethod,
+    User, UserAccess,
+};
 
 #[async_trait::async_trait]
 pub trait UserStore: Send + Sync {
-    async fn add_user(&mut self, user: User) -> Result<(), UserStoreError>;
+    async fn add_user(&self, user: User) -> Result<(), UserStoreError>;
     async fn get_user(&self, email: &Email) -> Result<User, UserStoreError>;
     async fn validate_user(&self, email: &Email, password: &Password)
         -> Result<(), UserStoreError>;
+    async fn update_password(
+        &self,
+        email: &Email,
+        password: Password,
+    ) -> Result<(), UserStoreError>;
+    async fn set_email_verified(&self, email: &Email) -> Result<(), UserStoreError>;
+    async fn set_two_fa_method(
+        &self,
+        email: &Email,
+        two_fa_method: TwoFAMethod,
+    ) -> Result<(), UserStoreError>;
+}
+
+// Where banned tokens and pending 2FA logins are kept, picked at startup
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EphemeralStoreBackend {
+    Redis,
+    // rows carry an expiry and are purged periodically, for deployments that would rather not run
+    // Redis for them
+    Postgres,
+}
+
+impl EphemeralStoreBackend {
+    pub fn parse(backend: &str) -> Result<Self, String> {
+        match backend.to_lowercase().as_str() {
+            "redis" => Ok(Self::Redis),
+            "postgres" => Ok(Self::Postgres),
+            _ => Err(format!("Unknown ephemeral store: {}", backend)),
+        }
+    }
 }
 
 #[async_trait::async_trait]
 pub trait BannedTokenStore: Send + Sync {
-    async fn store_token(&mut self, token: String) -> Result<(), BannedTokenStoreError>;
-    async fn check_token(&self, token: &str) -> Result<bool, BannedTokenStoreError>;
+    // Tokens are banned by their `jti` claim rather than the whole token string
+    async fn store_token(&self, jti: String) -> Result<(), BannedTokenStoreError>;
+    async fn check_token(&self, jti: &str) -> Result<bool, BannedTokenStoreError>;
+    // Ban every token of the user issued before `issued_before`, a unix timestamp in milliseconds so
+    // a token issued straight after the ban, in the same second, is still accepted
+    async fn ban_user_tokens(
+        &self,
+        email: &Email,
+        issued_before: i64,
+    ) -> Result<(), BannedTokenStoreError>;
+    async fn check_user_token(
+        &self,
+        email: &Email,
+        issued_at: i64,
+    ) -> Result<bool, BannedTokenStoreError>;
 }
 
 #[derive(Debug, PartialEq)]
@@ -30,31 +81,74 @@ pub enum BannedTokenStoreError {
     UnexpectedError,
 }
 
-// This trait represents the interface all concrete 2FA code stores should represent
+// This trait represents the interface all concrete 2FA code stores should represent.
+// Pending logins are keyed by their login attempt id, so a user can log in from several devices at
+// once. Each attempt stays bound to the email that started it.
 #[async_trait::async_trait]
-pub trait TwoFACodeStore {
+pub trait TwoFACodeStore: Send + Sync {
+    // Adds a pending login attempt, along with the organization the login asked to work in. When the
+    // user already has MAX_PENDING_LOGIN_ATTEMPTS of them, the oldest are discarded to make room.
     async fn add_code(
-        &mut self,
+        &self,
         email: Email,
         login_attempt_id: LoginAttemptId,
         code: TwoFACode,
+        organization_id: Option<OrganizationId>,
     ) -> Result<(), TwoFACodeStoreError>;
 
-    async fn remove_code(&mut self, email: &Email) -> Result<(), TwoFACodeStoreError>;
+    // Completes the login attempt and returns the organization it was started with. Fails with
+    // LoginAttemptIdNotFound if it is already gone, so of two requests completing the same attempt
+    // at once only one succeeds.
+    async fn remove_code(
+        &self,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<Option<OrganizationId>, TwoFACodeStoreError>;
 
+    // The email that started the login attempt and its current code
     async fn get_code(
         &self,
-        email: &Email,
-    ) -> Result<(LoginAttemptId, TwoFACode), TwoFACodeStoreError>;
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<(Email, TwoFACode), TwoFACodeStoreError>;
+
+    // Checks a guess at the code of the login attempt. Comparing it and counting it when wrong happen
+    // in one step, so concurrent guesses cannot get past MAX_TWO_FA_ATTEMPTS. The wrong guess that
+    // reaches the limit removes the attempt, and once the limit is reached no guess is compared.
+    async fn verify_code(
+        &self,
+        login_attempt_id: &LoginAttemptId,
+        code: &TwoFACode,
+    ) -> Result<(), TwoFACodeStoreError>;
+
+    // Counts a guess that is checked outside the store (TOTP and recovery codes) before it is checked,
+    // and returns how many guesses there have been. Counting first means concurrent guesses cannot all
+    // be checked before MAX_TWO_FA_ATTEMPTS is reached, after that guesses are refused with
+    // LoginAttemptIdNotFound. A right guess goes on to complete the attempt with `remove_code`, so
+    // only wrong ones stay counted.
+    async fn reserve_attempt(
+        &self,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<u32, TwoFACodeStoreError>;
+
+    // Replaces the code of the login attempt with a new one to send again, keeping its failed
+    // guesses. Refused until TWO_FA_RESEND_COOLDOWN_SECONDS after the last code, and after MAX_TWO_FA_RESENDS.
+    async fn resend_code(
+        &self,
+        login_attempt_id: &LoginAttemptId,
+        code: TwoFACode,
+    ) -> Result<(), TwoFACodeStoreError>;
 }
 
 #[derive(Debug, PartialEq)]
 pub enum TwoFACodeStoreError {
     LoginAttemptIdNotFound,
+    IncorrectCode,
+    // a new code may be sent in this many seconds
+    ResendCooldown(u64),
+    TooManyResends,
     UnexpectedError,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct LoginAttemptId(String);
 
 impl LoginAttemptId {
@@ -111,3 +205,742 @@ impl AsRef<str> for TwoFACode {
         &self.0
     }
 }
+
+// This trait represents the interface all concrete refresh token stores should implement.
+// Refresh tokens are grouped into families: every rotation issues a new token in the same family
+// and marks the presented token as used. Presenting a used token again revokes the whole family.
+#[async_trait::async_trait]
+pub trait RefreshTokenStore {
+    async fn add_token(
+        &mut self,
+        session: RefreshSession,
+        token: RefreshToken,
+    ) -> Result<(), RefreshTokenStoreError>;
+
+    async fn rotate_token(
+        &mut self,
+        token: &RefreshToken,
+    ) -> Result<(RefreshSession, RefreshToken), RefreshTokenStoreError>;
+
+    async fn revoke_token(&mut self, token: &RefreshToken) -> Result<(), RefreshTokenStoreError>;
+
+    // Revoke every token family belonging to the user
+    async fn revoke_user_tokens(&mut self, email: &Email) -> Result<(), RefreshTokenStoreError>;
+}
+
+// What a refresh token family keeps alive: the user and the organization they selected when the
+// session started, so every session keeps its own organization
+#[derive(Debug, Clone, PartialEq)]
+pub struct RefreshSession {
+    pub email: Email,
+    pub organization_id: Option<OrganizationId>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum RefreshTokenStoreError {
+    TokenNotFound,
+    TokenReused,
+    UnexpectedError,
+}
+
+#[derive(Debug, Clone, PartialEq, Hash, Eq)]
+pub struct RefreshToken(String);
+
+const REFRESH_TOKEN_LENGTH: usize = 64;
+
+impl RefreshToken {
+    pub fn parse(token: String) -> Result<Self, String> {
+        // Ensure `token` looks like a token we issued (64 alphanumeric characters)
+        if token.len() == REFRESH_TOKEN_LENGTH && token.chars().all(|c| c.is_ascii_alphanumeric()) {
+            Ok(Self(token))
+        } else {
+            Err("Invalid refresh token".into())
+        }
+    }
+
+    // What SQL stores keep instead of the token, like `OneTimeToken::hash`
+    pub fn hash(&self) -> String {
+        format!("{:x}", Sha256::digest(self.0.as_bytes()))
+    }
+}
+
+impl Default for RefreshToken {
+    fn default() -> Self {
+        // Refresh tokens are opaque random strings, they carry no claims
+        let token = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(REFRESH_TOKEN_LENGTH)
+            .map(char::from)
+            .collect();
+        Self(token)
+    }
+}
+
+impl AsRef<str> for RefreshToken {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+// What a single-use token emailed to the user is for. Each purpose keeps its tokens apart from the
+// others' and has its own lifetime.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OneTimeTokenPurpose {
+    PasswordReset,
+    EmailVerification,
+}
+
+// This trait represents the interface all concrete single-use token stores should implement.
+// A store holds the tokens of one purpose. Implementations must only persist `OneTimeToken::hash`,
+// never the token itself, and forget tokens once they expire.
+#[async_trait::async_trait]
+pub trait OneTimeTokenStore {
+    async fn add_token(
+        &mut self,
+        email: Email,
+        token: &OneTimeToken,
+    ) -> Result<(), OneTimeTokenStoreError>;
+
+    // Look up the token and remove it in one step, so even concurrent requests can only use it once
+    async fn consume_token(
+        &mut self,
+        token: &OneTimeToken,
+    ) -> Result<Email, OneTimeTokenStoreError>;
+}
+
+#[derive(Debug, PartialEq)]
+pub enum OneTimeTokenStoreError {
+    TokenNotFound,
+    UnexpectedError,
+}
+
+// Sent to the user in a link, e.g. to reset their password or verify their email address
+#[derive(Debug, Clone, PartialEq)]
+pub struct OneTimeToken(String);
+
+const ONE_TIME_TOKEN_LENGTH: usize = 64;
+
+impl OneTimeToken {
+    pub fn parse(token: String) -> Result<Self, String> {
+        if token.len() == ONE_TIME_TOKEN_LENGTH && token.chars().all(|c| c.is_ascii_alphanumeric())
+        {
+            Ok(Self(token))
+        } else {
+            Err("Invalid token".into())
+        }
+    }
+
+    // SHA-256 is enough here: the token is long and random so it cannot be brute-forced like a password
+    pub fn hash(&self) -> String {
+        format!("{:x}", Sha256::digest(self.0.as_bytes()))
+    }
+}
+
+impl Default for OneTimeToken {
+    fn default() -> Self {
+        let token = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(ONE_TIME_TOKEN_LENGTH)
+            .map(char::from)
+            .collect();
+        Self(token)
+    }
+}
+
+impl AsRef<str> for OneTimeToken {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+// This trait represents the interface all concrete TOTP secret stores should implement.
+// A secret starts out pending and only becomes usable for login once it is confirmed with a first code.
+#[async_trait::async_trait]
+pub trait TotpSecretStore {
+    // Store a new pending secret for the user, replacing any previous one
+    async fn add_secret(
+        &mut self,
+        email: Email,
+        secret: TotpSecret,
+    ) -> Result<(), TotpSecretStoreError>;
+
+    async fn get_secret(&self, email: &Email) -> Result<TotpEnrollment, TotpSecretStoreError>;
+
+    async fn confirm_secret(&mut self, email: &Email) -> Result<(), TotpSecretStoreError>;
+
+    // Record that a code from `time_step` was accepted. Fails if that step or a later one was
+    // already used, so every code can only be used once.
+    async fn use_time_step(
+        &mut self,
+        email: &Email,
+        time_step: u64,
+    ) -> Result<(), TotpSecretStoreError>;
+}
+
+#[derive(Debug, PartialEq)]
+pub enum TotpSecretStoreError {
+    SecretNotFound,
+    TimeStepAlreadyUsed,
+    UnexpectedError,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TotpEnrollment {
+    pub secret: TotpSecret,
+    pub confirmed: bool,
+}
+
+// Shared secret between the service and the user's authenticator app
+#[derive(Debug, Clone, PartialEq)]
+pub struct TotpSecret(Vec<u8>);
+
+// 160 bits, the length recommended by RFC 4226 for HMAC-SHA1
+const TOTP_SECRET_LENGTH: usize = 20;
+
+impl TotpSecret {
+    pub fn parse(secret: Vec<u8>) -> Result<Self, String> {
+        if secret.len() == TOTP_SECRET_LENGTH {
+            Ok(Self(secret))
+        } else {
+            Err("Invalid TOTP secret".into())
+        }
+    }
+
+    // Authenticator apps expect the secret as unpadded base32
+    pub fn to_base32(&self) -> String {
+        base32::encode(base32::Alphabet::Rfc4648 { padding: false }, &self.0)
+    }
+}
+
+impl Default for TotpSecret {
+    fn default() -> Self {
+        let mut secret = vec![0u8; TOTP_SECRET_LENGTH];
+        rand::thread_rng().fill(&mut secret[..]);
+        Self(secret)
+    }
+}
+
+impl AsRef<[u8]> for TotpSecret {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+// This trait represents the interface all concrete WebAuthn credential stores should implement.
+// A user can register several credentials (passkeys, security keys), each identified by the ID
+// the authenticator chose for it.
+#[async_trait::async_trait]
+pub trait WebAuthnCredentialStore {
+    async fn add_credential(
+        &mut self,
+        credential: WebAuthnCredential,
+    ) -> Result<(), WebAuthnCredentialStoreError>;
+
+    async fn get_credential(
+        &self,
+        credential_id: &CredentialId,
+    ) -> Result<WebAuthnCredential, WebAuthnCredentialStoreError>;
+
+    async fn get_user_credentials(
+        &self,
+        email: &Email,
+    ) -> Result<Vec<WebAuthnCredential>, WebAuthnCredentialStoreError>;
+
+    // Store the signature counter reported by the authenticator. Fails if the counter did not
+    // increase, which means the credential may have been cloned. Authenticators without a counter
+    // always report 0 and are exempt.
+    async fn update_sign_count(
+        &mut self,
+        credential_id: &CredentialId,
+        sign_count: u32,
+    ) -> Result<(), WebAuthnCredentialStoreError>;
+}
+
+#[derive(Debug, PartialEq)]
+pub enum WebAuthnCredentialStoreError {
+    CredentialAlreadyExists,
+    CredentialNotFound,
+    SignCountNotIncreased,
+    UnexpectedError,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WebAuthnCredential {
+    pub credential_id: CredentialId,
+    pub email: Email,
+    // COSE encoded public key from the attested credential data
+    pub public_key: Vec<u8>,
+    pub sign_count: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CredentialId(Vec<u8>);
+
+// The WebAuthn spec caps credential IDs at 1023 bytes
+const MAX_CREDENTIAL_ID_LENGTH: usize = 1023;
+
+impl CredentialId {
+    pub fn parse(id: Vec<u8>) -> Result<Self, String> {
+        if !id.is_empty() && id.len() <= MAX_CREDENTIAL_ID_LENGTH {
+            Ok(Self(id))
+        } else {
+            Err("Invalid credential ID".into())
+        }
+    }
+
+    // Browsers exchange binary values as unpadded base64url
+    pub fn parse_base64url(id: &str) -> Result<Self, String> {
+        let id = URL_SAFE_NO_PAD
+            .decode(id)
+            .map_err(|_| "Invalid credential ID".to_owned())?;
+        Self::parse(id)
+    }
+
+    pub fn to_base64url(&self) -> String {
+        URL_SAFE_NO_PAD.encode(&self.0)
+    }
+}
+
+impl AsRef<[u8]> for CredentialId {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+// This trait represents the interface all concrete WebAuthn challenge stores should implement.
+// Every ceremony starts by issuing a challenge, which the authenticator signs and which can only be used once.
+#[async_trait::async_trait]
+pub trait WebAuthnChallengeStore {
+    async fn add_challenge(
+        &mut self,
+        challenge: &WebAuthnChallenge,
+        ceremony: WebAuthnCeremony,
+    ) -> Result<(), WebAuthnChallengeStoreError>;
+
+    // Look up the challenge and remove it so it can only be used once
+    async fn consume_challenge(
+        &mut self,
+        challenge: &WebAuthnChallenge,
+    ) -> Result<WebAuthnCeremony, WebAuthnChallengeStoreError>;
+}
+
+#[derive(Debug, PartialEq)]
+pub enum WebAuthnChallengeStoreError {
+    ChallengeNotFound,
+    UnexpectedError,
+}
+
+// What a challenge was issued for
+#[derive(Debug, Clone, PartialEq)]
+pub enum WebAuthnCeremony {
+    Registration {
+        email: Email,
+    },
+    // `login_attempt_id` is set when the assertion is the second factor of a password login,
+    // otherwise the assertion is the only factor
+    Authentication {
+        email: Email,
+        login_attempt_id: Option<LoginAttemptId>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WebAuthnChallenge(String);
+
+const WEBAUTHN_CHALLENGE_LENGTH: usize = 32;
+
+impl WebAuthnChallenge {
+    pub fn parse(challenge: String) -> Result<Self, String> {
+        match URL_SAFE_NO_PAD.decode(&challenge) {
+            Ok(bytes) if bytes.len() == WEBAUTHN_CHALLENGE_LENGTH => Ok(Self(challenge)),
+            _ => Err("Invalid WebAuthn challenge".into()),
+        }
+    }
+}
+
+impl Default for WebAuthnChallenge {
+    fn default() -> Self {
+        let mut challenge = [0u8; WEBAUTHN_CHALLENGE_LENGTH];
+        rand::thread_rng().fill(&mut challenge[..]);
+        Self(URL_SAFE_NO_PAD.encode(challenge))
+    }
+}
+
+impl AsRef<str> for WebAuthnChallenge {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+// This trait represents the interface all concrete recovery code stores should implement.
+// Recovery codes let a 2FA user finish a login without their second factor. Each code works once.
+// Checking a code takes several Argon2 verifications, so implementations handle concurrent calls
+// themselves rather than being locked for the whole check.
+#[async_trait::async_trait]
+pub trait RecoveryCodeStore: Send + Sync {
+    // Store a new set of codes for the user, invalidating every code issued before
+    async fn replace_codes(
+        &self,
+        email: &Email,
+        codes: Vec<RecoveryCode>,
+    ) -> Result<(), RecoveryCodeStoreError>;
+
+    // Consume `code` if it is one of the user's unused codes
+    async fn use_code(
+        &self,
+        email: &Email,
+        code: &RecoveryCode,
+    ) -> Result<(), RecoveryCodeStoreError>;
+}
+
+#[derive(Debug, PartialEq)]
+pub enum RecoveryCodeStoreError {
+    CodeNotFound,
+    UnexpectedError,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecoveryCode(String);
+
+// Lowercase letters and digits without the easily confused 0/o and 1/i/l
+const RECOVERY_CODE_ALPHABET: &[u8] = b"abcdefghjkmnpqrstuvwxyz23456789";
+// Written down as two groups of five characters, e.g. `k7p2m-x9qrt`
+const RECOVERY_CODE_GROUP_LENGTH: usize = 5;
+
+impl RecoveryCode {
+    // Accepts the code with or without the dash and in any case, since users type it in by hand
+    pub fn parse(code: String) -> Result<Self, String> {
+        let normalized: String = code
+            .trim()
+            .chars()
+            .filter(|c| *c != '-')
+            .map(|c| c.to_ascii_lowercase())
+            .collect();
+
+        if normalized.len() == RECOVERY_CODE_GROUP_LENGTH * 2
+            && normalized
+                .bytes()
+                .all(|c| RECOVERY_CODE_ALPHABET.contains(&c))
+        {
+            let (first, second) = normalized.split_at(RECOVERY_CODE_GROUP_LENGTH);
+            Ok(Self(format!("{}-{}", first, second)))
+        } else {
+            Err("Invalid recovery code".into())
+        }
+    }
+}
+
+impl Default for RecoveryCode {
+    fn default() -> Self {
+        let mut rng = rand::thread_rng();
+        let mut group = || -> String {
+            (0..RECOVERY_CODE_GROUP_LENGTH)
+                .map(|_| {
+                    RECOVERY_CODE_ALPHABET[rng.gen_range(0..RECOVERY_CODE_ALPHABET.len())] as char
+                })
+                .collect()
+        };
+        Self(format!("{}-{}", group(), group()))
+    }
+}
+
+impl AsRef<str> for RecoveryCode {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+// Roles are defined together with their permissions by migrations, users are assigned roles at runtime
+#[async_trait::async_trait]
+pub trait RoleStore {
+    // The user's roles and the permissions they grant, empty for users without roles
+    async fn get_user_access(&self, email: &Email) -> Result<UserAccess, RoleStoreError>;
+
+    // Every role that can be assigned
+    async fn get_roles(&self) -> Result<Vec<RoleDefinition>, RoleStoreError>;
+
+    // Assigning a role the user already has is not an error
+    async fn assign_role(&mut self, email: &Email, role: &Role) -> Result<(), RoleStoreError>;
+
+    // Revoking a role the user does not have is not an error
+    async fn revoke_role(&mut self, email: &Email, role: &Role) -> Result<(), RoleStoreError>;
+}
+
+#[derive(Debug, PartialEq)]
+pub enum RoleStoreError {
+    RoleNotFound,
+    UserNotFound,
+    UnexpectedError,
+}
+
+// Organizations group users, each member holding one organization role.
+// Implementations must only persist `InvitationToken::hash`, never the token itself.
+#[async_trait::async_trait]
+pub trait OrganizationStore {
+    // Create the organization with `owner` as its first member
+    async fn create_organization(
+        &mut self,
+        name: OrganizationName,
+        owner: &Email,
+    ) -> Result<Organization, OrganizationStoreError>;
+
+    // Every organization the user belongs to, empty for users without organizations
+    async fn get_memberships(
+        &self,
+        email: &Email,
+    ) -> Result<Vec<Membership>, OrganizationStoreError>;
+
+    async fn get_membership(
+        &self,
+        email: &Email,
+        organization_id: &OrganizationId,
+    ) -> Result<Membership, OrganizationStoreError>;
+
+    async fn add_invitation(
+        &mut self,
+        token: &InvitationToken,
+        invitation: Invitation,
+    ) -> Result<(), OrganizationStoreError>;
+
+    // Turn the invitation into a membership. The invitation can only be accepted once and only by
+    // the email address it was sent to.
+    async fn accept_invitation(
+        &mut self,
+        token: &InvitationToken,
+        email: &Email,
+    ) -> Result<Membership, OrganizationStoreError>;
+}
+
+#[derive(Debug, PartialEq)]
+pub enum OrganizationStoreError {
+    OrganizationNotFound,
+    MembershipNotFound,
+    AlreadyMember,
+    InvitationNotFound,
+    UnexpectedError,
+}
+
+// Failed logins are counted, and logins locked, separately per account and per client address
+#[async_trait::async_trait]
+pub trait FailedLoginStore {
+    // Count a failed login, returning the number of failures since the last success. Failures are
+    // forgotten `FAILED_LOGIN_TTL_SECONDS` after the most recent one.
+    async fn add_failure(&mut self, key: &FailedLoginKey) -> Result<u32, FailedLoginStoreError>;
+
+    async fn clear_failures(&mut self, key: &FailedLoginKey) -> Result<(), FailedLoginStoreError>;
+
+    // Refuse logins for the key until the unix timestamp `until`
+    async fn lock(&mut self, key: &FailedLoginKey, until: i64)
+        -> Result<(), FailedLoginStoreError>;
+
+    // The unix timestamp the lock on the key ends at, `None` if the key is not locked
+    async fn get_lock(&self, key: &FailedLoginKey) -> Result<Option<i64>, FailedLoginStoreError>;
+}
+
+#[derive(Debug, PartialEq)]
+pub enum FailedLoginStoreError {
+    UnexpectedError,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FailedLoginKey(String);
+
+impl FailedLoginKey {
+    pub fn email(email: &Email) -> Self {
+        Self(format!("email:{}", email.as_ref()))
+    }
+
+    pub fn ip(ip: IpAddr) -> Self {
+        Self(format!("ip:{}", ip))
+    }
+}
+
+impl AsRef<str> for FailedLoginKey {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+// Token buckets that throttle requests, one per route and client address or email address.
+// Every request goes through the store, so implementations handle concurrent calls themselves.
+#[async_trait::async_trait]
+pub trait RateLimitStore: Send + Sync {
+    // Take a token from the key's bucket, a key seen for the first time starts with a full bucket.
+    // The request is refused when the bucket is empty.
+    async fn take_token(
+        &self,
+        key: &RateLimitKey,
+        limit: &RateLimit,
+    ) -> Result<RateLimitDecision, RateLimitStoreError>;
+}
+
+#[derive(Debug, PartialEq)]
+pub enum RateLimitStoreError {
+    UnexpectedError,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RateLimitKey(String);
+
+impl RateLimitKey {
+    pub fn email(scope: &str, email: &Email) -> Self {
+        Self(format!("{}:email:{}", scope, email.as_ref()))
+    }
+
+    pub fn ip(scope: &str, ip: IpAddr) -> Self {
+        Self(format!("{}:ip:{}", scope, ip))
+    }
+}
+
+impl AsRef<str> for RateLimitKey {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+// Emails waiting to be delivered. Requests queue their emails here and return, a background worker
+// delivers them and retries the ones the mail provider refused.
+#[async_trait::async_trait]
+pub trait EmailOutboxStore {
+    // Queue an email unless one with the same key was queued before, returning whether it was queued
+    async fn enqueue(
+        &mut self,
+        key: &EmailIdempotencyKey,
+        recipient: &Email,
+        message: &EmailMessage,
+    ) -> Result<bool, EmailOutboxStoreError>;
+
+    // Take up to `limit` pending emails that are due. They are not due again for `lease`, so other
+    // workers skip them while they are being sent.
+    async fn claim_due(
+        &mut self,
+        limit: u32,
+        lease: Duration,
+    ) -> Result<Vec<OutboxEmail>, EmailOutboxStoreError>;
+
+    // The bodies are dropped once the email is sent, they may hold 2FA codes and one-time links
+    async fn mark_sent(&mut self, id: &Uuid) -> Result<(), EmailOutboxStoreError>;
+
+    // Count a failed attempt. The email is tried again after `retry_in`, or dead-lettered when it is `None`.
+    async fn mark_failed(
+        &mut self,
+        id: &Uuid,
+        error: &str,
+        retry_in: Option<Duration>,
+    ) -> Result<(), EmailOutboxStoreError>;
+
+    // Emails that have not been sent yet, including the ones that are not due
+    async fn count_pending(&self) -> Result<u64, EmailOutboxStoreError>;
+
+    // Dead-lettered emails, and pending ones that failed at least once, most recent first
+    async fn get_stuck(&self) -> Result<Vec<OutboxEmail>, EmailOutboxStoreError>;
+}
+
+#[derive(Debug, PartialEq)]
+pub enum EmailOutboxStoreError {
+    EmailNotFound,
+    UnexpectedError,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutboxEmail {
+    pub id: Uuid,
+    pub recipient: Email,
+    pub message: EmailMessage,
+    pub status: OutboxEmailStatus,
+    pub attempts: u32,
+    pub last_error: Option<String>,
+    // unix timestamps in seconds
+    pub next_attempt_at: i64,
+    pub created_at: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutboxEmailStatus {
+    Pending,
+    Sent,
+    // gave up after too many failed attempts
+    Dead,
+}
+
+impl OutboxEmailStatus {
+    pub fn parse(status: String) -> Result<Self, String> {
+        match status.as_str() {
+            "pending" => Ok(Self::Pending),
+            "sent" => Ok(Self::Sent),
+            "dead" => Ok(Self::Dead),
+            _ => Err(format!("{} is not a valid outbox email status", status)),
+        }
+    }
+}
+
+impl AsRef<str> for OutboxEmailStatus {
+    fn as_ref(&self) -> &str {
+        match self {
+            Self::Pending => "pending",
+            Self::Sent => "sent",
+            Self::Dead => "dead",
+        }
+    }
+}
+
+// Identifies one email, e.g. the code of one login attempt, so queuing it twice sends it once. Keys
+// are built from codes and tokens, so only their SHA-256 hash is kept.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct EmailIdempotencyKey(String);
+
+impl EmailIdempotencyKey {
+    pub fn new(parts: &[&str]) -> Self {
+        Self(format!("{:x}", Sha256::digest(parts.join(":").as_bytes())))
+    }
+}
+
+impl AsRef<str> for EmailIdempotencyKey {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+// Signing keys promoted by rotating the JWT keys. Every instance of the service loads them, so a key
+// rotated on one instance signs and verifies on all of them.
+#[async_trait::async_trait]
+pub trait JwtKeyStore: Send + Sync {
+    // Make `key` the active signing key. The key active until now keeps verifying the tokens it
+    // signed until `previous.retire_at`, it is stored as well if it came from configuration.
+    async fn rotate_key(
+        &self,
+        key: &StoredJwtKey,
+        previous: &StoredJwtKey,
+    ) -> Result<(), JwtKeyStoreError>;
+
+    // The keys that are not retired yet, most recently rotated in first
+    async fn get_keys(&self) -> Result<Vec<StoredJwtKey>, JwtKeyStoreError>;
+}
+
+#[derive(Debug, PartialEq)]
+pub enum JwtKeyStoreError {
+    UnexpectedError,
+}
+
+// A signing key as kept by a JwtKeyStore. Stores persisting it must encrypt the private key.
+#[derive(Clone, PartialEq)]
+pub struct StoredJwtKey {
+    pub kid: String,
+    // the JWS algorithm, e.g. "EdDSA"
+    pub algorithm: String,
+    // a PEM, or the shared secret of HS256 keys
+    pub private_key: Vec<u8>,
+    // unix timestamp the key is no longer trusted from, none while it signs new tokens
+    pub retire_at: Option<i64>,
+}
+
+// Leaves out the private key
+impl std::fmt::Debug for StoredJwtKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StoredJwtKey")
+            .field("kid", &self.kid)
+            .field("algorithm", &self.algorithm)
+            .field("retire_at", &self.retire_at)
+            .finish_non_exhaustive()
+    }
+}
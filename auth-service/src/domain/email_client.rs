@@ -1,12 +1,44 @@
 use super::Email;
 
+// A rendered email. Every message carries an HTML body and a plain-text one for clients that do
+// not display HTML.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EmailMessage {
+    pub subject: String,
+    pub html_body: String,
+    pub text_body: String,
+}
+
+// Which email client delivers the emails, picked at startup
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EmailProvider {
+    // prints emails to stdout, for local development
+    Mock,
+    Smtp,
+    // a transactional email service's JSON HTTP API
+    Http,
+}
+
+impl EmailProvider {
+    pub fn parse(provider: &str) -> Result<Self, String> {
+        match provider.to_lowercase().as_str() {
+            "mock" => Ok(Self::Mock),
+            "smtp" => Ok(Self::Smtp),
+            "http" => Ok(Self::Http),
+            _ => Err(format!("Unknown email provider: {}", provider)),
+        }
+    }
+}
+
 // This trait represents the interface all concrete email clients should implement
 #[async_trait::async_trait]
 pub trait EmailClient {
+    // `idempotency_key` is the same for every attempt at delivering one email, so a provider that
+    // supports it can drop a retry of an email it already accepted
     async fn send_email(
         &self,
         recipient: &Email,
-        subject: &str,
-        content: &str,
+        message: &EmailMessage,
+        idempotency_key: &str,
     ) -> Result<(), String>;
 }
@@ -1,3 +1,5 @@
+use super::RateLimitDecision;
+
 pub enum AuthAPIError {
     UserAlreadyExists,
     InvalidCredentials,
@@ -5,4 +7,21 @@ pub enum AuthAPIError {
     IncorrectCredentials,
     MissingToken,
     InvalidToken,
+    EmailNotVerified,
+    TotpAlreadyEnabled,
+    TwoFANotEnabled,
+    InvalidClient,
+    MissingPermission,
+    UserNotFound,
+    RoleNotFound,
+    OrganizationNotFound,
+    AlreadyMember,
+    // logins are refused for this many more seconds
+    AccountLocked(u64),
+    // a new 2FA code can be sent in this many seconds
+    ResendCooldown(u64),
+    // the login attempt has used up its resends, the user has to log in again
+    TooManyResends,
+    // the bucket the request would have taken a token from is empty
+    RateLimited(RateLimitDecision),
 }
@@ -2,7 +2,10 @@ pub mod data_stores;
 mod email;
 mod email_client;
 mod error;
+mod organization;
 mod password;
+mod rate_limit;
+mod role;
 mod user;
 
 // re-export items from submodules
@@ -10,5 +13,8 @@ pub use data_stores::*;
 pub use email::*;
 pub use email_client::*;
 pub use error::*;
+pub use organization::*;
 pub use password::*;
+pub use rate_limit::*;
+pub use role::*;
 pub use user::*;
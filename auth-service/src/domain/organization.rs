@@ -0,0 +1,194 @@
+use rand::{distributions::Alphanumeric, Rng};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use super::Email;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct OrganizationId(Uuid);
+
+impl OrganizationId {
+    pub fn parse(id: String) -> Result<Self, String> {
+        Uuid::parse_str(&id)
+            .map(Self)
+            .map_err(|_| "Invalid UUID".to_owned())
+    }
+}
+
+impl Default for OrganizationId {
+    fn default() -> Self {
+        Self(Uuid::new_v4())
+    }
+}
+
+impl From<Uuid> for OrganizationId {
+    fn from(id: Uuid) -> Self {
+        Self(id)
+    }
+}
+
+impl AsRef<Uuid> for OrganizationId {
+    fn as_ref(&self) -> &Uuid {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for OrganizationId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrganizationName(String);
+
+const ORGANIZATION_NAME_MAX_LENGTH: usize = 100;
+
+impl OrganizationName {
+    pub fn parse(name: String) -> Result<Self, String> {
+        let name = name.trim();
+        if name.is_empty() || name.chars().count() > ORGANIZATION_NAME_MAX_LENGTH {
+            return Err(format!(
+                "Organization name must be between 1 and {} characters",
+                ORGANIZATION_NAME_MAX_LENGTH
+            ));
+        }
+        Ok(Self(name.to_owned()))
+    }
+}
+
+impl AsRef<str> for OrganizationName {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Organization {
+    pub id: OrganizationId,
+    pub name: OrganizationName,
+}
+
+// What a member may do within one organization, unrelated to the service-wide roles
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrgRole {
+    Owner,
+    Admin,
+    Member,
+}
+
+impl OrgRole {
+    pub fn parse(role: &str) -> Result<Self, String> {
+        match role {
+            "owner" => Ok(Self::Owner),
+            "admin" => Ok(Self::Admin),
+            "member" => Ok(Self::Member),
+            _ => Err(format!("{} is not a valid organization role", role)),
+        }
+    }
+
+    pub fn can_invite(&self) -> bool {
+        matches!(self, Self::Owner | Self::Admin)
+    }
+}
+
+impl AsRef<str> for OrgRole {
+    fn as_ref(&self) -> &str {
+        match self {
+            Self::Owner => "owner",
+            Self::Admin => "admin",
+            Self::Member => "member",
+        }
+    }
+}
+
+// A user's place in an organization
+#[derive(Debug, Clone, PartialEq)]
+pub struct Membership {
+    pub organization: Organization,
+    pub role: OrgRole,
+}
+
+// An outstanding invitation for an email address to join an organization with a role
+#[derive(Debug, Clone, PartialEq)]
+pub struct Invitation {
+    pub organization_id: OrganizationId,
+    pub email: Email,
+    pub role: OrgRole,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvitationToken(String);
+
+const INVITATION_TOKEN_LENGTH: usize = 64;
+
+impl InvitationToken {
+    pub fn parse(token: String) -> Result<Self, String> {
+        if token.len() == INVITATION_TOKEN_LENGTH
+            && token.chars().all(|c| c.is_ascii_alphanumeric())
+        {
+            Ok(Self(token))
+        } else {
+            Err("Invalid invitation token".into())
+        }
+    }
+
+    // SHA-256 is enough here: the token is long and random so it cannot be brute-forced like a password
+    pub fn hash(&self) -> String {
+        format!("{:x}", Sha256::digest(self.0.as_bytes()))
+    }
+}
+
+impl Default for InvitationToken {
+    fn default() -> Self {
+        let token = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(INVITATION_TOKEN_LENGTH)
+            .map(char::from)
+            .collect();
+        Self(token)
+    }
+}
+
+impl AsRef<str> for InvitationToken {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_organization_names_are_trimmed() {
+        assert_eq!(
+            OrganizationName::parse("  Acme  ".to_owned())
+                .unwrap()
+                .as_ref(),
+            "Acme"
+        );
+        assert!(OrganizationName::parse("   ".to_owned()).is_err());
+        assert!(OrganizationName::parse("a".repeat(101)).is_err());
+    }
+
+    #[test]
+    fn test_org_roles_round_trip() {
+        for role in [OrgRole::Owner, OrgRole::Admin, OrgRole::Member] {
+            assert_eq!(OrgRole::parse(role.as_ref()), Ok(role));
+        }
+        assert!(OrgRole::parse("Owner").is_err());
+        assert!(!OrgRole::Member.can_invite());
+    }
+
+    #[test]
+    fn test_invitation_token() {
+        let token = InvitationToken::default();
+        assert_eq!(
+            InvitationToken::parse(token.as_ref().to_owned()),
+            Ok(token.clone())
+        );
+        assert_ne!(token.hash(), token.as_ref());
+        assert!(InvitationToken::parse("short".to_owned()).is_err());
+    }
+}
@@ -0,0 +1,177 @@
+// A token bucket holding up to `capacity` requests, refilled evenly so that an empty bucket is full
+// again after `period_seconds`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimit {
+    pub capacity: u32,
+    pub period_seconds: u64,
+}
+
+impl RateLimit {
+    // Limits are configured as `capacity/period_seconds`, e.g. `10/60` for ten requests a minute
+    pub fn parse(limit: &str) -> Result<Self, String> {
+        let invalid = || format!("{} is not a valid rate limit", limit);
+
+        let (capacity, period_seconds) = limit.trim().split_once('/').ok_or_else(invalid)?;
+        let capacity: u32 = capacity.trim().parse().map_err(|_| invalid())?;
+        let period_seconds: u64 = period_seconds.trim().parse().map_err(|_| invalid())?;
+
+        if capacity == 0 || period_seconds == 0 {
+            return Err(invalid());
+        }
+
+        Ok(Self {
+            capacity,
+            period_seconds,
+        })
+    }
+
+    pub fn period_millis(&self) -> i64 {
+        self.period_seconds as i64 * 1000
+    }
+
+    // Tokens that flow back into the bucket over `millis`
+    fn refill(&self, millis: f64) -> f64 {
+        millis * self.capacity as f64 / self.period_millis() as f64
+    }
+
+    // Milliseconds it takes for `tokens` to flow back into the bucket
+    fn millis_to_refill(&self, tokens: f64) -> f64 {
+        (tokens * self.period_millis() as f64 / self.capacity as f64).ceil()
+    }
+
+    // Describe the bucket after a request took a token (`allowed`) or found none left, `tokens`
+    // being what remains in it
+    pub fn decision(&self, allowed: bool, tokens: f64) -> RateLimitDecision {
+        let seconds_until = |target: f64| {
+            (self.millis_to_refill((target - tokens).max(0.0)) / 1000.0).ceil() as u64
+        };
+
+        RateLimitDecision {
+            allowed,
+            limit: self.capacity,
+            remaining: tokens.floor() as u32,
+            reset_seconds: seconds_until(self.capacity as f64),
+            retry_after_seconds: if allowed { 0 } else { seconds_until(1.0) },
+        }
+    }
+}
+
+// The outcome of taking a token from a bucket, reported to clients through the `RateLimit-*` headers
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimitDecision {
+    pub allowed: bool,
+    pub limit: u32,
+    pub remaining: u32,
+    // seconds until the bucket is full again
+    pub reset_seconds: u64,
+    // seconds until the next request would be allowed, 0 when this one was
+    pub retry_after_seconds: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TokenBucket {
+    tokens: f64,
+    // unix timestamp in milliseconds the tokens were last counted at
+    updated_at: i64,
+}
+
+impl TokenBucket {
+    pub fn full(limit: &RateLimit, now: i64) -> Self {
+        Self {
+            tokens: limit.capacity as f64,
+            updated_at: now,
+        }
+    }
+
+    // A bucket as a store kept it, `updated_at` in unix milliseconds
+    pub fn restore(tokens: f64, updated_at: i64) -> Self {
+        Self { tokens, updated_at }
+    }
+
+    pub fn tokens(&self) -> f64 {
+        self.tokens
+    }
+
+    pub fn updated_at(&self) -> i64 {
+        self.updated_at
+    }
+
+    // Refill the bucket for the time that passed since it was last used, then take a token if
+    // there is one
+    pub fn take(&mut self, limit: &RateLimit, now: i64) -> RateLimitDecision {
+        let elapsed = (now - self.updated_at).max(0) as f64;
+        self.tokens = (self.tokens + limit.refill(elapsed)).min(limit.capacity as f64);
+        self.updated_at = now;
+
+        let allowed = self.tokens >= 1.0;
+        if allowed {
+            self.tokens -= 1.0;
+        }
+
+        limit.decision(allowed, self.tokens)
+    }
+
+    // Unix timestamp in milliseconds the bucket is full at, after which it can be forgotten
+    pub fn full_at(&self, limit: &RateLimit) -> i64 {
+        self.updated_at + limit.millis_to_refill(limit.capacity as f64 - self.tokens) as i64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIMIT: RateLimit = RateLimit {
+        capacity: 2,
+        period_seconds: 10,
+    };
+
+    #[test]
+    fn test_parse_rate_limit() {
+        assert_eq!(
+            RateLimit::parse(" 10/60 "),
+            Ok(RateLimit {
+                capacity: 10,
+                period_seconds: 60
+            })
+        );
+        assert!(RateLimit::parse("10").is_err());
+        assert!(RateLimit::parse("0/60").is_err());
+        assert!(RateLimit::parse("10/0").is_err());
+        assert!(RateLimit::parse("ten/60").is_err());
+    }
+
+    #[test]
+    fn test_bucket_refuses_requests_once_empty() {
+        let mut bucket = TokenBucket::full(&LIMIT, 0);
+
+        let decision = bucket.take(&LIMIT, 0);
+        assert!(decision.allowed);
+        assert_eq!(decision.remaining, 1);
+        assert_eq!(decision.reset_seconds, 5);
+
+        assert!(bucket.take(&LIMIT, 0).allowed);
+
+        let decision = bucket.take(&LIMIT, 0);
+        assert!(!decision.allowed);
+        assert_eq!(decision.remaining, 0);
+        assert_eq!(decision.reset_seconds, 10);
+        assert_eq!(decision.retry_after_seconds, 5);
+    }
+
+    #[test]
+    fn test_bucket_refills_over_time() {
+        let mut bucket = TokenBucket::full(&LIMIT, 0);
+        bucket.take(&LIMIT, 0);
+        bucket.take(&LIMIT, 0);
+
+        // one token comes back every 5 seconds
+        assert!(!bucket.take(&LIMIT, 4_000).allowed);
+        assert!(bucket.take(&LIMIT, 6_000).allowed);
+
+        // and the bucket never holds more than its capacity
+        let decision = bucket.take(&LIMIT, 60_000);
+        assert_eq!(decision.remaining, 1);
+        assert_eq!(bucket.full_at(&LIMIT), 65_000);
+    }
+}
@@ -0,0 +1,90 @@
+// A named set of permissions that can be assigned to users, e.g. `admin`
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Role(String);
+
+impl Role {
+    pub const ADMIN: &'static str = "admin";
+
+    pub fn parse(role: String) -> Result<Self, String> {
+        if is_identifier(&role) {
+            Ok(Self(role))
+        } else {
+            Err(format!("{} is not a valid role", role))
+        }
+    }
+}
+
+impl AsRef<str> for Role {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+// Permission to perform one action on one kind of resource, written as `resource:action`
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Permission(String);
+
+impl Permission {
+    pub const ROLES_READ: &'static str = "roles:read";
+    pub const ROLES_MANAGE: &'static str = "roles:manage";
+    pub const JWT_KEYS_ROTATE: &'static str = "jwt-keys:rotate";
+    pub const EMAIL_OUTBOX_READ: &'static str = "email-outbox:read";
+
+    pub fn parse(permission: String) -> Result<Self, String> {
+        match permission.split_once(':') {
+            Some((resource, action)) if is_identifier(resource) && is_identifier(action) => {
+                Ok(Self(permission))
+            }
+            _ => Err(format!("{} is not a valid permission", permission)),
+        }
+    }
+}
+
+impl AsRef<str> for Permission {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+// The roles assigned to a user and the permissions they grant together
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UserAccess {
+    pub roles: Vec<Role>,
+    pub permissions: Vec<Permission>,
+}
+
+// A role with the permissions it grants
+#[derive(Debug, Clone, PartialEq)]
+pub struct RoleDefinition {
+    pub role: Role,
+    pub permissions: Vec<Permission>,
+}
+
+fn is_identifier(s: &str) -> bool {
+    !s.is_empty()
+        && s.len() <= 64
+        && s.bytes()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == b'-' || c == b'_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Permission, Role};
+
+    #[test]
+    fn test_role_names() {
+        assert!(Role::parse(Role::ADMIN.to_owned()).is_ok());
+        assert!(Role::parse("support-staff".to_owned()).is_ok());
+        assert!(Role::parse("".to_owned()).is_err());
+        assert!(Role::parse("Admin".to_owned()).is_err());
+        assert!(Role::parse("roles:read".to_owned()).is_err());
+    }
+
+    #[test]
+    fn test_permissions_name_resource_and_action() {
+        assert!(Permission::parse(Permission::JWT_KEYS_ROTATE.to_owned()).is_ok());
+        assert!(Permission::parse("roles".to_owned()).is_err());
+        assert!(Permission::parse("roles:".to_owned()).is_err());
+        assert!(Permission::parse("roles:read:all".to_owned()).is_err());
+    }
+}
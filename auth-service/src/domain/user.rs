@@ -4,5 +4,64 @@ use crate::domain::{Email, Password};
 pub struct User {
     pub email: Email,
     pub password: Password,
-    pub requires_2fa: bool,
+    pub two_fa_method: TwoFAMethod,
+    pub email_verified: bool,
+}
+
+// The second factor a user has to provide after their password
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum TwoFAMethod {
+    #[default]
+    None,
+    // a code sent to the user's email address on every login
+    Email,
+    // a code generated by an authenticator app from a shared secret
+    Totp,
+    // an assertion signed by one of the user's registered WebAuthn credentials
+    WebAuthn,
+}
+
+impl TwoFAMethod {
+    pub fn parse(method: String) -> Result<Self, String> {
+        match method.as_str() {
+            "none" => Ok(Self::None),
+            "email" => Ok(Self::Email),
+            "totp" => Ok(Self::Totp),
+            "webauthn" => Ok(Self::WebAuthn),
+            _ => Err(format!("{} is not a valid 2FA method", method)),
+        }
+    }
+}
+
+impl AsRef<str> for TwoFAMethod {
+    fn as_ref(&self) -> &str {
+        match self {
+            Self::None => "none",
+            Self::Email => "email",
+            Self::Totp => "totp",
+            Self::WebAuthn => "webauthn",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TwoFAMethod;
+
+    #[test]
+    fn test_two_fa_method_round_trips_through_string() {
+        for method in [
+            TwoFAMethod::None,
+            TwoFAMethod::Email,
+            TwoFAMethod::Totp,
+            TwoFAMethod::WebAuthn,
+        ] {
+            assert_eq!(TwoFAMethod::parse(method.as_ref().to_owned()), Ok(method));
+        }
+    }
+
+    #[test]
+    fn test_unknown_two_fa_method_is_invalid() {
+        assert!(TwoFAMethod::parse("sms".to_owned()).is_err());
+    }
 }
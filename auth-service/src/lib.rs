@@ -1,7 +1,9 @@
 use axum::{
-    http::{Method, StatusCode},
+    extract::{connect_info::IntoMakeServiceWithConnectInfo, ConnectInfo},
+    http::{header::RETRY_AFTER, HeaderValue, Method, StatusCode},
+    middleware::{from_fn_with_state, AddExtension},
     response::{IntoResponse, Response},
-    routing::post,
+    routing::{get, post},
     serve::Serve,
     Json, Router,
 };
@@ -10,7 +12,7 @@ use redis::Client;
 use redis::RedisResult;
 use serde::{Deserialize, Serialize};
 use sqlx::{postgres::PgPoolOptions, PgPool};
-use std::error::Error;
+use std::{error::Error, net::SocketAddr};
 use tower_http::{cors::CorsLayer, services::ServeDir};
 
 pub mod domain;
@@ -19,11 +21,21 @@ pub mod services;
 pub mod utils;
 
 use crate::routes::*;
+use crate::utils::{
+    constants::{
+        GLOBAL_RATE_LIMIT, LOGIN_RATE_LIMIT, PASSWORD_RESET_RATE_LIMIT, SIGNUP_RATE_LIMIT,
+        VERIFY_2FA_RATE_LIMIT,
+    },
+    rate_limit::{insert_rate_limit_headers, rate_limit, RateLimiter},
+};
 use app_state::AppState;
 
 //This struct encapsulates our application related logic
 pub struct Application {
-    server: Serve<Router, Router>,
+    server: Serve<
+        IntoMakeServiceWithConnectInfo<Router, SocketAddr>,
+        AddExtension<Router, ConnectInfo<SocketAddr>>,
+    >,
     // address is exposed as a public field.
     // this makes it possible to access address in tests
     pub address: String,
@@ -45,20 +57,101 @@ impl Application {
             .allow_credentials(true)
             .allow_origin(allowed_origins);
 
+        // Throttle every client address across all routes, and the routes open to guessing or abuse
+        // per client address and per email address as well
+        let rate_limit_store = app_state.rate_limit_store.clone();
+        let limiter =
+            |scope, limit| RateLimiter::per_ip_and_email(rate_limit_store.clone(), scope, limit);
+        let global_limiter =
+            RateLimiter::per_ip(rate_limit_store.clone(), "global", *GLOBAL_RATE_LIMIT);
+
+        // Emails queued by the routes are delivered in the background
+        app_state
+            .email_outbox
+            .spawn_worker(app_state.email_client.clone());
+
         // Move the Router definition from main.rs here
         let router = Router::new()
             .nest_service("/", ServeDir::new("assets"))
-            .route("/signup", post(signup))
-            .route("/login", post(login))
+            .route(
+                "/signup",
+                limiter("signup", *SIGNUP_RATE_LIMIT).limit(post(signup)),
+            )
+            .route(
+                "/login",
+                limiter("login", *LOGIN_RATE_LIMIT).limit(post(login)),
+            )
             .route("/logout", post(logout))
-            .route("/verify-2fa", post(verify_2fa))
+            .route(
+                "/verify-2fa",
+                limiter("verify-2fa", *VERIFY_2FA_RATE_LIMIT).limit(post(verify_2fa)),
+            )
+            .route("/resend-2fa", post(resend_2fa))
             .route("/verify-token", post(verify_token))
+            .route("/introspect", post(introspect))
+            .route("/token", post(issue_token))
+            .route("/.well-known/jwks.json", get(jwks))
+            .route("/refresh", post(refresh))
+            .route("/password-reset", get(password_reset_page))
+            .route(
+                "/password-reset/request",
+                limiter("password-reset", *PASSWORD_RESET_RATE_LIMIT)
+                    .limit(post(request_password_reset)),
+            )
+            .route(
+                "/password-reset/confirm",
+                limiter("password-reset", *PASSWORD_RESET_RATE_LIMIT)
+                    .limit(post(confirm_password_reset)),
+            )
+            .route("/verify-email", get(verify_email))
+            .route("/resend-verification", post(resend_verification))
+            .route("/totp/enroll", post(enroll_totp))
+            .route("/totp/confirm", post(confirm_totp))
+            .route(
+                "/webauthn/register/start",
+                post(start_webauthn_registration),
+            )
+            .route(
+                "/webauthn/register/finish",
+                post(finish_webauthn_registration),
+            )
+            .route("/webauthn/login/start", post(start_webauthn_login))
+            .route("/webauthn/login/finish", post(finish_webauthn_login))
+            .route(
+                "/verify-recovery-code",
+                limiter("verify-2fa", *VERIFY_2FA_RATE_LIMIT).limit(post(verify_recovery_code)),
+            )
+            .route(
+                "/recovery-codes/regenerate",
+                post(regenerate_recovery_codes),
+            )
+            .route("/admin/jwt-keys/rotate", post(rotate_jwt_keys))
+            .route("/admin/roles", get(list_roles))
+            .route("/admin/roles/assign", post(assign_role))
+            .route("/admin/roles/revoke", post(revoke_role))
+            .route("/admin/users/:email/roles", get(get_user_roles))
+            .route("/admin/email-outbox", get(get_email_outbox))
+            .route(
+                "/organizations",
+                get(list_organizations).post(create_organization),
+            )
+            .route("/organizations/switch", post(switch_organization))
+            .route(
+                "/organizations/invitations/accept",
+                get(invitation_page).post(accept_invitation),
+            )
+            .route("/organizations/:id/invitations", post(invite_member))
+            .layer(from_fn_with_state(global_limiter, rate_limit))
             .with_state(app_state)
             .layer(cors); // Add CORS config to our Axum router
 
         let listener = tokio::net::TcpListener::bind(address).await?;
         let address = listener.local_addr()?.to_string();
-        let server = axum::serve(listener, router);
+        // Handlers can read the client address, e.g. to throttle failed logins per address
+        let server = axum::serve(
+            listener,
+            router.into_make_service_with_connect_info::<SocketAddr>(),
+        );
 
         // Create a new application instance and return it
         Ok(Application { server, address })
@@ -74,24 +167,43 @@ pub mod app_state {
     use std::sync::Arc;
     use tokio::sync::RwLock;
 
-    use crate::domain::{BannedTokenStore, EmailClient, TwoFACodeStore, UserStore};
+    use crate::domain::{
+        BannedTokenStore, EmailClient, EmailOutboxStore, FailedLoginStore, JwtKeyStore,
+        OneTimeTokenStore, OrganizationStore, RateLimitStore, RecoveryCodeStore, RefreshTokenStore,
+        RoleStore, TotpSecretStore, TwoFACodeStore, UserStore, WebAuthnChallengeStore,
+        WebAuthnCredentialStore,
+    };
+    use crate::utils::{email_outbox::EmailOutbox, jwt::KeyRing};
 
-    // we will use a type alias for representing Arc<RwLock<Box<dyn UserStore>>>
+    // we will use a type alias for representing Arc<dyn UserStore>
     // Wrapping the user store in an Arc allows shared ownership of the underlying store across threads.
     // Calling clone on an Arc produces a new Arc instance which points to the same allocation on the heap as source Arc.
     // Instead of copying the reference data, the reference count is incremented.
 
     // Arc only provides an immutable reference to the underlying data (user store in our case)
-    // It mutation were allowed, it would introduce possibility of data races and inconsistencies between threads.
-    // However, route handlers need mutable access to user store to add / remove users from user store.
-    // To achieve this we need a synchronization primitive and we will be using the RwLock provided by tokio.
-
-    // In Summary, by wrapping the user store in tokio's RwLock smart pointer, the user store can be safely mutated across threads.
-    // By wrapping RwLock<Box<dyn UserStore>> in an Arc smart pointer, the underlying data can be shared across threads while maintaining a single source of truth.
-    pub type UserStoreType = Arc<RwLock<Box<dyn UserStore + Send + Sync>>>;
-    pub type BannedTokenStoreType = Arc<RwLock<dyn BannedTokenStore + Send + Sync>>;
-    pub type TwoFACodeStoreType = Arc<RwLock<dyn TwoFACodeStore + Send + Sync>>;
+    // The user, banned token, 2FA code, recovery code and rate limit stores take `&self` and handle
+    // concurrent calls themselves
+    // (a connection pool, or a lock held only for the lookup), so requests using them never wait for
+    // one another. The other stores still need mutable access, which tokio's RwLock provides.
+
+    // In Summary, by wrapping RwLock<dyn Store> in an Arc smart pointer, the underlying data can be shared across threads while maintaining a single source of truth.
+    pub type UserStoreType = Arc<dyn UserStore + Send + Sync>;
+    pub type BannedTokenStoreType = Arc<dyn BannedTokenStore + Send + Sync>;
+    pub type TwoFACodeStoreType = Arc<dyn TwoFACodeStore + Send + Sync>;
     pub type EmailClientType = Arc<RwLock<dyn EmailClient + Send + Sync>>;
+    pub type RefreshTokenStoreType = Arc<RwLock<dyn RefreshTokenStore + Send + Sync>>;
+    pub type OneTimeTokenStoreType = Arc<RwLock<dyn OneTimeTokenStore + Send + Sync>>;
+    pub type TotpSecretStoreType = Arc<RwLock<dyn TotpSecretStore + Send + Sync>>;
+    pub type WebAuthnCredentialStoreType = Arc<RwLock<dyn WebAuthnCredentialStore + Send + Sync>>;
+    pub type WebAuthnChallengeStoreType = Arc<RwLock<dyn WebAuthnChallengeStore + Send + Sync>>;
+    pub type RecoveryCodeStoreType = Arc<dyn RecoveryCodeStore + Send + Sync>;
+    pub type KeyRingType = Arc<RwLock<KeyRing>>;
+    pub type JwtKeyStoreType = Arc<dyn JwtKeyStore + Send + Sync>;
+    pub type RoleStoreType = Arc<RwLock<dyn RoleStore + Send + Sync>>;
+    pub type OrganizationStoreType = Arc<RwLock<dyn OrganizationStore + Send + Sync>>;
+    pub type FailedLoginStoreType = Arc<RwLock<dyn FailedLoginStore + Send + Sync>>;
+    pub type RateLimitStoreType = Arc<dyn RateLimitStore + Send + Sync>;
+    pub type EmailOutboxStoreType = Arc<RwLock<dyn EmailOutboxStore + Send + Sync>>;
 
     #[derive(Clone)]
     // AppState derives the Clone trait
@@ -102,20 +214,60 @@ pub mod app_state {
         pub banned_token_store: BannedTokenStoreType,
         pub two_fa_code_store: TwoFACodeStoreType,
         pub email_client: EmailClientType,
+        pub refresh_token_store: RefreshTokenStoreType,
+        pub password_reset_token_store: OneTimeTokenStoreType,
+        pub email_verification_token_store: OneTimeTokenStoreType,
+        pub totp_secret_store: TotpSecretStoreType,
+        pub webauthn_credential_store: WebAuthnCredentialStoreType,
+        pub webauthn_challenge_store: WebAuthnChallengeStoreType,
+        pub recovery_code_store: RecoveryCodeStoreType,
+        pub key_ring: KeyRingType,
+        pub role_store: RoleStoreType,
+        pub organization_store: OrganizationStoreType,
+        pub failed_login_store: FailedLoginStoreType,
+        pub rate_limit_store: RateLimitStoreType,
+        pub email_outbox: EmailOutbox,
     }
 
     impl AppState {
+        #[allow(clippy::too_many_arguments)]
         pub fn new(
             user_store: UserStoreType,
             banned_token_store: BannedTokenStoreType,
             two_fa_code_store: TwoFACodeStoreType,
             email_client: EmailClientType,
+            refresh_token_store: RefreshTokenStoreType,
+            password_reset_token_store: OneTimeTokenStoreType,
+            email_verification_token_store: OneTimeTokenStoreType,
+            totp_secret_store: TotpSecretStoreType,
+            webauthn_credential_store: WebAuthnCredentialStoreType,
+            webauthn_challenge_store: WebAuthnChallengeStoreType,
+            recovery_code_store: RecoveryCodeStoreType,
+            key_ring: KeyRingType,
+            role_store: RoleStoreType,
+            organization_store: OrganizationStoreType,
+            failed_login_store: FailedLoginStoreType,
+            rate_limit_store: RateLimitStoreType,
+            email_outbox: EmailOutbox,
         ) -> Self {
             Self {
                 user_store,
                 banned_token_store,
                 two_fa_code_store,
                 email_client,
+                refresh_token_store,
+                password_reset_token_store,
+                email_verification_token_store,
+                totp_secret_store,
+                webauthn_credential_store,
+                webauthn_challenge_store,
+                recovery_code_store,
+                key_ring,
+                role_store,
+                organization_store,
+                failed_login_store,
+                rate_limit_store,
+                email_outbox,
             }
         }
     }
@@ -128,6 +280,19 @@ pub struct ErrorResponse {
 
 impl IntoResponse for AuthAPIError {
     fn into_response(self) -> Response {
+        let retry_after = match self {
+            AuthAPIError::AccountLocked(seconds) | AuthAPIError::ResendCooldown(seconds) => {
+                Some(seconds)
+            }
+            AuthAPIError::RateLimited(decision) => Some(decision.retry_after_seconds),
+            _ => None,
+        };
+
+        let rate_limit = match self {
+            AuthAPIError::RateLimited(decision) => Some(decision),
+            _ => None,
+        };
+
         let (status, error_message) = match self {
             AuthAPIError::InvalidCredentials => (StatusCode::BAD_REQUEST, "Invalid Credentials"),
             AuthAPIError::UserAlreadyExists => (StatusCode::CONFLICT, "User already exists"),
@@ -139,13 +304,39 @@ impl IntoResponse for AuthAPIError {
             }
             AuthAPIError::InvalidToken => (StatusCode::UNAUTHORIZED, "InvalidToken"),
             AuthAPIError::MissingToken => (StatusCode::BAD_REQUEST, "MissingToken"),
+            AuthAPIError::EmailNotVerified => (StatusCode::FORBIDDEN, "EmailNotVerified"),
+            AuthAPIError::TotpAlreadyEnabled => (StatusCode::CONFLICT, "TotpAlreadyEnabled"),
+            AuthAPIError::TwoFANotEnabled => (StatusCode::BAD_REQUEST, "TwoFANotEnabled"),
+            AuthAPIError::InvalidClient => (StatusCode::UNAUTHORIZED, "InvalidClient"),
+            AuthAPIError::MissingPermission => (StatusCode::FORBIDDEN, "MissingPermission"),
+            AuthAPIError::UserNotFound => (StatusCode::NOT_FOUND, "UserNotFound"),
+            AuthAPIError::RoleNotFound => (StatusCode::NOT_FOUND, "RoleNotFound"),
+            AuthAPIError::OrganizationNotFound => (StatusCode::NOT_FOUND, "OrganizationNotFound"),
+            AuthAPIError::AlreadyMember => (StatusCode::CONFLICT, "AlreadyMember"),
+            AuthAPIError::AccountLocked(_) => (StatusCode::TOO_MANY_REQUESTS, "AccountLocked"),
+            AuthAPIError::ResendCooldown(_) => (StatusCode::TOO_MANY_REQUESTS, "ResendCooldown"),
+            AuthAPIError::TooManyResends => (StatusCode::TOO_MANY_REQUESTS, "TooManyResends"),
+            AuthAPIError::RateLimited(_) => (StatusCode::TOO_MANY_REQUESTS, "RateLimited"),
         };
 
         let body = Json(ErrorResponse {
             error: error_message.to_string(),
         });
 
-        (status, body).into_response()
+        let mut response = (status, body).into_response();
+
+        // Tell the client when it may try again
+        if let Some(seconds) = retry_after {
+            response
+                .headers_mut()
+                .insert(RETRY_AFTER, HeaderValue::from(seconds));
+        }
+
+        if let Some(decision) = rate_limit {
+            insert_rate_limit_headers(response.headers_mut(), &decision);
+        }
+
+        response
     }
 }
 
@@ -155,6 +346,24 @@ pub async fn get_postgres_pool(url: &str) -> Result<PgPool, sqlx::Error> {
     PgPoolOptions::new().max_connections(5).connect(url).await
 }
 
+// Creates the database file if needed. WAL lets reads carry on while another connection writes,
+// and writers wait for each other instead of failing with SQLITE_BUSY.
+#[cfg(feature = "sqlite")]
+pub async fn get_sqlite_pool(url: &str) -> Result<sqlx::SqlitePool, sqlx::Error> {
+    use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions};
+    use std::{str::FromStr, time::Duration};
+
+    let options = SqliteConnectOptions::from_str(url)?
+        .create_if_missing(true)
+        .journal_mode(SqliteJournalMode::Wal)
+        .busy_timeout(Duration::from_secs(5));
+
+    SqlitePoolOptions::new()
+        .max_connections(5)
+        .connect_with(options)
+        .await
+}
+
 // Add get_redis_client helper function
 pub fn get_redis_client(redis_hostname: String) -> RedisResult<Client> {
     let redis_url = format!("redis://{}/", redis_hostname);
@@ -1,50 +1,123 @@
-use auth_service::services::MockEmailClient;
+use auth_service::services::{
+    HttpEmailClient, HttpEmailConfig, HttpRetryPolicy, MockEmailClient, SmtpEmailClient, SmtpTls,
+};
 use auth_service::utils::DATABASE_URL;
+use redis::aio::ConnectionManager;
 use sqlx::PgPool;
+use std::future::Future;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
-use auth_service::app_state::AppState;
-use auth_service::domain::UserStore;
+use auth_service::app_state::{
+    AppState, BannedTokenStoreType, EmailClientType, FailedLoginStoreType, JwtKeyStoreType,
+    KeyRingType, OneTimeTokenStoreType, RateLimitStoreType, RefreshTokenStoreType,
+    TwoFACodeStoreType, WebAuthnChallengeStoreType,
+};
+use auth_service::domain::{EmailProvider, EphemeralStoreBackend, OneTimeTokenPurpose};
 use auth_service::{get_postgres_pool, get_redis_client};
 use auth_service::{
     services::{
-        postgres_user_store::PostgresUserStore, redis_banned_token_store::RedisBannedTokenStore,
+        postgres_banned_token_store::PostgresBannedTokenStore,
+        postgres_email_outbox_store::PostgresEmailOutboxStore,
+        postgres_failed_login_store::PostgresFailedLoginStore,
+        postgres_jwt_key_store::PostgresJwtKeyStore,
+        postgres_one_time_token_store::PostgresOneTimeTokenStore,
+        postgres_organization_store::PostgresOrganizationStore,
+        postgres_rate_limit_store::PostgresRateLimitStore,
+        postgres_recovery_code_store::PostgresRecoveryCodeStore,
+        postgres_refresh_token_store::PostgresRefreshTokenStore,
+        postgres_role_store::PostgresRoleStore,
+        postgres_totp_secret_store::PostgresTotpSecretStore,
+        postgres_two_fa_code_store::PostgresTwoFACodeStore, postgres_user_store::PostgresUserStore,
+        postgres_webauthn_challenge_store::PostgresWebAuthnChallengeStore,
+        postgres_webauthn_credential_store::PostgresWebAuthnCredentialStore,
+        redis_banned_token_store::RedisBannedTokenStore,
+        redis_failed_login_store::RedisFailedLoginStore,
+        redis_one_time_token_store::RedisOneTimeTokenStore,
+        redis_rate_limit_store::RedisRateLimitStore,
+        redis_refresh_token_store::RedisRefreshTokenStore,
         redis_two_fa_code_store::RedisTwoFACodeStore,
+        redis_webauthn_challenge_store::RedisWebAuthnChallengeStore,
+    },
+    utils::{
+        constants::{
+            prod, EMAIL_API_CONNECT_TIMEOUT, EMAIL_API_MAX_RETRIES, EMAIL_API_TIMEOUT,
+            EMAIL_API_TOKEN, EMAIL_API_URL, EMAIL_PROVIDER, EMAIL_SENDER, EPHEMERAL_STORE,
+            EXPIRED_ROWS_PURGE_INTERVAL, JWT_KEY_REFRESH_INTERVAL, JWT_SIGNING_KEY,
+            JWT_VERIFICATION_KEYS, REDIS_HOST_NAME, SMTP_HOST, SMTP_PASSWORD, SMTP_PORT, SMTP_TLS,
+            SMTP_USERNAME, SQLITE_DATABASE_URL,
+        },
+        email_outbox::{EmailOutbox, EmailRetryPolicy},
+        jwt::{refresh_key_ring, KeyRing},
     },
-    utils::constants::{prod, REDIS_HOST_NAME},
     Application,
 };
 
 #[tokio::main]
 async fn main() {
-    let pg_pool = configure_postgresql().await;
-    let redis_connection = Arc::new(RwLock::new(configure_redis()));
+    // Load the signing and verification keys up front so a misconfigured key stops the service from starting
+    let key_ring = KeyRing::new(JWT_SIGNING_KEY.clone(), JWT_VERIFICATION_KEYS.clone());
+    let email_client = configure_email_client();
 
-    let user_store: Box<dyn UserStore + Send + Sync> =
-        Box::new(PostgresUserStore { pool: pg_pool });
-    let user_store = Arc::new(RwLock::new(user_store));
+    // SQLITE_DATABASE_URL keeps everything in one SQLite file, the service then connects to
+    // neither PostgreSQL nor Redis
+    let app_state = match SQLITE_DATABASE_URL.as_deref() {
+        Some(url) => configure_sqlite_app_state(url, key_ring, email_client).await,
+        None => configure_postgres_app_state(key_ring, email_client).await,
+    };
 
-    let banned_token_store = Arc::new(RwLock::new(RedisBannedTokenStore::new(
-        redis_connection.clone(),
-    )));
+    let app = Application::build(app_state, prod::APP_ADDRESS)
+        .await
+        .expect("Failed to build app");
 
-    let two_fa_code_store = Arc::new(RwLock::new(RedisTwoFACodeStore::new(redis_connection)));
+    app.run().await.expect("Failed to run the app");
+}
 
-    let email_client = Arc::new(RwLock::new(MockEmailClient));
+async fn configure_postgres_app_state(
+    key_ring: KeyRing,
+    email_client: EmailClientType,
+) -> AppState {
+    let pg_pool = configure_postgresql().await;
+    let key_ring = configure_key_ring(
+        key_ring,
+        Arc::new(PostgresJwtKeyStore::new(pg_pool.clone())),
+    )
+    .await;
 
-    let app_state = AppState::new(
-        user_store,
-        banned_token_store,
-        two_fa_code_store,
-        email_client,
+    let totp_secret_store = Arc::new(RwLock::new(PostgresTotpSecretStore::new(pg_pool.clone())));
+    let webauthn_credential_store = Arc::new(RwLock::new(PostgresWebAuthnCredentialStore::new(
+        pg_pool.clone(),
+    )));
+    let recovery_code_store = Arc::new(PostgresRecoveryCodeStore::new(pg_pool.clone()));
+    let role_store = Arc::new(RwLock::new(PostgresRoleStore::new(pg_pool.clone())));
+    let organization_store = Arc::new(RwLock::new(PostgresOrganizationStore::new(pg_pool.clone())));
+    // Queued emails live in Postgres so they survive restarts and every instance can deliver them
+    let email_outbox = EmailOutbox::new(
+        Arc::new(RwLock::new(PostgresEmailOutboxStore::new(pg_pool.clone()))),
+        EmailRetryPolicy::default(),
     );
 
-    let app = Application::build(app_state, prod::APP_ADDRESS)
-        .await
-        .expect("Failed to build app");
+    let ephemeral_stores = configure_ephemeral_stores(&pg_pool).await;
 
-    app.run().await.expect("Failed to run the app");
+    AppState::new(
+        Arc::new(PostgresUserStore::new(pg_pool)),
+        ephemeral_stores.banned_token_store,
+        ephemeral_stores.two_fa_code_store,
+        email_client,
+        ephemeral_stores.refresh_token_store,
+        ephemeral_stores.password_reset_token_store,
+        ephemeral_stores.email_verification_token_store,
+        totp_secret_store,
+        webauthn_credential_store,
+        ephemeral_stores.webauthn_challenge_store,
+        recovery_code_store,
+        key_ring,
+        role_store,
+        organization_store,
+        ephemeral_stores.failed_login_store,
+        ephemeral_stores.rate_limit_store,
+        email_outbox,
+    )
 }
 
 async fn configure_postgresql() -> PgPool {
@@ -62,9 +135,319 @@ async fn configure_postgresql() -> PgPool {
     pg_pool
 }
 
-fn configure_redis() -> redis::Connection {
+// Rotated keys are shared through the store. Every instance loads them on startup and reloads them
+// periodically, and sooner when a token names a key it does not know yet.
+async fn configure_key_ring(key_ring: KeyRing, store: JwtKeyStoreType) -> KeyRingType {
+    let key_ring = Arc::new(RwLock::new(key_ring.with_store(store)));
+
+    refresh_key_ring(&key_ring)
+        .await
+        .expect("Failed to load the JWT signing keys");
+
+    tokio::spawn({
+        let key_ring = key_ring.clone();
+        async move {
+            let mut interval = tokio::time::interval(*JWT_KEY_REFRESH_INTERVAL);
+            loop {
+                interval.tick().await;
+                // a failed refresh keeps the keys loaded last, the next tick tries again
+                let _ = refresh_key_ring(&key_ring).await;
+            }
+        }
+    });
+
+    key_ring
+}
+
+// The short-lived stores, whose entries all expire
+struct EphemeralStores {
+    banned_token_store: BannedTokenStoreType,
+    two_fa_code_store: TwoFACodeStoreType,
+    refresh_token_store: RefreshTokenStoreType,
+    password_reset_token_store: OneTimeTokenStoreType,
+    email_verification_token_store: OneTimeTokenStoreType,
+    webauthn_challenge_store: WebAuthnChallengeStoreType,
+    failed_login_store: FailedLoginStoreType,
+    rate_limit_store: RateLimitStoreType,
+}
+
+// EPHEMERAL_STORE picks where the short-lived stores live. Redis is only connected to when they
+// live there, so a Postgres deployment runs without it.
+async fn configure_ephemeral_stores(pg_pool: &PgPool) -> EphemeralStores {
+    match *EPHEMERAL_STORE {
+        EphemeralStoreBackend::Redis => {
+            let redis_connection = configure_redis().await;
+
+            EphemeralStores {
+                banned_token_store: Arc::new(RedisBannedTokenStore::new(redis_connection.clone())),
+                two_fa_code_store: Arc::new(RedisTwoFACodeStore::new(redis_connection.clone())),
+                refresh_token_store: Arc::new(RwLock::new(RedisRefreshTokenStore::new(
+                    redis_connection.clone(),
+                ))),
+                password_reset_token_store: Arc::new(RwLock::new(RedisOneTimeTokenStore::new(
+                    redis_connection.clone(),
+                    OneTimeTokenPurpose::PasswordReset,
+                ))),
+                email_verification_token_store: Arc::new(RwLock::new(RedisOneTimeTokenStore::new(
+                    redis_connection.clone(),
+                    OneTimeTokenPurpose::EmailVerification,
+                ))),
+                webauthn_challenge_store: Arc::new(RwLock::new(RedisWebAuthnChallengeStore::new(
+                    redis_connection.clone(),
+                ))),
+                failed_login_store: Arc::new(RwLock::new(RedisFailedLoginStore::new(
+                    redis_connection.clone(),
+                ))),
+                // Buckets live in Redis so every instance of the service enforces the same limits
+                rate_limit_store: Arc::new(RedisRateLimitStore::new(redis_connection)),
+            }
+        }
+        EphemeralStoreBackend::Postgres => {
+            spawn_expired_rows_purge({
+                let pg_pool = pg_pool.clone();
+                move || delete_expired_postgres_rows(pg_pool.clone())
+            });
+
+            EphemeralStores {
+                banned_token_store: Arc::new(PostgresBannedTokenStore::new(pg_pool.clone())),
+                two_fa_code_store: Arc::new(PostgresTwoFACodeStore::new(pg_pool.clone())),
+                refresh_token_store: Arc::new(RwLock::new(PostgresRefreshTokenStore::new(
+                    pg_pool.clone(),
+                ))),
+                password_reset_token_store: Arc::new(RwLock::new(PostgresOneTimeTokenStore::new(
+                    pg_pool.clone(),
+                    OneTimeTokenPurpose::PasswordReset,
+                ))),
+                email_verification_token_store: Arc::new(RwLock::new(
+                    PostgresOneTimeTokenStore::new(
+                        pg_pool.clone(),
+                        OneTimeTokenPurpose::EmailVerification,
+                    ),
+                )),
+                webauthn_challenge_store: Arc::new(RwLock::new(
+                    PostgresWebAuthnChallengeStore::new(pg_pool.clone()),
+                )),
+                failed_login_store: Arc::new(RwLock::new(PostgresFailedLoginStore::new(
+                    pg_pool.clone(),
+                ))),
+                rate_limit_store: Arc::new(PostgresRateLimitStore::new(pg_pool.clone())),
+            }
+        }
+    }
+}
+
+// The purge uses stores of its own, so it never waits for the locks of the ones serving requests
+async fn delete_expired_postgres_rows(pg_pool: PgPool) {
+    let _ = PostgresBannedTokenStore::new(pg_pool.clone())
+        .delete_expired()
+        .await;
+    let _ = PostgresTwoFACodeStore::new(pg_pool.clone())
+        .delete_expired()
+        .await;
+    let _ = PostgresRefreshTokenStore::new(pg_pool.clone())
+        .delete_expired()
+        .await;
+    for purpose in [
+        OneTimeTokenPurpose::PasswordReset,
+        OneTimeTokenPurpose::EmailVerification,
+    ] {
+        let _ = PostgresOneTimeTokenStore::new(pg_pool.clone(), purpose)
+            .delete_expired()
+            .await;
+    }
+    let _ = PostgresWebAuthnChallengeStore::new(pg_pool.clone())
+        .delete_expired()
+        .await;
+    let _ = PostgresFailedLoginStore::new(pg_pool.clone())
+        .delete_expired()
+        .await;
+    let _ = PostgresRateLimitStore::new(pg_pool).delete_expired().await;
+}
+
+// Every store lives in the one file, so the users' TOTP secrets, passkeys, recovery codes, roles and
+// memberships reference them in the same database
+#[cfg(feature = "sqlite")]
+async fn configure_sqlite_app_state(
+    url: &str,
+    key_ring: KeyRing,
+    email_client: EmailClientType,
+) -> AppState {
+    use auth_service::{
+        get_sqlite_pool,
+        services::{
+            sqlite_banned_token_store::SqliteBannedTokenStore,
+            sqlite_email_outbox_store::SqliteEmailOutboxStore,
+            sqlite_failed_login_store::SqliteFailedLoginStore,
+            sqlite_jwt_key_store::SqliteJwtKeyStore,
+            sqlite_one_time_token_store::SqliteOneTimeTokenStore,
+            sqlite_organization_store::SqliteOrganizationStore,
+            sqlite_rate_limit_store::SqliteRateLimitStore,
+            sqlite_recovery_code_store::SqliteRecoveryCodeStore,
+            sqlite_refresh_token_store::SqliteRefreshTokenStore,
+            sqlite_role_store::SqliteRoleStore, sqlite_totp_secret_store::SqliteTotpSecretStore,
+            sqlite_two_fa_code_store::SqliteTwoFACodeStore, sqlite_user_store::SqliteUserStore,
+            sqlite_webauthn_challenge_store::SqliteWebAuthnChallengeStore,
+            sqlite_webauthn_credential_store::SqliteWebAuthnCredentialStore,
+        },
+    };
+
+    let pool = get_sqlite_pool(url)
+        .await
+        .expect("Failed to open the SQLite database!");
+
+    sqlx::migrate!("./migrations_sqlite")
+        .run(&pool)
+        .await
+        .expect("Failed to run SQLite migrations");
+
+    let key_ring =
+        configure_key_ring(key_ring, Arc::new(SqliteJwtKeyStore::new(pool.clone()))).await;
+
+    spawn_expired_rows_purge({
+        let pool = pool.clone();
+        move || delete_expired_sqlite_rows(pool.clone())
+    });
+
+    let email_outbox = EmailOutbox::new(
+        Arc::new(RwLock::new(SqliteEmailOutboxStore::new(pool.clone()))),
+        EmailRetryPolicy::default(),
+    );
+
+    AppState::new(
+        Arc::new(SqliteUserStore::new(pool.clone())),
+        Arc::new(SqliteBannedTokenStore::new(pool.clone())),
+        Arc::new(SqliteTwoFACodeStore::new(pool.clone())),
+        email_client,
+        Arc::new(RwLock::new(SqliteRefreshTokenStore::new(pool.clone()))),
+        Arc::new(RwLock::new(SqliteOneTimeTokenStore::new(
+            pool.clone(),
+            OneTimeTokenPurpose::PasswordReset,
+        ))),
+        Arc::new(RwLock::new(SqliteOneTimeTokenStore::new(
+            pool.clone(),
+            OneTimeTokenPurpose::EmailVerification,
+        ))),
+        Arc::new(RwLock::new(SqliteTotpSecretStore::new(pool.clone()))),
+        Arc::new(RwLock::new(SqliteWebAuthnCredentialStore::new(
+            pool.clone(),
+        ))),
+        Arc::new(RwLock::new(SqliteWebAuthnChallengeStore::new(pool.clone()))),
+        Arc::new(SqliteRecoveryCodeStore::new(pool.clone())),
+        key_ring,
+        Arc::new(RwLock::new(SqliteRoleStore::new(pool.clone()))),
+        Arc::new(RwLock::new(SqliteOrganizationStore::new(pool.clone()))),
+        Arc::new(RwLock::new(SqliteFailedLoginStore::new(pool.clone()))),
+        Arc::new(SqliteRateLimitStore::new(pool)),
+        email_outbox,
+    )
+}
+
+// Like the Postgres purge, with stores of its own
+#[cfg(feature = "sqlite")]
+async fn delete_expired_sqlite_rows(pool: sqlx::SqlitePool) {
+    use auth_service::services::{
+        sqlite_banned_token_store::SqliteBannedTokenStore,
+        sqlite_failed_login_store::SqliteFailedLoginStore,
+        sqlite_one_time_token_store::SqliteOneTimeTokenStore,
+        sqlite_rate_limit_store::SqliteRateLimitStore,
+        sqlite_refresh_token_store::SqliteRefreshTokenStore,
+        sqlite_two_fa_code_store::SqliteTwoFACodeStore,
+        sqlite_webauthn_challenge_store::SqliteWebAuthnChallengeStore,
+    };
+
+    let _ = SqliteBannedTokenStore::new(pool.clone())
+        .delete_expired()
+        .await;
+    let _ = SqliteTwoFACodeStore::new(pool.clone())
+        .delete_expired()
+        .await;
+    let _ = SqliteRefreshTokenStore::new(pool.clone())
+        .delete_expired()
+        .await;
+    for purpose in [
+        OneTimeTokenPurpose::PasswordReset,
+        OneTimeTokenPurpose::EmailVerification,
+    ] {
+        let _ = SqliteOneTimeTokenStore::new(pool.clone(), purpose)
+            .delete_expired()
+            .await;
+    }
+    let _ = SqliteWebAuthnChallengeStore::new(pool.clone())
+        .delete_expired()
+        .await;
+    let _ = SqliteFailedLoginStore::new(pool.clone())
+        .delete_expired()
+        .await;
+    let _ = SqliteRateLimitStore::new(pool).delete_expired().await;
+}
+
+#[cfg(not(feature = "sqlite"))]
+async fn configure_sqlite_app_state(
+    _url: &str,
+    _key_ring: KeyRing,
+    _email_client: EmailClientType,
+) -> AppState {
+    panic!("SQLITE_DATABASE_URL is set but the service was built without the sqlite feature.");
+}
+
+// SQL databases do not expire rows by themselves, so expired ones are deleted periodically. Reads
+// already ignore them, a failed purge is retried on the next tick.
+fn spawn_expired_rows_purge<F, Fut>(delete_expired: F)
+where
+    F: Fn() -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send,
+{
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(*EXPIRED_ROWS_PURGE_INTERVAL);
+        loop {
+            interval.tick().await;
+            delete_expired().await;
+        }
+    });
+}
+
+// EMAIL_PROVIDER picks how emails go out, a provider missing its settings stops the service from starting
+fn configure_email_client() -> EmailClientType {
+    match *EMAIL_PROVIDER {
+        EmailProvider::Mock => Arc::new(RwLock::new(MockEmailClient)),
+        EmailProvider::Smtp => {
+            let host = SMTP_HOST.as_ref().expect("SMTP_HOST must be set.");
+            let tls = SmtpTls::parse(&SMTP_TLS).expect("Invalid SMTP_TLS");
+            let credentials = SMTP_USERNAME.clone().zip(SMTP_PASSWORD.clone());
+
+            let email_client =
+                SmtpEmailClient::new(host, *SMTP_PORT, tls, credentials, &EMAIL_SENDER)
+                    .expect("Failed to create SMTP email client");
+
+            Arc::new(RwLock::new(email_client))
+        }
+        EmailProvider::Http => {
+            let email_client = HttpEmailClient::new(HttpEmailConfig {
+                base_url: EMAIL_API_URL.clone().expect("EMAIL_API_URL must be set."),
+                api_token: EMAIL_API_TOKEN
+                    .clone()
+                    .expect("EMAIL_API_TOKEN must be set."),
+                sender: EMAIL_SENDER.clone(),
+                timeout: *EMAIL_API_TIMEOUT,
+                connect_timeout: *EMAIL_API_CONNECT_TIMEOUT,
+                retry_policy: HttpRetryPolicy {
+                    max_retries: *EMAIL_API_MAX_RETRIES,
+                    ..HttpRetryPolicy::default()
+                },
+            })
+            .expect("Failed to create HTTP email client");
+
+            Arc::new(RwLock::new(email_client))
+        }
+    }
+}
+
+// One multiplexed connection shared by every Redis store. It reconnects on its own, so the service
+// carries on once Redis is back after a restart.
+async fn configure_redis() -> ConnectionManager {
     get_redis_client(REDIS_HOST_NAME.to_owned())
         .expect("Failed to get Redis client")
-        .get_connection()
+        .get_connection_manager()
+        .await
         .expect("Failed to get Redis connection")
 }
@@ -0,0 +1,69 @@
+use axum::{extract::State, Json};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, OutboxEmail},
+    utils::permissions::{ReadEmailOutbox, RequirePermission},
+};
+
+// Emails that failed at least once, so operators can tell a mail provider outage from a bad
+// address. Bodies are left out, they may hold 2FA codes and one-time links.
+pub async fn get_email_outbox(
+    State(state): State<AppState>,
+    _: RequirePermission<ReadEmailOutbox>,
+) -> Result<Json<EmailOutboxResponse>, AuthAPIError> {
+    let store = state.email_outbox.store.read().await;
+
+    let pending = store
+        .count_pending()
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    let stuck = store
+        .get_stuck()
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?
+        .iter()
+        .map(OutboxEmailResponse::new)
+        .collect();
+
+    Ok(Json(EmailOutboxResponse { pending, stuck }))
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct EmailOutboxResponse {
+    pub pending: u64,
+    pub stuck: Vec<OutboxEmailResponse>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct OutboxEmailResponse {
+    pub id: String,
+    pub recipient: String,
+    pub subject: String,
+    pub status: String,
+    pub attempts: u32,
+    #[serde(rename = "lastError")]
+    pub last_error: Option<String>,
+    // unix timestamps in seconds
+    #[serde(rename = "nextAttemptAt")]
+    pub next_attempt_at: i64,
+    #[serde(rename = "createdAt")]
+    pub created_at: i64,
+}
+
+impl OutboxEmailResponse {
+    fn new(email: &OutboxEmail) -> Self {
+        Self {
+            id: email.id.to_string(),
+            recipient: email.recipient.as_ref().to_owned(),
+            subject: email.message.subject.clone(),
+            status: email.status.as_ref().to_owned(),
+            attempts: email.attempts,
+            last_error: email.last_error.clone(),
+            next_attempt_at: email.next_attempt_at,
+            created_at: email.created_at,
+        }
+    }
+}
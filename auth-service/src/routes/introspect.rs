@@ -0,0 +1,84 @@
+use axum::{extract::State, http::HeaderMap, Form, Json};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::AuthAPIError,
+    utils::{auth::authenticate_client, validate_token},
+};
+
+// RFC 7662 token introspection. Lets resource servers learn who a token belongs to and when it
+// expires. Only registered clients may ask, so the endpoint cannot be used to probe tokens.
+pub async fn introspect(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Form(request): Form<IntrospectionRequest>,
+) -> Result<Json<IntrospectionResponse>, AuthAPIError> {
+    let client_id = authenticate_client(&headers)?;
+
+    // A token is only described to the consumer it was issued for, the client id being its audience.
+    // Expired, revoked, malformed and others' tokens are all reported the same way, without the reason.
+    let claims = match validate_token(
+        &request.token,
+        &client_id,
+        state.key_ring.clone(),
+        state.banned_token_store.clone(),
+    )
+    .await
+    {
+        Ok(claims) => claims,
+        Err(_) => return Ok(Json(IntrospectionResponse::default())),
+    };
+
+    Ok(Json(IntrospectionResponse {
+        active: true,
+        // the permissions granted by the user's roles, space separated as in OAuth 2.0
+        scope: Some(claims.permissions.join(" ")),
+        token_type: Some("Bearer".to_owned()),
+        username: Some(claims.sub.clone()),
+        sub: Some(claims.sub),
+        exp: Some(claims.exp),
+        iat: Some(claims.iat),
+        nbf: Some(claims.nbf),
+        jti: Some(claims.jti),
+        iss: Some(claims.iss),
+        aud: Some(claims.aud),
+        org_id: claims.org_id,
+        org_role: claims.org_role,
+    }))
+}
+
+// Only access tokens can be introspected, so a `token_type_hint` is accepted and ignored
+#[derive(Deserialize)]
+pub struct IntrospectionRequest {
+    pub token: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct IntrospectionResponse {
+    pub active: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sub: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exp: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub iat: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nbf: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jti: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub iss: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub aud: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub org_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub org_role: Option<String>,
+}
@@ -0,0 +1,26 @@
+use axum::{
+    extract::State,
+    http::{header, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+
+use crate::app_state::AppState;
+
+// Consumers cache the key set, a rotated-in signing key shows up within this time
+const JWKS_MAX_AGE_SECONDS: u32 = 300; // 5 minutes
+
+// Publish the public keys tokens are signed with so other services can verify them locally.
+// Keys rotated out stay listed until the tokens signed with them have expired.
+pub async fn jwks(State(state): State<AppState>) -> impl IntoResponse {
+    let jwks = state.key_ring.read().await.jwks();
+
+    (
+        StatusCode::OK,
+        [(
+            header::CACHE_CONTROL,
+            format!("public, max-age={}", JWKS_MAX_AGE_SECONDS),
+        )],
+        Json(jwks),
+    )
+}
@@ -0,0 +1,64 @@
+use axum::{extract::State, http::StatusCode, Json};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::AuthAPIError,
+    utils::{
+        auth::TOKEN_TTL_SECONDS,
+        constants::{JWT_KEY_REFRESH_INTERVAL, JWT_LEEWAY_SECONDS},
+        jwt::{rotate_key_ring, SigningKey},
+        permissions::{RequirePermission, RotateJwtKeys},
+    },
+};
+
+// Promote a new signing key on every instance. The previous one only verifies from now on and is
+// retired once every token it signed has expired, clock skew leeway included. Instances that have
+// not reloaded the keys yet may still sign with it for up to one refresh interval.
+pub async fn rotate_jwt_keys(
+    State(state): State<AppState>,
+    _: RequirePermission<RotateJwtKeys>,
+    Json(request): Json<RotateJwtKeysRequest>,
+) -> Result<(StatusCode, Json<RotateJwtKeysResponse>), AuthAPIError> {
+    // Without a key in the request a new one is generated for the algorithm in use, so consumers
+    // pinned to an algorithm keep working. RSA keys cannot be generated here.
+    let new_key = match request.private_key {
+        Some(pem) => SigningKey::from_pem(&pem),
+        None => SigningKey::generate(state.key_ring.read().await.active().algorithm()),
+    }
+    .map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    let kid = new_key.kid().to_owned();
+    let retire_at = Utc::now().timestamp()
+        + TOKEN_TTL_SECONDS
+        + *JWT_LEEWAY_SECONDS as i64
+        + JWT_KEY_REFRESH_INTERVAL.as_secs() as i64;
+
+    let retired_kid = rotate_key_ring(&state.key_ring, new_key, retire_at)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    let response = RotateJwtKeysResponse {
+        kid,
+        retired_kid,
+        retire_at,
+    };
+
+    Ok((StatusCode::OK, Json(response)))
+}
+
+#[derive(Deserialize)]
+pub struct RotateJwtKeysRequest {
+    #[serde(rename = "privateKey")]
+    pub private_key: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct RotateJwtKeysResponse {
+    pub kid: String,
+    #[serde(rename = "retiredKid")]
+    pub retired_kid: String,
+    #[serde(rename = "retireAt")]
+    pub retire_at: i64,
+}
@@ -1,14 +1,31 @@
+use std::net::{IpAddr, SocketAddr};
+
 use crate::{
     app_state::AppState,
-    domain::{AuthAPIError, Email, LoginAttemptId, Password, TwoFACode, UserStoreError},
-    utils::auth::generate_auth_cookie,
+    domain::{
+        AuthAPIError, Email, EmailIdempotencyKey, FailedLoginKey, LoginAttemptId, OrganizationId,
+        Password, TwoFACode, TwoFAMethod, UserStoreError,
+    },
+    routes::organizations::requested_organization,
+    utils::{
+        auth::{generate_auth_cookie, generate_refresh_cookie, MAX_LOGIN_LOCKOUT_SECONDS},
+        constants::{LOGIN_IP_LOCKOUT_THRESHOLD, LOGIN_LOCKOUT_SECONDS, LOGIN_LOCKOUT_THRESHOLD},
+        email_templates,
+    },
+};
+use axum::{
+    extract::{ConnectInfo, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
 };
-use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
 use axum_extra::extract::CookieJar;
+use chrono::Utc;
 use serde::{Deserialize, Serialize};
 
 pub async fn login(
     State(state): State<AppState>,
+    ConnectInfo(address): ConnectInfo<SocketAddr>,
     jar: CookieJar,
     Json(request): Json<LoginRequest>,
 ) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
@@ -21,33 +38,185 @@ pub async fn login(
         Err(_) => return (jar, Err(AuthAPIError::InvalidCredentials)),
     };
 
-    // we need read access to state
-    // get exclusive write access to user store and add new_user to user store
-    let user_store = state.user_store.read().await;
+    let user_store = &state.user_store;
+
+    // Refused before the password is checked, so a locked account cannot be guessed at
+    if let Err(e) = check_lockout(&email, address.ip(), &state).await {
+        return (jar, Err(e));
+    }
 
     match user_store.validate_user(&email, &password).await {
         Ok(()) => {}
-        Err(UserStoreError::UserNotFound) => return (jar, Err(AuthAPIError::IncorrectCredentials)),
+        // Unknown accounts are counted and locked as well so lockouts do not reveal which exist
+        Err(UserStoreError::UserNotFound) => {
+            return (
+                jar,
+                Err(record_failed_login(&email, address.ip(), false, &state).await),
+            )
+        }
         Err(UserStoreError::InvalidCredentials) => {
-            return (jar, Err(AuthAPIError::IncorrectCredentials))
+            return (
+                jar,
+                Err(record_failed_login(&email, address.ip(), true, &state).await),
+            )
         }
         _ => return (jar, Err(AuthAPIError::UnexpectedError)),
     }
 
+    // Only the account's failures are forgotten. Those of the address are kept, otherwise an
+    // attacker could reset them by logging into an account of their own.
+    if state
+        .failed_login_store
+        .write()
+        .await
+        .clear_failures(&FailedLoginKey::email(&email))
+        .await
+        .is_err()
+    {
+        return (jar, Err(AuthAPIError::UnexpectedError));
+    }
+
     let user = match user_store.get_user(&email).await {
         Ok(user) => user,
         Err(_) => return (jar, Err(AuthAPIError::IncorrectCredentials)),
     };
 
+    // Only checked after the password so the response cannot be used to probe for accounts
+    if !user.email_verified {
+        return (jar, Err(AuthAPIError::EmailNotVerified));
+    }
+
+    // Checked now so a wrong id fails the login, but only selected once the login is complete. A
+    // pending 2FA login carries it until a second factor completes the login.
+    let organization_id = match request.organization_id {
+        Some(organization_id) => {
+            match requested_organization(&user.email, organization_id, &state).await {
+                Ok(organization_id) => Some(organization_id),
+                Err(e) => return (jar, Err(e)),
+            }
+        }
+        None => None,
+    };
+
     // Handle request based on user's 2FA configuration
-    match user.requires_2fa {
-        true => handle_2fa(&user.email, &state, jar).await,
-        false => handle_no_2fa(&user.email, jar).await,
+    match user.two_fa_method {
+        TwoFAMethod::None => handle_no_2fa(&user.email, organization_id, &state, jar).await,
+        two_fa_method => handle_2fa(&user.email, two_fa_method, organization_id, &state, jar).await,
+    }
+}
+
+async fn check_lockout(email: &Email, ip: IpAddr, state: &AppState) -> Result<(), AuthAPIError> {
+    let failed_login_store = state.failed_login_store.read().await;
+    let mut locked_until = None;
+
+    for key in [FailedLoginKey::email(email), FailedLoginKey::ip(ip)] {
+        let until = failed_login_store
+            .get_lock(&key)
+            .await
+            .map_err(|_| AuthAPIError::UnexpectedError)?;
+        locked_until = locked_until.max(until);
+    }
+
+    match locked_until {
+        Some(until) => Err(AuthAPIError::AccountLocked(
+            (until - Utc::now().timestamp()).max(1) as u64,
+        )),
+        None => Ok(()),
     }
 }
 
+// Count the failure against the account and the client address and lock whichever reached its
+// threshold, returning the error to respond with
+async fn record_failed_login(
+    email: &Email,
+    ip: IpAddr,
+    user_exists: bool,
+    state: &AppState,
+) -> AuthAPIError {
+    let account_lock = match add_failure(
+        FailedLoginKey::email(email),
+        *LOGIN_LOCKOUT_THRESHOLD,
+        state,
+    )
+    .await
+    {
+        Ok(lock) => lock,
+        Err(e) => return e,
+    };
+
+    let address_lock =
+        match add_failure(FailedLoginKey::ip(ip), *LOGIN_IP_LOCKOUT_THRESHOLD, state).await {
+            Ok(lock) => lock,
+            Err(e) => return e,
+        };
+
+    // The owner learns that someone is guessing their password
+    if let (Some(seconds), true) = (account_lock, user_exists) {
+        let message = match email_templates::account_locked((seconds + 59) / 60) {
+            Ok(message) => message,
+            Err(_) => return AuthAPIError::UnexpectedError,
+        };
+
+        // one notice per lock, however many requests ran into it
+        let locked_until = (Utc::now().timestamp() + seconds).to_string();
+        let key = EmailIdempotencyKey::new(&["account-locked", email.as_ref(), &locked_until]);
+
+        if state
+            .email_outbox
+            .enqueue(&key, email, &message)
+            .await
+            .is_err()
+        {
+            return AuthAPIError::UnexpectedError;
+        }
+    }
+
+    match account_lock.max(address_lock) {
+        Some(seconds) => AuthAPIError::AccountLocked(seconds as u64),
+        None => AuthAPIError::IncorrectCredentials,
+    }
+}
+
+// Returns how long the key got locked for, if the failure took it over the threshold
+async fn add_failure(
+    key: FailedLoginKey,
+    threshold: u32,
+    state: &AppState,
+) -> Result<Option<i64>, AuthAPIError> {
+    let mut failed_login_store = state.failed_login_store.write().await;
+
+    let failures = failed_login_store
+        .add_failure(&key)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    let Some(seconds) = lockout_seconds(failures, threshold) else {
+        return Ok(None);
+    };
+
+    failed_login_store
+        .lock(&key, Utc::now().timestamp() + seconds)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    Ok(Some(seconds))
+}
+
+// Reaching the threshold locks for LOGIN_LOCKOUT_SECONDS, every further failure doubles the lock
+fn lockout_seconds(failures: u32, threshold: u32) -> Option<i64> {
+    let doublings = failures.checked_sub(threshold)?;
+
+    Some(
+        LOGIN_LOCKOUT_SECONDS
+            .saturating_mul(2i64.saturating_pow(doublings))
+            .min(MAX_LOGIN_LOCKOUT_SECONDS),
+    )
+}
+
 async fn handle_no_2fa(
     email: &Email,
+    organization_id: Option<OrganizationId>,
+    state: &AppState,
     jar: CookieJar,
 ) -> (
     CookieJar,
@@ -55,12 +224,32 @@ async fn handle_no_2fa(
 ) {
     // Call the generate_auth_cookie function defined in the auth module.
     // If the function call fails return AuthAPIError::UnexpectedError.
-    let auth_cookie = match generate_auth_cookie(&email) {
+    let auth_cookie = match generate_auth_cookie(
+        email,
+        organization_id.as_ref(),
+        state.key_ring.clone(),
+        state.role_store.clone(),
+        state.organization_store.clone(),
+    )
+    .await
+    {
         Ok(c) => c,
         Err(_) => return (jar, Err(AuthAPIError::UnexpectedError)),
     };
 
-    let updated_jar = jar.add(auth_cookie);
+    // Start a new refresh token family so the session, and its organization, can outlive the auth cookie
+    let refresh_cookie = match generate_refresh_cookie(
+        email,
+        organization_id.as_ref(),
+        state.refresh_token_store.clone(),
+    )
+    .await
+    {
+        Ok(c) => c,
+        Err(_) => return (jar, Err(AuthAPIError::UnexpectedError)),
+    };
+
+    let updated_jar = jar.add(auth_cookie).add(refresh_cookie);
 
     (
         updated_jar,
@@ -70,6 +259,8 @@ async fn handle_no_2fa(
 
 async fn handle_2fa(
     email: &Email,
+    two_fa_method: TwoFAMethod,
+    organization_id: Option<OrganizationId>,
     state: &AppState,
     jar: CookieJar,
 ) -> (
@@ -82,32 +273,33 @@ async fn handle_2fa(
 
     // Store the ID and code in our 2FA code store.
     // Return `AuthAPIError::UnexpectedError` if the operation fails
+    // For TOTP users the code is never sent, only the login attempt ID is checked and the code comes from their app
 
     if state
         .two_fa_code_store
-        .write()
-        .await
-        .add_code(email.clone(), login_attempt_id.clone(), two_fa_code.clone())
+        .add_code(
+            email.clone(),
+            login_attempt_id.clone(),
+            two_fa_code.clone(),
+            organization_id,
+        )
         .await
         .is_err()
     {
         return (jar, Err(AuthAPIError::UnexpectedError));
     }
 
-    // TODO: send 2FA code via the email client. Return `AuthAPIError::UnexpectedError` if the operation fails.
-    let email_client = state.email_client.read().await;
-    match email_client
-        .send_email(email, "2FA Code", two_fa_code.as_ref())
-        .await
-    {
-        Ok(()) => {}
-        Err(_) => return (jar, Err(AuthAPIError::UnexpectedError)),
-    };
+    if two_fa_method == TwoFAMethod::Email {
+        if let Err(e) = send_2fa_code(email, &login_attempt_id, &two_fa_code, state).await {
+            return (jar, Err(e));
+        }
+    }
 
     // Return a TwoFactorAuthResponse. The message should be "2FA required".
     let two_factor_auth_response = TwoFactorAuthResponse {
         message: "2FA required".to_owned(),
         login_attempt_id: login_attempt_id.as_ref().to_string(),
+        two_fa_method: two_fa_method.as_ref().to_owned(),
     };
 
     (
@@ -119,10 +311,32 @@ async fn handle_2fa(
     )
 }
 
+// Email the code of a login attempt to users who chose email 2FA
+pub(crate) async fn send_2fa_code(
+    email: &Email,
+    login_attempt_id: &LoginAttemptId,
+    two_fa_code: &TwoFACode,
+    state: &AppState,
+) -> Result<(), AuthAPIError> {
+    let message =
+        email_templates::two_fa_code(two_fa_code).map_err(|_| AuthAPIError::UnexpectedError)?;
+    let key =
+        EmailIdempotencyKey::new(&["two-fa", login_attempt_id.as_ref(), two_fa_code.as_ref()]);
+
+    state
+        .email_outbox
+        .enqueue(&key, email, &message)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)
+}
+
 #[derive(Deserialize)]
 pub struct LoginRequest {
     pub email: String,
     pub password: String,
+    // the organization the new session works in, none when omitted
+    #[serde(rename = "organizationId")]
+    pub organization_id: Option<String>,
 }
 
 // The login route can return 2 possible success responses.
@@ -140,4 +354,7 @@ pub struct TwoFactorAuthResponse {
     pub message: String,
     #[serde(rename = "loginAttemptId")]
     pub login_attempt_id: String,
+    // tells the client where the user finds their code
+    #[serde(rename = "twoFAMethod")]
+    pub two_fa_method: String,
 }
@@ -3,8 +3,11 @@ use axum_extra::extract::cookie::{Cookie, CookieJar};
 
 use crate::{
     app_state::AppState,
-    domain::AuthAPIError,
-    utils::{auth::validate_token, constants::JWT_COOKIE_NAME},
+    domain::{AuthAPIError, RefreshToken},
+    utils::{
+        auth::{session_audience, validate_token},
+        constants::{JWT_COOKIE_NAME, REFRESH_COOKIE_NAME},
+    },
 };
 
 pub async fn logout(
@@ -19,29 +22,51 @@ pub async fn logout(
     };
 
     // Validate JWT token by calling `validate_token` from the auth service.
-    // If the token is valid you can ignore the returned claims for now.
     // Return AuthAPIError::InvalidToken if validation fails.
-    match validate_token(&token, state.banned_token_store.clone()).await {
-        Ok(_) => {}
+    let claims = match validate_token(
+        &token,
+        session_audience(),
+        state.key_ring.clone(),
+        state.banned_token_store.clone(),
+    )
+    .await
+    {
+        Ok(claims) => claims,
         Err(_) => return (jar, Err(AuthAPIError::InvalidToken)),
-    }
-
-    // Add users JWT token to the banned token store
+    };
 
-    // Add token to banned list
+    // Add the token's id to the banned list
     if state
         .banned_token_store
-        .write()
-        .await
-        .store_token(token.to_owned())
+        .store_token(claims.jti)
         .await
         .is_err()
     {
         return (jar, Err(AuthAPIError::UnexpectedError));
     }
 
-    // Remove JWT cookie from the cookie jar
-    let jar = jar.remove(Cookie::from(JWT_COOKIE_NAME));
+    // Revoke the refresh token family so the session cannot be renewed after logout
+    let refresh_token = jar
+        .get(REFRESH_COOKIE_NAME)
+        .and_then(|cookie| RefreshToken::parse(cookie.value().to_owned()).ok());
+
+    if let Some(refresh_token) = refresh_token {
+        if state
+            .refresh_token_store
+            .write()
+            .await
+            .revoke_token(&refresh_token)
+            .await
+            .is_err()
+        {
+            return (jar, Err(AuthAPIError::UnexpectedError));
+        }
+    }
+
+    // Remove JWT and refresh cookies from the cookie jar
+    let jar = jar
+        .remove(Cookie::from(JWT_COOKIE_NAME))
+        .remove(Cookie::from(REFRESH_COOKIE_NAME));
 
     (jar, Ok(StatusCode::OK))
 }
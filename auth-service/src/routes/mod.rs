@@ -1,12 +1,40 @@
+mod email_outbox;
+mod introspect;
+mod jwks;
+mod jwt_keys;
 mod login;
 mod logout;
+mod organizations;
+mod password_reset;
+mod recovery_codes;
+mod refresh;
+mod resend_2fa;
+mod roles;
 mod signup;
+mod token;
+mod totp;
 mod verify_2fa;
+mod verify_email;
 mod verify_token;
+mod webauthn;
 
 // re-export items from submodules
+pub use email_outbox::*;
+pub use introspect::*;
+pub use jwks::*;
+pub use jwt_keys::*;
 pub use login::*;
 pub use logout::*;
+pub use organizations::*;
+pub use password_reset::*;
+pub use recovery_codes::*;
+pub use refresh::*;
+pub use resend_2fa::*;
+pub use roles::*;
 pub use signup::*;
+pub use token::*;
+pub use totp::*;
 pub use verify_2fa::*;
+pub use verify_email::*;
 pub use verify_token::*;
+pub use webauthn::*;
@@ -0,0 +1,361 @@
+use askama::Template;
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{Html, IntoResponse},
+    Json,
+};
+use axum_extra::extract::CookieJar;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        AuthAPIError, Email, EmailIdempotencyKey, Invitation, InvitationToken, Membership, OrgRole,
+        OrganizationId, OrganizationName, OrganizationStoreError, RefreshToken,
+    },
+    utils::{
+        auth::{
+            generate_auth_cookie, generate_refresh_cookie, get_authenticated_claims,
+            get_authenticated_email, INVITATION_TOKEN_TTL_SECONDS,
+        },
+        constants::{AUTH_SERVICE_URL, REFRESH_COOKIE_NAME},
+        email_templates,
+    },
+};
+
+pub async fn create_organization(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Json(request): Json<CreateOrganizationRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = get_authenticated_email(&jar, &state).await?;
+    let name =
+        OrganizationName::parse(request.name).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    let organization = state
+        .organization_store
+        .write()
+        .await
+        .create_organization(name, &email)
+        .await
+        .map_err(organization_store_error)?;
+
+    let response = Json(OrganizationResponse::new(&Membership {
+        organization,
+        role: OrgRole::Owner,
+    }));
+
+    Ok((StatusCode::CREATED, response))
+}
+
+pub async fn list_organizations(
+    State(state): State<AppState>,
+    jar: CookieJar,
+) -> Result<Json<OrganizationsResponse>, AuthAPIError> {
+    let claims = get_authenticated_claims(&jar, &state).await?;
+    let email = Email::parse(claims.sub).map_err(|_| AuthAPIError::InvalidToken)?;
+
+    let organizations = state
+        .organization_store
+        .read()
+        .await
+        .get_memberships(&email)
+        .await
+        .map_err(organization_store_error)?
+        .iter()
+        .map(OrganizationResponse::new)
+        .collect();
+
+    // Each session works in its own organization, the one of the session making the request is reported
+    Ok(Json(OrganizationsResponse {
+        organizations,
+        selected_organization_id: claims.org_id,
+    }))
+}
+
+// Owners and admins invite by email, the invitee does not need an account yet
+pub async fn invite_member(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Path(organization_id): Path<String>,
+    Json(request): Json<InviteMemberRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = get_authenticated_email(&jar, &state).await?;
+    let organization_id =
+        OrganizationId::parse(organization_id).map_err(|_| AuthAPIError::InvalidCredentials)?;
+    let invitee = Email::parse(request.email).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    // Ownership is never handed out by invitation
+    let role = match OrgRole::parse(&request.role) {
+        Ok(OrgRole::Owner) | Err(_) => return Err(AuthAPIError::InvalidCredentials),
+        Ok(role) => role,
+    };
+
+    let mut organization_store = state.organization_store.write().await;
+
+    let membership = organization_store
+        .get_membership(&email, &organization_id)
+        .await
+        .map_err(organization_store_error)?;
+
+    if !membership.role.can_invite() {
+        return Err(AuthAPIError::MissingPermission);
+    }
+
+    match organization_store
+        .get_membership(&invitee, &organization_id)
+        .await
+    {
+        Ok(_) => return Err(AuthAPIError::AlreadyMember),
+        Err(OrganizationStoreError::MembershipNotFound) => {}
+        Err(e) => return Err(organization_store_error(e)),
+    }
+
+    // Only the hash of the token is persisted, the token itself is only ever sent to the invitee
+    let token = InvitationToken::default();
+
+    organization_store
+        .add_invitation(
+            &token,
+            Invitation {
+                organization_id,
+                email: invitee.clone(),
+                role,
+            },
+        )
+        .await
+        .map_err(organization_store_error)?;
+
+    let link = format!(
+        "{}/organizations/invitations/accept?token={}",
+        AUTH_SERVICE_URL.as_str(),
+        token.as_ref()
+    );
+    let message = email_templates::organization_invitation(
+        membership.organization.name.as_ref(),
+        role.as_ref(),
+        &link,
+        INVITATION_TOKEN_TTL_SECONDS / (60 * 60 * 24),
+    )
+    .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    let key = EmailIdempotencyKey::new(&["invitation", token.as_ref()]);
+
+    state
+        .email_outbox
+        .enqueue(&key, &invitee, &message)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    let response = Json(InviteMemberResponse {
+        message: "Invitation sent".to_owned(),
+    });
+
+    Ok((StatusCode::CREATED, response))
+}
+
+// Opened from the invitation email. Accepting needs the invitee's auth cookie, so the page posts the
+// token back to `accept_invitation` from the browser instead of accepting on a GET.
+pub async fn invitation_page(
+    Query(request): Query<AcceptInvitationRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let token = InvitationToken::parse(request.token).map_err(|_| AuthAPIError::InvalidToken)?;
+
+    let page = InvitationPage {
+        token: token.as_ref(),
+    }
+    .render()
+    .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    Ok(Html(page))
+}
+
+// The invitation is bound to the address it was sent to, so the invitee has to be logged in as that user
+pub async fn accept_invitation(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Json(request): Json<AcceptInvitationRequest>,
+) -> Result<Json<OrganizationResponse>, AuthAPIError> {
+    let email = get_authenticated_email(&jar, &state).await?;
+    let token = InvitationToken::parse(request.token).map_err(|_| AuthAPIError::InvalidToken)?;
+
+    let membership = match state
+        .organization_store
+        .write()
+        .await
+        .accept_invitation(&token, &email)
+        .await
+    {
+        Ok(membership) => membership,
+        Err(OrganizationStoreError::InvitationNotFound) => return Err(AuthAPIError::InvalidToken),
+        Err(e) => return Err(organization_store_error(e)),
+    };
+
+    Ok(Json(OrganizationResponse::new(&membership)))
+}
+
+// Move the session to another organization. The session's refresh token family is replaced by one
+// for the new organization and the auth cookie is reissued, the user's other sessions are unaffected.
+pub async fn switch_organization(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Json(request): Json<SwitchOrganizationRequest>,
+) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+    let email = match get_authenticated_email(&jar, &state).await {
+        Ok(email) => email,
+        Err(e) => return (jar, Err(e)),
+    };
+
+    let organization_id = match OrganizationId::parse(request.organization_id) {
+        Ok(id) => id,
+        Err(_) => return (jar, Err(AuthAPIError::InvalidCredentials)),
+    };
+
+    // Users outside the organization are told it does not exist, so ids cannot be probed
+    let membership = match state
+        .organization_store
+        .read()
+        .await
+        .get_membership(&email, &organization_id)
+        .await
+    {
+        Ok(membership) => membership,
+        Err(e) => return (jar, Err(organization_store_error(e))),
+    };
+
+    // The family of the session's refresh token would otherwise keep refreshing into the old organization
+    if let Some(token) = jar
+        .get(REFRESH_COOKIE_NAME)
+        .and_then(|cookie| RefreshToken::parse(cookie.value().to_owned()).ok())
+    {
+        if state
+            .refresh_token_store
+            .write()
+            .await
+            .revoke_token(&token)
+            .await
+            .is_err()
+        {
+            return (jar, Err(AuthAPIError::UnexpectedError));
+        }
+    }
+
+    let auth_cookie = match generate_auth_cookie(
+        &email,
+        Some(&organization_id),
+        state.key_ring.clone(),
+        state.role_store.clone(),
+        state.organization_store.clone(),
+    )
+    .await
+    {
+        Ok(c) => c,
+        Err(_) => return (jar, Err(AuthAPIError::UnexpectedError)),
+    };
+
+    let refresh_cookie = match generate_refresh_cookie(
+        &email,
+        Some(&organization_id),
+        state.refresh_token_store.clone(),
+    )
+    .await
+    {
+        Ok(c) => c,
+        Err(_) => return (jar, Err(AuthAPIError::UnexpectedError)),
+    };
+
+    let updated_jar = jar.add(auth_cookie).add(refresh_cookie);
+
+    (
+        updated_jar,
+        Ok(Json(OrganizationResponse::new(&membership))),
+    )
+}
+
+// The organization a login asks to work in, refused like `switch_organization` refuses it
+pub(crate) async fn requested_organization(
+    email: &Email,
+    organization_id: String,
+    state: &AppState,
+) -> Result<OrganizationId, AuthAPIError> {
+    let organization_id =
+        OrganizationId::parse(organization_id).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    state
+        .organization_store
+        .read()
+        .await
+        .get_membership(email, &organization_id)
+        .await
+        .map_err(organization_store_error)?;
+
+    Ok(organization_id)
+}
+
+fn organization_store_error(error: OrganizationStoreError) -> AuthAPIError {
+    match error {
+        OrganizationStoreError::OrganizationNotFound
+        | OrganizationStoreError::MembershipNotFound => AuthAPIError::OrganizationNotFound,
+        OrganizationStoreError::AlreadyMember => AuthAPIError::AlreadyMember,
+        OrganizationStoreError::InvitationNotFound => AuthAPIError::InvalidToken,
+        OrganizationStoreError::UnexpectedError => AuthAPIError::UnexpectedError,
+    }
+}
+
+#[derive(Deserialize)]
+pub struct CreateOrganizationRequest {
+    pub name: String,
+}
+
+#[derive(Deserialize)]
+pub struct InviteMemberRequest {
+    pub email: String,
+    pub role: String,
+}
+
+#[derive(Deserialize)]
+pub struct AcceptInvitationRequest {
+    pub token: String,
+}
+
+#[derive(Template)]
+#[template(path = "pages/organization_invitation.html")]
+struct InvitationPage<'a> {
+    token: &'a str,
+}
+
+#[derive(Deserialize)]
+pub struct SwitchOrganizationRequest {
+    #[serde(rename = "organizationId")]
+    pub organization_id: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct OrganizationResponse {
+    pub id: String,
+    pub name: String,
+    pub role: String,
+}
+
+impl OrganizationResponse {
+    fn new(membership: &Membership) -> Self {
+        Self {
+            id: membership.organization.id.to_string(),
+            name: membership.organization.name.as_ref().to_owned(),
+            role: membership.role.as_ref().to_owned(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct OrganizationsResponse {
+    pub organizations: Vec<OrganizationResponse>,
+    #[serde(rename = "selectedOrganizationId")]
+    pub selected_organization_id: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct InviteMemberResponse {
+    pub message: String,
+}
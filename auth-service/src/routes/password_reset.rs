@@ -0,0 +1,159 @@
+use askama::Template;
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::{Html, IntoResponse},
+    Json,
+};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        AuthAPIError, Email, EmailIdempotencyKey, OneTimeToken, OneTimeTokenStoreError, Password,
+        UserStoreError,
+    },
+    utils::{auth::PASSWORD_RESET_TOKEN_TTL_SECONDS, constants::AUTH_SERVICE_URL, email_templates},
+};
+
+pub async fn request_password_reset(
+    State(state): State<AppState>,
+    Json(request): Json<PasswordResetRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = Email::parse(request.email).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    // Respond the same way whether or not the user exists so the route cannot be used to probe for accounts
+    let response = Json(PasswordResetResponse {
+        message: "If the account exists a password reset link has been sent".to_owned(),
+    });
+
+    match state.user_store.get_user(&email).await {
+        Ok(_) => {}
+        Err(UserStoreError::UserNotFound) => return Ok((StatusCode::OK, response)),
+        Err(_) => return Err(AuthAPIError::UnexpectedError),
+    }
+
+    let token = OneTimeToken::default();
+
+    if state
+        .password_reset_token_store
+        .write()
+        .await
+        .add_token(email.clone(), &token)
+        .await
+        .is_err()
+    {
+        return Err(AuthAPIError::UnexpectedError);
+    }
+
+    let link = format!(
+        "{}/password-reset?token={}",
+        AUTH_SERVICE_URL.as_str(),
+        token.as_ref()
+    );
+    let message = email_templates::password_reset(&link, PASSWORD_RESET_TOKEN_TTL_SECONDS / 60)
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    let key = EmailIdempotencyKey::new(&["password-reset", token.as_ref()]);
+
+    state
+        .email_outbox
+        .enqueue(&key, &email, &message)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    Ok((StatusCode::OK, response))
+}
+
+// Served at the emailed link. The page only collects the new password, the token is checked when the
+// form is posted to `confirm_password_reset`.
+pub async fn password_reset_page(
+    Query(request): Query<PasswordResetPageRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let token = OneTimeToken::parse(request.token).map_err(|_| AuthAPIError::InvalidToken)?;
+
+    let page = PasswordResetPage {
+        token: token.as_ref(),
+    }
+    .render()
+    .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    Ok(Html(page))
+}
+
+pub async fn confirm_password_reset(
+    State(state): State<AppState>,
+    Json(request): Json<PasswordResetConfirmRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let token = OneTimeToken::parse(request.token).map_err(|_| AuthAPIError::InvalidToken)?;
+    let password =
+        Password::parse(request.new_password).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    let email = match state
+        .password_reset_token_store
+        .write()
+        .await
+        .consume_token(&token)
+        .await
+    {
+        Ok(email) => email,
+        Err(OneTimeTokenStoreError::TokenNotFound) => return Err(AuthAPIError::InvalidToken),
+        Err(OneTimeTokenStoreError::UnexpectedError) => return Err(AuthAPIError::UnexpectedError),
+    };
+
+    match state.user_store.update_password(&email, password).await {
+        Ok(()) => {}
+        Err(UserStoreError::UserNotFound) => return Err(AuthAPIError::InvalidToken),
+        Err(_) => return Err(AuthAPIError::UnexpectedError),
+    }
+
+    // Invalidate every live session of the user: outstanding auth tokens and refresh token families
+    state
+        .banned_token_store
+        .ban_user_tokens(&email, Utc::now().timestamp_millis())
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    state
+        .refresh_token_store
+        .write()
+        .await
+        .revoke_user_tokens(&email)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    let response = Json(PasswordResetResponse {
+        message: "Password has been reset".to_owned(),
+    });
+
+    Ok((StatusCode::OK, response))
+}
+
+#[derive(Deserialize)]
+pub struct PasswordResetRequest {
+    pub email: String,
+}
+
+#[derive(Deserialize)]
+pub struct PasswordResetPageRequest {
+    pub token: String,
+}
+
+#[derive(Template)]
+#[template(path = "pages/password_reset.html")]
+struct PasswordResetPage<'a> {
+    token: &'a str,
+}
+
+#[derive(Deserialize)]
+pub struct PasswordResetConfirmRequest {
+    pub token: String,
+    #[serde(rename = "newPassword")]
+    pub new_password: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PasswordResetResponse {
+    pub message: String,
+}
@@ -0,0 +1,156 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use axum_extra::extract::CookieJar;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        AuthAPIError, Email, LoginAttemptId, RecoveryCode, RecoveryCodeStoreError, TwoFAMethod,
+    },
+    routes::verify_2fa::{complete_login_attempt, reject_attempt, reserve_attempt},
+    utils::{auth::get_authenticated_email, generate_auth_cookie, generate_refresh_cookie},
+};
+
+// Number of codes in a set, every new set replaces the previous one
+pub const RECOVERY_CODE_COUNT: usize = 10;
+
+// Finish a 2FA login with a recovery code in place of the second factor
+pub async fn verify_recovery_code(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Json(request): Json<VerifyRecoveryCodeRequest>,
+) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+    let email = match Email::parse(request.email) {
+        Ok(e) => e,
+        Err(_) => return (jar, Err(AuthAPIError::InvalidCredentials)),
+    };
+
+    let login_attempt_id = match LoginAttemptId::parse(request.login_attempt_id) {
+        Ok(l) => l,
+        Err(_) => return (jar, Err(AuthAPIError::InvalidCredentials)),
+    };
+
+    let recovery_code = match RecoveryCode::parse(request.recovery_code) {
+        Ok(c) => c,
+        Err(_) => return (jar, Err(AuthAPIError::InvalidCredentials)),
+    };
+
+    let two_fa_code_store = &state.two_fa_code_store;
+
+    // the password step of the login must have been passed already
+    match two_fa_code_store.get_code(&login_attempt_id).await {
+        Ok((stored_email, _)) if stored_email == email => {}
+        _ => return (jar, Err(AuthAPIError::IncorrectCredentials)),
+    }
+
+    let guesses = match reserve_attempt(&login_attempt_id, two_fa_code_store.as_ref()).await {
+        Ok(guesses) => guesses,
+        Err(e) => return (jar, Err(e)),
+    };
+
+    match state
+        .recovery_code_store
+        .use_code(&email, &recovery_code)
+        .await
+    {
+        Ok(()) => {}
+        Err(RecoveryCodeStoreError::CodeNotFound) => {
+            let error =
+                reject_attempt(&login_attempt_id, guesses, two_fa_code_store.as_ref()).await;
+            return (jar, Err(error));
+        }
+        Err(_) => return (jar, Err(AuthAPIError::UnexpectedError)),
+    }
+
+    let organization_id = match complete_login_attempt(&login_attempt_id, &state).await {
+        Ok(organization_id) => organization_id,
+        Err(e) => return (jar, Err(e)),
+    };
+
+    let auth_cookie = match generate_auth_cookie(
+        &email,
+        organization_id.as_ref(),
+        state.key_ring.clone(),
+        state.role_store.clone(),
+        state.organization_store.clone(),
+    )
+    .await
+    {
+        Ok(c) => c,
+        Err(_) => return (jar, Err(AuthAPIError::UnexpectedError)),
+    };
+
+    let refresh_cookie = match generate_refresh_cookie(
+        &email,
+        organization_id.as_ref(),
+        state.refresh_token_store.clone(),
+    )
+    .await
+    {
+        Ok(c) => c,
+        Err(_) => return (jar, Err(AuthAPIError::UnexpectedError)),
+    };
+
+    let updated_jar = jar.add(auth_cookie).add(refresh_cookie);
+
+    (updated_jar, Ok(StatusCode::OK.into_response()))
+}
+
+// Replace the user's recovery codes with a new set, the old codes stop working
+pub async fn regenerate_recovery_codes(
+    State(state): State<AppState>,
+    jar: CookieJar,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = get_authenticated_email(&jar, &state).await?;
+
+    let user = state
+        .user_store
+        .get_user(&email)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    if user.two_fa_method == TwoFAMethod::None {
+        return Err(AuthAPIError::TwoFANotEnabled);
+    }
+
+    let response = Json(RecoveryCodesResponse {
+        recovery_codes: issue_recovery_codes(&email, &state).await?,
+    });
+
+    Ok((StatusCode::OK, response))
+}
+
+// Generate and store a new set of codes. The plain codes are returned so they can be shown
+// to the user exactly once.
+pub(crate) async fn issue_recovery_codes(
+    email: &Email,
+    state: &AppState,
+) -> Result<Vec<String>, AuthAPIError> {
+    let codes: Vec<RecoveryCode> = (0..RECOVERY_CODE_COUNT)
+        .map(|_| RecoveryCode::default())
+        .collect();
+    let plain_codes = codes.iter().map(|c| c.as_ref().to_owned()).collect();
+
+    state
+        .recovery_code_store
+        .replace_codes(email, codes)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    Ok(plain_codes)
+}
+
+#[derive(Deserialize)]
+pub struct VerifyRecoveryCodeRequest {
+    pub email: String,
+    #[serde(rename = "loginAttemptId")]
+    pub login_attempt_id: String,
+    #[serde(rename = "recoveryCode")]
+    pub recovery_code: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RecoveryCodesResponse {
+    #[serde(rename = "recoveryCodes")]
+    pub recovery_codes: Vec<String>,
+}
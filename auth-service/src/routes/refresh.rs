@@ -0,0 +1,64 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse};
+use axum_extra::extract::CookieJar;
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, RefreshToken, RefreshTokenStoreError},
+    utils::{
+        auth::{create_refresh_cookie, generate_auth_cookie},
+        constants::REFRESH_COOKIE_NAME,
+    },
+};
+
+pub async fn refresh(
+    State(state): State<AppState>,
+    jar: CookieJar,
+) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+    // Retrieve refresh token cookie from the CookieJar
+    // Return AuthAPIError::MissingToken if the cookie is not found
+    let token = match jar.get(REFRESH_COOKIE_NAME) {
+        Some(cookie) => cookie.value().to_owned(),
+        None => return (jar, Err(AuthAPIError::MissingToken)),
+    };
+
+    let token = match RefreshToken::parse(token) {
+        Ok(t) => t,
+        Err(_) => return (jar, Err(AuthAPIError::InvalidToken)),
+    };
+
+    // Exchange the presented refresh token for a new one in the same family.
+    // A token that was already exchanged revokes the family and is rejected like any invalid token.
+    let (session, new_token) = match state
+        .refresh_token_store
+        .write()
+        .await
+        .rotate_token(&token)
+        .await
+    {
+        Ok(value) => value,
+        Err(RefreshTokenStoreError::TokenNotFound) | Err(RefreshTokenStoreError::TokenReused) => {
+            return (jar, Err(AuthAPIError::InvalidToken))
+        }
+        Err(RefreshTokenStoreError::UnexpectedError) => {
+            return (jar, Err(AuthAPIError::UnexpectedError))
+        }
+    };
+
+    // The new auth token works in the organization the session was started or switched to
+    let auth_cookie = match generate_auth_cookie(
+        &session.email,
+        session.organization_id.as_ref(),
+        state.key_ring.clone(),
+        state.role_store.clone(),
+        state.organization_store.clone(),
+    )
+    .await
+    {
+        Ok(c) => c,
+        Err(_) => return (jar, Err(AuthAPIError::UnexpectedError)),
+    };
+
+    let updated_jar = jar.add(auth_cookie).add(create_refresh_cookie(&new_token));
+
+    (updated_jar, Ok(StatusCode::OK))
+}
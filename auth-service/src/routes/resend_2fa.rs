@@ -0,0 +1,72 @@
+use axum::{extract::State, http::StatusCode, Json};
+use serde::Deserialize;
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, Email, LoginAttemptId, TwoFACode, TwoFACodeStoreError, TwoFAMethod},
+    routes::{login::send_2fa_code, TwoFactorAuthResponse},
+};
+
+// Send a new code for a pending login attempt when the first email did not arrive. The attempt keeps
+// its id, so the client can carry on with /verify-2fa as before.
+pub async fn resend_2fa(
+    State(state): State<AppState>,
+    Json(request): Json<Resend2FARequest>,
+) -> Result<(StatusCode, Json<TwoFactorAuthResponse>), AuthAPIError> {
+    let email = Email::parse(request.email).map_err(|_| AuthAPIError::InvalidCredentials)?;
+    let login_attempt_id = LoginAttemptId::parse(request.login_attempt_id)
+        .map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    let two_fa_code_store = &state.two_fa_code_store;
+
+    // the password step of the login must have been passed already
+    match two_fa_code_store.get_code(&login_attempt_id).await {
+        Ok((stored_email, _)) if stored_email == email => {}
+        _ => return Err(AuthAPIError::IncorrectCredentials),
+    }
+
+    let user = state
+        .user_store
+        .get_user(&email)
+        .await
+        .map_err(|_| AuthAPIError::IncorrectCredentials)?;
+
+    // TOTP and WebAuthn users never get a code by email
+    if user.two_fa_method != TwoFAMethod::Email {
+        return Err(AuthAPIError::TwoFANotEnabled);
+    }
+
+    let two_fa_code = TwoFACode::default();
+
+    match two_fa_code_store
+        .resend_code(&login_attempt_id, two_fa_code.clone())
+        .await
+    {
+        Ok(()) => {}
+        Err(TwoFACodeStoreError::LoginAttemptIdNotFound) => {
+            return Err(AuthAPIError::IncorrectCredentials)
+        }
+        Err(TwoFACodeStoreError::ResendCooldown(seconds)) => {
+            return Err(AuthAPIError::ResendCooldown(seconds))
+        }
+        Err(TwoFACodeStoreError::TooManyResends) => return Err(AuthAPIError::TooManyResends),
+        Err(_) => return Err(AuthAPIError::UnexpectedError),
+    }
+
+    send_2fa_code(&email, &login_attempt_id, &two_fa_code, &state).await?;
+
+    let response = TwoFactorAuthResponse {
+        message: "2FA code resent".to_owned(),
+        login_attempt_id: login_attempt_id.as_ref().to_owned(),
+        two_fa_method: TwoFAMethod::Email.as_ref().to_owned(),
+    };
+
+    Ok((StatusCode::OK, Json(response)))
+}
+
+#[derive(Deserialize)]
+pub struct Resend2FARequest {
+    pub email: String,
+    #[serde(rename = "loginAttemptId")]
+    pub login_attempt_id: String,
+}
@@ -0,0 +1,176 @@
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, Email, Role, RoleStoreError, UserAccess, UserStoreError},
+    utils::permissions::{ManageRoles, ReadRoles, RequirePermission},
+};
+
+pub async fn list_roles(
+    State(state): State<AppState>,
+    _: RequirePermission<ReadRoles>,
+) -> Result<Json<RolesResponse>, AuthAPIError> {
+    let roles = state
+        .role_store
+        .read()
+        .await
+        .get_roles()
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?
+        .into_iter()
+        .map(|definition| RoleResponse {
+            name: definition.role.as_ref().to_owned(),
+            permissions: definition
+                .permissions
+                .iter()
+                .map(|permission| permission.as_ref().to_owned())
+                .collect(),
+        })
+        .collect();
+
+    Ok(Json(RolesResponse { roles }))
+}
+
+pub async fn get_user_roles(
+    State(state): State<AppState>,
+    _: RequirePermission<ReadRoles>,
+    Path(email): Path<String>,
+) -> Result<Json<UserRolesResponse>, AuthAPIError> {
+    let email = Email::parse(email).map_err(|_| AuthAPIError::InvalidCredentials)?;
+    ensure_user_exists(&state, &email).await?;
+
+    user_roles_response(&state, email).await
+}
+
+// Permissions are carried in tokens, so a new role takes effect on the user's next login or refresh
+pub async fn assign_role(
+    State(state): State<AppState>,
+    _: RequirePermission<ManageRoles>,
+    Json(request): Json<RoleAssignmentRequest>,
+) -> Result<Json<UserRolesResponse>, AuthAPIError> {
+    let (email, role) = request.parse()?;
+    ensure_user_exists(&state, &email).await?;
+
+    state
+        .role_store
+        .write()
+        .await
+        .assign_role(&email, &role)
+        .await
+        .map_err(role_store_error)?;
+
+    user_roles_response(&state, email).await
+}
+
+pub async fn revoke_role(
+    State(state): State<AppState>,
+    _: RequirePermission<ManageRoles>,
+    Json(request): Json<RoleAssignmentRequest>,
+) -> Result<Json<UserRolesResponse>, AuthAPIError> {
+    let (email, role) = request.parse()?;
+    ensure_user_exists(&state, &email).await?;
+
+    state
+        .role_store
+        .write()
+        .await
+        .revoke_role(&email, &role)
+        .await
+        .map_err(role_store_error)?;
+
+    // Tokens issued so far still grant the revoked permissions. Banning them makes the revocation
+    // immediate, the user's client picks up a token without them on the next refresh.
+    state
+        .banned_token_store
+        .ban_user_tokens(&email, Utc::now().timestamp_millis())
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    user_roles_response(&state, email).await
+}
+
+async fn ensure_user_exists(state: &AppState, email: &Email) -> Result<(), AuthAPIError> {
+    match state.user_store.get_user(email).await {
+        Ok(_) => Ok(()),
+        Err(UserStoreError::UserNotFound) => Err(AuthAPIError::UserNotFound),
+        Err(_) => Err(AuthAPIError::UnexpectedError),
+    }
+}
+
+async fn user_roles_response(
+    state: &AppState,
+    email: Email,
+) -> Result<Json<UserRolesResponse>, AuthAPIError> {
+    let access = state
+        .role_store
+        .read()
+        .await
+        .get_user_access(&email)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    Ok(Json(UserRolesResponse::new(email, access)))
+}
+
+fn role_store_error(error: RoleStoreError) -> AuthAPIError {
+    match error {
+        RoleStoreError::RoleNotFound => AuthAPIError::RoleNotFound,
+        RoleStoreError::UserNotFound => AuthAPIError::UserNotFound,
+        RoleStoreError::UnexpectedError => AuthAPIError::UnexpectedError,
+    }
+}
+
+#[derive(Deserialize)]
+pub struct RoleAssignmentRequest {
+    pub email: String,
+    pub role: String,
+}
+
+impl RoleAssignmentRequest {
+    fn parse(self) -> Result<(Email, Role), AuthAPIError> {
+        let email = Email::parse(self.email).map_err(|_| AuthAPIError::InvalidCredentials)?;
+        let role = Role::parse(self.role).map_err(|_| AuthAPIError::InvalidCredentials)?;
+        Ok((email, role))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct RolesResponse {
+    pub roles: Vec<RoleResponse>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct RoleResponse {
+    pub name: String,
+    pub permissions: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct UserRolesResponse {
+    pub email: String,
+    pub roles: Vec<String>,
+    pub permissions: Vec<String>,
+}
+
+impl UserRolesResponse {
+    fn new(email: Email, access: UserAccess) -> Self {
+        Self {
+            email: email.as_ref().to_owned(),
+            roles: access
+                .roles
+                .iter()
+                .map(|role| role.as_ref().to_owned())
+                .collect(),
+            permissions: access
+                .permissions
+                .iter()
+                .map(|permission| permission.as_ref().to_owned())
+                .collect(),
+        }
+    }
+}
@@ -3,7 +3,8 @@ use serde::{Deserialize, Serialize};
 
 use crate::{
     app_state::AppState,
-    domain::{AuthAPIError, Email, Password, User, UserStoreError},
+    domain::{AuthAPIError, Email, Password, TwoFAMethod, User, UserStoreError},
+    routes::{recovery_codes::issue_recovery_codes, verify_email::send_verification_email},
 };
 
 // Use axum's state extractor to pass in AppState
@@ -18,24 +19,40 @@ pub async fn signup(
 
     // create a new User instance using data int the request
     let new_user = User {
-        email: email,
-        password: password,
-        requires_2fa: request.requires_2fa,
+        email,
+        password,
+        // 2FA chosen at signup is always email based, an authenticator app can be enrolled later
+        two_fa_method: if request.requires_2fa {
+            TwoFAMethod::Email
+        } else {
+            TwoFAMethod::None
+        },
+        email_verified: false,
     };
 
-    // get exclusive write access to user store and add new_user to user store
-    let mut user_store = state.user_store.write().await;
+    let email = new_user.email.clone();
 
+    // add new_user to user store
     // early return AuthAPIError::UserAlreadyExists if add_user returns UserStoreError::UserAlreadyExists
     // early return AuthAPIError::UnexpectedError if add_user fails
-    match user_store.add_user(new_user).await {
+    match state.user_store.add_user(new_user).await {
         Ok(()) => {}
         Err(UserStoreError::UserAlreadyExists) => return Err(AuthAPIError::UserAlreadyExists),
         _ => return Err(AuthAPIError::UnexpectedError),
     };
 
+    // the user can log in once they follow the link
+    send_verification_email(&email, &state).await?;
+
+    let recovery_codes = if request.requires_2fa {
+        Some(issue_recovery_codes(&email, &state).await?)
+    } else {
+        None
+    };
+
     let response = Json(SignupResponse {
         message: "User created successfully!".to_string(),
+        recovery_codes,
     });
 
     Ok((StatusCode::CREATED, response))
@@ -49,7 +66,14 @@ pub struct SignupRequest {
     pub requires_2fa: bool,
 }
 
-#[derive(Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct SignupResponse {
     pub message: String,
+    // Only present when 2FA was enabled, the codes are never shown again
+    #[serde(
+        rename = "recoveryCodes",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub recovery_codes: Option<Vec<String>>,
 }
@@ -0,0 +1,62 @@
+use axum::{extract::State, Json};
+use axum_extra::extract::CookieJar;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, Email, OrganizationId},
+    utils::{
+        auth::{generate_access_token, get_authenticated_claims, TOKEN_TTL_SECONDS},
+        constants::JWT_AUDIENCE,
+    },
+};
+
+// Issue a token of the logged-in session for another consumer listed in JWT_AUDIENCE. The token names
+// only that consumer, so neither this service nor any other consumer accepts it.
+pub async fn issue_token(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Json(request): Json<TokenRequest>,
+) -> Result<Json<TokenResponse>, AuthAPIError> {
+    let claims = get_authenticated_claims(&jar, &state).await?;
+    let email = Email::parse(claims.sub).map_err(|_| AuthAPIError::InvalidToken)?;
+
+    if !JWT_AUDIENCE.contains(&request.audience) {
+        return Err(AuthAPIError::InvalidCredentials);
+    }
+
+    // The token works in the same organization as the session
+    let organization_id = claims
+        .org_id
+        .map(OrganizationId::parse)
+        .transpose()
+        .map_err(|_| AuthAPIError::InvalidToken)?;
+
+    let token = generate_access_token(
+        &email,
+        organization_id.as_ref(),
+        &request.audience,
+        state.key_ring.clone(),
+        state.role_store.clone(),
+        state.organization_store.clone(),
+    )
+    .await
+    .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    Ok(Json(TokenResponse {
+        token,
+        expires_in: TOKEN_TTL_SECONDS,
+    }))
+}
+
+#[derive(Deserialize)]
+pub struct TokenRequest {
+    pub audience: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct TokenResponse {
+    pub token: String,
+    #[serde(rename = "expiresIn")]
+    pub expires_in: i64,
+}
@@ -0,0 +1,167 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use axum_extra::extract::CookieJar;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        AuthAPIError, Email, TotpEnrollment, TotpSecret, TotpSecretStoreError, TwoFACode,
+        TwoFAMethod,
+    },
+    routes::recovery_codes::issue_recovery_codes,
+    utils::{
+        auth::get_authenticated_email,
+        totp::{provisioning_uri, verify_code},
+    },
+};
+
+// Start enrolling an authenticator app. The secret stays pending until confirmed with a first code.
+pub async fn enroll_totp(
+    State(state): State<AppState>,
+    jar: CookieJar,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = get_authenticated_email(&jar, &state).await?;
+
+    let user = state
+        .user_store
+        .get_user(&email)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    if user.two_fa_method == TwoFAMethod::Totp {
+        return Err(AuthAPIError::TotpAlreadyEnabled);
+    }
+
+    let secret = TotpSecret::default();
+
+    state
+        .totp_secret_store
+        .write()
+        .await
+        .add_secret(email.clone(), secret.clone())
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    let response = Json(EnrollTotpResponse {
+        secret: secret.to_base32(),
+        otpauth_uri: provisioning_uri(&secret, &email),
+    });
+
+    Ok((StatusCode::OK, response))
+}
+
+// Finish enrolling by proving the authenticator app generates valid codes, then switch the user to TOTP
+pub async fn confirm_totp(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Json(request): Json<ConfirmTotpRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = get_authenticated_email(&jar, &state).await?;
+    let code = TwoFACode::parse(request.code).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    let enrollment = match state
+        .totp_secret_store
+        .read()
+        .await
+        .get_secret(&email)
+        .await
+    {
+        Ok(enrollment) => enrollment,
+        Err(TotpSecretStoreError::SecretNotFound) => {
+            return Err(AuthAPIError::IncorrectCredentials)
+        }
+        Err(_) => return Err(AuthAPIError::UnexpectedError),
+    };
+
+    if enrollment.confirmed {
+        return Err(AuthAPIError::TotpAlreadyEnabled);
+    }
+
+    check_totp_code(&email, &code, &enrollment, &state).await?;
+
+    let user = state
+        .user_store
+        .get_user(&email)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    state
+        .totp_secret_store
+        .write()
+        .await
+        .confirm_secret(&email)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    state
+        .user_store
+        .set_two_fa_method(&email, TwoFAMethod::Totp)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    // users switching over from another 2FA method keep their existing codes
+    let recovery_codes = if user.two_fa_method == TwoFAMethod::None {
+        Some(issue_recovery_codes(&email, &state).await?)
+    } else {
+        None
+    };
+
+    let response = Json(ConfirmTotpResponse {
+        message: "Authenticator app enabled".to_owned(),
+        recovery_codes,
+    });
+
+    Ok((StatusCode::OK, response))
+}
+
+// Accept `code` if it is valid for the enrolled secret and its time step has not been used before
+pub(crate) async fn check_totp_code(
+    email: &Email,
+    code: &TwoFACode,
+    enrollment: &TotpEnrollment,
+    state: &AppState,
+) -> Result<(), AuthAPIError> {
+    let now: u64 = Utc::now()
+        .timestamp()
+        .try_into()
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    let time_step =
+        verify_code(&enrollment.secret, code, now).ok_or(AuthAPIError::IncorrectCredentials)?;
+
+    match state
+        .totp_secret_store
+        .write()
+        .await
+        .use_time_step(email, time_step)
+        .await
+    {
+        Ok(()) => Ok(()),
+        Err(TotpSecretStoreError::TimeStepAlreadyUsed) => Err(AuthAPIError::IncorrectCredentials),
+        Err(_) => Err(AuthAPIError::UnexpectedError),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ConfirmTotpRequest {
+    pub code: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EnrollTotpResponse {
+    pub secret: String,
+    #[serde(rename = "otpauthUri")]
+    pub otpauth_uri: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ConfirmTotpResponse {
+    pub message: String,
+    #[serde(
+        rename = "recoveryCodes",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub recovery_codes: Option<Vec<String>>,
+}
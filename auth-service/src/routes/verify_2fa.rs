@@ -1,7 +1,11 @@
 use crate::{
     app_state::AppState,
-    domain::{AuthAPIError, Email, LoginAttemptId, TwoFACode},
-    utils::generate_auth_cookie,
+    domain::{
+        AuthAPIError, Email, LoginAttemptId, OrganizationId, TwoFACode, TwoFACodeStore,
+        TwoFACodeStoreError, TwoFAMethod,
+    },
+    routes::totp::check_totp_code,
+    utils::{auth::MAX_TWO_FA_ATTEMPTS, generate_auth_cookie, generate_refresh_cookie},
 };
 use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
 use axum_extra::extract::CookieJar;
@@ -30,44 +34,158 @@ pub async fn verify_2fa(
         Err(_) => return (jar, Err(AuthAPIError::InvalidCredentials)),
     };
 
-    // get write lock to two_fa_code_store
-    let mut two_fa_code_store = state.two_fa_code_store.write().await;
+    let two_fa_code_store = &state.two_fa_code_store;
 
     // call two_fa_code_store.get_code.
     // if the call fails return a AuthAPIError::IncorrectCredentials
-    let code_tuple = match two_fa_code_store.get_code(&email).await {
-        Ok((l, t)) => (l, t),
+    let stored_email = match two_fa_code_store.get_code(&login_attempt_id).await {
+        Ok((e, _)) => e,
         Err(_) => return (jar, Err(AuthAPIError::IncorrectCredentials)),
     };
 
-    // validate that the login_attempt_id and ut in the request body matches the values in code_tuple
+    // validate that the login attempt was started by the email in the request body
     // if they do not match then return AuthAPIError::IncorrectCredentials
 
-    if code_tuple.0 != login_attempt_id {
+    if stored_email != email {
         return (jar, Err(AuthAPIError::IncorrectCredentials));
     }
 
-    if code_tuple.1 != two_fa_code {
+    let user = match state.user_store.get_user(&email).await {
+        Ok(user) => user,
+        Err(_) => return (jar, Err(AuthAPIError::IncorrectCredentials)),
+    };
+
+    // WebAuthn users complete the login through /webauthn/login/finish, a code is never valid for them
+    if user.two_fa_method == TwoFAMethod::WebAuthn {
         return (jar, Err(AuthAPIError::IncorrectCredentials));
     }
 
-    // remove 2fa code from the code store after successful authentication
-    if two_fa_code_store.remove_code(&email).await.is_err() {
-        return (jar, Err(AuthAPIError::UnexpectedError));
+    // TOTP users enter the code from their authenticator app instead of the one we generated
+    if user.two_fa_method == TwoFAMethod::Totp {
+        let enrollment = match state
+            .totp_secret_store
+            .read()
+            .await
+            .get_secret(&email)
+            .await
+        {
+            Ok(enrollment) if enrollment.confirmed => enrollment,
+            _ => return (jar, Err(AuthAPIError::IncorrectCredentials)),
+        };
+
+        let guesses = match reserve_attempt(&login_attempt_id, two_fa_code_store.as_ref()).await {
+            Ok(guesses) => guesses,
+            Err(e) => return (jar, Err(e)),
+        };
+
+        match check_totp_code(&email, &two_fa_code, &enrollment, &state).await {
+            Ok(()) => {}
+            Err(AuthAPIError::IncorrectCredentials) => {
+                let error =
+                    reject_attempt(&login_attempt_id, guesses, two_fa_code_store.as_ref()).await;
+                return (jar, Err(error));
+            }
+            Err(e) => return (jar, Err(e)),
+        }
+    } else {
+        // The store compares and counts the guess in one step
+        match two_fa_code_store
+            .verify_code(&login_attempt_id, &two_fa_code)
+            .await
+        {
+            Ok(()) => {}
+            Err(
+                TwoFACodeStoreError::IncorrectCode | TwoFACodeStoreError::LoginAttemptIdNotFound,
+            ) => return (jar, Err(AuthAPIError::IncorrectCredentials)),
+            Err(_) => return (jar, Err(AuthAPIError::UnexpectedError)),
+        }
     }
 
+    // remove 2fa code from the code store after successful authentication
+    let organization_id = match complete_login_attempt(&login_attempt_id, &state).await {
+        Ok(organization_id) => organization_id,
+        Err(e) => return (jar, Err(e)),
+    };
+
     // email, login attemptid, and 2fa are correct
     // as a result, we will update the cookie jar with a new JWT auth cookie
-    let auth_cookie = match generate_auth_cookie(&email) {
+    let auth_cookie = match generate_auth_cookie(
+        &email,
+        organization_id.as_ref(),
+        state.key_ring.clone(),
+        state.role_store.clone(),
+        state.organization_store.clone(),
+    )
+    .await
+    {
+        Ok(c) => c,
+        Err(_) => return (jar, Err(AuthAPIError::UnexpectedError)),
+    };
+
+    let refresh_cookie = match generate_refresh_cookie(
+        &email,
+        organization_id.as_ref(),
+        state.refresh_token_store.clone(),
+    )
+    .await
+    {
         Ok(c) => c,
         Err(_) => return (jar, Err(AuthAPIError::UnexpectedError)),
     };
-    let updated_jar = jar.add(auth_cookie);
+
+    let updated_jar = jar.add(auth_cookie).add(refresh_cookie);
 
     // send 200 response
     (updated_jar, Ok(StatusCode::OK.into_response()))
 }
 
+// A guess checked outside the store (TOTP and recovery codes) is counted against the login attempt
+// before it is checked, so a burst of guesses cannot all be checked before the limit is reached.
+// Returns how many guesses there have been, including this one.
+pub(crate) async fn reserve_attempt(
+    login_attempt_id: &LoginAttemptId,
+    two_fa_code_store: &(dyn TwoFACodeStore + Send + Sync),
+) -> Result<u32, AuthAPIError> {
+    match two_fa_code_store.reserve_attempt(login_attempt_id).await {
+        Ok(guesses) => Ok(guesses),
+        Err(TwoFACodeStoreError::LoginAttemptIdNotFound) => Err(AuthAPIError::IncorrectCredentials),
+        Err(_) => Err(AuthAPIError::UnexpectedError),
+    }
+}
+
+// The reserved guess was wrong. Once it was the last one the login attempt is discarded and the user
+// has to log in with their password again.
+pub(crate) async fn reject_attempt(
+    login_attempt_id: &LoginAttemptId,
+    guesses: u32,
+    two_fa_code_store: &(dyn TwoFACodeStore + Send + Sync),
+) -> AuthAPIError {
+    if guesses < MAX_TWO_FA_ATTEMPTS {
+        return AuthAPIError::IncorrectCredentials;
+    }
+
+    match two_fa_code_store.remove_code(login_attempt_id).await {
+        // a concurrent request may have removed it already
+        Ok(_) | Err(TwoFACodeStoreError::LoginAttemptIdNotFound) => {
+            AuthAPIError::IncorrectCredentials
+        }
+        Err(_) => AuthAPIError::UnexpectedError,
+    }
+}
+
+// Only one request can complete a login attempt, another one completing it at the same time is refused.
+// Returns the organization the login asked for, the new session works in it.
+pub(crate) async fn complete_login_attempt(
+    login_attempt_id: &LoginAttemptId,
+    state: &AppState,
+) -> Result<Option<OrganizationId>, AuthAPIError> {
+    match state.two_fa_code_store.remove_code(login_attempt_id).await {
+        Ok(organization_id) => Ok(organization_id),
+        Err(TwoFACodeStoreError::LoginAttemptIdNotFound) => Err(AuthAPIError::IncorrectCredentials),
+        Err(_) => Err(AuthAPIError::UnexpectedError),
+    }
+}
+
 #[derive(Deserialize)]
 pub struct Verify2FARequest {
     pub email: String,
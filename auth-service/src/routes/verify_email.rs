@@ -0,0 +1,122 @@
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        AuthAPIError, Email, EmailIdempotencyKey, OneTimeToken, OneTimeTokenStoreError,
+        UserStoreError,
+    },
+    utils::{
+        auth::EMAIL_VERIFICATION_TOKEN_TTL_SECONDS, constants::AUTH_SERVICE_URL, email_templates,
+    },
+};
+
+// This route is the target of the link in the verification email, so the token comes in the query string
+pub async fn verify_email(
+    State(state): State<AppState>,
+    Query(request): Query<VerifyEmailRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let token = OneTimeToken::parse(request.token).map_err(|_| AuthAPIError::InvalidToken)?;
+
+    let email = match state
+        .email_verification_token_store
+        .write()
+        .await
+        .consume_token(&token)
+        .await
+    {
+        Ok(email) => email,
+        Err(OneTimeTokenStoreError::TokenNotFound) => return Err(AuthAPIError::InvalidToken),
+        Err(OneTimeTokenStoreError::UnexpectedError) => return Err(AuthAPIError::UnexpectedError),
+    };
+
+    match state.user_store.set_email_verified(&email).await {
+        Ok(()) => {}
+        Err(UserStoreError::UserNotFound) => return Err(AuthAPIError::InvalidToken),
+        Err(_) => return Err(AuthAPIError::UnexpectedError),
+    }
+
+    let response = Json(VerifyEmailResponse {
+        message: "Email verified successfully!".to_owned(),
+    });
+
+    Ok((StatusCode::OK, response))
+}
+
+pub async fn resend_verification(
+    State(state): State<AppState>,
+    Json(request): Json<ResendVerificationRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = Email::parse(request.email).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    // Unknown, unverified and already verified addresses all get this answer, like password resets do
+    let response = Json(VerifyEmailResponse {
+        message: "If the account exists and is not verified a verification link has been sent"
+            .to_owned(),
+    });
+
+    let user = match state.user_store.get_user(&email).await {
+        Ok(user) => user,
+        Err(UserStoreError::UserNotFound) => return Ok((StatusCode::OK, response)),
+        Err(_) => return Err(AuthAPIError::UnexpectedError),
+    };
+
+    if !user.email_verified {
+        send_verification_email(&email, &state).await?;
+    }
+
+    Ok((StatusCode::OK, response))
+}
+
+// Store a new verification token for the user and email them the link to consume it
+pub(crate) async fn send_verification_email(
+    email: &Email,
+    state: &AppState,
+) -> Result<(), AuthAPIError> {
+    let token = OneTimeToken::default();
+
+    state
+        .email_verification_token_store
+        .write()
+        .await
+        .add_token(email.clone(), &token)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    let link = format!(
+        "{}/verify-email?token={}",
+        AUTH_SERVICE_URL.as_str(),
+        token.as_ref()
+    );
+    let message = email_templates::verify_email(&link, EMAIL_VERIFICATION_TOKEN_TTL_SECONDS / 3600)
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    let key = EmailIdempotencyKey::new(&["verify-email", token.as_ref()]);
+
+    state
+        .email_outbox
+        .enqueue(&key, email, &message)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)
+}
+
+#[derive(Deserialize)]
+pub struct VerifyEmailRequest {
+    pub token: String,
+}
+
+#[derive(Deserialize)]
+pub struct ResendVerificationRequest {
+    pub email: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VerifyEmailResponse {
+    pub message: String,
+}
@@ -1,14 +1,27 @@
 use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
 use serde::Deserialize;
 
-use crate::{app_state::AppState, domain::AuthAPIError, utils::validate_token};
+use crate::{
+    app_state::AppState,
+    domain::AuthAPIError,
+    utils::{auth::session_audience, validate_token},
+};
 
 pub async fn verify_token(
     State(state): State<AppState>,
     Json(request): Json<VerifyTokenRequest>,
 ) -> Result<impl IntoResponse, AuthAPIError> {
-    // validate token
-    match validate_token(&request.token, state.banned_token_store.clone()).await {
+    // validate token, for the consumer asking or for the auth cookie's consumer if it does not say
+    let audience = request.audience.as_deref().unwrap_or(session_audience());
+
+    match validate_token(
+        &request.token,
+        audience,
+        state.key_ring.clone(),
+        state.banned_token_store.clone(),
+    )
+    .await
+    {
         Ok(_) => {}
         Err(_) => return Err(AuthAPIError::InvalidToken),
     }
@@ -19,4 +32,6 @@ pub async fn verify_token(
 #[derive(Deserialize)]
 pub struct VerifyTokenRequest {
     pub token: String,
+    // the consumer checking the token, a token issued for any other consumer is invalid
+    pub audience: Option<String>,
 }
@@ -0,0 +1,518 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use axum_extra::extract::CookieJar;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        AuthAPIError, CredentialId, Email, LoginAttemptId, OrganizationId, TwoFAMethod,
+        WebAuthnCeremony, WebAuthnChallenge, WebAuthnCredential, WebAuthnCredentialStoreError,
+    },
+    routes::{recovery_codes::issue_recovery_codes, verify_2fa::complete_login_attempt},
+    utils::{
+        auth::{
+            generate_auth_cookie, generate_refresh_cookie, get_authenticated_email,
+            WEBAUTHN_CHALLENGE_TTL_SECONDS,
+        },
+        constants::WEBAUTHN_RP_ID,
+        webauthn::{
+            parse_client_data, verify_assertion, verify_attestation, AuthenticatorData,
+            CLIENT_DATA_TYPE_CREATE, CLIENT_DATA_TYPE_GET, SUPPORTED_COSE_ALGORITHMS,
+            WEBAUTHN_RP_NAME,
+        },
+    },
+};
+
+// Issue the options for `navigator.credentials.create()` so the signed in user can register a credential
+pub async fn start_webauthn_registration(
+    State(state): State<AppState>,
+    jar: CookieJar,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = get_authenticated_email(&jar, &state).await?;
+
+    // Ask the authenticator not to create a second credential for the same user
+    let exclude_credentials = get_credential_descriptors(&email, &state).await?;
+
+    let challenge = WebAuthnChallenge::default();
+
+    state
+        .webauthn_challenge_store
+        .write()
+        .await
+        .add_challenge(
+            &challenge,
+            WebAuthnCeremony::Registration {
+                email: email.clone(),
+            },
+        )
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    let response = Json(CreationOptions {
+        challenge: challenge.as_ref().to_owned(),
+        rp: RelyingParty {
+            id: WEBAUTHN_RP_ID.to_owned(),
+            name: WEBAUTHN_RP_NAME.to_owned(),
+        },
+        user: UserEntity {
+            // the user handle must not contain personal information, so it is derived from the email
+            id: URL_SAFE_NO_PAD.encode(Sha256::digest(email.as_ref().as_bytes())),
+            name: email.as_ref().to_owned(),
+            display_name: email.as_ref().to_owned(),
+        },
+        pub_key_cred_params: SUPPORTED_COSE_ALGORITHMS
+            .iter()
+            .map(|alg| CredentialParameters {
+                credential_type: PUBLIC_KEY_CREDENTIAL_TYPE.to_owned(),
+                alg: *alg,
+            })
+            .collect(),
+        timeout: WEBAUTHN_CHALLENGE_TTL_SECONDS * 1000,
+        attestation: "none".to_owned(),
+        exclude_credentials,
+        authenticator_selection: AuthenticatorSelection {
+            resident_key: "preferred".to_owned(),
+            user_verification: "preferred".to_owned(),
+        },
+    });
+
+    Ok((StatusCode::OK, response))
+}
+
+// Verify the new credential and store it. From then on the user signs in with it as their second factor.
+pub async fn finish_webauthn_registration(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Json(request): Json<RegistrationCredential>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = get_authenticated_email(&jar, &state).await?;
+
+    let client_data_json = decode_base64url(&request.response.client_data_json)?;
+    let attestation_object = decode_base64url(&request.response.attestation_object)?;
+
+    let challenge = parse_client_data(&client_data_json, CLIENT_DATA_TYPE_CREATE)
+        .map_err(|_| AuthAPIError::IncorrectCredentials)?;
+
+    // The challenge must have been issued to this user for a registration
+    match state
+        .webauthn_challenge_store
+        .write()
+        .await
+        .consume_challenge(&challenge)
+        .await
+    {
+        Ok(WebAuthnCeremony::Registration {
+            email: challenge_email,
+        }) if challenge_email == email => {}
+        _ => return Err(AuthAPIError::IncorrectCredentials),
+    }
+
+    let auth_data = verify_attestation(&attestation_object, &client_data_json)
+        .map_err(|_| AuthAPIError::IncorrectCredentials)?;
+    auth_data
+        .check(false)
+        .map_err(|_| AuthAPIError::IncorrectCredentials)?;
+
+    let attested_credential = auth_data
+        .attested_credential
+        .ok_or(AuthAPIError::IncorrectCredentials)?;
+
+    if attested_credential.credential_id.to_base64url() != request.id {
+        return Err(AuthAPIError::InvalidCredentials);
+    }
+
+    let user = state
+        .user_store
+        .get_user(&email)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    let credential = WebAuthnCredential {
+        credential_id: attested_credential.credential_id,
+        email: email.clone(),
+        public_key: attested_credential.public_key,
+        sign_count: auth_data.sign_count,
+    };
+
+    match state
+        .webauthn_credential_store
+        .write()
+        .await
+        .add_credential(credential)
+        .await
+    {
+        Ok(()) => {}
+        // a credential ID is unique to one authenticator, it can only belong to one account
+        Err(WebAuthnCredentialStoreError::CredentialAlreadyExists) => {
+            return Err(AuthAPIError::InvalidCredentials)
+        }
+        Err(_) => return Err(AuthAPIError::UnexpectedError),
+    }
+
+    state
+        .user_store
+        .set_two_fa_method(&email, TwoFAMethod::WebAuthn)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    // only the first second factor comes with codes, registering another passkey keeps the current set
+    let recovery_codes = if user.two_fa_method == TwoFAMethod::None {
+        Some(issue_recovery_codes(&email, &state).await?)
+    } else {
+        None
+    };
+
+    let response = Json(WebAuthnResponse {
+        message: "Credential registered".to_owned(),
+        recovery_codes,
+    });
+
+    Ok((StatusCode::CREATED, response))
+}
+
+// Issue the options for `navigator.credentials.get()`.
+// With a `loginAttemptId` the assertion completes a password login as the second factor,
+// without one it signs the user in on its own and user verification is required.
+pub async fn start_webauthn_login(
+    State(state): State<AppState>,
+    Json(request): Json<StartWebAuthnLoginRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = Email::parse(request.email).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    let login_attempt_id = match request.login_attempt_id {
+        Some(login_attempt_id) => {
+            let login_attempt_id = LoginAttemptId::parse(login_attempt_id)
+                .map_err(|_| AuthAPIError::InvalidCredentials)?;
+            check_login_attempt(&email, &login_attempt_id, &state).await?;
+            Some(login_attempt_id)
+        }
+        None => None,
+    };
+
+    let user_verification = if login_attempt_id.is_some() {
+        "preferred"
+    } else {
+        "required"
+    };
+
+    // Unknown users simply get no credentials to choose from
+    let allow_credentials = get_credential_descriptors(&email, &state).await?;
+
+    let challenge = WebAuthnChallenge::default();
+
+    state
+        .webauthn_challenge_store
+        .write()
+        .await
+        .add_challenge(
+            &challenge,
+            WebAuthnCeremony::Authentication {
+                email,
+                login_attempt_id,
+            },
+        )
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    let response = Json(RequestOptions {
+        challenge: challenge.as_ref().to_owned(),
+        timeout: WEBAUTHN_CHALLENGE_TTL_SECONDS * 1000,
+        rp_id: WEBAUTHN_RP_ID.to_owned(),
+        allow_credentials,
+        user_verification: user_verification.to_owned(),
+    });
+
+    Ok((StatusCode::OK, response))
+}
+
+// Verify the assertion and sign the user in
+pub async fn finish_webauthn_login(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Json(request): Json<AuthenticationCredential>,
+) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+    let (email, organization_id) = match verify_login_assertion(&request, &state).await {
+        Ok(session) => session,
+        Err(e) => return (jar, Err(e)),
+    };
+
+    let auth_cookie = match generate_auth_cookie(
+        &email,
+        organization_id.as_ref(),
+        state.key_ring.clone(),
+        state.role_store.clone(),
+        state.organization_store.clone(),
+    )
+    .await
+    {
+        Ok(c) => c,
+        Err(_) => return (jar, Err(AuthAPIError::UnexpectedError)),
+    };
+
+    let refresh_cookie = match generate_refresh_cookie(
+        &email,
+        organization_id.as_ref(),
+        state.refresh_token_store.clone(),
+    )
+    .await
+    {
+        Ok(c) => c,
+        Err(_) => return (jar, Err(AuthAPIError::UnexpectedError)),
+    };
+
+    let updated_jar = jar.add(auth_cookie).add(refresh_cookie);
+
+    (updated_jar, Ok(StatusCode::OK.into_response()))
+}
+
+// Run every check of the authentication ceremony and return the user the assertion signs in, along
+// with the organization their login asked for when the assertion completes a 2FA login
+async fn verify_login_assertion(
+    request: &AuthenticationCredential,
+    state: &AppState,
+) -> Result<(Email, Option<OrganizationId>), AuthAPIError> {
+    let credential_id =
+        CredentialId::parse_base64url(&request.id).map_err(|_| AuthAPIError::InvalidCredentials)?;
+    let client_data_json = decode_base64url(&request.response.client_data_json)?;
+    let authenticator_data = decode_base64url(&request.response.authenticator_data)?;
+    let signature = decode_base64url(&request.response.signature)?;
+
+    let challenge = parse_client_data(&client_data_json, CLIENT_DATA_TYPE_GET)
+        .map_err(|_| AuthAPIError::IncorrectCredentials)?;
+
+    let (email, login_attempt_id) = match state
+        .webauthn_challenge_store
+        .write()
+        .await
+        .consume_challenge(&challenge)
+        .await
+    {
+        Ok(WebAuthnCeremony::Authentication {
+            email,
+            login_attempt_id,
+        }) => (email, login_attempt_id),
+        _ => return Err(AuthAPIError::IncorrectCredentials),
+    };
+
+    let credential = match state
+        .webauthn_credential_store
+        .read()
+        .await
+        .get_credential(&credential_id)
+        .await
+    {
+        Ok(credential) if credential.email == email => credential,
+        Ok(_) | Err(WebAuthnCredentialStoreError::CredentialNotFound) => {
+            return Err(AuthAPIError::IncorrectCredentials)
+        }
+        Err(_) => return Err(AuthAPIError::UnexpectedError),
+    };
+
+    // Without a password the assertion has to prove the user was verified (PIN, biometrics)
+    let auth_data = AuthenticatorData::parse(&authenticator_data)
+        .map_err(|_| AuthAPIError::IncorrectCredentials)?;
+    auth_data
+        .check(login_attempt_id.is_none())
+        .map_err(|_| AuthAPIError::IncorrectCredentials)?;
+
+    verify_assertion(
+        &credential.public_key,
+        &authenticator_data,
+        &client_data_json,
+        &signature,
+    )
+    .map_err(|_| AuthAPIError::IncorrectCredentials)?;
+
+    match state
+        .webauthn_credential_store
+        .write()
+        .await
+        .update_sign_count(&credential_id, auth_data.sign_count)
+        .await
+    {
+        Ok(()) => {}
+        Err(WebAuthnCredentialStoreError::SignCountNotIncreased) => {
+            return Err(AuthAPIError::IncorrectCredentials)
+        }
+        Err(_) => return Err(AuthAPIError::UnexpectedError),
+    }
+
+    let organization_id = match login_attempt_id {
+        Some(login_attempt_id) => {
+            check_login_attempt(&email, &login_attempt_id, state).await?;
+
+            complete_login_attempt(&login_attempt_id, state).await?
+        }
+        None => {
+            let user = state
+                .user_store
+                .get_user(&email)
+                .await
+                .map_err(|_| AuthAPIError::IncorrectCredentials)?;
+
+            if !user.email_verified {
+                return Err(AuthAPIError::EmailNotVerified);
+            }
+
+            None
+        }
+    };
+
+    Ok((email, organization_id))
+}
+
+// The login attempt must be the one started by the password step of this user
+async fn check_login_attempt(
+    email: &Email,
+    login_attempt_id: &LoginAttemptId,
+    state: &AppState,
+) -> Result<(), AuthAPIError> {
+    match state.two_fa_code_store.get_code(login_attempt_id).await {
+        Ok((stored_email, _)) if stored_email == *email => Ok(()),
+        _ => Err(AuthAPIError::IncorrectCredentials),
+    }
+}
+
+async fn get_credential_descriptors(
+    email: &Email,
+    state: &AppState,
+) -> Result<Vec<CredentialDescriptor>, AuthAPIError> {
+    let credentials = state
+        .webauthn_credential_store
+        .read()
+        .await
+        .get_user_credentials(email)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    Ok(credentials
+        .iter()
+        .map(|credential| CredentialDescriptor {
+            credential_type: PUBLIC_KEY_CREDENTIAL_TYPE.to_owned(),
+            id: credential.credential_id.to_base64url(),
+        })
+        .collect())
+}
+
+fn decode_base64url(value: &str) -> Result<Vec<u8>, AuthAPIError> {
+    URL_SAFE_NO_PAD
+        .decode(value)
+        .map_err(|_| AuthAPIError::InvalidCredentials)
+}
+
+const PUBLIC_KEY_CREDENTIAL_TYPE: &str = "public-key";
+
+// Binary values are exchanged as unpadded base64url, the same encoding the browser's
+// `PublicKeyCredential.toJSON()` produces
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreationOptions {
+    pub challenge: String,
+    pub rp: RelyingParty,
+    pub user: UserEntity,
+    #[serde(rename = "pubKeyCredParams")]
+    pub pub_key_cred_params: Vec<CredentialParameters>,
+    pub timeout: i64,
+    pub attestation: String,
+    #[serde(rename = "excludeCredentials")]
+    pub exclude_credentials: Vec<CredentialDescriptor>,
+    #[serde(rename = "authenticatorSelection")]
+    pub authenticator_selection: AuthenticatorSelection,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RelyingParty {
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UserEntity {
+    pub id: String,
+    pub name: String,
+    #[serde(rename = "displayName")]
+    pub display_name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CredentialParameters {
+    #[serde(rename = "type")]
+    pub credential_type: String,
+    pub alg: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CredentialDescriptor {
+    #[serde(rename = "type")]
+    pub credential_type: String,
+    pub id: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuthenticatorSelection {
+    #[serde(rename = "residentKey")]
+    pub resident_key: String,
+    #[serde(rename = "userVerification")]
+    pub user_verification: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RequestOptions {
+    pub challenge: String,
+    pub timeout: i64,
+    #[serde(rename = "rpId")]
+    pub rp_id: String,
+    #[serde(rename = "allowCredentials")]
+    pub allow_credentials: Vec<CredentialDescriptor>,
+    #[serde(rename = "userVerification")]
+    pub user_verification: String,
+}
+
+#[derive(Deserialize)]
+pub struct RegistrationCredential {
+    pub id: String,
+    pub response: AttestationResponse,
+}
+
+#[derive(Deserialize)]
+pub struct AttestationResponse {
+    #[serde(rename = "clientDataJSON")]
+    pub client_data_json: String,
+    #[serde(rename = "attestationObject")]
+    pub attestation_object: String,
+}
+
+#[derive(Deserialize)]
+pub struct StartWebAuthnLoginRequest {
+    pub email: String,
+    #[serde(rename = "loginAttemptId")]
+    pub login_attempt_id: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct AuthenticationCredential {
+    pub id: String,
+    pub response: AssertionResponse,
+}
+
+#[derive(Deserialize)]
+pub struct AssertionResponse {
+    #[serde(rename = "clientDataJSON")]
+    pub client_data_json: String,
+    #[serde(rename = "authenticatorData")]
+    pub authenticator_data: String,
+    pub signature: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WebAuthnResponse {
+    pub message: String,
+    #[serde(
+        rename = "recoveryCodes",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub recovery_codes: Option<Vec<String>>,
+}
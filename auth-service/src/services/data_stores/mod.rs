@@ -1,3 +1,52 @@
+pub mod postgres_banned_token_store;
+pub mod postgres_email_outbox_store;
+pub mod postgres_failed_login_store;
+pub mod postgres_jwt_key_store;
+pub mod postgres_one_time_token_store;
+pub mod postgres_organization_store;
+pub mod postgres_rate_limit_store;
+pub mod postgres_recovery_code_store;
+pub mod postgres_refresh_token_store;
+pub mod postgres_role_store;
+pub mod postgres_totp_secret_store;
+pub mod postgres_two_fa_code_store;
 pub mod postgres_user_store;
+pub mod postgres_webauthn_challenge_store;
+pub mod postgres_webauthn_credential_store;
 pub mod redis_banned_token_store;
+pub mod redis_failed_login_store;
+pub mod redis_one_time_token_store;
+pub mod redis_rate_limit_store;
+pub mod redis_refresh_token_store;
 pub mod redis_two_fa_code_store;
+pub mod redis_webauthn_challenge_store;
+#[cfg(feature = "sqlite")]
+pub mod sqlite_banned_token_store;
+#[cfg(feature = "sqlite")]
+pub mod sqlite_email_outbox_store;
+#[cfg(feature = "sqlite")]
+pub mod sqlite_failed_login_store;
+#[cfg(feature = "sqlite")]
+pub mod sqlite_jwt_key_store;
+#[cfg(feature = "sqlite")]
+pub mod sqlite_one_time_token_store;
+#[cfg(feature = "sqlite")]
+pub mod sqlite_organization_store;
+#[cfg(feature = "sqlite")]
+pub mod sqlite_rate_limit_store;
+#[cfg(feature = "sqlite")]
+pub mod sqlite_recovery_code_store;
+#[cfg(feature = "sqlite")]
+pub mod sqlite_refresh_token_store;
+#[cfg(feature = "sqlite")]
+pub mod sqlite_role_store;
+#[cfg(feature = "sqlite")]
+pub mod sqlite_totp_secret_store;
+#[cfg(feature = "sqlite")]
+pub mod sqlite_two_fa_code_store;
+#[cfg(feature = "sqlite")]
+pub mod sqlite_user_store;
+#[cfg(feature = "sqlite")]
+pub mod sqlite_webauthn_challenge_store;
+#[cfg(feature = "sqlite")]
+pub mod sqlite_webauthn_credential_store;
@@ -0,0 +1,202 @@
+use std::time::Duration;
+
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::domain::{
+    data_stores::{
+        EmailIdempotencyKey, EmailOutboxStore, EmailOutboxStoreError, OutboxEmail,
+        OutboxEmailStatus,
+    },
+    Email, EmailMessage,
+};
+
+// Any number of instances can work the same outbox, `FOR UPDATE SKIP LOCKED` hands every due email
+// to only one of them
+pub struct PostgresEmailOutboxStore {
+    pool: PgPool,
+}
+
+impl PostgresEmailOutboxStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailOutboxStore for PostgresEmailOutboxStore {
+    async fn enqueue(
+        &mut self,
+        key: &EmailIdempotencyKey,
+        recipient: &Email,
+        message: &EmailMessage,
+    ) -> Result<bool, EmailOutboxStoreError> {
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO email_outbox (id, idempotency_key, recipient, subject, html_body, text_body)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            ON CONFLICT (idempotency_key) DO NOTHING
+            "#,
+            Uuid::new_v4(),
+            key.as_ref(),
+            recipient.as_ref(),
+            message.subject,
+            message.html_body,
+            message.text_body
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| EmailOutboxStoreError::UnexpectedError)?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn claim_due(
+        &mut self,
+        limit: u32,
+        lease: Duration,
+    ) -> Result<Vec<OutboxEmail>, EmailOutboxStoreError> {
+        let rows = sqlx::query_as!(
+            OutboxEmailRow,
+            r#"
+            UPDATE email_outbox
+            SET next_attempt_at = NOW() + make_interval(secs => $2)
+            WHERE id IN (
+                SELECT id FROM email_outbox
+                WHERE status = 'pending' AND next_attempt_at <= NOW()
+                ORDER BY next_attempt_at
+                LIMIT $1
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING id, recipient, subject, html_body, text_body, status, attempts, last_error,
+                EXTRACT(EPOCH FROM next_attempt_at)::BIGINT AS "next_attempt_at!",
+                EXTRACT(EPOCH FROM created_at)::BIGINT AS "created_at!"
+            "#,
+            limit as i64,
+            lease.as_secs_f64()
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|_| EmailOutboxStoreError::UnexpectedError)?;
+
+        rows.into_iter().map(OutboxEmailRow::parse).collect()
+    }
+
+    async fn mark_sent(&mut self, id: &Uuid) -> Result<(), EmailOutboxStoreError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE email_outbox
+            SET status = 'sent', attempts = attempts + 1, sent_at = NOW(),
+                html_body = '', text_body = ''
+            WHERE id = $1
+            "#,
+            id
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| EmailOutboxStoreError::UnexpectedError)?;
+
+        if result.rows_affected() == 0 {
+            return Err(EmailOutboxStoreError::EmailNotFound);
+        }
+
+        Ok(())
+    }
+
+    async fn mark_failed(
+        &mut self,
+        id: &Uuid,
+        error: &str,
+        retry_in: Option<Duration>,
+    ) -> Result<(), EmailOutboxStoreError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE email_outbox
+            SET attempts = attempts + 1, last_error = $2,
+                status = CASE WHEN $3::FLOAT8 IS NULL THEN 'dead' ELSE status END,
+                next_attempt_at = NOW() + make_interval(secs => COALESCE($3, 0))
+            WHERE id = $1
+            "#,
+            id,
+            error,
+            retry_in.map(|retry_in| retry_in.as_secs_f64())
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| EmailOutboxStoreError::UnexpectedError)?;
+
+        if result.rows_affected() == 0 {
+            return Err(EmailOutboxStoreError::EmailNotFound);
+        }
+
+        Ok(())
+    }
+
+    async fn count_pending(&self) -> Result<u64, EmailOutboxStoreError> {
+        let count = sqlx::query_scalar!(
+            r#"
+            SELECT COUNT(*) AS "count!"
+            FROM email_outbox
+            WHERE status = 'pending'
+            "#
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|_| EmailOutboxStoreError::UnexpectedError)?;
+
+        Ok(count as u64)
+    }
+
+    async fn get_stuck(&self) -> Result<Vec<OutboxEmail>, EmailOutboxStoreError> {
+        let rows = sqlx::query_as!(
+            OutboxEmailRow,
+            r#"
+            SELECT id, recipient, subject, html_body, text_body, status, attempts, last_error,
+                EXTRACT(EPOCH FROM next_attempt_at)::BIGINT AS "next_attempt_at!",
+                EXTRACT(EPOCH FROM created_at)::BIGINT AS "created_at!"
+            FROM email_outbox
+            WHERE status = 'dead' OR (status = 'pending' AND attempts > 0)
+            ORDER BY created_at DESC
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|_| EmailOutboxStoreError::UnexpectedError)?;
+
+        rows.into_iter().map(OutboxEmailRow::parse).collect()
+    }
+}
+
+struct OutboxEmailRow {
+    id: Uuid,
+    recipient: String,
+    subject: String,
+    html_body: String,
+    text_body: String,
+    status: String,
+    attempts: i32,
+    last_error: Option<String>,
+    next_attempt_at: i64,
+    created_at: i64,
+}
+
+impl OutboxEmailRow {
+    fn parse(self) -> Result<OutboxEmail, EmailOutboxStoreError> {
+        Ok(OutboxEmail {
+            id: self.id,
+            recipient: Email::parse(self.recipient)
+                .map_err(|_| EmailOutboxStoreError::UnexpectedError)?,
+            message: EmailMessage {
+                subject: self.subject,
+                html_body: self.html_body,
+                text_body: self.text_body,
+            },
+            status: OutboxEmailStatus::parse(self.status)
+                .map_err(|_| EmailOutboxStoreError::UnexpectedError)?,
+            attempts: self.attempts as u32,
+            last_error: self.last_error,
+            next_attempt_at: self.next_attempt_at,
+            created_at: self.created_at,
+        })
+    }
+}
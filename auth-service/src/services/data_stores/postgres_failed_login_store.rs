@@ -0,0 +1,110 @@
+use chrono::Utc;
+use sqlx::PgPool;
+
+use crate::{
+    domain::data_stores::{FailedLoginKey, FailedLoginStore, FailedLoginStoreError},
+    utils::auth::FAILED_LOGIN_TTL_SECONDS,
+};
+
+// Failures and locks expire like the Redis keys do, expired rows are ignored and left for
+// `delete_expired`
+pub struct PostgresFailedLoginStore {
+    pool: PgPool,
+}
+
+impl PostgresFailedLoginStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    // Returns how many expired failure counts and locks were deleted
+    pub async fn delete_expired(&self) -> Result<u64, FailedLoginStoreError> {
+        let failures = sqlx::query!("DELETE FROM failed_logins WHERE expires_at <= NOW()")
+            .execute(&self.pool)
+            .await
+            .map_err(|_| FailedLoginStoreError::UnexpectedError)?;
+
+        let locks = sqlx::query!("DELETE FROM login_locks WHERE expires_at <= NOW()")
+            .execute(&self.pool)
+            .await
+            .map_err(|_| FailedLoginStoreError::UnexpectedError)?;
+
+        Ok(failures.rows_affected() + locks.rows_affected())
+    }
+}
+
+#[async_trait::async_trait]
+impl FailedLoginStore for PostgresFailedLoginStore {
+    async fn add_failure(&mut self, key: &FailedLoginKey) -> Result<u32, FailedLoginStoreError> {
+        // Every failure pushes the expiry back, so failures are forgotten after a quiet period.
+        // Counting in one statement keeps concurrent failures from being lost.
+        let failures = sqlx::query_scalar!(
+            r#"
+            INSERT INTO failed_logins (key, failures, expires_at)
+            VALUES ($1, 1, NOW() + make_interval(secs => $2))
+            ON CONFLICT (key) DO UPDATE
+            SET failures = CASE
+                    WHEN failed_logins.expires_at > NOW() THEN failed_logins.failures + 1
+                    ELSE 1
+                END,
+                expires_at = EXCLUDED.expires_at
+            RETURNING failures
+            "#,
+            key.as_ref(),
+            FAILED_LOGIN_TTL_SECONDS as f64
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|_| FailedLoginStoreError::UnexpectedError)?;
+
+        Ok(failures as u32)
+    }
+
+    async fn clear_failures(&mut self, key: &FailedLoginKey) -> Result<(), FailedLoginStoreError> {
+        sqlx::query!("DELETE FROM failed_logins WHERE key = $1", key.as_ref())
+            .execute(&self.pool)
+            .await
+            .map_err(|_| FailedLoginStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    async fn lock(
+        &mut self,
+        key: &FailedLoginKey,
+        until: i64,
+    ) -> Result<(), FailedLoginStoreError> {
+        if until <= Utc::now().timestamp() {
+            return Ok(());
+        }
+
+        sqlx::query!(
+            r#"
+            INSERT INTO login_locks (key, expires_at)
+            VALUES ($1, to_timestamp($2::BIGINT))
+            ON CONFLICT (key) DO UPDATE SET expires_at = EXCLUDED.expires_at
+            "#,
+            key.as_ref(),
+            until
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| FailedLoginStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    async fn get_lock(&self, key: &FailedLoginKey) -> Result<Option<i64>, FailedLoginStoreError> {
+        sqlx::query_scalar!(
+            r#"
+            SELECT EXTRACT(EPOCH FROM expires_at)::BIGINT AS "until!"
+            FROM login_locks
+            WHERE key = $1 AND expires_at > NOW()
+            "#,
+            key.as_ref()
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|_| FailedLoginStoreError::UnexpectedError)
+    }
+}
@@ -0,0 +1,127 @@
+use sqlx::PgPool;
+
+use crate::{
+    domain::{JwtKeyStore, JwtKeyStoreError, StoredJwtKey},
+    utils::encryption::{decrypt, encrypt},
+};
+
+pub struct PostgresJwtKeyStore {
+    pool: PgPool,
+}
+
+impl PostgresJwtKeyStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl JwtKeyStore for PostgresJwtKeyStore {
+    async fn rotate_key(
+        &self,
+        key: &StoredJwtKey,
+        previous: &StoredJwtKey,
+    ) -> Result<(), JwtKeyStoreError> {
+        let retire_at = previous
+            .retire_at
+            .ok_or(JwtKeyStoreError::UnexpectedError)?;
+
+        let mut transaction = self
+            .pool
+            .begin()
+            .await
+            .map_err(|_| JwtKeyStoreError::UnexpectedError)?;
+
+        // Rotations from several instances run one after the other, so only one key is left active
+        sqlx::query!("LOCK TABLE jwt_keys IN EXCLUSIVE MODE")
+            .execute(&mut *transaction)
+            .await
+            .map_err(|_| JwtKeyStoreError::UnexpectedError)?;
+
+        sqlx::query!("DELETE FROM jwt_keys WHERE retire_at <= NOW()")
+            .execute(&mut *transaction)
+            .await
+            .map_err(|_| JwtKeyStoreError::UnexpectedError)?;
+
+        // Keys rotated in by other instances since the caller last loaded the ring are retired too
+        sqlx::query!(
+            r#"
+            UPDATE jwt_keys
+            SET retire_at = to_timestamp($1::BIGINT)
+            WHERE retire_at IS NULL
+            "#,
+            retire_at
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|_| JwtKeyStoreError::UnexpectedError)?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO jwt_keys (kid, algorithm, encrypted_private_key, retire_at)
+            VALUES ($1, $2, $3, to_timestamp($4::BIGINT))
+            ON CONFLICT (kid) DO UPDATE
+            SET retire_at = LEAST(jwt_keys.retire_at, EXCLUDED.retire_at)
+            "#,
+            previous.kid,
+            previous.algorithm,
+            &encrypt_private_key(previous)?,
+            retire_at
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|_| JwtKeyStoreError::UnexpectedError)?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO jwt_keys (kid, algorithm, encrypted_private_key, retire_at)
+            VALUES ($1, $2, $3, NULL)
+            ON CONFLICT (kid) DO UPDATE
+            SET retire_at = NULL, rotated_at = clock_timestamp()
+            "#,
+            key.kid,
+            key.algorithm,
+            &encrypt_private_key(key)?
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|_| JwtKeyStoreError::UnexpectedError)?;
+
+        transaction
+            .commit()
+            .await
+            .map_err(|_| JwtKeyStoreError::UnexpectedError)
+    }
+
+    async fn get_keys(&self) -> Result<Vec<StoredJwtKey>, JwtKeyStoreError> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT kid, algorithm, encrypted_private_key,
+                EXTRACT(EPOCH FROM retire_at)::BIGINT AS "retire_at?"
+            FROM jwt_keys
+            WHERE retire_at IS NULL OR retire_at > NOW()
+            ORDER BY rotated_at DESC
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|_| JwtKeyStoreError::UnexpectedError)?;
+
+        rows.into_iter()
+            .map(|row| {
+                Ok(StoredJwtKey {
+                    private_key: decrypt(&row.encrypted_private_key, row.kid.as_bytes())
+                        .map_err(|_| JwtKeyStoreError::UnexpectedError)?,
+                    kid: row.kid,
+                    algorithm: row.algorithm,
+                    retire_at: row.retire_at,
+                })
+            })
+            .collect()
+    }
+}
+
+// The kid is bound as associated data so a ciphertext cannot be moved to another key's row
+fn encrypt_private_key(key: &StoredJwtKey) -> Result<Vec<u8>, JwtKeyStoreError> {
+    encrypt(&key.private_key, key.kid.as_bytes()).map_err(|_| JwtKeyStoreError::UnexpectedError)
+}
@@ -0,0 +1,100 @@
+use std::time::Duration;
+
+use sqlx::PgPool;
+
+use crate::{
+    domain::{
+        data_stores::{
+            OneTimeToken, OneTimeTokenPurpose, OneTimeTokenStore, OneTimeTokenStoreError,
+        },
+        Email,
+    },
+    utils::auth::one_time_token_ttl,
+};
+
+// Tokens expire like the Redis keys do, expired rows are ignored and left for `delete_expired`
+pub struct PostgresOneTimeTokenStore {
+    pool: PgPool,
+    purpose: OneTimeTokenPurpose,
+    // how long a token can be used for
+    ttl: Duration,
+}
+
+impl PostgresOneTimeTokenStore {
+    pub fn new(pool: PgPool, purpose: OneTimeTokenPurpose) -> Self {
+        Self::with_ttl(pool, purpose, one_time_token_ttl(purpose))
+    }
+
+    pub fn with_ttl(pool: PgPool, purpose: OneTimeTokenPurpose, ttl: Duration) -> Self {
+        Self { pool, purpose, ttl }
+    }
+
+    // Returns how many expired tokens of the store's purpose were deleted
+    pub async fn delete_expired(&self) -> Result<u64, OneTimeTokenStoreError> {
+        let result = sqlx::query!(
+            "DELETE FROM one_time_tokens WHERE purpose = $1 AND expires_at <= NOW()",
+            get_purpose(self.purpose)
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| OneTimeTokenStoreError::UnexpectedError)?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+#[async_trait::async_trait]
+impl OneTimeTokenStore for PostgresOneTimeTokenStore {
+    async fn add_token(
+        &mut self,
+        email: Email,
+        token: &OneTimeToken,
+    ) -> Result<(), OneTimeTokenStoreError> {
+        sqlx::query!(
+            r#"
+            INSERT INTO one_time_tokens (purpose, token_hash, email, expires_at)
+            VALUES ($1, $2, $3, NOW() + make_interval(secs => $4))
+            ON CONFLICT (purpose, token_hash) DO UPDATE
+            SET email = EXCLUDED.email, expires_at = EXCLUDED.expires_at
+            "#,
+            get_purpose(self.purpose),
+            token.hash(),
+            email.as_ref(),
+            self.ttl.as_secs_f64()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| OneTimeTokenStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    async fn consume_token(
+        &mut self,
+        token: &OneTimeToken,
+    ) -> Result<Email, OneTimeTokenStoreError> {
+        // Deleting the row returns it, so concurrent requests cannot both use the token
+        let email = sqlx::query_scalar!(
+            r#"
+            DELETE FROM one_time_tokens
+            WHERE purpose = $1 AND token_hash = $2 AND expires_at > NOW()
+            RETURNING email
+            "#,
+            get_purpose(self.purpose),
+            token.hash()
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|_| OneTimeTokenStoreError::UnexpectedError)?
+        .ok_or(OneTimeTokenStoreError::TokenNotFound)?;
+
+        Email::parse(email).map_err(|_| OneTimeTokenStoreError::UnexpectedError)
+    }
+}
+
+fn get_purpose(purpose: OneTimeTokenPurpose) -> &'static str {
+    match purpose {
+        OneTimeTokenPurpose::PasswordReset => "password_reset",
+        OneTimeTokenPurpose::EmailVerification => "email_verification",
+    }
+}
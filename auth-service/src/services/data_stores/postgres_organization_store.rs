@@ -0,0 +1,229 @@
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    domain::{
+        data_stores::{OrganizationStore, OrganizationStoreError},
+        Email, Invitation, InvitationToken, Membership, OrgRole, Organization, OrganizationId,
+        OrganizationName,
+    },
+    utils::auth::INVITATION_TOKEN_TTL_SECONDS,
+};
+
+pub struct PostgresOrganizationStore {
+    pool: PgPool,
+}
+
+impl PostgresOrganizationStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl OrganizationStore for PostgresOrganizationStore {
+    async fn create_organization(
+        &mut self,
+        name: OrganizationName,
+        owner: &Email,
+    ) -> Result<Organization, OrganizationStoreError> {
+        let organization = Organization {
+            id: OrganizationId::default(),
+            name,
+        };
+
+        let mut transaction = self
+            .pool
+            .begin()
+            .await
+            .map_err(|_| OrganizationStoreError::UnexpectedError)?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO organizations (id, name)
+            VALUES ($1, $2)
+            "#,
+            organization.id.as_ref(),
+            organization.name.as_ref()
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|_| OrganizationStoreError::UnexpectedError)?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO organization_memberships (organization_id, email, role)
+            VALUES ($1, $2, $3)
+            "#,
+            organization.id.as_ref(),
+            owner.as_ref(),
+            OrgRole::Owner.as_ref()
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|_| OrganizationStoreError::UnexpectedError)?;
+
+        transaction
+            .commit()
+            .await
+            .map_err(|_| OrganizationStoreError::UnexpectedError)?;
+
+        Ok(organization)
+    }
+
+    async fn get_memberships(
+        &self,
+        email: &Email,
+    ) -> Result<Vec<Membership>, OrganizationStoreError> {
+        sqlx::query!(
+            r#"
+            SELECT organizations.id, organizations.name, organization_memberships.role
+            FROM organization_memberships
+            JOIN organizations ON organizations.id = organization_memberships.organization_id
+            WHERE organization_memberships.email = $1
+            ORDER BY organization_memberships.created_at
+            "#,
+            email.as_ref()
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|_| OrganizationStoreError::UnexpectedError)?
+        .into_iter()
+        .map(|row| to_membership(row.id, row.name, &row.role))
+        .collect()
+    }
+
+    async fn get_membership(
+        &self,
+        email: &Email,
+        organization_id: &OrganizationId,
+    ) -> Result<Membership, OrganizationStoreError> {
+        let row = sqlx::query!(
+            r#"
+            SELECT organizations.id, organizations.name, organization_memberships.role
+            FROM organization_memberships
+            JOIN organizations ON organizations.id = organization_memberships.organization_id
+            WHERE organization_memberships.email = $1
+              AND organization_memberships.organization_id = $2
+            "#,
+            email.as_ref(),
+            organization_id.as_ref()
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|_| OrganizationStoreError::UnexpectedError)?
+        .ok_or(OrganizationStoreError::MembershipNotFound)?;
+
+        to_membership(row.id, row.name, &row.role)
+    }
+
+    async fn add_invitation(
+        &mut self,
+        token: &InvitationToken,
+        invitation: Invitation,
+    ) -> Result<(), OrganizationStoreError> {
+        sqlx::query!(
+            r#"
+            INSERT INTO organization_invitations (token_hash, organization_id, email, role, expires_at)
+            VALUES ($1, $2, $3, $4, NOW() + make_interval(secs => $5))
+            "#,
+            token.hash(),
+            invitation.organization_id.as_ref(),
+            invitation.email.as_ref(),
+            invitation.role.as_ref(),
+            INVITATION_TOKEN_TTL_SECONDS as f64
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|error| {
+            if let sqlx::Error::Database(db_err) = &error {
+                // 23503 = foreign_key_violation
+                if db_err.code().as_deref() == Some("23503") {
+                    return OrganizationStoreError::OrganizationNotFound;
+                }
+            }
+            OrganizationStoreError::UnexpectedError
+        })?;
+
+        Ok(())
+    }
+
+    async fn accept_invitation(
+        &mut self,
+        token: &InvitationToken,
+        email: &Email,
+    ) -> Result<Membership, OrganizationStoreError> {
+        let mut transaction = self
+            .pool
+            .begin()
+            .await
+            .map_err(|_| OrganizationStoreError::UnexpectedError)?;
+
+        // Deleting the invitation in the same transaction that adds the membership means it can only
+        // be accepted once, even by concurrent requests
+        let invitation = sqlx::query!(
+            r#"
+            DELETE FROM organization_invitations
+            WHERE token_hash = $1 AND email = $2 AND expires_at > NOW()
+            RETURNING organization_id, role
+            "#,
+            token.hash(),
+            email.as_ref()
+        )
+        .fetch_optional(&mut *transaction)
+        .await
+        .map_err(|_| OrganizationStoreError::UnexpectedError)?
+        .ok_or(OrganizationStoreError::InvitationNotFound)?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO organization_memberships (organization_id, email, role)
+            VALUES ($1, $2, $3)
+            "#,
+            invitation.organization_id,
+            email.as_ref(),
+            invitation.role
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|error| {
+            if let sqlx::Error::Database(db_err) = &error {
+                // 23505 = unique_violation, dropping the transaction keeps the invitation
+                if db_err.code().as_deref() == Some("23505") {
+                    return OrganizationStoreError::AlreadyMember;
+                }
+            }
+            OrganizationStoreError::UnexpectedError
+        })?;
+
+        let organization = sqlx::query!(
+            r#"
+            SELECT id, name
+            FROM organizations
+            WHERE id = $1
+            "#,
+            invitation.organization_id
+        )
+        .fetch_one(&mut *transaction)
+        .await
+        .map_err(|_| OrganizationStoreError::UnexpectedError)?;
+
+        transaction
+            .commit()
+            .await
+            .map_err(|_| OrganizationStoreError::UnexpectedError)?;
+
+        to_membership(organization.id, organization.name, &invitation.role)
+    }
+}
+
+fn to_membership(id: Uuid, name: String, role: &str) -> Result<Membership, OrganizationStoreError> {
+    Ok(Membership {
+        organization: Organization {
+            id: OrganizationId::from(id),
+            name: OrganizationName::parse(name)
+                .map_err(|_| OrganizationStoreError::UnexpectedError)?,
+        },
+        role: OrgRole::parse(role).map_err(|_| OrganizationStoreError::UnexpectedError)?,
+    })
+}
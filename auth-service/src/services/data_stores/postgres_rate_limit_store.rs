@@ -0,0 +1,88 @@
+use chrono::Utc;
+use sqlx::PgPool;
+
+use crate::domain::{
+    data_stores::{RateLimitKey, RateLimitStore, RateLimitStoreError},
+    RateLimit, RateLimitDecision, TokenBucket,
+};
+
+// Buckets are forgotten once they are full again, like the Redis keys. Full rows are left for
+// `delete_expired`, a bucket found that way is refilled to full anyway.
+pub struct PostgresRateLimitStore {
+    pool: PgPool,
+}
+
+impl PostgresRateLimitStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    // Returns how many full buckets were deleted
+    pub async fn delete_expired(&self) -> Result<u64, RateLimitStoreError> {
+        let result = sqlx::query!("DELETE FROM rate_limit_buckets WHERE expires_at <= NOW()")
+            .execute(&self.pool)
+            .await
+            .map_err(|_| RateLimitStoreError::UnexpectedError)?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+#[async_trait::async_trait]
+impl RateLimitStore for PostgresRateLimitStore {
+    async fn take_token(
+        &self,
+        key: &RateLimitKey,
+        limit: &RateLimit,
+    ) -> Result<RateLimitDecision, RateLimitStoreError> {
+        let now = Utc::now().timestamp_millis();
+
+        let mut transaction = self
+            .pool
+            .begin()
+            .await
+            .map_err(|_| RateLimitStoreError::UnexpectedError)?;
+
+        // Creating a missing bucket full, or touching an existing one, locks its row, so concurrent
+        // requests, possibly on other instances, cannot both take the last token
+        let row = sqlx::query!(
+            r#"
+            INSERT INTO rate_limit_buckets (key, tokens, updated_at, expires_at)
+            VALUES ($1, $2, $3, NOW())
+            ON CONFLICT (key) DO UPDATE SET key = EXCLUDED.key
+            RETURNING tokens, updated_at
+            "#,
+            key.as_ref(),
+            limit.capacity as f64,
+            now
+        )
+        .fetch_one(&mut *transaction)
+        .await
+        .map_err(|_| RateLimitStoreError::UnexpectedError)?;
+
+        let mut bucket = TokenBucket::restore(row.tokens, row.updated_at);
+        let decision = bucket.take(limit, now);
+
+        sqlx::query!(
+            r#"
+            UPDATE rate_limit_buckets
+            SET tokens = $2, updated_at = $3, expires_at = to_timestamp($4::DOUBLE PRECISION)
+            WHERE key = $1
+            "#,
+            key.as_ref(),
+            bucket.tokens(),
+            bucket.updated_at(),
+            bucket.full_at(limit) as f64 / 1000.0
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|_| RateLimitStoreError::UnexpectedError)?;
+
+        transaction
+            .commit()
+            .await
+            .map_err(|_| RateLimitStoreError::UnexpectedError)?;
+
+        Ok(decision)
+    }
+}
@@ -0,0 +1,126 @@
+use sqlx::PgPool;
+
+use super::postgres_user_store::{compute_password_hash, verify_password_hash};
+use crate::domain::{
+    data_stores::{RecoveryCode, RecoveryCodeStore, RecoveryCodeStoreError},
+    Email,
+};
+
+// Codes are stored as Argon2 hashes, the same way as passwords
+pub struct PostgresRecoveryCodeStore {
+    pool: PgPool,
+}
+
+impl PostgresRecoveryCodeStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl RecoveryCodeStore for PostgresRecoveryCodeStore {
+    async fn replace_codes(
+        &self,
+        email: &Email,
+        codes: Vec<RecoveryCode>,
+    ) -> Result<(), RecoveryCodeStoreError> {
+        // Hash the whole set in parallel, one after the other would noticeably delay the response
+        let hash_tasks: Vec<_> = codes
+            .into_iter()
+            .map(|code| tokio::spawn(compute_password_hash(code.as_ref().to_owned())))
+            .collect();
+
+        let mut code_hashes = Vec::with_capacity(hash_tasks.len());
+        for task in hash_tasks {
+            let code_hash = task
+                .await
+                .map_err(|_| RecoveryCodeStoreError::UnexpectedError)?
+                .map_err(|_| RecoveryCodeStoreError::UnexpectedError)?;
+            code_hashes.push(code_hash);
+        }
+
+        let mut transaction = self
+            .pool
+            .begin()
+            .await
+            .map_err(|_| RecoveryCodeStoreError::UnexpectedError)?;
+
+        sqlx::query!(
+            r#"
+            DELETE FROM recovery_codes
+            WHERE email = $1
+            "#,
+            email.as_ref()
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|_| RecoveryCodeStoreError::UnexpectedError)?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO recovery_codes (email, code_hash)
+            SELECT $1, code_hash FROM UNNEST($2::TEXT[]) AS code_hash
+            "#,
+            email.as_ref(),
+            &code_hashes
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|_| RecoveryCodeStoreError::UnexpectedError)?;
+
+        transaction
+            .commit()
+            .await
+            .map_err(|_| RecoveryCodeStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    async fn use_code(
+        &self,
+        email: &Email,
+        code: &RecoveryCode,
+    ) -> Result<(), RecoveryCodeStoreError> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT id, code_hash
+            FROM recovery_codes
+            WHERE email = $1
+            "#,
+            email.as_ref()
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|_| RecoveryCodeStoreError::UnexpectedError)?;
+
+        // Hashes are salted, so every unused code has to be checked
+        for row in rows {
+            if verify_password_hash(row.code_hash, code.as_ref().to_owned())
+                .await
+                .is_err()
+            {
+                continue;
+            }
+
+            // A concurrent request may have consumed the same code in the meantime
+            let result = sqlx::query!(
+                r#"
+                DELETE FROM recovery_codes
+                WHERE id = $1
+                "#,
+                row.id
+            )
+            .execute(&self.pool)
+            .await
+            .map_err(|_| RecoveryCodeStoreError::UnexpectedError)?;
+
+            return if result.rows_affected() == 1 {
+                Ok(())
+            } else {
+                Err(RecoveryCodeStoreError::CodeNotFound)
+            };
+        }
+
+        Err(RecoveryCodeStoreError::CodeNotFound)
+    }
+}
@@ -0,0 +1,216 @@
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    domain::{
+        data_stores::{RefreshSession, RefreshToken, RefreshTokenStore, RefreshTokenStoreError},
+        Email, OrganizationId,
+    },
+    utils::auth::REFRESH_TOKEN_TTL_SECONDS,
+};
+
+// Tokens and families expire like the Redis keys do, expired rows are ignored and left for
+// `delete_expired`. Only the SHA-256 hash of a token is kept.
+pub struct PostgresRefreshTokenStore {
+    pool: PgPool,
+}
+
+impl PostgresRefreshTokenStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    // Returns how many expired tokens and families were deleted
+    pub async fn delete_expired(&self) -> Result<u64, RefreshTokenStoreError> {
+        let tokens = sqlx::query!("DELETE FROM refresh_tokens WHERE expires_at <= NOW()")
+            .execute(&self.pool)
+            .await
+            .map_err(|_| RefreshTokenStoreError::UnexpectedError)?;
+
+        // the tokens of a family go along with it
+        let families = sqlx::query!("DELETE FROM refresh_token_families WHERE expires_at <= NOW()")
+            .execute(&self.pool)
+            .await
+            .map_err(|_| RefreshTokenStoreError::UnexpectedError)?;
+
+        Ok(tokens.rows_affected() + families.rows_affected())
+    }
+}
+
+#[async_trait::async_trait]
+impl RefreshTokenStore for PostgresRefreshTokenStore {
+    async fn add_token(
+        &mut self,
+        session: RefreshSession,
+        token: RefreshToken,
+    ) -> Result<(), RefreshTokenStoreError> {
+        let mut transaction = self
+            .pool
+            .begin()
+            .await
+            .map_err(|_| RefreshTokenStoreError::UnexpectedError)?;
+
+        // a freshly issued token always starts a new family
+        let family_id = Uuid::new_v4();
+
+        sqlx::query!(
+            r#"
+            INSERT INTO refresh_token_families (family_id, email, organization_id, expires_at)
+            VALUES ($1, $2, $3, NOW() + make_interval(secs => $4))
+            "#,
+            family_id,
+            session.email.as_ref(),
+            session.organization_id.as_ref().map(AsRef::as_ref),
+            REFRESH_TOKEN_TTL_SECONDS as f64
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|_| RefreshTokenStoreError::UnexpectedError)?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO refresh_tokens (token_hash, family_id, expires_at)
+            VALUES ($1, $2, NOW() + make_interval(secs => $3))
+            "#,
+            token.hash(),
+            family_id,
+            REFRESH_TOKEN_TTL_SECONDS as f64
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|_| RefreshTokenStoreError::UnexpectedError)?;
+
+        transaction
+            .commit()
+            .await
+            .map_err(|_| RefreshTokenStoreError::UnexpectedError)
+    }
+
+    async fn rotate_token(
+        &mut self,
+        token: &RefreshToken,
+    ) -> Result<(RefreshSession, RefreshToken), RefreshTokenStoreError> {
+        let mut transaction = self
+            .pool
+            .begin()
+            .await
+            .map_err(|_| RefreshTokenStoreError::UnexpectedError)?;
+
+        // The row lock makes concurrent refreshes, possibly on other instances, exchange the token
+        // one after the other, so only the first one gets a new token
+        let row = sqlx::query!(
+            r#"
+            SELECT refresh_tokens.used, refresh_token_families.family_id,
+                refresh_token_families.email, refresh_token_families.organization_id
+            FROM refresh_tokens
+            JOIN refresh_token_families
+                ON refresh_token_families.family_id = refresh_tokens.family_id
+            WHERE refresh_tokens.token_hash = $1
+                AND refresh_tokens.expires_at > NOW()
+                AND refresh_token_families.expires_at > NOW()
+            FOR UPDATE OF refresh_tokens, refresh_token_families
+            "#,
+            token.hash()
+        )
+        .fetch_optional(&mut *transaction)
+        .await
+        .map_err(|_| RefreshTokenStoreError::UnexpectedError)?
+        .ok_or(RefreshTokenStoreError::TokenNotFound)?;
+
+        // the token was already exchanged once, someone is replaying it so revoke the whole family
+        if row.used {
+            sqlx::query!(
+                "DELETE FROM refresh_token_families WHERE family_id = $1",
+                row.family_id
+            )
+            .execute(&mut *transaction)
+            .await
+            .map_err(|_| RefreshTokenStoreError::UnexpectedError)?;
+
+            transaction
+                .commit()
+                .await
+                .map_err(|_| RefreshTokenStoreError::UnexpectedError)?;
+
+            return Err(RefreshTokenStoreError::TokenReused);
+        }
+
+        sqlx::query!(
+            "UPDATE refresh_tokens SET used = TRUE WHERE token_hash = $1",
+            token.hash()
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|_| RefreshTokenStoreError::UnexpectedError)?;
+
+        // extend the lifetime of the family along with the new token
+        let new_token = RefreshToken::default();
+        sqlx::query!(
+            r#"
+            INSERT INTO refresh_tokens (token_hash, family_id, expires_at)
+            VALUES ($1, $2, NOW() + make_interval(secs => $3))
+            "#,
+            new_token.hash(),
+            row.family_id,
+            REFRESH_TOKEN_TTL_SECONDS as f64
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|_| RefreshTokenStoreError::UnexpectedError)?;
+
+        sqlx::query!(
+            r#"
+            UPDATE refresh_token_families
+            SET expires_at = NOW() + make_interval(secs => $2)
+            WHERE family_id = $1
+            "#,
+            row.family_id,
+            REFRESH_TOKEN_TTL_SECONDS as f64
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|_| RefreshTokenStoreError::UnexpectedError)?;
+
+        transaction
+            .commit()
+            .await
+            .map_err(|_| RefreshTokenStoreError::UnexpectedError)?;
+
+        let email = Email::parse(row.email).map_err(|_| RefreshTokenStoreError::UnexpectedError)?;
+
+        Ok((
+            RefreshSession {
+                email,
+                organization_id: row.organization_id.map(OrganizationId::from),
+            },
+            new_token,
+        ))
+    }
+
+    async fn revoke_token(&mut self, token: &RefreshToken) -> Result<(), RefreshTokenStoreError> {
+        sqlx::query!(
+            r#"
+            DELETE FROM refresh_token_families
+            WHERE family_id IN (SELECT family_id FROM refresh_tokens WHERE token_hash = $1)
+            "#,
+            token.hash()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| RefreshTokenStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    async fn revoke_user_tokens(&mut self, email: &Email) -> Result<(), RefreshTokenStoreError> {
+        sqlx::query!(
+            "DELETE FROM refresh_token_families WHERE email = $1",
+            email.as_ref()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| RefreshTokenStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+}
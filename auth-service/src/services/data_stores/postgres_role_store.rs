@@ -0,0 +1,152 @@
+use std::collections::BTreeSet;
+
+use sqlx::PgPool;
+
+use crate::domain::{
+    data_stores::{RoleStore, RoleStoreError},
+    Email, Permission, Role, RoleDefinition, UserAccess,
+};
+
+pub struct PostgresRoleStore {
+    pool: PgPool,
+}
+
+impl PostgresRoleStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl RoleStore for PostgresRoleStore {
+    async fn get_user_access(&self, email: &Email) -> Result<UserAccess, RoleStoreError> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT user_roles.role, role_permissions.permission AS "permission?"
+            FROM user_roles
+            LEFT JOIN role_permissions ON role_permissions.role = user_roles.role
+            WHERE user_roles.email = $1
+            "#,
+            email.as_ref()
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|_| RoleStoreError::UnexpectedError)?;
+
+        // A permission granted by several roles is listed once
+        let mut roles = BTreeSet::new();
+        let mut permissions = BTreeSet::new();
+
+        for row in rows {
+            roles.insert(Role::parse(row.role).map_err(|_| RoleStoreError::UnexpectedError)?);
+
+            if let Some(permission) = row.permission {
+                permissions.insert(
+                    Permission::parse(permission).map_err(|_| RoleStoreError::UnexpectedError)?,
+                );
+            }
+        }
+
+        Ok(UserAccess {
+            roles: roles.into_iter().collect(),
+            permissions: permissions.into_iter().collect(),
+        })
+    }
+
+    async fn get_roles(&self) -> Result<Vec<RoleDefinition>, RoleStoreError> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT roles.name, role_permissions.permission AS "permission?"
+            FROM roles
+            LEFT JOIN role_permissions ON role_permissions.role = roles.name
+            ORDER BY roles.name, role_permissions.permission
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|_| RoleStoreError::UnexpectedError)?;
+
+        let mut definitions: Vec<RoleDefinition> = Vec::new();
+
+        for row in rows {
+            let role = Role::parse(row.name).map_err(|_| RoleStoreError::UnexpectedError)?;
+
+            // rows are ordered by role, so the permissions of a role are next to each other
+            if definitions.last().map(|definition| &definition.role) != Some(&role) {
+                definitions.push(RoleDefinition {
+                    role,
+                    permissions: Vec::new(),
+                });
+            }
+
+            if let Some(permission) = row.permission {
+                let permission =
+                    Permission::parse(permission).map_err(|_| RoleStoreError::UnexpectedError)?;
+                if let Some(definition) = definitions.last_mut() {
+                    definition.permissions.push(permission);
+                }
+            }
+        }
+
+        Ok(definitions)
+    }
+
+    async fn assign_role(&mut self, email: &Email, role: &Role) -> Result<(), RoleStoreError> {
+        sqlx::query!(
+            r#"
+            INSERT INTO user_roles (email, role)
+            VALUES ($1, $2)
+            ON CONFLICT DO NOTHING
+            "#,
+            email.as_ref(),
+            role.as_ref()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|error| {
+            if let sqlx::Error::Database(db_err) = &error {
+                // 23503 = foreign_key_violation, the constraint tells which reference is missing
+                if db_err.code().as_deref() == Some("23503") {
+                    return match db_err.constraint() {
+                        Some("user_roles_role_fkey") => RoleStoreError::RoleNotFound,
+                        _ => RoleStoreError::UserNotFound,
+                    };
+                }
+            }
+            RoleStoreError::UnexpectedError
+        })?;
+
+        Ok(())
+    }
+
+    async fn revoke_role(&mut self, email: &Email, role: &Role) -> Result<(), RoleStoreError> {
+        let role_exists = sqlx::query!(
+            r#"
+            SELECT EXISTS(SELECT 1 FROM roles WHERE name = $1) AS "exists!"
+            "#,
+            role.as_ref()
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|_| RoleStoreError::UnexpectedError)?
+        .exists;
+
+        if !role_exists {
+            return Err(RoleStoreError::RoleNotFound);
+        }
+
+        sqlx::query!(
+            r#"
+            DELETE FROM user_roles
+            WHERE email = $1 AND role = $2
+            "#,
+            email.as_ref(),
+            role.as_ref()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| RoleStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,137 @@
+use sqlx::PgPool;
+
+use crate::{
+    domain::{
+        data_stores::{TotpEnrollment, TotpSecret, TotpSecretStore, TotpSecretStoreError},
+        Email,
+    },
+    utils::encryption::{decrypt, encrypt},
+};
+
+pub struct PostgresTotpSecretStore {
+    pool: PgPool,
+}
+
+impl PostgresTotpSecretStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl TotpSecretStore for PostgresTotpSecretStore {
+    async fn add_secret(
+        &mut self,
+        email: Email,
+        secret: TotpSecret,
+    ) -> Result<(), TotpSecretStoreError> {
+        let encrypted_secret = encrypt_secret(&email, &secret)?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO totp_secrets (email, encrypted_secret, confirmed, last_used_time_step)
+            VALUES ($1, $2, FALSE, NULL)
+            ON CONFLICT (email) DO UPDATE
+            SET encrypted_secret = EXCLUDED.encrypted_secret,
+                confirmed = FALSE,
+                last_used_time_step = NULL
+            "#,
+            email.as_ref(),
+            &encrypted_secret
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| TotpSecretStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    async fn get_secret(&self, email: &Email) -> Result<TotpEnrollment, TotpSecretStoreError> {
+        let row = sqlx::query!(
+            r#"
+            SELECT encrypted_secret, confirmed
+            FROM totp_secrets
+            WHERE email = $1
+            "#,
+            email.as_ref()
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|_| TotpSecretStoreError::UnexpectedError)?
+        .ok_or(TotpSecretStoreError::SecretNotFound)?;
+
+        Ok(TotpEnrollment {
+            secret: decrypt_secret(email, &row.encrypted_secret)?,
+            confirmed: row.confirmed,
+        })
+    }
+
+    async fn confirm_secret(&mut self, email: &Email) -> Result<(), TotpSecretStoreError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE totp_secrets
+            SET confirmed = TRUE
+            WHERE email = $1
+            "#,
+            email.as_ref()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| TotpSecretStoreError::UnexpectedError)?;
+
+        if result.rows_affected() == 0 {
+            return Err(TotpSecretStoreError::SecretNotFound);
+        }
+
+        Ok(())
+    }
+
+    async fn use_time_step(
+        &mut self,
+        email: &Email,
+        time_step: u64,
+    ) -> Result<(), TotpSecretStoreError> {
+        let time_step: i64 = time_step
+            .try_into()
+            .map_err(|_| TotpSecretStoreError::UnexpectedError)?;
+
+        // Checking and recording the step in one statement keeps concurrent logins from both using a code
+        let result = sqlx::query!(
+            r#"
+            UPDATE totp_secrets
+            SET last_used_time_step = $1
+            WHERE email = $2
+            AND (last_used_time_step IS NULL OR last_used_time_step < $1)
+            "#,
+            time_step,
+            email.as_ref()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| TotpSecretStoreError::UnexpectedError)?;
+
+        if result.rows_affected() == 0 {
+            // tell a missing secret apart from a replayed code
+            self.get_secret(email).await?;
+            return Err(TotpSecretStoreError::TimeStepAlreadyUsed);
+        }
+
+        Ok(())
+    }
+}
+
+// The email is bound as associated data so a ciphertext cannot be moved to another user's row
+fn encrypt_secret(email: &Email, secret: &TotpSecret) -> Result<Vec<u8>, TotpSecretStoreError> {
+    encrypt(secret.as_ref(), email.as_ref().as_bytes())
+        .map_err(|_| TotpSecretStoreError::UnexpectedError)
+}
+
+fn decrypt_secret(
+    email: &Email,
+    encrypted_secret: &[u8],
+) -> Result<TotpSecret, TotpSecretStoreError> {
+    let secret = decrypt(encrypted_secret, email.as_ref().as_bytes())
+        .map_err(|_| TotpSecretStoreError::UnexpectedError)?;
+
+    TotpSecret::parse(secret).map_err(|_| TotpSecretStoreError::UnexpectedError)
+}
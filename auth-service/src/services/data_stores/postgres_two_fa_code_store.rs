@@ -0,0 +1,266 @@
+use std::time::Duration;
+
+use sqlx::PgPool;
+
+use crate::{
+    domain::{
+        data_stores::{LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError},
+        Email, OrganizationId,
+    },
+    utils::{
+        auth::{
+            MAX_TWO_FA_ATTEMPTS, MAX_TWO_FA_RESENDS, TWO_FA_CODE_TTL_SECONDS,
+            TWO_FA_RESEND_COOLDOWN_SECONDS,
+        },
+        constants::MAX_PENDING_LOGIN_ATTEMPTS,
+    },
+};
+
+// Pending logins expire after the TTL like the Redis keys do, expired rows are ignored and left
+// for `delete_expired`
+pub struct PostgresTwoFACodeStore {
+    pool: PgPool,
+    // how long a login attempt can be completed for
+    ttl: Duration,
+}
+
+impl PostgresTwoFACodeStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self::with_ttl(pool, Duration::from_secs(TWO_FA_CODE_TTL_SECONDS as u64))
+    }
+
+    pub fn with_ttl(pool: PgPool, ttl: Duration) -> Self {
+        Self { pool, ttl }
+    }
+
+    // Returns how many expired login attempts were deleted
+    pub async fn delete_expired(&self) -> Result<u64, TwoFACodeStoreError> {
+        let result = sqlx::query!("DELETE FROM two_fa_codes WHERE expires_at <= NOW()")
+            .execute(&self.pool)
+            .await
+            .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+#[async_trait::async_trait]
+impl TwoFACodeStore for PostgresTwoFACodeStore {
+    async fn add_code(
+        &self,
+        email: Email,
+        login_attempt_id: LoginAttemptId,
+        code: TwoFACode,
+        organization_id: Option<OrganizationId>,
+    ) -> Result<(), TwoFACodeStoreError> {
+        let mut transaction = self
+            .pool
+            .begin()
+            .await
+            .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+
+        // Logins of the same user wait for each other, otherwise each could miss the others' rows
+        // and leave more than the cap pending
+        sqlx::query!("SELECT pg_advisory_xact_lock(hashtext($1))", email.as_ref())
+            .execute(&mut *transaction)
+            .await
+            .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO two_fa_codes (login_attempt_id, email, code, organization_id, expires_at)
+            VALUES ($1, $2, $3, $4, NOW() + make_interval(secs => $5))
+            "#,
+            login_attempt_id.as_ref(),
+            email.as_ref(),
+            code.as_ref(),
+            organization_id.as_ref().map(AsRef::as_ref),
+            self.ttl.as_secs_f64()
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+
+        // The user's pending attempts beyond the cap are discarded, oldest first
+        sqlx::query!(
+            r#"
+            DELETE FROM two_fa_codes
+            WHERE login_attempt_id IN (
+                SELECT login_attempt_id FROM two_fa_codes
+                WHERE email = $1 AND expires_at > NOW()
+                ORDER BY created_at DESC
+                OFFSET $2
+            )
+            "#,
+            email.as_ref(),
+            *MAX_PENDING_LOGIN_ATTEMPTS as i64
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+
+        transaction
+            .commit()
+            .await
+            .map_err(|_| TwoFACodeStoreError::UnexpectedError)
+    }
+
+    async fn remove_code(
+        &self,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<Option<OrganizationId>, TwoFACodeStoreError> {
+        let organization_id = sqlx::query_scalar!(
+            r#"
+            DELETE FROM two_fa_codes
+            WHERE login_attempt_id = $1 AND expires_at > NOW()
+            RETURNING organization_id
+            "#,
+            login_attempt_id.as_ref()
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|_| TwoFACodeStoreError::UnexpectedError)?
+        .ok_or(TwoFACodeStoreError::LoginAttemptIdNotFound)?;
+
+        Ok(organization_id.map(OrganizationId::from))
+    }
+
+    async fn get_code(
+        &self,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<(Email, TwoFACode), TwoFACodeStoreError> {
+        let row = sqlx::query!(
+            r#"
+            SELECT email, code
+            FROM two_fa_codes
+            WHERE login_attempt_id = $1 AND expires_at > NOW()
+            "#,
+            login_attempt_id.as_ref()
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|_| TwoFACodeStoreError::UnexpectedError)?
+        .ok_or(TwoFACodeStoreError::LoginAttemptIdNotFound)?;
+
+        let email = Email::parse(row.email).map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+        let code = TwoFACode::parse(row.code).map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+
+        Ok((email, code))
+    }
+
+    async fn verify_code(
+        &self,
+        login_attempt_id: &LoginAttemptId,
+        code: &TwoFACode,
+    ) -> Result<(), TwoFACodeStoreError> {
+        // One statement, so concurrent guesses are counted one after the other and none of them is
+        // compared once the limit is reached
+        let row = sqlx::query!(
+            r#"
+            UPDATE two_fa_codes
+            SET failed_attempts = failed_attempts + CASE WHEN code = $2 THEN 0 ELSE 1 END
+            WHERE login_attempt_id = $1 AND expires_at > NOW() AND failed_attempts < $3
+            RETURNING code = $2 AS "verified!", failed_attempts
+            "#,
+            login_attempt_id.as_ref(),
+            code.as_ref(),
+            MAX_TWO_FA_ATTEMPTS as i32
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|_| TwoFACodeStoreError::UnexpectedError)?
+        .ok_or(TwoFACodeStoreError::LoginAttemptIdNotFound)?;
+
+        if row.verified {
+            return Ok(());
+        }
+
+        if row.failed_attempts as u32 >= MAX_TWO_FA_ATTEMPTS {
+            match self.remove_code(login_attempt_id).await {
+                // a concurrent request may have removed it already
+                Ok(_) | Err(TwoFACodeStoreError::LoginAttemptIdNotFound) => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        Err(TwoFACodeStoreError::IncorrectCode)
+    }
+
+    async fn reserve_attempt(
+        &self,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<u32, TwoFACodeStoreError> {
+        // One statement, so concurrent guesses cannot take more than the limit
+        let failed_attempts = sqlx::query_scalar!(
+            r#"
+            UPDATE two_fa_codes
+            SET failed_attempts = failed_attempts + 1
+            WHERE login_attempt_id = $1 AND expires_at > NOW() AND failed_attempts < $2
+            RETURNING failed_attempts
+            "#,
+            login_attempt_id.as_ref(),
+            MAX_TWO_FA_ATTEMPTS as i32
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|_| TwoFACodeStoreError::UnexpectedError)?
+        .ok_or(TwoFACodeStoreError::LoginAttemptIdNotFound)?;
+
+        Ok(failed_attempts as u32)
+    }
+
+    async fn resend_code(
+        &self,
+        login_attempt_id: &LoginAttemptId,
+        code: TwoFACode,
+    ) -> Result<(), TwoFACodeStoreError> {
+        // One statement, so concurrent resends cannot exceed the limit or skip the cooldown
+        let result = sqlx::query!(
+            r#"
+            UPDATE two_fa_codes
+            SET code = $2, resends = resends + 1, sent_at = NOW(),
+                expires_at = NOW() + make_interval(secs => $3)
+            WHERE login_attempt_id = $1 AND expires_at > NOW()
+                AND resends < $4
+                AND sent_at <= NOW() - make_interval(secs => $5)
+            "#,
+            login_attempt_id.as_ref(),
+            code.as_ref(),
+            self.ttl.as_secs_f64(),
+            MAX_TWO_FA_RESENDS as i32,
+            TWO_FA_RESEND_COOLDOWN_SECONDS as f64
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+
+        if result.rows_affected() == 1 {
+            return Ok(());
+        }
+
+        // Find out why the code was not replaced
+        let row = sqlx::query!(
+            r#"
+            SELECT resends,
+                CEIL(EXTRACT(EPOCH FROM sent_at + make_interval(secs => $2) - NOW()))::BIGINT
+                    AS "cooldown_seconds!"
+            FROM two_fa_codes
+            WHERE login_attempt_id = $1 AND expires_at > NOW()
+            "#,
+            login_attempt_id.as_ref(),
+            TWO_FA_RESEND_COOLDOWN_SECONDS as f64
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|_| TwoFACodeStoreError::UnexpectedError)?
+        .ok_or(TwoFACodeStoreError::LoginAttemptIdNotFound)?;
+
+        if row.resends as u32 >= MAX_TWO_FA_RESENDS {
+            return Err(TwoFACodeStoreError::TooManyResends);
+        }
+
+        Err(TwoFACodeStoreError::ResendCooldown(
+            row.cooldown_seconds.max(1) as u64,
+        ))
+    }
+}
@@ -10,7 +10,7 @@ use sqlx::PgPool;
 
 use crate::domain::{
     data_stores::{UserStore, UserStoreError},
-    Email, Password, User,
+    Email, Password, TwoFAMethod, User,
 };
 
 pub struct PostgresUserStore {
@@ -25,19 +25,20 @@ impl PostgresUserStore {
 
 #[async_trait::async_trait]
 impl UserStore for PostgresUserStore {
-    async fn add_user(&mut self, user: User) -> Result<(), UserStoreError> {
+    async fn add_user(&self, user: User) -> Result<(), UserStoreError> {
         let password_hash = compute_password_hash(user.password.as_ref().to_owned())
             .await
             .map_err(|_| UserStoreError::UnexpectedError)?;
 
         sqlx::query!(
             r#"
-            INSERT INTO users (email, password_hash, requires_2fa)
-            VALUES ($1, $2, $3)
+            INSERT INTO users (email, password_hash, two_fa_method, email_verified)
+            VALUES ($1, $2, $3, $4)
             "#,
             user.email.as_ref(),
             &password_hash,
-            user.requires_2fa
+            user.two_fa_method.as_ref(),
+            user.email_verified
         )
         .execute(&self.pool)
         .await
@@ -58,7 +59,7 @@ impl UserStore for PostgresUserStore {
     async fn get_user(&self, email: &Email) -> Result<User, UserStoreError> {
         sqlx::query!(
             r#"
-            SELECT email, password_hash, requires_2fa
+            SELECT email, password_hash, two_fa_method, email_verified
             FROM users
             WHERE email = $1
             "#,
@@ -72,7 +73,9 @@ impl UserStore for PostgresUserStore {
                 email: Email::parse(row.email).map_err(|_| UserStoreError::UnexpectedError)?,
                 password: Password::parse(row.password_hash)
                     .map_err(|_| UserStoreError::UnexpectedError)?,
-                requires_2fa: row.requires_2fa,
+                two_fa_method: TwoFAMethod::parse(row.two_fa_method)
+                    .map_err(|_| UserStoreError::UnexpectedError)?,
+                email_verified: row.email_verified,
             })
         })
         .ok_or(UserStoreError::UserNotFound)?
@@ -92,9 +95,83 @@ impl UserStore for PostgresUserStore {
         .await
         .map_err(|_| UserStoreError::InvalidCredentials)
     }
+
+    async fn update_password(
+        &self,
+        email: &Email,
+        password: Password,
+    ) -> Result<(), UserStoreError> {
+        let password_hash = compute_password_hash(password.as_ref().to_owned())
+            .await
+            .map_err(|_| UserStoreError::UnexpectedError)?;
+
+        let result = sqlx::query!(
+            r#"
+            UPDATE users
+            SET password_hash = $1
+            WHERE email = $2
+            "#,
+            &password_hash,
+            email.as_ref()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| UserStoreError::UnexpectedError)?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        Ok(())
+    }
+
+    async fn set_email_verified(&self, email: &Email) -> Result<(), UserStoreError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE users
+            SET email_verified = TRUE
+            WHERE email = $1
+            "#,
+            email.as_ref()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| UserStoreError::UnexpectedError)?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        Ok(())
+    }
+
+    async fn set_two_fa_method(
+        &self,
+        email: &Email,
+        two_fa_method: TwoFAMethod,
+    ) -> Result<(), UserStoreError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE users
+            SET two_fa_method = $1
+            WHERE email = $2
+            "#,
+            two_fa_method.as_ref(),
+            email.as_ref()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| UserStoreError::UnexpectedError)?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        Ok(())
+    }
 }
 
-async fn verify_password_hash(
+pub(crate) async fn verify_password_hash(
     expected_password_hash: String,
     password_candidate: String,
 ) -> Result<(), Box<dyn Error + Send + Sync>> {
@@ -110,7 +187,9 @@ async fn verify_password_hash(
     result?
 }
 
-async fn compute_password_hash(password: String) -> Result<String, Box<dyn Error + Send + Sync>> {
+pub(crate) async fn compute_password_hash(
+    password: String,
+) -> Result<String, Box<dyn Error + Send + Sync>> {
     let result = tokio::task::spawn_blocking(move || {
         let salt: SaltString = SaltString::generate(&mut OsRng);
         let password_hash = Argon2::new(
@@ -0,0 +1,114 @@
+use sqlx::PgPool;
+
+use crate::{
+    domain::{
+        data_stores::{
+            LoginAttemptId, WebAuthnCeremony, WebAuthnChallenge, WebAuthnChallengeStore,
+            WebAuthnChallengeStoreError,
+        },
+        Email,
+    },
+    utils::auth::WEBAUTHN_CHALLENGE_TTL_SECONDS,
+};
+
+// Challenges expire like the Redis keys do, expired rows are ignored and left for `delete_expired`
+pub struct PostgresWebAuthnChallengeStore {
+    pool: PgPool,
+}
+
+impl PostgresWebAuthnChallengeStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    // Returns how many expired challenges were deleted
+    pub async fn delete_expired(&self) -> Result<u64, WebAuthnChallengeStoreError> {
+        let result = sqlx::query!("DELETE FROM webauthn_challenges WHERE expires_at <= NOW()")
+            .execute(&self.pool)
+            .await
+            .map_err(|_| WebAuthnChallengeStoreError::UnexpectedError)?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+const REGISTRATION: &str = "registration";
+const AUTHENTICATION: &str = "authentication";
+
+#[async_trait::async_trait]
+impl WebAuthnChallengeStore for PostgresWebAuthnChallengeStore {
+    async fn add_challenge(
+        &mut self,
+        challenge: &WebAuthnChallenge,
+        ceremony: WebAuthnCeremony,
+    ) -> Result<(), WebAuthnChallengeStoreError> {
+        let (kind, email, login_attempt_id) = match ceremony {
+            WebAuthnCeremony::Registration { email } => (REGISTRATION, email, None),
+            WebAuthnCeremony::Authentication {
+                email,
+                login_attempt_id,
+            } => (AUTHENTICATION, email, login_attempt_id),
+        };
+
+        sqlx::query!(
+            r#"
+            INSERT INTO webauthn_challenges (challenge, ceremony, email, login_attempt_id, expires_at)
+            VALUES ($1, $2, $3, $4, NOW() + make_interval(secs => $5))
+            ON CONFLICT (challenge) DO UPDATE
+            SET ceremony = EXCLUDED.ceremony,
+                email = EXCLUDED.email,
+                login_attempt_id = EXCLUDED.login_attempt_id,
+                expires_at = EXCLUDED.expires_at
+            "#,
+            challenge.as_ref(),
+            kind,
+            email.as_ref(),
+            login_attempt_id.as_ref().map(AsRef::as_ref),
+            WEBAUTHN_CHALLENGE_TTL_SECONDS as f64
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| WebAuthnChallengeStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    async fn consume_challenge(
+        &mut self,
+        challenge: &WebAuthnChallenge,
+    ) -> Result<WebAuthnCeremony, WebAuthnChallengeStoreError> {
+        // Deleting the row returns it, so a challenge is only ever used once
+        let row = sqlx::query!(
+            r#"
+            DELETE FROM webauthn_challenges
+            WHERE challenge = $1 AND expires_at > NOW()
+            RETURNING ceremony, email, login_attempt_id
+            "#,
+            challenge.as_ref()
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|_| WebAuthnChallengeStoreError::UnexpectedError)?
+        .ok_or(WebAuthnChallengeStoreError::ChallengeNotFound)?;
+
+        let email =
+            Email::parse(row.email).map_err(|_| WebAuthnChallengeStoreError::UnexpectedError)?;
+
+        match row.ceremony.as_str() {
+            REGISTRATION => Ok(WebAuthnCeremony::Registration { email }),
+            AUTHENTICATION => {
+                let login_attempt_id = row
+                    .login_attempt_id
+                    .map(LoginAttemptId::parse)
+                    .transpose()
+                    .map_err(|_| WebAuthnChallengeStoreError::UnexpectedError)?;
+
+                Ok(WebAuthnCeremony::Authentication {
+                    email,
+                    login_attempt_id,
+                })
+            }
+            _ => Err(WebAuthnChallengeStoreError::UnexpectedError),
+        }
+    }
+}
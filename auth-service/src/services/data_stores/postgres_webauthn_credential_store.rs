@@ -0,0 +1,137 @@
+use sqlx::PgPool;
+
+use crate::domain::{
+    data_stores::{
+        CredentialId, WebAuthnCredential, WebAuthnCredentialStore, WebAuthnCredentialStoreError,
+    },
+    Email,
+};
+
+pub struct PostgresWebAuthnCredentialStore {
+    pool: PgPool,
+}
+
+impl PostgresWebAuthnCredentialStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl WebAuthnCredentialStore for PostgresWebAuthnCredentialStore {
+    async fn add_credential(
+        &mut self,
+        credential: WebAuthnCredential,
+    ) -> Result<(), WebAuthnCredentialStoreError> {
+        sqlx::query!(
+            r#"
+            INSERT INTO webauthn_credentials (credential_id, email, public_key, sign_count)
+            VALUES ($1, $2, $3, $4)
+            "#,
+            credential.credential_id.as_ref(),
+            credential.email.as_ref(),
+            &credential.public_key,
+            i64::from(credential.sign_count)
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|error| {
+            if let sqlx::Error::Database(db_err) = &error {
+                // 23505 = unique_violation
+                if db_err.code().as_deref() == Some("23505") {
+                    return WebAuthnCredentialStoreError::CredentialAlreadyExists;
+                }
+            }
+            WebAuthnCredentialStoreError::UnexpectedError
+        })?;
+
+        Ok(())
+    }
+
+    async fn get_credential(
+        &self,
+        credential_id: &CredentialId,
+    ) -> Result<WebAuthnCredential, WebAuthnCredentialStoreError> {
+        let row = sqlx::query!(
+            r#"
+            SELECT credential_id, email, public_key, sign_count
+            FROM webauthn_credentials
+            WHERE credential_id = $1
+            "#,
+            credential_id.as_ref()
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|_| WebAuthnCredentialStoreError::UnexpectedError)?
+        .ok_or(WebAuthnCredentialStoreError::CredentialNotFound)?;
+
+        to_credential(row.credential_id, row.email, row.public_key, row.sign_count)
+    }
+
+    async fn get_user_credentials(
+        &self,
+        email: &Email,
+    ) -> Result<Vec<WebAuthnCredential>, WebAuthnCredentialStoreError> {
+        sqlx::query!(
+            r#"
+            SELECT credential_id, email, public_key, sign_count
+            FROM webauthn_credentials
+            WHERE email = $1
+            ORDER BY created_at
+            "#,
+            email.as_ref()
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|_| WebAuthnCredentialStoreError::UnexpectedError)?
+        .into_iter()
+        .map(|row| to_credential(row.credential_id, row.email, row.public_key, row.sign_count))
+        .collect()
+    }
+
+    async fn update_sign_count(
+        &mut self,
+        credential_id: &CredentialId,
+        sign_count: u32,
+    ) -> Result<(), WebAuthnCredentialStoreError> {
+        // Checking and storing the counter in one statement keeps concurrent logins from both passing the check
+        let result = sqlx::query!(
+            r#"
+            UPDATE webauthn_credentials
+            SET sign_count = $1
+            WHERE credential_id = $2
+            AND ($1 > sign_count OR ($1 = 0 AND sign_count = 0))
+            "#,
+            i64::from(sign_count),
+            credential_id.as_ref()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| WebAuthnCredentialStoreError::UnexpectedError)?;
+
+        if result.rows_affected() == 0 {
+            // tell a missing credential apart from a counter that went backwards
+            self.get_credential(credential_id).await?;
+            return Err(WebAuthnCredentialStoreError::SignCountNotIncreased);
+        }
+
+        Ok(())
+    }
+}
+
+fn to_credential(
+    credential_id: Vec<u8>,
+    email: String,
+    public_key: Vec<u8>,
+    sign_count: i64,
+) -> Result<WebAuthnCredential, WebAuthnCredentialStoreError> {
+    Ok(WebAuthnCredential {
+        credential_id: CredentialId::parse(credential_id)
+            .map_err(|_| WebAuthnCredentialStoreError::UnexpectedError)?,
+        email: Email::parse(email).map_err(|_| WebAuthnCredentialStoreError::UnexpectedError)?,
+        public_key,
+        sign_count: sign_count
+            .try_into()
+            .map_err(|_| WebAuthnCredentialStoreError::UnexpectedError)?,
+    })
+}
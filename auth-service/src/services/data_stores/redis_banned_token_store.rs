@@ -1,67 +1,107 @@
-use redis::{Commands, Connection};
-use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::time::Duration;
+
+use redis::{aio::ConnectionManager, AsyncCommands};
 
 use crate::{
-    domain::data_stores::{BannedTokenStore, BannedTokenStoreError},
-    utils::auth::TOKEN_TTL_SECONDS,
+    domain::{
+        data_stores::{BannedTokenStore, BannedTokenStoreError},
+        Email,
+    },
+    utils::auth::banned_token_ttl,
 };
 
 pub struct RedisBannedTokenStore {
-    conn: Arc<RwLock<Connection>>,
+    conn: ConnectionManager,
+    // how long bans are kept
+    ttl: Duration,
 }
 
 impl RedisBannedTokenStore {
-    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
-        Self { conn }
+    pub fn new(conn: ConnectionManager) -> Self {
+        Self::with_ttl(conn, banned_token_ttl())
+    }
+
+    pub fn with_ttl(conn: ConnectionManager, ttl: Duration) -> Self {
+        Self { conn, ttl }
     }
 }
 
 #[async_trait::async_trait]
 impl BannedTokenStore for RedisBannedTokenStore {
-    async fn store_token(&mut self, token: String) -> Result<(), BannedTokenStoreError> {
+    async fn store_token(&self, jti: String) -> Result<(), BannedTokenStoreError> {
         // 1. Create a new key using the get_key helper function.
         // 2. Call the set_ex command on the Redis connection to set a new key/value pair with an expiration time (TTL).
         // The value should simply be a `true` (boolean value).
-        // The expiration time should be set to TOKEN_TTL_SECONDS.
-        // NOTE: The TTL is expected to be a u64 so you will have to cast TOKEN_TTL_SECONDS to a u64.
-        // Return BannedTokenStoreError::UnexpectedError if casting fails or the call to set_ex fails.
+        // The expiration time should outlast the token, see banned_token_ttl.
+        // Return BannedTokenStoreError::UnexpectedError if the call to set_ex fails.
 
-        let token_key = get_key(token.as_str());
+        let token_key = get_key(jti.as_str());
 
         let value = true;
 
-        let ttl: u64 = TOKEN_TTL_SECONDS
-            .try_into()
-            .map_err(|_| BannedTokenStoreError::UnexpectedError)?;
-
         let _: () = self
             .conn
-            .write()
+            .clone()
+            .set_ex(&token_key, value, self.ttl.as_secs())
             .await
-            .set_ex(&token_key, value, ttl)
             .map_err(|_| BannedTokenStoreError::UnexpectedError)?;
 
         Ok(())
     }
 
-    async fn check_token(&self, token: &str) -> Result<bool, BannedTokenStoreError> {
+    async fn check_token(&self, jti: &str) -> Result<bool, BannedTokenStoreError> {
         // Check if the token exists by calling the exists method on the Redis connection
-        let token_key = get_key(token);
+        let token_key = get_key(jti);
 
         let is_banned: bool = self
             .conn
-            .write()
-            .await
+            .clone()
             .exists(&token_key)
+            .await
             .map_err(|_| BannedTokenStoreError::UnexpectedError)?;
 
         Ok(is_banned)
     }
+
+    async fn ban_user_tokens(
+        &self,
+        email: &Email,
+        issued_before: i64,
+    ) -> Result<(), BannedTokenStoreError> {
+        // Once the ban TTL has passed every token issued before the ban has expired on its own
+        let _: () = self
+            .conn
+            .clone()
+            .set_ex(get_user_key(email), issued_before, self.ttl.as_secs())
+            .await
+            .map_err(|_| BannedTokenStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    async fn check_user_token(
+        &self,
+        email: &Email,
+        issued_at: i64,
+    ) -> Result<bool, BannedTokenStoreError> {
+        let issued_before: Option<i64> = self
+            .conn
+            .clone()
+            .get(get_user_key(email))
+            .await
+            .map_err(|_| BannedTokenStoreError::UnexpectedError)?;
+
+        Ok(issued_before.is_some_and(|issued_before| issued_at < issued_before))
+    }
 }
 
 const BANNED_TOKEN_KEY_PREFIX: &str = "banned_token:";
+const BANNED_USER_KEY_PREFIX: &str = "banned_user:";
+
+fn get_key(jti: &str) -> String {
+    format!("{}{}", BANNED_TOKEN_KEY_PREFIX, jti)
+}
 
-fn get_key(token: &str) -> String {
-    format!("{}{}", BANNED_TOKEN_KEY_PREFIX, token)
+fn get_user_key(email: &Email) -> String {
+    format!("{}{}", BANNED_USER_KEY_PREFIX, email.as_ref())
 }
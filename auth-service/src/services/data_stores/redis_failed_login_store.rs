@@ -0,0 +1,89 @@
+use chrono::Utc;
+use redis::{aio::ConnectionManager, AsyncCommands};
+
+use crate::{
+    domain::data_stores::{FailedLoginKey, FailedLoginStore, FailedLoginStoreError},
+    utils::auth::FAILED_LOGIN_TTL_SECONDS,
+};
+
+pub struct RedisFailedLoginStore {
+    conn: ConnectionManager,
+}
+
+impl RedisFailedLoginStore {
+    pub fn new(conn: ConnectionManager) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait::async_trait]
+impl FailedLoginStore for RedisFailedLoginStore {
+    async fn add_failure(&mut self, key: &FailedLoginKey) -> Result<u32, FailedLoginStoreError> {
+        let key = get_failures_key(key);
+        let mut conn = self.conn.clone();
+
+        let count: u32 = conn
+            .incr(&key, 1)
+            .await
+            .map_err(|_| FailedLoginStoreError::UnexpectedError)?;
+
+        // Every failure pushes the expiry back, so failures are forgotten after a quiet period
+        let _: () = conn
+            .expire(&key, FAILED_LOGIN_TTL_SECONDS)
+            .await
+            .map_err(|_| FailedLoginStoreError::UnexpectedError)?;
+
+        Ok(count)
+    }
+
+    async fn clear_failures(&mut self, key: &FailedLoginKey) -> Result<(), FailedLoginStoreError> {
+        let _: () = self
+            .conn
+            .clone()
+            .del(get_failures_key(key))
+            .await
+            .map_err(|_| FailedLoginStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    async fn lock(
+        &mut self,
+        key: &FailedLoginKey,
+        until: i64,
+    ) -> Result<(), FailedLoginStoreError> {
+        let ttl = until - Utc::now().timestamp();
+        if ttl <= 0 {
+            return Ok(());
+        }
+
+        // Redis drops the key when the lock ends
+        let _: () = self
+            .conn
+            .clone()
+            .set_ex(get_lock_key(key), until, ttl as u64)
+            .await
+            .map_err(|_| FailedLoginStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    async fn get_lock(&self, key: &FailedLoginKey) -> Result<Option<i64>, FailedLoginStoreError> {
+        self.conn
+            .clone()
+            .get::<_, Option<i64>>(get_lock_key(key))
+            .await
+            .map_err(|_| FailedLoginStoreError::UnexpectedError)
+    }
+}
+
+const FAILED_LOGIN_KEY_PREFIX: &str = "failed_login:";
+const LOGIN_LOCK_KEY_PREFIX: &str = "login_lock:";
+
+fn get_failures_key(key: &FailedLoginKey) -> String {
+    format!("{}{}", FAILED_LOGIN_KEY_PREFIX, key.as_ref())
+}
+
+fn get_lock_key(key: &FailedLoginKey) -> String {
+    format!("{}{}", LOGIN_LOCK_KEY_PREFIX, key.as_ref())
+}
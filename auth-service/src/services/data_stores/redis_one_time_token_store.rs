@@ -0,0 +1,80 @@
+use std::time::Duration;
+
+use redis::{aio::ConnectionManager, AsyncCommands};
+
+use crate::{
+    domain::{
+        data_stores::{
+            OneTimeToken, OneTimeTokenPurpose, OneTimeTokenStore, OneTimeTokenStoreError,
+        },
+        Email,
+    },
+    utils::auth::one_time_token_ttl,
+};
+
+pub struct RedisOneTimeTokenStore {
+    conn: ConnectionManager,
+    purpose: OneTimeTokenPurpose,
+    // how long a token can be used for
+    ttl: Duration,
+}
+
+impl RedisOneTimeTokenStore {
+    pub fn new(conn: ConnectionManager, purpose: OneTimeTokenPurpose) -> Self {
+        Self::with_ttl(conn, purpose, one_time_token_ttl(purpose))
+    }
+
+    pub fn with_ttl(conn: ConnectionManager, purpose: OneTimeTokenPurpose, ttl: Duration) -> Self {
+        Self { conn, purpose, ttl }
+    }
+}
+
+#[async_trait::async_trait]
+impl OneTimeTokenStore for RedisOneTimeTokenStore {
+    async fn add_token(
+        &mut self,
+        email: Email,
+        token: &OneTimeToken,
+    ) -> Result<(), OneTimeTokenStoreError> {
+        let _: () = self
+            .conn
+            .clone()
+            .set_ex(
+                get_key(self.purpose, token),
+                email.as_ref(),
+                self.ttl.as_secs(),
+            )
+            .await
+            .map_err(|_| OneTimeTokenStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    async fn consume_token(
+        &mut self,
+        token: &OneTimeToken,
+    ) -> Result<Email, OneTimeTokenStoreError> {
+        // GETDEL reads and removes the token in one step, so concurrent requests cannot both use it
+        let email: Option<String> = self
+            .conn
+            .clone()
+            .get_del(get_key(self.purpose, token))
+            .await
+            .map_err(|_| OneTimeTokenStoreError::UnexpectedError)?;
+
+        let email = email.ok_or(OneTimeTokenStoreError::TokenNotFound)?;
+
+        Email::parse(email).map_err(|_| OneTimeTokenStoreError::UnexpectedError)
+    }
+}
+
+const PASSWORD_RESET_TOKEN_KEY_PREFIX: &str = "password_reset_token:";
+const EMAIL_VERIFICATION_TOKEN_KEY_PREFIX: &str = "email_verification_token:";
+
+fn get_key(purpose: OneTimeTokenPurpose, token: &OneTimeToken) -> String {
+    let prefix = match purpose {
+        OneTimeTokenPurpose::PasswordReset => PASSWORD_RESET_TOKEN_KEY_PREFIX,
+        OneTimeTokenPurpose::EmailVerification => EMAIL_VERIFICATION_TOKEN_KEY_PREFIX,
+    };
+    format!("{}{}", prefix, token.hash())
+}
@@ -0,0 +1,79 @@
+use chrono::Utc;
+use redis::{aio::ConnectionManager, Script};
+
+use crate::domain::{
+    data_stores::{RateLimitKey, RateLimitStore, RateLimitStoreError},
+    RateLimit, RateLimitDecision,
+};
+
+pub struct RedisRateLimitStore {
+    conn: ConnectionManager,
+    script: Script,
+}
+
+impl RedisRateLimitStore {
+    pub fn new(conn: ConnectionManager) -> Self {
+        Self {
+            conn,
+            script: Script::new(TAKE_TOKEN_SCRIPT),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl RateLimitStore for RedisRateLimitStore {
+    async fn take_token(
+        &self,
+        key: &RateLimitKey,
+        limit: &RateLimit,
+    ) -> Result<RateLimitDecision, RateLimitStoreError> {
+        let (allowed, tokens): (i32, String) = self
+            .script
+            .key(get_key(key))
+            .arg(limit.capacity)
+            .arg(limit.period_millis())
+            .arg(Utc::now().timestamp_millis())
+            .invoke_async(&mut self.conn.clone())
+            .await
+            .map_err(|_| RateLimitStoreError::UnexpectedError)?;
+
+        let tokens: f64 = tokens
+            .parse()
+            .map_err(|_| RateLimitStoreError::UnexpectedError)?;
+
+        Ok(limit.decision(allowed == 1, tokens))
+    }
+}
+
+// The same refill as `TokenBucket::take`, run as a script so concurrent requests, possibly on other
+// instances, cannot both take the last token. The bucket expires once it would be full again.
+// Tokens are returned as a string since Redis truncates Lua numbers to integers.
+const TAKE_TOKEN_SCRIPT: &str = r#"
+local capacity = tonumber(ARGV[1])
+local period = tonumber(ARGV[2])
+local now = tonumber(ARGV[3])
+
+local bucket = redis.call('HMGET', KEYS[1], 'tokens', 'updated_at')
+local tokens = tonumber(bucket[1]) or capacity
+local updated_at = tonumber(bucket[2]) or now
+
+local elapsed = math.max(0, now - updated_at)
+tokens = math.min(capacity, tokens + elapsed * capacity / period)
+
+local allowed = 0
+if tokens >= 1 then
+    tokens = tokens - 1
+    allowed = 1
+end
+
+redis.call('HSET', KEYS[1], 'tokens', tostring(tokens), 'updated_at', tostring(now))
+redis.call('PEXPIRE', KEYS[1], math.ceil((capacity - tokens) * period / capacity))
+
+return { allowed, tostring(tokens) }
+"#;
+
+const RATE_LIMIT_PREFIX: &str = "rate_limit:";
+
+fn get_key(key: &RateLimitKey) -> String {
+    format!("{}{}", RATE_LIMIT_PREFIX, key.as_ref())
+}
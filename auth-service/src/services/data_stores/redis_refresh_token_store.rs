@@ -0,0 +1,247 @@
+use redis::{aio::ConnectionManager, AsyncCommands, Script};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    domain::{
+        data_stores::{RefreshSession, RefreshToken, RefreshTokenStore, RefreshTokenStoreError},
+        Email, OrganizationId,
+    },
+    utils::auth::REFRESH_TOKEN_TTL_SECONDS,
+};
+
+pub struct RedisRefreshTokenStore {
+    conn: ConnectionManager,
+    rotate_script: Script,
+}
+
+impl RedisRefreshTokenStore {
+    pub fn new(conn: ConnectionManager) -> Self {
+        Self {
+            conn,
+            rotate_script: Script::new(ROTATE_TOKEN_SCRIPT),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl RefreshTokenStore for RedisRefreshTokenStore {
+    async fn add_token(
+        &mut self,
+        session: RefreshSession,
+        token: RefreshToken,
+    ) -> Result<(), RefreshTokenStoreError> {
+        // a freshly issued token always starts a new family
+        let family_id = Uuid::new_v4().to_string();
+
+        let record = RefreshTokenRecord {
+            email: session.email.as_ref().to_owned(),
+            organization_id: session.organization_id.map(|id| id.to_string()),
+            family_id,
+            used: false,
+        };
+
+        let mut conn = self.conn.clone();
+        store_family(&mut conn, &record.family_id).await?;
+        store_record(&mut conn, &token, &record).await?;
+
+        // keep track of the user's families so they can all be revoked at once
+        let user_key = get_user_key(&session.email);
+        let _: () = conn
+            .sadd(&user_key, &record.family_id)
+            .await
+            .map_err(|_| RefreshTokenStoreError::UnexpectedError)?;
+        let _: () = conn
+            .expire(&user_key, REFRESH_TOKEN_TTL_SECONDS)
+            .await
+            .map_err(|_| RefreshTokenStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    async fn rotate_token(
+        &mut self,
+        token: &RefreshToken,
+    ) -> Result<(RefreshSession, RefreshToken), RefreshTokenStoreError> {
+        let new_token = RefreshToken::default();
+
+        let result: Vec<String> = self
+            .rotate_script
+            .key(get_token_key(token))
+            .key(get_token_key(&new_token))
+            .arg(REFRESH_TOKEN_FAMILY_KEY_PREFIX)
+            .arg(get_ttl()?)
+            .invoke_async(&mut self.conn.clone())
+            .await
+            .map_err(|_| RefreshTokenStoreError::UnexpectedError)?;
+
+        match result.as_slice() {
+            [outcome, email, organization_id @ ..] if outcome == "rotated" => {
+                let email = Email::parse(email.to_owned())
+                    .map_err(|_| RefreshTokenStoreError::UnexpectedError)?;
+                let organization_id = organization_id
+                    .first()
+                    .map(|id| OrganizationId::parse(id.to_owned()))
+                    .transpose()
+                    .map_err(|_| RefreshTokenStoreError::UnexpectedError)?;
+                Ok((
+                    RefreshSession {
+                        email,
+                        organization_id,
+                    },
+                    new_token,
+                ))
+            }
+            [outcome] if outcome == "reused" => Err(RefreshTokenStoreError::TokenReused),
+            [outcome] if outcome == "not_found" => Err(RefreshTokenStoreError::TokenNotFound),
+            _ => Err(RefreshTokenStoreError::UnexpectedError),
+        }
+    }
+
+    async fn revoke_token(&mut self, token: &RefreshToken) -> Result<(), RefreshTokenStoreError> {
+        let mut conn = self.conn.clone();
+
+        let value = conn
+            .get::<_, Option<String>>(get_token_key(token))
+            .await
+            .map_err(|_| RefreshTokenStoreError::UnexpectedError)?;
+
+        if let Some(value) = value {
+            let record: RefreshTokenRecord = serde_json::from_str(&value)
+                .map_err(|_| RefreshTokenStoreError::UnexpectedError)?;
+
+            let _: () = conn
+                .del(get_family_key(&record.family_id))
+                .await
+                .map_err(|_| RefreshTokenStoreError::UnexpectedError)?;
+        }
+
+        Ok(())
+    }
+
+    async fn revoke_user_tokens(&mut self, email: &Email) -> Result<(), RefreshTokenStoreError> {
+        let mut conn = self.conn.clone();
+        let user_key = get_user_key(email);
+
+        let family_ids: Vec<String> = conn
+            .smembers(&user_key)
+            .await
+            .map_err(|_| RefreshTokenStoreError::UnexpectedError)?;
+
+        for family_id in family_ids {
+            let _: () = conn
+                .del(get_family_key(&family_id))
+                .await
+                .map_err(|_| RefreshTokenStoreError::UnexpectedError)?;
+        }
+
+        let _: () = conn
+            .del(&user_key)
+            .await
+            .map_err(|_| RefreshTokenStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+}
+
+async fn store_record(
+    conn: &mut ConnectionManager,
+    token: &RefreshToken,
+    record: &RefreshTokenRecord,
+) -> Result<(), RefreshTokenStoreError> {
+    let serialized_record =
+        serde_json::to_string(record).map_err(|_| RefreshTokenStoreError::UnexpectedError)?;
+
+    let _: () = conn
+        .set_ex(get_token_key(token), serialized_record, get_ttl()?)
+        .await
+        .map_err(|_| RefreshTokenStoreError::UnexpectedError)?;
+
+    Ok(())
+}
+
+async fn store_family(
+    conn: &mut ConnectionManager,
+    family_id: &str,
+) -> Result<(), RefreshTokenStoreError> {
+    let _: () = conn
+        .set_ex(get_family_key(family_id), true, get_ttl()?)
+        .await
+        .map_err(|_| RefreshTokenStoreError::UnexpectedError)?;
+
+    Ok(())
+}
+
+fn get_ttl() -> Result<u64, RefreshTokenStoreError> {
+    REFRESH_TOKEN_TTL_SECONDS
+        .try_into()
+        .map_err(|_| RefreshTokenStoreError::UnexpectedError)
+}
+
+// Checking the presented token and marking it used happen in one script, so concurrent refreshes,
+// possibly on other instances, cannot both exchange it. Records are the JSON `store_record` writes.
+// The family key is only known once the record is read, so it is built from the prefix in ARGV.
+const ROTATE_TOKEN_SCRIPT: &str = r#"
+local value = redis.call('GET', KEYS[1])
+if not value then
+    return { 'not_found' }
+end
+
+local record = cjson.decode(value)
+local family_key = ARGV[1] .. record.family_id
+if redis.call('EXISTS', family_key) == 0 then
+    return { 'not_found' }
+end
+
+-- the token was already exchanged once, someone is replaying it so revoke the whole family
+if record.used then
+    redis.call('DEL', family_key)
+    return { 'reused' }
+end
+
+local ttl = tonumber(ARGV[2])
+record.used = true
+redis.call('SET', KEYS[1], cjson.encode(record), 'EX', ttl)
+
+-- extend the lifetime of the family along with the new token, which keeps the session's organization
+local new_record = {
+    email = record.email,
+    organization_id = record.organization_id,
+    family_id = record.family_id,
+    used = false,
+}
+redis.call('SET', KEYS[2], cjson.encode(new_record), 'EX', ttl)
+redis.call('SET', family_key, 1, 'EX', ttl)
+
+-- sessions without an organization store null, which decodes to cjson.null rather than nil
+if type(record.organization_id) == 'string' then
+    return { 'rotated', record.email, record.organization_id }
+end
+return { 'rotated', record.email }
+"#;
+
+#[derive(Serialize, Deserialize)]
+struct RefreshTokenRecord {
+    email: String,
+    // records written before sessions kept their organization have none
+    #[serde(default)]
+    organization_id: Option<String>,
+    family_id: String,
+    used: bool,
+}
+
+const REFRESH_TOKEN_KEY_PREFIX: &str = "refresh_token:";
+const REFRESH_TOKEN_FAMILY_KEY_PREFIX: &str = "refresh_token_family:";
+const REFRESH_TOKEN_USER_KEY_PREFIX: &str = "refresh_token_user:";
+
+fn get_token_key(token: &RefreshToken) -> String {
+    format!("{}{}", REFRESH_TOKEN_KEY_PREFIX, token.as_ref())
+}
+
+fn get_family_key(family_id: &str) -> String {
+    format!("{}{}", REFRESH_TOKEN_FAMILY_KEY_PREFIX, family_id)
+}
+
+fn get_user_key(email: &Email) -> String {
+    format!("{}{}", REFRESH_TOKEN_USER_KEY_PREFIX, email.as_ref())
+}
@@ -1,94 +1,383 @@
-use std::sync::Arc;
+use std::time::Duration;
 
-use redis::{Commands, Connection};
+use redis::{aio::ConnectionManager, AsyncCommands, ExistenceCheck, Script, SetExpiry, SetOptions};
 use serde::{Deserialize, Serialize};
-use tokio::sync::RwLock;
 
-use crate::domain::{
-    data_stores::{LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError},
-    Email,
+use crate::{
+    domain::{
+        data_stores::{LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError},
+        Email, OrganizationId,
+    },
+    utils::{
+        auth::{
+            MAX_TWO_FA_ATTEMPTS, MAX_TWO_FA_RESENDS, TWO_FA_CODE_TTL_SECONDS,
+            TWO_FA_RESEND_COOLDOWN_SECONDS,
+        },
+        constants::MAX_PENDING_LOGIN_ATTEMPTS,
+    },
 };
 
 pub struct RedisTwoFACodeStore {
-    conn: Arc<RwLock<Connection>>,
+    conn: ConnectionManager,
+    // how long a login attempt can be completed for
+    ttl: Duration,
+    verify_script: Script,
+    reserve_script: Script,
 }
 
 impl RedisTwoFACodeStore {
-    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
-        Self { conn }
+    pub fn new(conn: ConnectionManager) -> Self {
+        Self::with_ttl(conn, Duration::from_secs(TWO_FA_CODE_TTL_SECONDS as u64))
+    }
+
+    pub fn with_ttl(conn: ConnectionManager, ttl: Duration) -> Self {
+        Self {
+            conn,
+            ttl,
+            verify_script: Script::new(VERIFY_CODE_SCRIPT),
+            reserve_script: Script::new(RESERVE_ATTEMPT_SCRIPT),
+        }
     }
 }
 
 #[async_trait::async_trait]
 impl TwoFACodeStore for RedisTwoFACodeStore {
     async fn add_code(
-        &mut self,
+        &self,
         email: Email,
         login_attempt_id: LoginAttemptId,
         code: TwoFACode,
+        organization_id: Option<OrganizationId>,
     ) -> Result<(), TwoFACodeStoreError> {
-        let key = get_key(&email);
-
-        let data = TwoFATuple(
-            login_attempt_id.as_ref().to_owned(),
-            code.as_ref().to_owned(),
-        );
-        let serialized_data =
-            serde_json::to_string(&data).map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
-
-        let _: () = self
-            .conn
-            .write()
+        let serialized_data = serialize_code(&email, &code, organization_id)?;
+        let pending_key = get_pending_key(&email);
+        let max_pending = *MAX_PENDING_LOGIN_ATTEMPTS as isize;
+
+        let mut conn = self.conn.clone();
+
+        let _: () = conn
+            .set_ex(
+                get_cooldown_key(&login_attempt_id),
+                1,
+                TWO_FA_RESEND_COOLDOWN_SECONDS as u64,
+            )
+            .await
+            .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+
+        let _: () = conn
+            .set_ex(
+                get_key(&login_attempt_id),
+                serialized_data,
+                self.ttl.as_secs(),
+            )
+            .await
+            .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+
+        // The user's pending attempts, newest first. Anything beyond the cap is discarded. Pushing,
+        // reading what falls beyond the cap and trimming it happen in one transaction, so
+        // concurrent logins cannot leave more than the cap pending.
+        let (discarded,): (Vec<String>,) = redis::pipe()
+            .atomic()
+            .lpush(&pending_key, login_attempt_id.as_ref())
+            .ignore()
+            .lrange(&pending_key, max_pending, -1)
+            .ltrim(&pending_key, 0, max_pending - 1)
+            .ignore()
+            .expire(&pending_key, self.ttl.as_secs() as i64)
+            .ignore()
+            .query_async(&mut conn)
             .await
-            .set_ex(&key, serialized_data, TEN_MINUTES_IN_SECONDS)
             .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
 
+        for id in discarded {
+            let id = LoginAttemptId::parse(id).map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+            let _: () = conn
+                .del(&get_keys(&id))
+                .await
+                .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+        }
+
         Ok(())
     }
 
-    async fn remove_code(&mut self, email: &Email) -> Result<(), TwoFACodeStoreError> {
-        let key = get_key(email);
+    async fn remove_code(
+        &self,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<Option<OrganizationId>, TwoFACodeStoreError> {
+        let (email, organization_id) = match self.get_login(login_attempt_id).await {
+            Ok((email, _, organization_id)) => (Some(email), organization_id),
+            Err(TwoFACodeStoreError::LoginAttemptIdNotFound) => (None, None),
+            Err(e) => return Err(e),
+        };
+
+        let mut conn = self.conn.clone();
 
-        let _: () = self
-            .conn
-            .write()
+        // Whoever deletes the code completes the login attempt
+        let removed: u32 = conn
+            .del(get_key(login_attempt_id))
             .await
-            .del(&key)
             .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
 
-        Ok(())
+        let _: () = conn
+            .del(&get_keys(login_attempt_id))
+            .await
+            .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+
+        if let Some(email) = email {
+            let _: () = conn
+                .lrem(get_pending_key(&email), 0, login_attempt_id.as_ref())
+                .await
+                .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+        }
+
+        if removed == 0 {
+            return Err(TwoFACodeStoreError::LoginAttemptIdNotFound);
+        }
+
+        Ok(organization_id)
     }
 
     async fn get_code(
         &self,
-        email: &Email,
-    ) -> Result<(LoginAttemptId, TwoFACode), TwoFACodeStoreError> {
-        let key = get_key(email);
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<(Email, TwoFACode), TwoFACodeStoreError> {
+        let (email, code, _) = self.get_login(login_attempt_id).await?;
+        Ok((email, code))
+    }
+
+    async fn verify_code(
+        &self,
+        login_attempt_id: &LoginAttemptId,
+        code: &TwoFACode,
+    ) -> Result<(), TwoFACodeStoreError> {
+        let outcome: String = self
+            .verify_script
+            .key(&get_keys(login_attempt_id))
+            .arg(code.as_ref())
+            .arg(MAX_TWO_FA_ATTEMPTS)
+            .arg(self.ttl.as_secs())
+            .arg(TWO_FA_PENDING_PREFIX)
+            .arg(login_attempt_id.as_ref())
+            .invoke_async(&mut self.conn.clone())
+            .await
+            .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
 
-        match self.conn.write().await.get::<_, String>(&key) {
+        match outcome.as_str() {
+            "verified" => Ok(()),
+            "incorrect" => Err(TwoFACodeStoreError::IncorrectCode),
+            "not_found" => Err(TwoFACodeStoreError::LoginAttemptIdNotFound),
+            _ => Err(TwoFACodeStoreError::UnexpectedError),
+        }
+    }
+
+    async fn reserve_attempt(
+        &self,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<u32, TwoFACodeStoreError> {
+        let attempts: i64 = self
+            .reserve_script
+            .key(get_key(login_attempt_id))
+            .key(get_attempts_key(login_attempt_id))
+            .arg(MAX_TWO_FA_ATTEMPTS)
+            .arg(self.ttl.as_secs())
+            .invoke_async(&mut self.conn.clone())
+            .await
+            .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+
+        u32::try_from(attempts).map_err(|_| TwoFACodeStoreError::LoginAttemptIdNotFound)
+    }
+
+    async fn resend_code(
+        &self,
+        login_attempt_id: &LoginAttemptId,
+        code: TwoFACode,
+    ) -> Result<(), TwoFACodeStoreError> {
+        let (email, _, organization_id) = self.get_login(login_attempt_id).await?;
+
+        let serialized_data = serialize_code(&email, &code, organization_id)?;
+        let resends_key = get_resends_key(login_attempt_id);
+        let cooldown_key = get_cooldown_key(login_attempt_id);
+        let mut conn = self.conn.clone();
+
+        let resends: Option<u32> = conn
+            .get(&resends_key)
+            .await
+            .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+        if resends.unwrap_or(0) >= MAX_TWO_FA_RESENDS {
+            return Err(TwoFACodeStoreError::TooManyResends);
+        }
+
+        // Only one request can start the next cooldown, so concurrent resends cannot exceed the limit
+        let options = SetOptions::default()
+            .conditional_set(ExistenceCheck::NX)
+            .with_expiration(SetExpiry::EX(TWO_FA_RESEND_COOLDOWN_SECONDS as usize));
+        let started: Option<String> = conn
+            .set_options(&cooldown_key, 1, options)
+            .await
+            .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+
+        if started.is_none() {
+            let seconds: i64 = conn
+                .ttl(&cooldown_key)
+                .await
+                .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+            return Err(TwoFACodeStoreError::ResendCooldown(seconds.max(1) as u64));
+        }
+
+        let _: () = conn
+            .incr(&resends_key, 1)
+            .await
+            .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+
+        let _: () = conn
+            .expire(&resends_key, self.ttl.as_secs() as i64)
+            .await
+            .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+
+        let _: () = conn
+            .set_ex(
+                get_key(login_attempt_id),
+                serialized_data,
+                self.ttl.as_secs(),
+            )
+            .await
+            .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+}
+
+impl RedisTwoFACodeStore {
+    // The email, code and organization of the login attempt
+    async fn get_login(
+        &self,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<(Email, TwoFACode, Option<OrganizationId>), TwoFACodeStoreError> {
+        let key = get_key(login_attempt_id);
+
+        match self.conn.clone().get::<_, String>(&key).await {
             Ok(value) => {
                 let data: TwoFATuple = serde_json::from_str(&value)
                     .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
 
-                let login_attempt_id = LoginAttemptId::parse(data.0)
-                    .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+                let email =
+                    Email::parse(data.0).map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
 
                 let email_code =
                     TwoFACode::parse(data.1).map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
 
-                Ok((login_attempt_id, email_code))
+                let organization_id = data
+                    .2
+                    .map(OrganizationId::parse)
+                    .transpose()
+                    .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+
+                Ok((email, email_code, organization_id))
             }
             Err(_) => Err(TwoFACodeStoreError::LoginAttemptIdNotFound),
         }
     }
 }
 
+fn serialize_code(
+    email: &Email,
+    code: &TwoFACode,
+    organization_id: Option<OrganizationId>,
+) -> Result<String, TwoFACodeStoreError> {
+    let data = TwoFATuple(
+        email.as_ref().to_owned(),
+        code.as_ref().to_owned(),
+        organization_id.map(|id| id.to_string()),
+    );
+    serde_json::to_string(&data).map_err(|_| TwoFACodeStoreError::UnexpectedError)
+}
+
+// Codes stored before logins carried an organization have no third element
 #[derive(Serialize, Deserialize)]
-struct TwoFATuple(pub String, pub String);
+struct TwoFATuple(pub String, pub String, #[serde(default)] pub Option<String>);
+
+// Comparing the guess and counting it when wrong happen in one script, so concurrent guesses, possibly
+// on other instances, cannot all be compared before the count reaches the limit. KEYS are those of
+// `get_keys`, the code record is the JSON array `serialize_code` writes.
+const VERIFY_CODE_SCRIPT: &str = r#"
+local value = redis.call('GET', KEYS[1])
+if not value then
+    return 'not_found'
+end
+
+if tonumber(redis.call('GET', KEYS[2]) or '0') >= tonumber(ARGV[2]) then
+    return 'not_found'
+end
+
+local login = cjson.decode(value)
+if login[2] == ARGV[1] then
+    return 'verified'
+end
+
+local failed_attempts = redis.call('INCR', KEYS[2])
+redis.call('EXPIRE', KEYS[2], ARGV[3])
+
+if failed_attempts >= tonumber(ARGV[2]) then
+    redis.call('DEL', unpack(KEYS))
+    redis.call('LREM', ARGV[4] .. login[1], 0, ARGV[5])
+end
+
+return 'incorrect'
+"#;
+
+// Counting a guess only if the attempt still exists and has guesses left happens in one script, so
+// concurrent guesses cannot take more than the limit. KEYS are the code and the attempts keys, -1
+// means the guess is refused. The count never needs to outlive the code it belongs to.
+const RESERVE_ATTEMPT_SCRIPT: &str = r#"
+if redis.call('EXISTS', KEYS[1]) == 0 then
+    return -1
+end
+
+if tonumber(redis.call('GET', KEYS[2]) or '0') >= tonumber(ARGV[1]) then
+    return -1
+end
+
+local attempts = redis.call('INCR', KEYS[2])
+redis.call('EXPIRE', KEYS[2], ARGV[2])
+return attempts
+"#;
 
-const TEN_MINUTES_IN_SECONDS: u64 = 600;
 const TWO_FA_CODE_PREFIX: &str = "two_fa_code:";
+const TWO_FA_ATTEMPTS_PREFIX: &str = "two_fa_attempts:";
+const TWO_FA_RESENDS_PREFIX: &str = "two_fa_resends:";
+const TWO_FA_RESEND_COOLDOWN_PREFIX: &str = "two_fa_resend_cooldown:";
+const TWO_FA_PENDING_PREFIX: &str = "two_fa_pending:";
+
+fn get_key(login_attempt_id: &LoginAttemptId) -> String {
+    format!("{}{}", TWO_FA_CODE_PREFIX, login_attempt_id.as_ref())
+}
+
+fn get_attempts_key(login_attempt_id: &LoginAttemptId) -> String {
+    format!("{}{}", TWO_FA_ATTEMPTS_PREFIX, login_attempt_id.as_ref())
+}
+
+fn get_resends_key(login_attempt_id: &LoginAttemptId) -> String {
+    format!("{}{}", TWO_FA_RESENDS_PREFIX, login_attempt_id.as_ref())
+}
+
+fn get_cooldown_key(login_attempt_id: &LoginAttemptId) -> String {
+    format!(
+        "{}{}",
+        TWO_FA_RESEND_COOLDOWN_PREFIX,
+        login_attempt_id.as_ref()
+    )
+}
+
+// Every key belonging to a login attempt
+fn get_keys(login_attempt_id: &LoginAttemptId) -> [String; 4] {
+    [
+        get_key(login_attempt_id),
+        get_attempts_key(login_attempt_id),
+        get_resends_key(login_attempt_id),
+        get_cooldown_key(login_attempt_id),
+    ]
+}
 
-fn get_key(email: &Email) -> String {
-    format!("{}{}", TWO_FA_CODE_PREFIX, email.as_ref())
+fn get_pending_key(email: &Email) -> String {
+    format!("{}{}", TWO_FA_PENDING_PREFIX, email.as_ref())
 }
@@ -0,0 +1,125 @@
+use redis::{aio::ConnectionManager, AsyncCommands};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    domain::{
+        data_stores::{
+            LoginAttemptId, WebAuthnCeremony, WebAuthnChallenge, WebAuthnChallengeStore,
+            WebAuthnChallengeStoreError,
+        },
+        Email,
+    },
+    utils::auth::WEBAUTHN_CHALLENGE_TTL_SECONDS,
+};
+
+pub struct RedisWebAuthnChallengeStore {
+    conn: ConnectionManager,
+}
+
+impl RedisWebAuthnChallengeStore {
+    pub fn new(conn: ConnectionManager) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait::async_trait]
+impl WebAuthnChallengeStore for RedisWebAuthnChallengeStore {
+    async fn add_challenge(
+        &mut self,
+        challenge: &WebAuthnChallenge,
+        ceremony: WebAuthnCeremony,
+    ) -> Result<(), WebAuthnChallengeStoreError> {
+        let record = match ceremony {
+            WebAuthnCeremony::Registration { email } => CeremonyRecord {
+                kind: CeremonyKind::Registration,
+                email: email.as_ref().to_owned(),
+                login_attempt_id: None,
+            },
+            WebAuthnCeremony::Authentication {
+                email,
+                login_attempt_id,
+            } => CeremonyRecord {
+                kind: CeremonyKind::Authentication,
+                email: email.as_ref().to_owned(),
+                login_attempt_id: login_attempt_id.map(|id| id.as_ref().to_owned()),
+            },
+        };
+
+        let serialized_record = serde_json::to_string(&record)
+            .map_err(|_| WebAuthnChallengeStoreError::UnexpectedError)?;
+
+        let ttl: u64 = WEBAUTHN_CHALLENGE_TTL_SECONDS
+            .try_into()
+            .map_err(|_| WebAuthnChallengeStoreError::UnexpectedError)?;
+
+        let _: () = self
+            .conn
+            .clone()
+            .set_ex(get_key(challenge), serialized_record, ttl)
+            .await
+            .map_err(|_| WebAuthnChallengeStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    async fn consume_challenge(
+        &mut self,
+        challenge: &WebAuthnChallenge,
+    ) -> Result<WebAuthnCeremony, WebAuthnChallengeStoreError> {
+        let key = get_key(challenge);
+        let mut conn = self.conn.clone();
+
+        let value: Option<String> = conn
+            .get(&key)
+            .await
+            .map_err(|_| WebAuthnChallengeStoreError::UnexpectedError)?;
+
+        let value = value.ok_or(WebAuthnChallengeStoreError::ChallengeNotFound)?;
+
+        let _: () = conn
+            .del(&key)
+            .await
+            .map_err(|_| WebAuthnChallengeStoreError::UnexpectedError)?;
+
+        let record: CeremonyRecord = serde_json::from_str(&value)
+            .map_err(|_| WebAuthnChallengeStoreError::UnexpectedError)?;
+
+        let email =
+            Email::parse(record.email).map_err(|_| WebAuthnChallengeStoreError::UnexpectedError)?;
+
+        match record.kind {
+            CeremonyKind::Registration => Ok(WebAuthnCeremony::Registration { email }),
+            CeremonyKind::Authentication => {
+                let login_attempt_id = record
+                    .login_attempt_id
+                    .map(LoginAttemptId::parse)
+                    .transpose()
+                    .map_err(|_| WebAuthnChallengeStoreError::UnexpectedError)?;
+
+                Ok(WebAuthnCeremony::Authentication {
+                    email,
+                    login_attempt_id,
+                })
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+enum CeremonyKind {
+    Registration,
+    Authentication,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CeremonyRecord {
+    kind: CeremonyKind,
+    email: String,
+    login_attempt_id: Option<String>,
+}
+
+const WEBAUTHN_CHALLENGE_KEY_PREFIX: &str = "webauthn_challenge:";
+
+fn get_key(challenge: &WebAuthnChallenge) -> String {
+    format!("{}{}", WEBAUTHN_CHALLENGE_KEY_PREFIX, challenge.as_ref())
+}
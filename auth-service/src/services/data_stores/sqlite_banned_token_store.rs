@@ -0,0 +1,121 @@
+use std::time::Duration;
+
+use sqlx::SqlitePool;
+
+use crate::{
+    domain::{
+        data_stores::{BannedTokenStore, BannedTokenStoreError},
+        Email,
+    },
+    utils::auth::banned_token_ttl,
+};
+
+// Bans expire like the Redis keys do, expired rows are ignored and left for `delete_expired`
+pub struct SqliteBannedTokenStore {
+    pool: SqlitePool,
+    // how long bans are kept
+    ttl: Duration,
+}
+
+impl SqliteBannedTokenStore {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self::with_ttl(pool, banned_token_ttl())
+    }
+
+    pub fn with_ttl(pool: SqlitePool, ttl: Duration) -> Self {
+        Self { pool, ttl }
+    }
+
+    // Returns how many expired bans were deleted
+    pub async fn delete_expired(&self) -> Result<u64, BannedTokenStoreError> {
+        let tokens = sqlx::query("DELETE FROM banned_tokens WHERE expires_at <= unixepoch()")
+            .execute(&self.pool)
+            .await
+            .map_err(|_| BannedTokenStoreError::UnexpectedError)?;
+
+        let users = sqlx::query("DELETE FROM banned_users WHERE expires_at <= unixepoch()")
+            .execute(&self.pool)
+            .await
+            .map_err(|_| BannedTokenStoreError::UnexpectedError)?;
+
+        Ok(tokens.rows_affected() + users.rows_affected())
+    }
+}
+
+#[async_trait::async_trait]
+impl BannedTokenStore for SqliteBannedTokenStore {
+    async fn store_token(&self, jti: String) -> Result<(), BannedTokenStoreError> {
+        sqlx::query(
+            r#"
+            INSERT INTO banned_tokens (jti, expires_at)
+            VALUES ($1, unixepoch() + $2)
+            ON CONFLICT (jti) DO UPDATE SET expires_at = excluded.expires_at
+            "#,
+        )
+        .bind(jti)
+        .bind(self.ttl.as_secs() as i64)
+        .execute(&self.pool)
+        .await
+        .map_err(|_| BannedTokenStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    async fn check_token(&self, jti: &str) -> Result<bool, BannedTokenStoreError> {
+        sqlx::query_scalar(
+            r#"
+            SELECT EXISTS(
+                SELECT 1 FROM banned_tokens WHERE jti = $1 AND expires_at > unixepoch()
+            )
+            "#,
+        )
+        .bind(jti)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|_| BannedTokenStoreError::UnexpectedError)
+    }
+
+    async fn ban_user_tokens(
+        &self,
+        email: &Email,
+        issued_before: i64,
+    ) -> Result<(), BannedTokenStoreError> {
+        // Once the ban TTL has passed every token issued before the ban has expired on its own
+        sqlx::query(
+            r#"
+            INSERT INTO banned_users (email, issued_before, expires_at)
+            VALUES ($1, $2, unixepoch() + $3)
+            ON CONFLICT (email) DO UPDATE
+            SET issued_before = excluded.issued_before, expires_at = excluded.expires_at
+            "#,
+        )
+        .bind(email.as_ref())
+        .bind(issued_before)
+        .bind(self.ttl.as_secs() as i64)
+        .execute(&self.pool)
+        .await
+        .map_err(|_| BannedTokenStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    async fn check_user_token(
+        &self,
+        email: &Email,
+        issued_at: i64,
+    ) -> Result<bool, BannedTokenStoreError> {
+        let issued_before: Option<i64> = sqlx::query_scalar(
+            r#"
+            SELECT issued_before
+            FROM banned_users
+            WHERE email = $1 AND expires_at > unixepoch()
+            "#,
+        )
+        .bind(email.as_ref())
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|_| BannedTokenStoreError::UnexpectedError)?;
+
+        Ok(issued_before.is_some_and(|issued_before| issued_at < issued_before))
+    }
+}
@@ -0,0 +1,195 @@
+use std::time::Duration;
+
+use sqlx::SqlitePool;
+use uuid::Uuid;
+
+use crate::domain::{
+    data_stores::{
+        EmailIdempotencyKey, EmailOutboxStore, EmailOutboxStoreError, OutboxEmail,
+        OutboxEmailStatus,
+    },
+    Email, EmailMessage,
+};
+
+// Claiming due emails is a single statement and SQLite runs one writer at a time, so every due
+// email is handed to only one worker
+pub struct SqliteEmailOutboxStore {
+    pool: SqlitePool,
+}
+
+impl SqliteEmailOutboxStore {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailOutboxStore for SqliteEmailOutboxStore {
+    async fn enqueue(
+        &mut self,
+        key: &EmailIdempotencyKey,
+        recipient: &Email,
+        message: &EmailMessage,
+    ) -> Result<bool, EmailOutboxStoreError> {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO email_outbox (id, idempotency_key, recipient, subject, html_body, text_body)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            ON CONFLICT (idempotency_key) DO NOTHING
+            "#,
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(key.as_ref())
+        .bind(recipient.as_ref())
+        .bind(&message.subject)
+        .bind(&message.html_body)
+        .bind(&message.text_body)
+        .execute(&self.pool)
+        .await
+        .map_err(|_| EmailOutboxStoreError::UnexpectedError)?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn claim_due(
+        &mut self,
+        limit: u32,
+        lease: Duration,
+    ) -> Result<Vec<OutboxEmail>, EmailOutboxStoreError> {
+        let rows: Vec<OutboxEmailRow> = sqlx::query_as(
+            r#"
+            UPDATE email_outbox
+            SET next_attempt_at = unixepoch('subsec') + $2
+            WHERE id IN (
+                SELECT id FROM email_outbox
+                WHERE status = 'pending' AND next_attempt_at <= unixepoch('subsec')
+                ORDER BY next_attempt_at
+                LIMIT $1
+            )
+            RETURNING id, recipient, subject, html_body, text_body, status, attempts, last_error,
+                CAST(next_attempt_at AS INTEGER) AS next_attempt_at,
+                CAST(created_at AS INTEGER) AS created_at
+            "#,
+        )
+        .bind(limit as i64)
+        .bind(lease.as_secs_f64())
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|_| EmailOutboxStoreError::UnexpectedError)?;
+
+        rows.into_iter().map(OutboxEmailRow::parse).collect()
+    }
+
+    async fn mark_sent(&mut self, id: &Uuid) -> Result<(), EmailOutboxStoreError> {
+        let result = sqlx::query(
+            r#"
+            UPDATE email_outbox
+            SET status = 'sent', attempts = attempts + 1, sent_at = unixepoch('subsec'),
+                html_body = '', text_body = ''
+            WHERE id = $1
+            "#,
+        )
+        .bind(id.to_string())
+        .execute(&self.pool)
+        .await
+        .map_err(|_| EmailOutboxStoreError::UnexpectedError)?;
+
+        if result.rows_affected() == 0 {
+            return Err(EmailOutboxStoreError::EmailNotFound);
+        }
+
+        Ok(())
+    }
+
+    async fn mark_failed(
+        &mut self,
+        id: &Uuid,
+        error: &str,
+        retry_in: Option<Duration>,
+    ) -> Result<(), EmailOutboxStoreError> {
+        let result = sqlx::query(
+            r#"
+            UPDATE email_outbox
+            SET attempts = attempts + 1, last_error = $2,
+                status = CASE WHEN $3 IS NULL THEN 'dead' ELSE status END,
+                next_attempt_at = unixepoch('subsec') + COALESCE($3, 0)
+            WHERE id = $1
+            "#,
+        )
+        .bind(id.to_string())
+        .bind(error)
+        .bind(retry_in.map(|retry_in| retry_in.as_secs_f64()))
+        .execute(&self.pool)
+        .await
+        .map_err(|_| EmailOutboxStoreError::UnexpectedError)?;
+
+        if result.rows_affected() == 0 {
+            return Err(EmailOutboxStoreError::EmailNotFound);
+        }
+
+        Ok(())
+    }
+
+    async fn count_pending(&self) -> Result<u64, EmailOutboxStoreError> {
+        let count: i64 =
+            sqlx::query_scalar("SELECT COUNT(*) FROM email_outbox WHERE status = 'pending'")
+                .fetch_one(&self.pool)
+                .await
+                .map_err(|_| EmailOutboxStoreError::UnexpectedError)?;
+
+        Ok(count as u64)
+    }
+
+    async fn get_stuck(&self) -> Result<Vec<OutboxEmail>, EmailOutboxStoreError> {
+        let rows: Vec<OutboxEmailRow> = sqlx::query_as(
+            r#"
+            SELECT id, recipient, subject, html_body, text_body, status, attempts, last_error,
+                CAST(next_attempt_at AS INTEGER) AS next_attempt_at,
+                CAST(created_at AS INTEGER) AS created_at
+            FROM email_outbox
+            WHERE status = 'dead' OR (status = 'pending' AND attempts > 0)
+            ORDER BY created_at DESC
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|_| EmailOutboxStoreError::UnexpectedError)?;
+
+        rows.into_iter().map(OutboxEmailRow::parse).collect()
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct OutboxEmailRow {
+    id: String,
+    recipient: String,
+    subject: String,
+    html_body: String,
+    text_body: String,
+    status: String,
+    attempts: i64,
+    last_error: Option<String>,
+    next_attempt_at: i64,
+    created_at: i64,
+}
+
+impl OutboxEmailRow {
+    fn parse(self) -> Result<OutboxEmail, EmailOutboxStoreError> {
+        Ok(OutboxEmail {
+            id: Uuid::parse_str(&self.id).map_err(|_| EmailOutboxStoreError::UnexpectedError)?,
+            recipient: Email::parse(self.recipient)
+                .map_err(|_| EmailOutboxStoreError::UnexpectedError)?,
+            message: EmailMessage {
+                subject: self.subject,
+                html_body: self.html_body,
+                text_body: self.text_body,
+            },
+            status: OutboxEmailStatus::parse(self.status)
+                .map_err(|_| EmailOutboxStoreError::UnexpectedError)?,
+            attempts: self.attempts as u32,
+            last_error: self.last_error,
+            next_attempt_at: self.next_attempt_at,
+            created_at: self.created_at,
+        })
+    }
+}
@@ -0,0 +1,124 @@
+use sqlx::SqlitePool;
+
+use crate::{
+    domain::{JwtKeyStore, JwtKeyStoreError, StoredJwtKey},
+    utils::encryption::{decrypt, encrypt},
+};
+
+pub struct SqliteJwtKeyStore {
+    pool: SqlitePool,
+}
+
+impl SqliteJwtKeyStore {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct JwtKeyRow {
+    kid: String,
+    algorithm: String,
+    encrypted_private_key: Vec<u8>,
+    retire_at: Option<i64>,
+}
+
+#[async_trait::async_trait]
+impl JwtKeyStore for SqliteJwtKeyStore {
+    async fn rotate_key(
+        &self,
+        key: &StoredJwtKey,
+        previous: &StoredJwtKey,
+    ) -> Result<(), JwtKeyStoreError> {
+        let retire_at = previous
+            .retire_at
+            .ok_or(JwtKeyStoreError::UnexpectedError)?;
+
+        // Taking the write lock up front makes concurrent rotations run one after the other, so
+        // only one key is left active
+        let mut transaction = self
+            .pool
+            .begin_with("BEGIN IMMEDIATE")
+            .await
+            .map_err(|_| JwtKeyStoreError::UnexpectedError)?;
+
+        sqlx::query("DELETE FROM jwt_keys WHERE retire_at <= unixepoch()")
+            .execute(&mut *transaction)
+            .await
+            .map_err(|_| JwtKeyStoreError::UnexpectedError)?;
+
+        // Keys rotated in by other instances since the caller last loaded the ring are retired too
+        sqlx::query("UPDATE jwt_keys SET retire_at = $1 WHERE retire_at IS NULL")
+            .bind(retire_at)
+            .execute(&mut *transaction)
+            .await
+            .map_err(|_| JwtKeyStoreError::UnexpectedError)?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO jwt_keys (kid, algorithm, encrypted_private_key, rotation, retire_at)
+            VALUES ($1, $2, $3, (SELECT COALESCE(MAX(rotation), 0) + 1 FROM jwt_keys), $4)
+            ON CONFLICT (kid) DO UPDATE
+            SET retire_at = MIN(jwt_keys.retire_at, excluded.retire_at)
+            "#,
+        )
+        .bind(&previous.kid)
+        .bind(&previous.algorithm)
+        .bind(encrypt_private_key(previous)?)
+        .bind(retire_at)
+        .execute(&mut *transaction)
+        .await
+        .map_err(|_| JwtKeyStoreError::UnexpectedError)?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO jwt_keys (kid, algorithm, encrypted_private_key, rotation, retire_at)
+            VALUES ($1, $2, $3, (SELECT COALESCE(MAX(rotation), 0) + 1 FROM jwt_keys), NULL)
+            ON CONFLICT (kid) DO UPDATE
+            SET retire_at = NULL, rotation = excluded.rotation
+            "#,
+        )
+        .bind(&key.kid)
+        .bind(&key.algorithm)
+        .bind(encrypt_private_key(key)?)
+        .execute(&mut *transaction)
+        .await
+        .map_err(|_| JwtKeyStoreError::UnexpectedError)?;
+
+        transaction
+            .commit()
+            .await
+            .map_err(|_| JwtKeyStoreError::UnexpectedError)
+    }
+
+    async fn get_keys(&self) -> Result<Vec<StoredJwtKey>, JwtKeyStoreError> {
+        let rows: Vec<JwtKeyRow> = sqlx::query_as(
+            r#"
+            SELECT kid, algorithm, encrypted_private_key, retire_at
+            FROM jwt_keys
+            WHERE retire_at IS NULL OR retire_at > unixepoch()
+            ORDER BY rotation DESC
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|_| JwtKeyStoreError::UnexpectedError)?;
+
+        rows.into_iter()
+            .map(|row| {
+                Ok(StoredJwtKey {
+                    private_key: decrypt(&row.encrypted_private_key, row.kid.as_bytes())
+                        .map_err(|_| JwtKeyStoreError::UnexpectedError)?,
+                    kid: row.kid,
+                    algorithm: row.algorithm,
+                    retire_at: row.retire_at,
+                })
+            })
+            .collect()
+    }
+}
+
+// The kid is bound as associated data so a ciphertext cannot be moved to another key's row
+fn encrypt_private_key(key: &StoredJwtKey) -> Result<Vec<u8>, JwtKeyStoreError> {
+    encrypt(&key.private_key, key.kid.as_bytes()).map_err(|_| JwtKeyStoreError::UnexpectedError)
+}
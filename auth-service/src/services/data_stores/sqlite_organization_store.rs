@@ -0,0 +1,225 @@
+use sqlx::SqlitePool;
+
+use crate::{
+    domain::{
+        data_stores::{OrganizationStore, OrganizationStoreError},
+        Email, Invitation, InvitationToken, Membership, OrgRole, Organization, OrganizationId,
+        OrganizationName,
+    },
+    utils::auth::INVITATION_TOKEN_TTL_SECONDS,
+};
+
+pub struct SqliteOrganizationStore {
+    pool: SqlitePool,
+}
+
+impl SqliteOrganizationStore {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct MembershipRow {
+    id: String,
+    name: String,
+    role: String,
+}
+
+#[async_trait::async_trait]
+impl OrganizationStore for SqliteOrganizationStore {
+    async fn create_organization(
+        &mut self,
+        name: OrganizationName,
+        owner: &Email,
+    ) -> Result<Organization, OrganizationStoreError> {
+        let organization = Organization {
+            id: OrganizationId::default(),
+            name,
+        };
+
+        let mut transaction = self
+            .pool
+            .begin()
+            .await
+            .map_err(|_| OrganizationStoreError::UnexpectedError)?;
+
+        sqlx::query("INSERT INTO organizations (id, name) VALUES ($1, $2)")
+            .bind(organization.id.to_string())
+            .bind(organization.name.as_ref())
+            .execute(&mut *transaction)
+            .await
+            .map_err(|_| OrganizationStoreError::UnexpectedError)?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO organization_memberships (organization_id, email, role)
+            VALUES ($1, $2, $3)
+            "#,
+        )
+        .bind(organization.id.to_string())
+        .bind(owner.as_ref())
+        .bind(OrgRole::Owner.as_ref())
+        .execute(&mut *transaction)
+        .await
+        .map_err(|_| OrganizationStoreError::UnexpectedError)?;
+
+        transaction
+            .commit()
+            .await
+            .map_err(|_| OrganizationStoreError::UnexpectedError)?;
+
+        Ok(organization)
+    }
+
+    async fn get_memberships(
+        &self,
+        email: &Email,
+    ) -> Result<Vec<Membership>, OrganizationStoreError> {
+        // memberships added within the same second keep the order they were added in
+        sqlx::query_as(
+            r#"
+            SELECT organizations.id, organizations.name, organization_memberships.role
+            FROM organization_memberships
+            JOIN organizations ON organizations.id = organization_memberships.organization_id
+            WHERE organization_memberships.email = $1
+            ORDER BY organization_memberships.created_at, organization_memberships.rowid
+            "#,
+        )
+        .bind(email.as_ref())
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|_| OrganizationStoreError::UnexpectedError)?
+        .into_iter()
+        .map(to_membership)
+        .collect()
+    }
+
+    async fn get_membership(
+        &self,
+        email: &Email,
+        organization_id: &OrganizationId,
+    ) -> Result<Membership, OrganizationStoreError> {
+        let row: MembershipRow = sqlx::query_as(
+            r#"
+            SELECT organizations.id, organizations.name, organization_memberships.role
+            FROM organization_memberships
+            JOIN organizations ON organizations.id = organization_memberships.organization_id
+            WHERE organization_memberships.email = $1
+              AND organization_memberships.organization_id = $2
+            "#,
+        )
+        .bind(email.as_ref())
+        .bind(organization_id.to_string())
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|_| OrganizationStoreError::UnexpectedError)?
+        .ok_or(OrganizationStoreError::MembershipNotFound)?;
+
+        to_membership(row)
+    }
+
+    async fn add_invitation(
+        &mut self,
+        token: &InvitationToken,
+        invitation: Invitation,
+    ) -> Result<(), OrganizationStoreError> {
+        sqlx::query(
+            r#"
+            INSERT INTO organization_invitations (token_hash, organization_id, email, role, expires_at)
+            VALUES ($1, $2, $3, $4, unixepoch() + $5)
+            "#,
+        )
+        .bind(token.hash())
+        .bind(invitation.organization_id.to_string())
+        .bind(invitation.email.as_ref())
+        .bind(invitation.role.as_ref())
+        .bind(INVITATION_TOKEN_TTL_SECONDS)
+        .execute(&self.pool)
+        .await
+        .map_err(|error| match &error {
+            sqlx::Error::Database(db_err) if db_err.is_foreign_key_violation() => {
+                OrganizationStoreError::OrganizationNotFound
+            }
+            _ => OrganizationStoreError::UnexpectedError,
+        })?;
+
+        Ok(())
+    }
+
+    async fn accept_invitation(
+        &mut self,
+        token: &InvitationToken,
+        email: &Email,
+    ) -> Result<Membership, OrganizationStoreError> {
+        let mut transaction = self
+            .pool
+            .begin()
+            .await
+            .map_err(|_| OrganizationStoreError::UnexpectedError)?;
+
+        // Deleting the invitation in the same transaction that adds the membership means it can only
+        // be accepted once, even by concurrent requests
+        let (organization_id, role): (String, String) = sqlx::query_as(
+            r#"
+            DELETE FROM organization_invitations
+            WHERE token_hash = $1 AND email = $2 AND expires_at > unixepoch()
+            RETURNING organization_id, role
+            "#,
+        )
+        .bind(token.hash())
+        .bind(email.as_ref())
+        .fetch_optional(&mut *transaction)
+        .await
+        .map_err(|_| OrganizationStoreError::UnexpectedError)?
+        .ok_or(OrganizationStoreError::InvitationNotFound)?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO organization_memberships (organization_id, email, role)
+            VALUES ($1, $2, $3)
+            "#,
+        )
+        .bind(&organization_id)
+        .bind(email.as_ref())
+        .bind(&role)
+        .execute(&mut *transaction)
+        .await
+        .map_err(|error| match &error {
+            // dropping the transaction keeps the invitation
+            sqlx::Error::Database(db_err) if db_err.is_unique_violation() => {
+                OrganizationStoreError::AlreadyMember
+            }
+            _ => OrganizationStoreError::UnexpectedError,
+        })?;
+
+        let name: String = sqlx::query_scalar("SELECT name FROM organizations WHERE id = $1")
+            .bind(&organization_id)
+            .fetch_one(&mut *transaction)
+            .await
+            .map_err(|_| OrganizationStoreError::UnexpectedError)?;
+
+        transaction
+            .commit()
+            .await
+            .map_err(|_| OrganizationStoreError::UnexpectedError)?;
+
+        to_membership(MembershipRow {
+            id: organization_id,
+            name,
+            role,
+        })
+    }
+}
+
+fn to_membership(row: MembershipRow) -> Result<Membership, OrganizationStoreError> {
+    Ok(Membership {
+        organization: Organization {
+            id: OrganizationId::parse(row.id)
+                .map_err(|_| OrganizationStoreError::UnexpectedError)?,
+            name: OrganizationName::parse(row.name)
+                .map_err(|_| OrganizationStoreError::UnexpectedError)?,
+        },
+        role: OrgRole::parse(&row.role).map_err(|_| OrganizationStoreError::UnexpectedError)?,
+    })
+}
@@ -0,0 +1,87 @@
+use chrono::Utc;
+use sqlx::SqlitePool;
+
+use crate::domain::{
+    data_stores::{RateLimitKey, RateLimitStore, RateLimitStoreError},
+    RateLimit, RateLimitDecision, TokenBucket,
+};
+
+// Buckets are forgotten once they are full again, like the Redis keys. Full rows are left for
+// `delete_expired`, a bucket found that way is refilled to full anyway.
+pub struct SqliteRateLimitStore {
+    pool: SqlitePool,
+}
+
+impl SqliteRateLimitStore {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    // Returns how many full buckets were deleted
+    pub async fn delete_expired(&self) -> Result<u64, RateLimitStoreError> {
+        let result = sqlx::query("DELETE FROM rate_limit_buckets WHERE expires_at <= unixepoch()")
+            .execute(&self.pool)
+            .await
+            .map_err(|_| RateLimitStoreError::UnexpectedError)?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+#[async_trait::async_trait]
+impl RateLimitStore for SqliteRateLimitStore {
+    async fn take_token(
+        &self,
+        key: &RateLimitKey,
+        limit: &RateLimit,
+    ) -> Result<RateLimitDecision, RateLimitStoreError> {
+        let now = Utc::now().timestamp_millis();
+
+        // Taking the write lock up front makes concurrent requests count the bucket one after the
+        // other, so they cannot both take the last token
+        let mut transaction = self
+            .pool
+            .begin_with("BEGIN IMMEDIATE")
+            .await
+            .map_err(|_| RateLimitStoreError::UnexpectedError)?;
+
+        let row: Option<(f64, i64)> =
+            sqlx::query_as("SELECT tokens, updated_at FROM rate_limit_buckets WHERE key = $1")
+                .bind(key.as_ref())
+                .fetch_optional(&mut *transaction)
+                .await
+                .map_err(|_| RateLimitStoreError::UnexpectedError)?;
+
+        let mut bucket = match row {
+            Some((tokens, updated_at)) => TokenBucket::restore(tokens, updated_at),
+            None => TokenBucket::full(limit, now),
+        };
+        let decision = bucket.take(limit, now);
+
+        // rounded up to the second, so the bucket is only forgotten once it is full
+        sqlx::query(
+            r#"
+            INSERT INTO rate_limit_buckets (key, tokens, updated_at, expires_at)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (key) DO UPDATE
+            SET tokens = excluded.tokens,
+                updated_at = excluded.updated_at,
+                expires_at = excluded.expires_at
+            "#,
+        )
+        .bind(key.as_ref())
+        .bind(bucket.tokens())
+        .bind(bucket.updated_at())
+        .bind((bucket.full_at(limit) + 999) / 1000)
+        .execute(&mut *transaction)
+        .await
+        .map_err(|_| RateLimitStoreError::UnexpectedError)?;
+
+        transaction
+            .commit()
+            .await
+            .map_err(|_| RateLimitStoreError::UnexpectedError)?;
+
+        Ok(decision)
+    }
+}
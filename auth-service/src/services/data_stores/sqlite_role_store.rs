@@ -0,0 +1,143 @@
+use std::collections::BTreeSet;
+
+use sqlx::SqlitePool;
+
+use crate::domain::{
+    data_stores::{RoleStore, RoleStoreError},
+    Email, Permission, Role, RoleDefinition, UserAccess,
+};
+
+pub struct SqliteRoleStore {
+    pool: SqlitePool,
+}
+
+impl SqliteRoleStore {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    async fn role_exists(&self, role: &Role) -> Result<bool, RoleStoreError> {
+        sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM roles WHERE name = $1)")
+            .bind(role.as_ref())
+            .fetch_one(&self.pool)
+            .await
+            .map_err(|_| RoleStoreError::UnexpectedError)
+    }
+}
+
+#[async_trait::async_trait]
+impl RoleStore for SqliteRoleStore {
+    async fn get_user_access(&self, email: &Email) -> Result<UserAccess, RoleStoreError> {
+        let rows: Vec<(String, Option<String>)> = sqlx::query_as(
+            r#"
+            SELECT user_roles.role, role_permissions.permission
+            FROM user_roles
+            LEFT JOIN role_permissions ON role_permissions.role = user_roles.role
+            WHERE user_roles.email = $1
+            "#,
+        )
+        .bind(email.as_ref())
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|_| RoleStoreError::UnexpectedError)?;
+
+        // A permission granted by several roles is listed once
+        let mut roles = BTreeSet::new();
+        let mut permissions = BTreeSet::new();
+
+        for (role, permission) in rows {
+            roles.insert(Role::parse(role).map_err(|_| RoleStoreError::UnexpectedError)?);
+
+            if let Some(permission) = permission {
+                permissions.insert(
+                    Permission::parse(permission).map_err(|_| RoleStoreError::UnexpectedError)?,
+                );
+            }
+        }
+
+        Ok(UserAccess {
+            roles: roles.into_iter().collect(),
+            permissions: permissions.into_iter().collect(),
+        })
+    }
+
+    async fn get_roles(&self) -> Result<Vec<RoleDefinition>, RoleStoreError> {
+        let rows: Vec<(String, Option<String>)> = sqlx::query_as(
+            r#"
+            SELECT roles.name, role_permissions.permission
+            FROM roles
+            LEFT JOIN role_permissions ON role_permissions.role = roles.name
+            ORDER BY roles.name, role_permissions.permission
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|_| RoleStoreError::UnexpectedError)?;
+
+        let mut definitions: Vec<RoleDefinition> = Vec::new();
+
+        for (role, permission) in rows {
+            let role = Role::parse(role).map_err(|_| RoleStoreError::UnexpectedError)?;
+
+            // rows are ordered by role, so the permissions of a role are next to each other
+            if definitions.last().map(|definition| &definition.role) != Some(&role) {
+                definitions.push(RoleDefinition {
+                    role,
+                    permissions: Vec::new(),
+                });
+            }
+
+            if let Some(permission) = permission {
+                let permission =
+                    Permission::parse(permission).map_err(|_| RoleStoreError::UnexpectedError)?;
+                if let Some(definition) = definitions.last_mut() {
+                    definition.permissions.push(permission);
+                }
+            }
+        }
+
+        Ok(definitions)
+    }
+
+    async fn assign_role(&mut self, email: &Email, role: &Role) -> Result<(), RoleStoreError> {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO user_roles (email, role)
+            VALUES ($1, $2)
+            ON CONFLICT DO NOTHING
+            "#,
+        )
+        .bind(email.as_ref())
+        .bind(role.as_ref())
+        .execute(&self.pool)
+        .await;
+
+        match result {
+            Ok(_) => Ok(()),
+            // SQLite does not say which reference is missing, so look the role up
+            Err(sqlx::Error::Database(db_err)) if db_err.is_foreign_key_violation() => {
+                if self.role_exists(role).await? {
+                    Err(RoleStoreError::UserNotFound)
+                } else {
+                    Err(RoleStoreError::RoleNotFound)
+                }
+            }
+            Err(_) => Err(RoleStoreError::UnexpectedError),
+        }
+    }
+
+    async fn revoke_role(&mut self, email: &Email, role: &Role) -> Result<(), RoleStoreError> {
+        if !self.role_exists(role).await? {
+            return Err(RoleStoreError::RoleNotFound);
+        }
+
+        sqlx::query("DELETE FROM user_roles WHERE email = $1 AND role = $2")
+            .bind(email.as_ref())
+            .bind(role.as_ref())
+            .execute(&self.pool)
+            .await
+            .map_err(|_| RoleStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+}
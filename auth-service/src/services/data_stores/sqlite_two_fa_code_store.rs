@@ -0,0 +1,265 @@
+use std::time::Duration;
+
+use sqlx::SqlitePool;
+
+use crate::{
+    domain::{
+        data_stores::{LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError},
+        Email, OrganizationId,
+    },
+    utils::{
+        auth::{
+            MAX_TWO_FA_ATTEMPTS, MAX_TWO_FA_RESENDS, TWO_FA_CODE_TTL_SECONDS,
+            TWO_FA_RESEND_COOLDOWN_SECONDS,
+        },
+        constants::MAX_PENDING_LOGIN_ATTEMPTS,
+    },
+};
+
+// Pending logins expire after the TTL like the Redis keys do, expired rows are ignored and left
+// for `delete_expired`
+pub struct SqliteTwoFACodeStore {
+    pool: SqlitePool,
+    // how long a login attempt can be completed for
+    ttl: Duration,
+}
+
+impl SqliteTwoFACodeStore {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self::with_ttl(pool, Duration::from_secs(TWO_FA_CODE_TTL_SECONDS as u64))
+    }
+
+    pub fn with_ttl(pool: SqlitePool, ttl: Duration) -> Self {
+        Self { pool, ttl }
+    }
+
+    // Returns how many expired login attempts were deleted
+    pub async fn delete_expired(&self) -> Result<u64, TwoFACodeStoreError> {
+        let result = sqlx::query("DELETE FROM two_fa_codes WHERE expires_at <= unixepoch()")
+            .execute(&self.pool)
+            .await
+            .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct ResendRow {
+    resends: i64,
+    cooldown_seconds: i64,
+}
+
+#[async_trait::async_trait]
+impl TwoFACodeStore for SqliteTwoFACodeStore {
+    async fn add_code(
+        &self,
+        email: Email,
+        login_attempt_id: LoginAttemptId,
+        code: TwoFACode,
+        organization_id: Option<OrganizationId>,
+    ) -> Result<(), TwoFACodeStoreError> {
+        let mut transaction = self
+            .pool
+            .begin()
+            .await
+            .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO two_fa_codes (login_attempt_id, email, code, organization_id, expires_at)
+            VALUES ($1, $2, $3, $4, unixepoch() + $5)
+            "#,
+        )
+        .bind(login_attempt_id.as_ref())
+        .bind(email.as_ref())
+        .bind(code.as_ref())
+        .bind(organization_id.map(|id| id.to_string()))
+        .bind(self.ttl.as_secs() as i64)
+        .execute(&mut *transaction)
+        .await
+        .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+
+        // The user's pending attempts beyond the cap are discarded, oldest first
+        sqlx::query(
+            r#"
+            DELETE FROM two_fa_codes
+            WHERE id IN (
+                SELECT id FROM two_fa_codes
+                WHERE email = $1 AND expires_at > unixepoch()
+                ORDER BY id DESC
+                LIMIT -1 OFFSET $2
+            )
+            "#,
+        )
+        .bind(email.as_ref())
+        .bind(*MAX_PENDING_LOGIN_ATTEMPTS as i64)
+        .execute(&mut *transaction)
+        .await
+        .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+
+        transaction
+            .commit()
+            .await
+            .map_err(|_| TwoFACodeStoreError::UnexpectedError)
+    }
+
+    async fn remove_code(
+        &self,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<Option<OrganizationId>, TwoFACodeStoreError> {
+        let (organization_id,): (Option<String>,) = sqlx::query_as(
+            r#"
+            DELETE FROM two_fa_codes
+            WHERE login_attempt_id = $1 AND expires_at > unixepoch()
+            RETURNING organization_id
+            "#,
+        )
+        .bind(login_attempt_id.as_ref())
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|_| TwoFACodeStoreError::UnexpectedError)?
+        .ok_or(TwoFACodeStoreError::LoginAttemptIdNotFound)?;
+
+        organization_id
+            .map(OrganizationId::parse)
+            .transpose()
+            .map_err(|_| TwoFACodeStoreError::UnexpectedError)
+    }
+
+    async fn get_code(
+        &self,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<(Email, TwoFACode), TwoFACodeStoreError> {
+        let (email, code): (String, String) = sqlx::query_as(
+            r#"
+            SELECT email, code
+            FROM two_fa_codes
+            WHERE login_attempt_id = $1 AND expires_at > unixepoch()
+            "#,
+        )
+        .bind(login_attempt_id.as_ref())
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|_| TwoFACodeStoreError::UnexpectedError)?
+        .ok_or(TwoFACodeStoreError::LoginAttemptIdNotFound)?;
+
+        let email = Email::parse(email).map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+        let code = TwoFACode::parse(code).map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+
+        Ok((email, code))
+    }
+
+    async fn verify_code(
+        &self,
+        login_attempt_id: &LoginAttemptId,
+        code: &TwoFACode,
+    ) -> Result<(), TwoFACodeStoreError> {
+        // One statement, so concurrent guesses are counted one after the other and none of them is
+        // compared once the limit is reached
+        let (verified, failed_attempts): (bool, i64) = sqlx::query_as(
+            r#"
+            UPDATE two_fa_codes
+            SET failed_attempts = failed_attempts + CASE WHEN code = $2 THEN 0 ELSE 1 END
+            WHERE login_attempt_id = $1 AND expires_at > unixepoch() AND failed_attempts < $3
+            RETURNING code = $2, failed_attempts
+            "#,
+        )
+        .bind(login_attempt_id.as_ref())
+        .bind(code.as_ref())
+        .bind(MAX_TWO_FA_ATTEMPTS as i64)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|_| TwoFACodeStoreError::UnexpectedError)?
+        .ok_or(TwoFACodeStoreError::LoginAttemptIdNotFound)?;
+
+        if verified {
+            return Ok(());
+        }
+
+        if failed_attempts as u32 >= MAX_TWO_FA_ATTEMPTS {
+            match self.remove_code(login_attempt_id).await {
+                // a concurrent request may have removed it already
+                Ok(_) | Err(TwoFACodeStoreError::LoginAttemptIdNotFound) => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        Err(TwoFACodeStoreError::IncorrectCode)
+    }
+
+    async fn reserve_attempt(
+        &self,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<u32, TwoFACodeStoreError> {
+        let failed_attempts: i64 = sqlx::query_scalar(
+            r#"
+            UPDATE two_fa_codes
+            SET failed_attempts = failed_attempts + 1
+            WHERE login_attempt_id = $1 AND expires_at > unixepoch() AND failed_attempts < $2
+            RETURNING failed_attempts
+            "#,
+        )
+        .bind(login_attempt_id.as_ref())
+        .bind(MAX_TWO_FA_ATTEMPTS as i64)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|_| TwoFACodeStoreError::UnexpectedError)?
+        .ok_or(TwoFACodeStoreError::LoginAttemptIdNotFound)?;
+
+        Ok(failed_attempts as u32)
+    }
+
+    async fn resend_code(
+        &self,
+        login_attempt_id: &LoginAttemptId,
+        code: TwoFACode,
+    ) -> Result<(), TwoFACodeStoreError> {
+        // One statement, so concurrent resends cannot exceed the limit or skip the cooldown
+        let result = sqlx::query(
+            r#"
+            UPDATE two_fa_codes
+            SET code = $2, resends = resends + 1, sent_at = unixepoch(),
+                expires_at = unixepoch() + $3
+            WHERE login_attempt_id = $1 AND expires_at > unixepoch()
+                AND resends < $4
+                AND sent_at <= unixepoch() - $5
+            "#,
+        )
+        .bind(login_attempt_id.as_ref())
+        .bind(code.as_ref())
+        .bind(self.ttl.as_secs() as i64)
+        .bind(MAX_TWO_FA_RESENDS as i64)
+        .bind(TWO_FA_RESEND_COOLDOWN_SECONDS)
+        .execute(&self.pool)
+        .await
+        .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+
+        if result.rows_affected() == 1 {
+            return Ok(());
+        }
+
+        // Find out why the code was not replaced
+        let row: ResendRow = sqlx::query_as(
+            r#"
+            SELECT resends, sent_at + $2 - unixepoch() AS cooldown_seconds
+            FROM two_fa_codes
+            WHERE login_attempt_id = $1 AND expires_at > unixepoch()
+            "#,
+        )
+        .bind(login_attempt_id.as_ref())
+        .bind(TWO_FA_RESEND_COOLDOWN_SECONDS)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|_| TwoFACodeStoreError::UnexpectedError)?
+        .ok_or(TwoFACodeStoreError::LoginAttemptIdNotFound)?;
+
+        if row.resends as u32 >= MAX_TWO_FA_RESENDS {
+            return Err(TwoFACodeStoreError::TooManyResends);
+        }
+
+        Err(TwoFACodeStoreError::ResendCooldown(
+            row.cooldown_seconds.max(1) as u64,
+        ))
+    }
+}
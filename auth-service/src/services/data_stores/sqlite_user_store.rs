@@ -0,0 +1,152 @@
+use sqlx::SqlitePool;
+
+use super::postgres_user_store::{compute_password_hash, verify_password_hash};
+use crate::domain::{
+    data_stores::{UserStore, UserStoreError},
+    Email, Password, TwoFAMethod, User,
+};
+
+// The checked query macros are tied to the Postgres database the crate is built against, so the
+// SQLite stores use plain queries
+pub struct SqliteUserStore {
+    pool: SqlitePool,
+}
+
+impl SqliteUserStore {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct UserRow {
+    email: String,
+    password_hash: String,
+    two_fa_method: String,
+    email_verified: bool,
+}
+
+#[async_trait::async_trait]
+impl UserStore for SqliteUserStore {
+    async fn add_user(&self, user: User) -> Result<(), UserStoreError> {
+        let password_hash = compute_password_hash(user.password.as_ref().to_owned())
+            .await
+            .map_err(|_| UserStoreError::UnexpectedError)?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO users (email, password_hash, two_fa_method, email_verified)
+            VALUES ($1, $2, $3, $4)
+            "#,
+        )
+        .bind(user.email.as_ref())
+        .bind(&password_hash)
+        .bind(user.two_fa_method.as_ref())
+        .bind(user.email_verified)
+        .execute(&self.pool)
+        .await
+        .map_err(|error| match &error {
+            sqlx::Error::Database(db_err) if db_err.is_unique_violation() => {
+                UserStoreError::UserAlreadyExists
+            }
+            _ => UserStoreError::UnexpectedError,
+        })?;
+
+        Ok(())
+    }
+
+    async fn get_user(&self, email: &Email) -> Result<User, UserStoreError> {
+        let row: UserRow = sqlx::query_as(
+            r#"
+            SELECT email, password_hash, two_fa_method, email_verified
+            FROM users
+            WHERE email = $1
+            "#,
+        )
+        .bind(email.as_ref())
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|_| UserStoreError::UnexpectedError)?
+        .ok_or(UserStoreError::UserNotFound)?;
+
+        Ok(User {
+            email: Email::parse(row.email).map_err(|_| UserStoreError::UnexpectedError)?,
+            password: Password::parse(row.password_hash)
+                .map_err(|_| UserStoreError::UnexpectedError)?,
+            two_fa_method: TwoFAMethod::parse(row.two_fa_method)
+                .map_err(|_| UserStoreError::UnexpectedError)?,
+            email_verified: row.email_verified,
+        })
+    }
+
+    async fn validate_user(
+        &self,
+        email: &Email,
+        password: &Password,
+    ) -> Result<(), UserStoreError> {
+        let user = self.get_user(email).await?;
+
+        verify_password_hash(
+            user.password.as_ref().to_owned(),
+            password.as_ref().to_owned(),
+        )
+        .await
+        .map_err(|_| UserStoreError::InvalidCredentials)
+    }
+
+    async fn update_password(
+        &self,
+        email: &Email,
+        password: Password,
+    ) -> Result<(), UserStoreError> {
+        let password_hash = compute_password_hash(password.as_ref().to_owned())
+            .await
+            .map_err(|_| UserStoreError::UnexpectedError)?;
+
+        let result = sqlx::query("UPDATE users SET password_hash = $1 WHERE email = $2")
+            .bind(&password_hash)
+            .bind(email.as_ref())
+            .execute(&self.pool)
+            .await
+            .map_err(|_| UserStoreError::UnexpectedError)?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        Ok(())
+    }
+
+    async fn set_email_verified(&self, email: &Email) -> Result<(), UserStoreError> {
+        let result = sqlx::query("UPDATE users SET email_verified = TRUE WHERE email = $1")
+            .bind(email.as_ref())
+            .execute(&self.pool)
+            .await
+            .map_err(|_| UserStoreError::UnexpectedError)?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        Ok(())
+    }
+
+    async fn set_two_fa_method(
+        &self,
+        email: &Email,
+        two_fa_method: TwoFAMethod,
+    ) -> Result<(), UserStoreError> {
+        let result = sqlx::query("UPDATE users SET two_fa_method = $1 WHERE email = $2")
+            .bind(two_fa_method.as_ref())
+            .bind(email.as_ref())
+            .execute(&self.pool)
+            .await
+            .map_err(|_| UserStoreError::UnexpectedError)?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,121 @@
+use sqlx::SqlitePool;
+
+use crate::{
+    domain::{
+        data_stores::{
+            LoginAttemptId, WebAuthnCeremony, WebAuthnChallenge, WebAuthnChallengeStore,
+            WebAuthnChallengeStoreError,
+        },
+        Email,
+    },
+    utils::auth::WEBAUTHN_CHALLENGE_TTL_SECONDS,
+};
+
+// Challenges expire like the Redis keys do, expired rows are ignored and left for `delete_expired`
+pub struct SqliteWebAuthnChallengeStore {
+    pool: SqlitePool,
+}
+
+impl SqliteWebAuthnChallengeStore {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    // Returns how many expired challenges were deleted
+    pub async fn delete_expired(&self) -> Result<u64, WebAuthnChallengeStoreError> {
+        let result = sqlx::query("DELETE FROM webauthn_challenges WHERE expires_at <= unixepoch()")
+            .execute(&self.pool)
+            .await
+            .map_err(|_| WebAuthnChallengeStoreError::UnexpectedError)?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+const REGISTRATION: &str = "registration";
+const AUTHENTICATION: &str = "authentication";
+
+#[derive(sqlx::FromRow)]
+struct ChallengeRow {
+    ceremony: String,
+    email: String,
+    login_attempt_id: Option<String>,
+}
+
+#[async_trait::async_trait]
+impl WebAuthnChallengeStore for SqliteWebAuthnChallengeStore {
+    async fn add_challenge(
+        &mut self,
+        challenge: &WebAuthnChallenge,
+        ceremony: WebAuthnCeremony,
+    ) -> Result<(), WebAuthnChallengeStoreError> {
+        let (kind, email, login_attempt_id) = match ceremony {
+            WebAuthnCeremony::Registration { email } => (REGISTRATION, email, None),
+            WebAuthnCeremony::Authentication {
+                email,
+                login_attempt_id,
+            } => (AUTHENTICATION, email, login_attempt_id),
+        };
+
+        sqlx::query(
+            r#"
+            INSERT INTO webauthn_challenges (challenge, ceremony, email, login_attempt_id, expires_at)
+            VALUES ($1, $2, $3, $4, unixepoch() + $5)
+            ON CONFLICT (challenge) DO UPDATE
+            SET ceremony = excluded.ceremony,
+                email = excluded.email,
+                login_attempt_id = excluded.login_attempt_id,
+                expires_at = excluded.expires_at
+            "#,
+        )
+        .bind(challenge.as_ref())
+        .bind(kind)
+        .bind(email.as_ref())
+        .bind(login_attempt_id.as_ref().map(AsRef::<str>::as_ref))
+        .bind(WEBAUTHN_CHALLENGE_TTL_SECONDS)
+        .execute(&self.pool)
+        .await
+        .map_err(|_| WebAuthnChallengeStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    async fn consume_challenge(
+        &mut self,
+        challenge: &WebAuthnChallenge,
+    ) -> Result<WebAuthnCeremony, WebAuthnChallengeStoreError> {
+        // Deleting the row returns it, so a challenge is only ever used once
+        let row: ChallengeRow = sqlx::query_as(
+            r#"
+            DELETE FROM webauthn_challenges
+            WHERE challenge = $1 AND expires_at > unixepoch()
+            RETURNING ceremony, email, login_attempt_id
+            "#,
+        )
+        .bind(challenge.as_ref())
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|_| WebAuthnChallengeStoreError::UnexpectedError)?
+        .ok_or(WebAuthnChallengeStoreError::ChallengeNotFound)?;
+
+        let email =
+            Email::parse(row.email).map_err(|_| WebAuthnChallengeStoreError::UnexpectedError)?;
+
+        match row.ceremony.as_str() {
+            REGISTRATION => Ok(WebAuthnCeremony::Registration { email }),
+            AUTHENTICATION => {
+                let login_attempt_id = row
+                    .login_attempt_id
+                    .map(LoginAttemptId::parse)
+                    .transpose()
+                    .map_err(|_| WebAuthnChallengeStoreError::UnexpectedError)?;
+
+                Ok(WebAuthnCeremony::Authentication {
+                    email,
+                    login_attempt_id,
+                })
+            }
+            _ => Err(WebAuthnChallengeStoreError::UnexpectedError),
+        }
+    }
+}
@@ -0,0 +1,140 @@
+use sqlx::SqlitePool;
+
+use crate::domain::{
+    data_stores::{
+        CredentialId, WebAuthnCredential, WebAuthnCredentialStore, WebAuthnCredentialStoreError,
+    },
+    Email,
+};
+
+pub struct SqliteWebAuthnCredentialStore {
+    pool: SqlitePool,
+}
+
+impl SqliteWebAuthnCredentialStore {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct CredentialRow {
+    credential_id: Vec<u8>,
+    email: String,
+    public_key: Vec<u8>,
+    sign_count: i64,
+}
+
+#[async_trait::async_trait]
+impl WebAuthnCredentialStore for SqliteWebAuthnCredentialStore {
+    async fn add_credential(
+        &mut self,
+        credential: WebAuthnCredential,
+    ) -> Result<(), WebAuthnCredentialStoreError> {
+        sqlx::query(
+            r#"
+            INSERT INTO webauthn_credentials (credential_id, email, public_key, sign_count)
+            VALUES ($1, $2, $3, $4)
+            "#,
+        )
+        .bind(credential.credential_id.as_ref())
+        .bind(credential.email.as_ref())
+        .bind(&credential.public_key)
+        .bind(i64::from(credential.sign_count))
+        .execute(&self.pool)
+        .await
+        .map_err(|error| match &error {
+            sqlx::Error::Database(db_err) if db_err.is_unique_violation() => {
+                WebAuthnCredentialStoreError::CredentialAlreadyExists
+            }
+            _ => WebAuthnCredentialStoreError::UnexpectedError,
+        })?;
+
+        Ok(())
+    }
+
+    async fn get_credential(
+        &self,
+        credential_id: &CredentialId,
+    ) -> Result<WebAuthnCredential, WebAuthnCredentialStoreError> {
+        let row: CredentialRow = sqlx::query_as(
+            r#"
+            SELECT credential_id, email, public_key, sign_count
+            FROM webauthn_credentials
+            WHERE credential_id = $1
+            "#,
+        )
+        .bind(credential_id.as_ref())
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|_| WebAuthnCredentialStoreError::UnexpectedError)?
+        .ok_or(WebAuthnCredentialStoreError::CredentialNotFound)?;
+
+        to_credential(row)
+    }
+
+    async fn get_user_credentials(
+        &self,
+        email: &Email,
+    ) -> Result<Vec<WebAuthnCredential>, WebAuthnCredentialStoreError> {
+        // credentials registered within the same second keep the order they were added in
+        sqlx::query_as(
+            r#"
+            SELECT credential_id, email, public_key, sign_count
+            FROM webauthn_credentials
+            WHERE email = $1
+            ORDER BY created_at, rowid
+            "#,
+        )
+        .bind(email.as_ref())
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|_| WebAuthnCredentialStoreError::UnexpectedError)?
+        .into_iter()
+        .map(to_credential)
+        .collect()
+    }
+
+    async fn update_sign_count(
+        &mut self,
+        credential_id: &CredentialId,
+        sign_count: u32,
+    ) -> Result<(), WebAuthnCredentialStoreError> {
+        // Checking and storing the counter in one statement keeps concurrent logins from both passing the check
+        let result = sqlx::query(
+            r#"
+            UPDATE webauthn_credentials
+            SET sign_count = $1
+            WHERE credential_id = $2
+            AND ($1 > sign_count OR ($1 = 0 AND sign_count = 0))
+            "#,
+        )
+        .bind(i64::from(sign_count))
+        .bind(credential_id.as_ref())
+        .execute(&self.pool)
+        .await
+        .map_err(|_| WebAuthnCredentialStoreError::UnexpectedError)?;
+
+        if result.rows_affected() == 0 {
+            // tell a missing credential apart from a counter that went backwards
+            self.get_credential(credential_id).await?;
+            return Err(WebAuthnCredentialStoreError::SignCountNotIncreased);
+        }
+
+        Ok(())
+    }
+}
+
+fn to_credential(row: CredentialRow) -> Result<WebAuthnCredential, WebAuthnCredentialStoreError> {
+    Ok(WebAuthnCredential {
+        credential_id: CredentialId::parse(row.credential_id)
+            .map_err(|_| WebAuthnCredentialStoreError::UnexpectedError)?,
+        email: Email::parse(row.email)
+            .map_err(|_| WebAuthnCredentialStoreError::UnexpectedError)?,
+        public_key: row.public_key,
+        sign_count: row
+            .sign_count
+            .try_into()
+            .map_err(|_| WebAuthnCredentialStoreError::UnexpectedError)?,
+    })
+}
@@ -0,0 +1,259 @@
+use std::{
+    collections::{HashMap, HashSet},
+    time::Duration,
+};
+
+use chrono::Utc;
+use uuid::Uuid;
+
+use crate::domain::{
+    Email, EmailIdempotencyKey, EmailMessage, EmailOutboxStore, EmailOutboxStoreError, OutboxEmail,
+    OutboxEmailStatus,
+};
+
+#[derive(Default)]
+pub struct HashMapEmailOutboxStore {
+    emails: HashMap<Uuid, OutboxEmail>,
+    keys: HashSet<EmailIdempotencyKey>,
+}
+
+#[async_trait::async_trait]
+impl EmailOutboxStore for HashMapEmailOutboxStore {
+    async fn enqueue(
+        &mut self,
+        key: &EmailIdempotencyKey,
+        recipient: &Email,
+        message: &EmailMessage,
+    ) -> Result<bool, EmailOutboxStoreError> {
+        if !self.keys.insert(key.clone()) {
+            return Ok(false);
+        }
+
+        let now = Utc::now().timestamp();
+        let email = OutboxEmail {
+            id: Uuid::new_v4(),
+            recipient: recipient.clone(),
+            message: message.clone(),
+            status: OutboxEmailStatus::Pending,
+            attempts: 0,
+            last_error: None,
+            next_attempt_at: now,
+            created_at: now,
+        };
+        self.emails.insert(email.id, email);
+
+        Ok(true)
+    }
+
+    async fn claim_due(
+        &mut self,
+        limit: u32,
+        lease: Duration,
+    ) -> Result<Vec<OutboxEmail>, EmailOutboxStoreError> {
+        let now = Utc::now().timestamp();
+
+        let mut due: Vec<&mut OutboxEmail> = self
+            .emails
+            .values_mut()
+            .filter(|email| {
+                email.status == OutboxEmailStatus::Pending && email.next_attempt_at <= now
+            })
+            .collect();
+        due.sort_by_key(|email| email.next_attempt_at);
+
+        Ok(due
+            .into_iter()
+            .take(limit as usize)
+            .map(|email| {
+                email.next_attempt_at = now + lease.as_secs() as i64;
+                email.clone()
+            })
+            .collect())
+    }
+
+    async fn mark_sent(&mut self, id: &Uuid) -> Result<(), EmailOutboxStoreError> {
+        let email = self
+            .emails
+            .get_mut(id)
+            .ok_or(EmailOutboxStoreError::EmailNotFound)?;
+
+        email.status = OutboxEmailStatus::Sent;
+        email.attempts += 1;
+        email.message.html_body.clear();
+        email.message.text_body.clear();
+
+        Ok(())
+    }
+
+    async fn mark_failed(
+        &mut self,
+        id: &Uuid,
+        error: &str,
+        retry_in: Option<Duration>,
+    ) -> Result<(), EmailOutboxStoreError> {
+        let email = self
+            .emails
+            .get_mut(id)
+            .ok_or(EmailOutboxStoreError::EmailNotFound)?;
+
+        email.attempts += 1;
+        email.last_error = Some(error.to_owned());
+        match retry_in {
+            Some(retry_in) => {
+                email.next_attempt_at = Utc::now().timestamp() + retry_in.as_secs() as i64
+            }
+            None => email.status = OutboxEmailStatus::Dead,
+        }
+
+        Ok(())
+    }
+
+    async fn count_pending(&self) -> Result<u64, EmailOutboxStoreError> {
+        Ok(self
+            .emails
+            .values()
+            .filter(|email| email.status == OutboxEmailStatus::Pending)
+            .count() as u64)
+    }
+
+    async fn get_stuck(&self) -> Result<Vec<OutboxEmail>, EmailOutboxStoreError> {
+        let mut stuck: Vec<OutboxEmail> = self
+            .emails
+            .values()
+            .filter(|email| {
+                email.status == OutboxEmailStatus::Dead
+                    || (email.status == OutboxEmailStatus::Pending && email.attempts > 0)
+            })
+            .cloned()
+            .collect();
+        stuck.sort_by_key(|email| std::cmp::Reverse(email.created_at));
+
+        Ok(stuck)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_email() -> Email {
+        Email::parse("user@example.com".to_owned()).unwrap()
+    }
+
+    fn get_message() -> EmailMessage {
+        EmailMessage {
+            subject: "2FA Code".to_owned(),
+            html_body: "<p>123456</p>".to_owned(),
+            text_body: "123456".to_owned(),
+        }
+    }
+
+    #[tokio::test]
+    async fn email_with_same_key_should_be_queued_once() {
+        let mut store = HashMapEmailOutboxStore::default();
+        let key = EmailIdempotencyKey::new(&["two-fa", "attempt", "123456"]);
+
+        assert_eq!(
+            store.enqueue(&key, &get_email(), &get_message()).await,
+            Ok(true)
+        );
+        assert_eq!(
+            store.enqueue(&key, &get_email(), &get_message()).await,
+            Ok(false)
+        );
+
+        let other_key = EmailIdempotencyKey::new(&["two-fa", "attempt", "654321"]);
+        assert_eq!(
+            store
+                .enqueue(&other_key, &get_email(), &get_message())
+                .await,
+            Ok(true)
+        );
+        assert_eq!(store.count_pending().await, Ok(2));
+    }
+
+    #[tokio::test]
+    async fn claimed_email_should_not_be_due_until_lease_ends() {
+        let mut store = HashMapEmailOutboxStore::default();
+        let key = EmailIdempotencyKey::new(&["two-fa", "attempt", "123456"]);
+        store
+            .enqueue(&key, &get_email(), &get_message())
+            .await
+            .unwrap();
+
+        let claimed = store.claim_due(10, Duration::from_secs(60)).await.unwrap();
+        assert_eq!(claimed.len(), 1);
+        assert_eq!(claimed[0].message, get_message());
+
+        assert_eq!(
+            store.claim_due(10, Duration::from_secs(60)).await,
+            Ok(vec![])
+        );
+    }
+
+    #[tokio::test]
+    async fn sent_email_should_drop_its_bodies() {
+        let mut store = HashMapEmailOutboxStore::default();
+        let key = EmailIdempotencyKey::new(&["two-fa", "attempt", "123456"]);
+        store
+            .enqueue(&key, &get_email(), &get_message())
+            .await
+            .unwrap();
+        let id = store.claim_due(10, Duration::ZERO).await.unwrap()[0].id;
+
+        store.mark_sent(&id).await.unwrap();
+
+        let email = &store.emails[&id];
+        assert_eq!(email.status, OutboxEmailStatus::Sent);
+        assert!(email.message.text_body.is_empty());
+        assert!(email.message.html_body.is_empty());
+        assert_eq!(store.count_pending().await, Ok(0));
+        assert_eq!(store.claim_due(10, Duration::ZERO).await, Ok(vec![]));
+    }
+
+    #[tokio::test]
+    async fn failed_email_should_be_retried_until_dead_lettered() {
+        let mut store = HashMapEmailOutboxStore::default();
+        let key = EmailIdempotencyKey::new(&["two-fa", "attempt", "123456"]);
+        store
+            .enqueue(&key, &get_email(), &get_message())
+            .await
+            .unwrap();
+        let id = store.claim_due(10, Duration::ZERO).await.unwrap()[0].id;
+
+        store
+            .mark_failed(&id, "451 Try again later", Some(Duration::ZERO))
+            .await
+            .unwrap();
+
+        let stuck = store.get_stuck().await.unwrap();
+        assert_eq!(stuck.len(), 1);
+        assert_eq!(stuck[0].status, OutboxEmailStatus::Pending);
+        assert_eq!(stuck[0].attempts, 1);
+        assert_eq!(stuck[0].last_error.as_deref(), Some("451 Try again later"));
+
+        let claimed = store.claim_due(10, Duration::ZERO).await.unwrap();
+        assert_eq!(claimed.len(), 1);
+
+        store
+            .mark_failed(&id, "550 Mailbox unavailable", None)
+            .await
+            .unwrap();
+
+        let stuck = store.get_stuck().await.unwrap();
+        assert_eq!(stuck[0].status, OutboxEmailStatus::Dead);
+        assert_eq!(stuck[0].attempts, 2);
+        assert_eq!(store.claim_due(10, Duration::ZERO).await, Ok(vec![]));
+        assert_eq!(store.count_pending().await, Ok(0));
+    }
+
+    #[tokio::test]
+    async fn unknown_email_should_not_be_found() {
+        let mut store = HashMapEmailOutboxStore::default();
+
+        assert_eq!(
+            store.mark_sent(&Uuid::new_v4()).await,
+            Err(EmailOutboxStoreError::EmailNotFound)
+        );
+    }
+}
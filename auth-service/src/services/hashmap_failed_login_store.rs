@@ -0,0 +1,93 @@
+use std::collections::HashMap;
+
+use chrono::Utc;
+
+use crate::{
+    domain::{FailedLoginKey, FailedLoginStore, FailedLoginStoreError},
+    utils::auth::FAILED_LOGIN_TTL_SECONDS,
+};
+
+#[derive(Default)]
+pub struct HashMapFailedLoginStore {
+    // key -> (number of failures, unix timestamp they are forgotten at)
+    failures: HashMap<FailedLoginKey, (u32, i64)>,
+    // key -> unix timestamp the lock ends at
+    locks: HashMap<FailedLoginKey, i64>,
+}
+
+#[async_trait::async_trait]
+impl FailedLoginStore for HashMapFailedLoginStore {
+    async fn add_failure(&mut self, key: &FailedLoginKey) -> Result<u32, FailedLoginStoreError> {
+        let now = Utc::now().timestamp();
+        let (count, expires_at) = self.failures.entry(key.clone()).or_insert((0, now));
+
+        if *expires_at <= now {
+            *count = 0;
+        }
+        *count += 1;
+        *expires_at = now + FAILED_LOGIN_TTL_SECONDS;
+
+        Ok(*count)
+    }
+
+    async fn clear_failures(&mut self, key: &FailedLoginKey) -> Result<(), FailedLoginStoreError> {
+        self.failures.remove(key);
+        Ok(())
+    }
+
+    async fn lock(
+        &mut self,
+        key: &FailedLoginKey,
+        until: i64,
+    ) -> Result<(), FailedLoginStoreError> {
+        self.locks.insert(key.clone(), until);
+        Ok(())
+    }
+
+    async fn get_lock(&self, key: &FailedLoginKey) -> Result<Option<i64>, FailedLoginStoreError> {
+        Ok(self
+            .locks
+            .get(key)
+            .copied()
+            .filter(|until| *until > Utc::now().timestamp()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::Email;
+
+    fn get_key() -> FailedLoginKey {
+        FailedLoginKey::email(&Email::parse("user@example.com".to_owned()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn failures_should_be_counted_until_cleared() {
+        let mut store = HashMapFailedLoginStore::default();
+        let other_key = FailedLoginKey::ip("127.0.0.1".parse().unwrap());
+
+        assert_eq!(store.add_failure(&get_key()).await, Ok(1));
+        assert_eq!(store.add_failure(&get_key()).await, Ok(2));
+        assert_eq!(store.add_failure(&other_key).await, Ok(1));
+
+        store.clear_failures(&get_key()).await.unwrap();
+
+        assert_eq!(store.add_failure(&get_key()).await, Ok(1));
+        assert_eq!(store.add_failure(&other_key).await, Ok(2));
+    }
+
+    #[tokio::test]
+    async fn lock_should_end_at_given_time() {
+        let mut store = HashMapFailedLoginStore::default();
+        let now = Utc::now().timestamp();
+
+        assert_eq!(store.get_lock(&get_key()).await, Ok(None));
+
+        store.lock(&get_key(), now + 60).await.unwrap();
+        assert_eq!(store.get_lock(&get_key()).await, Ok(Some(now + 60)));
+
+        store.lock(&get_key(), now - 1).await.unwrap();
+        assert_eq!(store.get_lock(&get_key()).await, Ok(None));
+    }
+}
@@ -0,0 +1,60 @@
+use chrono::Utc;
+use tokio::sync::Mutex;
+
+use crate::domain::{JwtKeyStore, JwtKeyStoreError, StoredJwtKey};
+
+#[derive(Default)]
+pub struct HashMapJwtKeyStore {
+    // most recently rotated in first
+    keys: Mutex<Vec<StoredJwtKey>>,
+}
+
+#[async_trait::async_trait]
+impl JwtKeyStore for HashMapJwtKeyStore {
+    async fn rotate_key(
+        &self,
+        key: &StoredJwtKey,
+        previous: &StoredJwtKey,
+    ) -> Result<(), JwtKeyStoreError> {
+        let now = Utc::now().timestamp();
+        let mut keys = self.keys.lock().await;
+
+        keys.retain(|stored| stored.retire_at.is_none_or(|retire_at| now < retire_at));
+        keys.retain(|stored| stored.kid != key.kid);
+
+        // Keys rotated in by others since the caller last loaded the ring are retired the same way
+        for stored in keys.iter_mut() {
+            if stored.retire_at.is_none() {
+                stored.retire_at = previous.retire_at;
+            }
+        }
+
+        match keys.iter_mut().find(|stored| stored.kid == previous.kid) {
+            Some(stored) => stored.retire_at = stored.retire_at.min(previous.retire_at),
+            None => keys.push(previous.clone()),
+        }
+
+        keys.insert(
+            0,
+            StoredJwtKey {
+                retire_at: None,
+                ..key.clone()
+            },
+        );
+
+        Ok(())
+    }
+
+    async fn get_keys(&self) -> Result<Vec<StoredJwtKey>, JwtKeyStoreError> {
+        let now = Utc::now().timestamp();
+
+        Ok(self
+            .keys
+            .lock()
+            .await
+            .iter()
+            .filter(|stored| stored.retire_at.is_none_or(|retire_at| now < retire_at))
+            .cloned()
+            .collect())
+    }
+}
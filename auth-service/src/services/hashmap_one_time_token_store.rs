@@ -0,0 +1,98 @@
+use std::{collections::HashMap, time::Duration};
+
+use chrono::Utc;
+
+use crate::{
+    domain::{Email, OneTimeToken, OneTimeTokenPurpose, OneTimeTokenStore, OneTimeTokenStoreError},
+    utils::auth::one_time_token_ttl,
+};
+
+pub struct HashMapOneTimeTokenStore {
+    // token hash -> (email, unix timestamp the token expires at)
+    pub tokens: HashMap<String, (Email, i64)>,
+    ttl: Duration,
+}
+
+impl HashMapOneTimeTokenStore {
+    pub fn new(purpose: OneTimeTokenPurpose) -> Self {
+        Self::with_ttl(one_time_token_ttl(purpose))
+    }
+
+    pub fn with_ttl(ttl: Duration) -> Self {
+        Self {
+            tokens: HashMap::new(),
+            ttl,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl OneTimeTokenStore for HashMapOneTimeTokenStore {
+    async fn add_token(
+        &mut self,
+        email: Email,
+        token: &OneTimeToken,
+    ) -> Result<(), OneTimeTokenStoreError> {
+        let expires_at = Utc::now().timestamp() + self.ttl.as_secs() as i64;
+        self.tokens.insert(token.hash(), (email, expires_at));
+        Ok(())
+    }
+
+    async fn consume_token(
+        &mut self,
+        token: &OneTimeToken,
+    ) -> Result<Email, OneTimeTokenStoreError> {
+        match self.tokens.remove(&token.hash()) {
+            Some((email, expires_at)) if expires_at > Utc::now().timestamp() => Ok(email),
+            _ => Err(OneTimeTokenStoreError::TokenNotFound),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn consume_token_should_return_email_once() {
+        let mut store = HashMapOneTimeTokenStore::new(OneTimeTokenPurpose::PasswordReset);
+        let email = Email::parse("user@example.com".to_owned()).unwrap();
+        let token = OneTimeToken::default();
+
+        store.add_token(email.clone(), &token).await.unwrap();
+
+        assert_eq!(store.consume_token(&token).await, Ok(email));
+        assert_eq!(
+            store.consume_token(&token).await,
+            Err(OneTimeTokenStoreError::TokenNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn add_token_should_only_store_hash() {
+        let mut store = HashMapOneTimeTokenStore::new(OneTimeTokenPurpose::EmailVerification);
+        let email = Email::parse("user@example.com".to_owned()).unwrap();
+        let token = OneTimeToken::default();
+
+        store.add_token(email, &token).await.unwrap();
+
+        assert!(store.tokens.contains_key(&token.hash()));
+        assert!(!store.tokens.contains_key(token.as_ref()));
+    }
+
+    #[tokio::test]
+    async fn consume_token_should_reject_expired_token() {
+        let mut store = HashMapOneTimeTokenStore::new(OneTimeTokenPurpose::PasswordReset);
+        let email = Email::parse("user@example.com".to_owned()).unwrap();
+        let token = OneTimeToken::default();
+
+        store
+            .tokens
+            .insert(token.hash(), (email, Utc::now().timestamp() - 1));
+
+        assert_eq!(
+            store.consume_token(&token).await,
+            Err(OneTimeTokenStoreError::TokenNotFound)
+        );
+    }
+}
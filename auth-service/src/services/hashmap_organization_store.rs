@@ -0,0 +1,233 @@
+use std::collections::HashMap;
+
+use chrono::Utc;
+
+use crate::{
+    domain::{
+        Email, Invitation, InvitationToken, Membership, OrgRole, Organization, OrganizationId,
+        OrganizationName, OrganizationStore, OrganizationStoreError,
+    },
+    utils::auth::INVITATION_TOKEN_TTL_SECONDS,
+};
+
+#[derive(Default)]
+pub struct HashMapOrganizationStore {
+    organizations: HashMap<OrganizationId, Organization>,
+    // email -> the organizations the user belongs to, in the order they joined
+    memberships: HashMap<Email, Vec<(OrganizationId, OrgRole)>>,
+    // token hash -> (invitation, unix timestamp the invitation expires at)
+    invitations: HashMap<String, (Invitation, i64)>,
+}
+
+impl HashMapOrganizationStore {
+    fn membership(
+        &self,
+        organization_id: &OrganizationId,
+        role: OrgRole,
+    ) -> Result<Membership, OrganizationStoreError> {
+        let organization = self
+            .organizations
+            .get(organization_id)
+            .ok_or(OrganizationStoreError::OrganizationNotFound)?;
+
+        Ok(Membership {
+            organization: organization.clone(),
+            role,
+        })
+    }
+
+    fn role(&self, email: &Email, organization_id: &OrganizationId) -> Option<OrgRole> {
+        self.memberships
+            .get(email)?
+            .iter()
+            .find(|(id, _)| id == organization_id)
+            .map(|(_, role)| *role)
+    }
+}
+
+#[async_trait::async_trait]
+impl OrganizationStore for HashMapOrganizationStore {
+    async fn create_organization(
+        &mut self,
+        name: OrganizationName,
+        owner: &Email,
+    ) -> Result<Organization, OrganizationStoreError> {
+        let organization = Organization {
+            id: OrganizationId::default(),
+            name,
+        };
+
+        self.organizations
+            .insert(organization.id, organization.clone());
+        self.memberships
+            .entry(owner.clone())
+            .or_default()
+            .push((organization.id, OrgRole::Owner));
+
+        Ok(organization)
+    }
+
+    async fn get_memberships(
+        &self,
+        email: &Email,
+    ) -> Result<Vec<Membership>, OrganizationStoreError> {
+        self.memberships
+            .get(email)
+            .into_iter()
+            .flatten()
+            .map(|(id, role)| self.membership(id, *role))
+            .collect()
+    }
+
+    async fn get_membership(
+        &self,
+        email: &Email,
+        organization_id: &OrganizationId,
+    ) -> Result<Membership, OrganizationStoreError> {
+        let role = self
+            .role(email, organization_id)
+            .ok_or(OrganizationStoreError::MembershipNotFound)?;
+
+        self.membership(organization_id, role)
+    }
+
+    async fn add_invitation(
+        &mut self,
+        token: &InvitationToken,
+        invitation: Invitation,
+    ) -> Result<(), OrganizationStoreError> {
+        if !self.organizations.contains_key(&invitation.organization_id) {
+            return Err(OrganizationStoreError::OrganizationNotFound);
+        }
+
+        let expires_at = Utc::now().timestamp() + INVITATION_TOKEN_TTL_SECONDS;
+        self.invitations
+            .insert(token.hash(), (invitation, expires_at));
+        Ok(())
+    }
+
+    async fn accept_invitation(
+        &mut self,
+        token: &InvitationToken,
+        email: &Email,
+    ) -> Result<Membership, OrganizationStoreError> {
+        let invitation = match self.invitations.get(&token.hash()) {
+            Some((invitation, expires_at))
+                if &invitation.email == email && *expires_at > Utc::now().timestamp() =>
+            {
+                invitation.clone()
+            }
+            _ => return Err(OrganizationStoreError::InvitationNotFound),
+        };
+
+        if self.role(email, &invitation.organization_id).is_some() {
+            return Err(OrganizationStoreError::AlreadyMember);
+        }
+
+        let membership = self.membership(&invitation.organization_id, invitation.role)?;
+
+        self.invitations.remove(&token.hash());
+        self.memberships
+            .entry(email.clone())
+            .or_default()
+            .push((invitation.organization_id, invitation.role));
+
+        Ok(membership)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn owner() -> Email {
+        Email::parse("owner@example.com".to_owned()).unwrap()
+    }
+
+    fn invitee() -> Email {
+        Email::parse("invitee@example.com".to_owned()).unwrap()
+    }
+
+    async fn create_organization(store: &mut HashMapOrganizationStore) -> Organization {
+        store
+            .create_organization(
+                OrganizationName::parse("Acme".to_owned()).unwrap(),
+                &owner(),
+            )
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn creator_should_become_owner() {
+        let mut store = HashMapOrganizationStore::default();
+        let organization = create_organization(&mut store).await;
+
+        let memberships = store.get_memberships(&owner()).await.unwrap();
+        assert_eq!(
+            memberships,
+            vec![Membership {
+                organization: organization.clone(),
+                role: OrgRole::Owner,
+            }]
+        );
+
+        assert_eq!(store.get_memberships(&invitee()).await, Ok(vec![]));
+        assert_eq!(
+            store.get_membership(&invitee(), &organization.id).await,
+            Err(OrganizationStoreError::MembershipNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn invitation_should_be_accepted_once_by_invitee() {
+        let mut store = HashMapOrganizationStore::default();
+        let organization = create_organization(&mut store).await;
+        let token = InvitationToken::default();
+
+        store
+            .add_invitation(
+                &token,
+                Invitation {
+                    organization_id: organization.id,
+                    email: invitee(),
+                    role: OrgRole::Member,
+                },
+            )
+            .await
+            .unwrap();
+
+        // sent to someone else, the invitation stays valid for the invitee
+        assert_eq!(
+            store.accept_invitation(&token, &owner()).await,
+            Err(OrganizationStoreError::InvitationNotFound)
+        );
+
+        let membership = store.accept_invitation(&token, &invitee()).await.unwrap();
+        assert_eq!(membership.organization, organization);
+        assert_eq!(membership.role, OrgRole::Member);
+
+        assert_eq!(
+            store.accept_invitation(&token, &invitee()).await,
+            Err(OrganizationStoreError::InvitationNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn invitation_to_unknown_organization_should_fail() {
+        let mut store = HashMapOrganizationStore::default();
+
+        let result = store
+            .add_invitation(
+                &InvitationToken::default(),
+                Invitation {
+                    organization_id: OrganizationId::default(),
+                    email: invitee(),
+                    role: OrgRole::Member,
+                },
+            )
+            .await;
+
+        assert_eq!(result, Err(OrganizationStoreError::OrganizationNotFound));
+    }
+}
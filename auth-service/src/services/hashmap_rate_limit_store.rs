@@ -0,0 +1,89 @@
+use std::collections::HashMap;
+
+use chrono::Utc;
+use tokio::sync::Mutex;
+
+use crate::domain::{
+    RateLimit, RateLimitDecision, RateLimitKey, RateLimitStore, RateLimitStoreError, TokenBucket,
+};
+
+// Buckets are only swept once there are this many, a full bucket carries no information
+const PRUNE_THRESHOLD: usize = 10_000;
+
+#[derive(Default)]
+pub struct HashMapRateLimitStore {
+    // key -> (bucket, unix timestamp in milliseconds the bucket is full again at)
+    buckets: Mutex<HashMap<RateLimitKey, (TokenBucket, i64)>>,
+}
+
+#[async_trait::async_trait]
+impl RateLimitStore for HashMapRateLimitStore {
+    async fn take_token(
+        &self,
+        key: &RateLimitKey,
+        limit: &RateLimit,
+    ) -> Result<RateLimitDecision, RateLimitStoreError> {
+        let now = Utc::now().timestamp_millis();
+        let mut buckets = self.buckets.lock().await;
+
+        if buckets.len() >= PRUNE_THRESHOLD {
+            buckets.retain(|_, (_, full_at)| *full_at > now);
+        }
+
+        let (bucket, full_at) = buckets
+            .entry(key.clone())
+            .or_insert_with(|| (TokenBucket::full(limit, now), now));
+
+        let decision = bucket.take(limit, now);
+        *full_at = bucket.full_at(limit);
+
+        Ok(decision)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{IpAddr, Ipv4Addr};
+
+    const LIMIT: RateLimit = RateLimit {
+        capacity: 3,
+        period_seconds: 60,
+    };
+
+    #[tokio::test]
+    async fn requests_should_be_refused_once_the_bucket_is_empty() {
+        let store = HashMapRateLimitStore::default();
+        let key = RateLimitKey::ip("login", IpAddr::V4(Ipv4Addr::LOCALHOST));
+
+        for remaining in (0..LIMIT.capacity).rev() {
+            let decision = store.take_token(&key, &LIMIT).await.unwrap();
+            assert!(decision.allowed);
+            assert_eq!(decision.remaining, remaining);
+        }
+
+        let decision = store.take_token(&key, &LIMIT).await.unwrap();
+        assert!(!decision.allowed);
+        assert_eq!(decision.limit, LIMIT.capacity);
+        assert!(decision.retry_after_seconds > 0);
+    }
+
+    #[tokio::test]
+    async fn keys_should_have_separate_buckets() {
+        let store = HashMapRateLimitStore::default();
+        let ip = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
+        for _ in 0..LIMIT.capacity {
+            store
+                .take_token(&RateLimitKey::ip("login", ip), &LIMIT)
+                .await
+                .unwrap();
+        }
+
+        let decision = store
+            .take_token(&RateLimitKey::ip("signup", ip), &LIMIT)
+            .await
+            .unwrap();
+        assert!(decision.allowed);
+    }
+}
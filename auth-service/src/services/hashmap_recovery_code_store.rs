@@ -0,0 +1,93 @@
+use std::collections::HashMap;
+
+use tokio::sync::Mutex;
+
+use crate::domain::{Email, RecoveryCode, RecoveryCodeStore, RecoveryCodeStoreError};
+
+// The map is locked only for the lookup or update itself
+#[derive(Default)]
+pub struct HashMapRecoveryCodeStore {
+    codes: Mutex<HashMap<Email, Vec<RecoveryCode>>>,
+}
+
+#[async_trait::async_trait]
+impl RecoveryCodeStore for HashMapRecoveryCodeStore {
+    async fn replace_codes(
+        &self,
+        email: &Email,
+        codes: Vec<RecoveryCode>,
+    ) -> Result<(), RecoveryCodeStoreError> {
+        self.codes.lock().await.insert(email.clone(), codes);
+        Ok(())
+    }
+
+    async fn use_code(
+        &self,
+        email: &Email,
+        code: &RecoveryCode,
+    ) -> Result<(), RecoveryCodeStoreError> {
+        let mut users_codes = self.codes.lock().await;
+        let codes = users_codes
+            .get_mut(email)
+            .ok_or(RecoveryCodeStoreError::CodeNotFound)?;
+
+        let position = codes
+            .iter()
+            .position(|c| c == code)
+            .ok_or(RecoveryCodeStoreError::CodeNotFound)?;
+
+        codes.remove(position);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_email() -> Email {
+        Email::parse("user@example.com".to_owned()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn use_code_should_accept_each_code_once() {
+        let store = HashMapRecoveryCodeStore::default();
+        let code = RecoveryCode::default();
+
+        store
+            .replace_codes(&get_email(), vec![code.clone(), RecoveryCode::default()])
+            .await
+            .unwrap();
+
+        // users may type the code without the dash and in upper case
+        let typed = RecoveryCode::parse(code.as_ref().replace('-', "").to_uppercase()).unwrap();
+
+        assert!(store.use_code(&get_email(), &typed).await.is_ok());
+        assert_eq!(
+            store.use_code(&get_email(), &code).await,
+            Err(RecoveryCodeStoreError::CodeNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn replace_codes_should_invalidate_old_codes() {
+        let store = HashMapRecoveryCodeStore::default();
+        let old_code = RecoveryCode::default();
+        let new_code = RecoveryCode::default();
+
+        store
+            .replace_codes(&get_email(), vec![old_code.clone()])
+            .await
+            .unwrap();
+        store
+            .replace_codes(&get_email(), vec![new_code.clone()])
+            .await
+            .unwrap();
+
+        assert_eq!(
+            store.use_code(&get_email(), &old_code).await,
+            Err(RecoveryCodeStoreError::CodeNotFound)
+        );
+        assert!(store.use_code(&get_email(), &new_code).await.is_ok());
+    }
+}
@@ -0,0 +1,235 @@
+use std::collections::{HashMap, HashSet};
+
+use uuid::Uuid;
+
+use crate::domain::{
+    Email, RefreshSession, RefreshToken, RefreshTokenStore, RefreshTokenStoreError,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RefreshTokenRecord {
+    pub session: RefreshSession,
+    pub family_id: String,
+    pub used: bool,
+}
+
+#[derive(Default)]
+pub struct HashMapRefreshTokenStore {
+    // every token ever issued, used tokens are kept around so reuse can be detected
+    pub tokens: HashMap<RefreshToken, RefreshTokenRecord>,
+    // families that have not been revoked
+    pub families: HashSet<String>,
+}
+
+#[async_trait::async_trait]
+impl RefreshTokenStore for HashMapRefreshTokenStore {
+    async fn add_token(
+        &mut self,
+        session: RefreshSession,
+        token: RefreshToken,
+    ) -> Result<(), RefreshTokenStoreError> {
+        // a freshly issued token always starts a new family
+        let family_id = Uuid::new_v4().to_string();
+
+        self.families.insert(family_id.clone());
+        self.tokens.insert(
+            token,
+            RefreshTokenRecord {
+                session,
+                family_id,
+                used: false,
+            },
+        );
+        Ok(())
+    }
+
+    async fn rotate_token(
+        &mut self,
+        token: &RefreshToken,
+    ) -> Result<(RefreshSession, RefreshToken), RefreshTokenStoreError> {
+        let record = match self.tokens.get_mut(token) {
+            Some(record) => record,
+            None => return Err(RefreshTokenStoreError::TokenNotFound),
+        };
+
+        if !self.families.contains(&record.family_id) {
+            return Err(RefreshTokenStoreError::TokenNotFound);
+        }
+
+        // the token was already exchanged once, someone is replaying it so revoke the whole family
+        if record.used {
+            self.families.remove(&record.family_id);
+            return Err(RefreshTokenStoreError::TokenReused);
+        }
+
+        record.used = true;
+
+        let session = record.session.clone();
+        let new_record = RefreshTokenRecord {
+            session: session.clone(),
+            family_id: record.family_id.clone(),
+            used: false,
+        };
+
+        let new_token = RefreshToken::default();
+        self.tokens.insert(new_token.clone(), new_record);
+
+        Ok((session, new_token))
+    }
+
+    async fn revoke_token(&mut self, token: &RefreshToken) -> Result<(), RefreshTokenStoreError> {
+        if let Some(record) = self.tokens.get(token) {
+            self.families.remove(&record.family_id);
+        }
+        Ok(())
+    }
+
+    async fn revoke_user_tokens(&mut self, email: &Email) -> Result<(), RefreshTokenStoreError> {
+        for record in self.tokens.values() {
+            if record.session.email == *email {
+                self.families.remove(&record.family_id);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::OrganizationId;
+
+    use super::*;
+
+    fn get_email() -> Email {
+        Email::parse("user@example.com".to_owned()).unwrap()
+    }
+
+    fn get_session(email: Email) -> RefreshSession {
+        RefreshSession {
+            email,
+            organization_id: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn rotate_token_should_return_new_token_for_same_user() {
+        let mut store = HashMapRefreshTokenStore::default();
+        let token = RefreshToken::default();
+
+        store
+            .add_token(get_session(get_email()), token.clone())
+            .await
+            .unwrap();
+
+        let (session, new_token) = store.rotate_token(&token).await.unwrap();
+        assert_eq!(session.email, get_email());
+        assert_ne!(new_token, token);
+    }
+
+    #[tokio::test]
+    async fn rotate_token_should_keep_organization_of_session() {
+        let mut store = HashMapRefreshTokenStore::default();
+        let token = RefreshToken::default();
+        let session = RefreshSession {
+            email: get_email(),
+            organization_id: Some(OrganizationId::default()),
+        };
+
+        store
+            .add_token(session.clone(), token.clone())
+            .await
+            .unwrap();
+
+        let (rotated_session, new_token) = store.rotate_token(&token).await.unwrap();
+        assert_eq!(rotated_session, session);
+
+        // the organization survives every later rotation too
+        let (rotated_session, _) = store.rotate_token(&new_token).await.unwrap();
+        assert_eq!(rotated_session, session);
+    }
+
+    #[tokio::test]
+    async fn rotate_token_should_return_error_for_unknown_token() {
+        let mut store = HashMapRefreshTokenStore::default();
+
+        assert_eq!(
+            store.rotate_token(&RefreshToken::default()).await,
+            Err(RefreshTokenStoreError::TokenNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn reusing_rotated_token_should_revoke_family() {
+        let mut store = HashMapRefreshTokenStore::default();
+        let token = RefreshToken::default();
+
+        store
+            .add_token(get_session(get_email()), token.clone())
+            .await
+            .unwrap();
+        let (_, new_token) = store.rotate_token(&token).await.unwrap();
+
+        // replaying the first token is detected
+        assert_eq!(
+            store.rotate_token(&token).await,
+            Err(RefreshTokenStoreError::TokenReused)
+        );
+
+        // and the legitimate successor no longer works either
+        assert_eq!(
+            store.rotate_token(&new_token).await,
+            Err(RefreshTokenStoreError::TokenNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn revoke_token_should_invalidate_family() {
+        let mut store = HashMapRefreshTokenStore::default();
+        let token = RefreshToken::default();
+
+        store
+            .add_token(get_session(get_email()), token.clone())
+            .await
+            .unwrap();
+        store.revoke_token(&token).await.unwrap();
+
+        assert_eq!(
+            store.rotate_token(&token).await,
+            Err(RefreshTokenStoreError::TokenNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn revoke_user_tokens_should_invalidate_every_family_of_user() {
+        let mut store = HashMapRefreshTokenStore::default();
+        let first_token = RefreshToken::default();
+        let second_token = RefreshToken::default();
+        let other_token = RefreshToken::default();
+        let other_email = Email::parse("other@example.com".to_owned()).unwrap();
+
+        store
+            .add_token(get_session(get_email()), first_token.clone())
+            .await
+            .unwrap();
+        store
+            .add_token(get_session(get_email()), second_token.clone())
+            .await
+            .unwrap();
+        store
+            .add_token(get_session(other_email), other_token.clone())
+            .await
+            .unwrap();
+
+        store.revoke_user_tokens(&get_email()).await.unwrap();
+
+        assert_eq!(
+            store.rotate_token(&first_token).await,
+            Err(RefreshTokenStoreError::TokenNotFound)
+        );
+        assert_eq!(
+            store.rotate_token(&second_token).await,
+            Err(RefreshTokenStoreError::TokenNotFound)
+        );
+        assert!(store.rotate_token(&other_token).await.is_ok());
+    }
+}
@@ -0,0 +1,138 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use crate::domain::{
+    Email, Permission, Role, RoleDefinition, RoleStore, RoleStoreError, UserAccess,
+};
+
+pub struct HashMapRoleStore {
+    roles: BTreeMap<Role, BTreeSet<Permission>>,
+    user_roles: HashMap<Email, BTreeSet<Role>>,
+}
+
+// Starts with the same roles the migrations create
+impl Default for HashMapRoleStore {
+    fn default() -> Self {
+        let admin = Role::parse(Role::ADMIN.to_owned()).unwrap();
+        let admin_permissions = [
+            Permission::ROLES_READ,
+            Permission::ROLES_MANAGE,
+            Permission::JWT_KEYS_ROTATE,
+            Permission::EMAIL_OUTBOX_READ,
+        ]
+        .into_iter()
+        .map(|permission| Permission::parse(permission.to_owned()).unwrap())
+        .collect();
+
+        Self {
+            roles: BTreeMap::from([(admin, admin_permissions)]),
+            user_roles: HashMap::new(),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl RoleStore for HashMapRoleStore {
+    async fn get_user_access(&self, email: &Email) -> Result<UserAccess, RoleStoreError> {
+        let Some(roles) = self.user_roles.get(email) else {
+            return Ok(UserAccess::default());
+        };
+
+        let permissions: BTreeSet<Permission> = roles
+            .iter()
+            .filter_map(|role| self.roles.get(role))
+            .flatten()
+            .cloned()
+            .collect();
+
+        Ok(UserAccess {
+            roles: roles.iter().cloned().collect(),
+            permissions: permissions.into_iter().collect(),
+        })
+    }
+
+    async fn get_roles(&self) -> Result<Vec<RoleDefinition>, RoleStoreError> {
+        Ok(self
+            .roles
+            .iter()
+            .map(|(role, permissions)| RoleDefinition {
+                role: role.clone(),
+                permissions: permissions.iter().cloned().collect(),
+            })
+            .collect())
+    }
+
+    async fn assign_role(&mut self, email: &Email, role: &Role) -> Result<(), RoleStoreError> {
+        if !self.roles.contains_key(role) {
+            return Err(RoleStoreError::RoleNotFound);
+        }
+
+        self.user_roles
+            .entry(email.clone())
+            .or_default()
+            .insert(role.clone());
+        Ok(())
+    }
+
+    async fn revoke_role(&mut self, email: &Email, role: &Role) -> Result<(), RoleStoreError> {
+        if !self.roles.contains_key(role) {
+            return Err(RoleStoreError::RoleNotFound);
+        }
+
+        if let Some(roles) = self.user_roles.get_mut(email) {
+            roles.remove(role);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_email() -> Email {
+        Email::parse("user@example.com".to_owned()).unwrap()
+    }
+
+    fn admin() -> Role {
+        Role::parse(Role::ADMIN.to_owned()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn assigned_role_should_grant_its_permissions() {
+        let mut store = HashMapRoleStore::default();
+
+        assert_eq!(
+            store.get_user_access(&get_email()).await,
+            Ok(UserAccess::default())
+        );
+
+        store.assign_role(&get_email(), &admin()).await.unwrap();
+        // assigning twice is not an error
+        store.assign_role(&get_email(), &admin()).await.unwrap();
+
+        let access = store.get_user_access(&get_email()).await.unwrap();
+        assert_eq!(access.roles, vec![admin()]);
+        assert!(access
+            .permissions
+            .iter()
+            .any(|permission| permission.as_ref() == Permission::ROLES_MANAGE));
+
+        store.revoke_role(&get_email(), &admin()).await.unwrap();
+
+        assert_eq!(
+            store.get_user_access(&get_email()).await,
+            Ok(UserAccess::default())
+        );
+    }
+
+    #[tokio::test]
+    async fn unknown_role_cannot_be_assigned() {
+        let mut store = HashMapRoleStore::default();
+        let role = Role::parse("superuser".to_owned()).unwrap();
+
+        assert_eq!(
+            store.assign_role(&get_email(), &role).await,
+            Err(RoleStoreError::RoleNotFound)
+        );
+    }
+}
@@ -0,0 +1,129 @@
+use std::collections::HashMap;
+
+use crate::domain::{Email, TotpEnrollment, TotpSecret, TotpSecretStore, TotpSecretStoreError};
+
+#[derive(Default)]
+pub struct HashMapTotpSecretStore {
+    enrollments: HashMap<Email, TotpEnrollment>,
+    last_used_time_steps: HashMap<Email, u64>,
+}
+
+#[async_trait::async_trait]
+impl TotpSecretStore for HashMapTotpSecretStore {
+    async fn add_secret(
+        &mut self,
+        email: Email,
+        secret: TotpSecret,
+    ) -> Result<(), TotpSecretStoreError> {
+        self.last_used_time_steps.remove(&email);
+        self.enrollments.insert(
+            email,
+            TotpEnrollment {
+                secret,
+                confirmed: false,
+            },
+        );
+        Ok(())
+    }
+
+    async fn get_secret(&self, email: &Email) -> Result<TotpEnrollment, TotpSecretStoreError> {
+        self.enrollments
+            .get(email)
+            .cloned()
+            .ok_or(TotpSecretStoreError::SecretNotFound)
+    }
+
+    async fn confirm_secret(&mut self, email: &Email) -> Result<(), TotpSecretStoreError> {
+        match self.enrollments.get_mut(email) {
+            Some(enrollment) => {
+                enrollment.confirmed = true;
+                Ok(())
+            }
+            None => Err(TotpSecretStoreError::SecretNotFound),
+        }
+    }
+
+    async fn use_time_step(
+        &mut self,
+        email: &Email,
+        time_step: u64,
+    ) -> Result<(), TotpSecretStoreError> {
+        if !self.enrollments.contains_key(email) {
+            return Err(TotpSecretStoreError::SecretNotFound);
+        }
+
+        if let Some(last_used_time_step) = self.last_used_time_steps.get(email) {
+            if time_step <= *last_used_time_step {
+                return Err(TotpSecretStoreError::TimeStepAlreadyUsed);
+            }
+        }
+
+        self.last_used_time_steps.insert(email.clone(), time_step);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_email() -> Email {
+        Email::parse("user@example.com".to_owned()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn add_secret_should_store_pending_secret() {
+        let mut store = HashMapTotpSecretStore::default();
+        let secret = TotpSecret::default();
+
+        store.add_secret(get_email(), secret.clone()).await.unwrap();
+
+        assert_eq!(
+            store.get_secret(&get_email()).await,
+            Ok(TotpEnrollment {
+                secret,
+                confirmed: false
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn confirm_secret_should_mark_secret_confirmed() {
+        let mut store = HashMapTotpSecretStore::default();
+
+        assert_eq!(
+            store.confirm_secret(&get_email()).await,
+            Err(TotpSecretStoreError::SecretNotFound)
+        );
+
+        store
+            .add_secret(get_email(), TotpSecret::default())
+            .await
+            .unwrap();
+        store.confirm_secret(&get_email()).await.unwrap();
+
+        assert!(store.get_secret(&get_email()).await.unwrap().confirmed);
+    }
+
+    #[tokio::test]
+    async fn use_time_step_should_reject_replayed_and_older_steps() {
+        let mut store = HashMapTotpSecretStore::default();
+
+        store
+            .add_secret(get_email(), TotpSecret::default())
+            .await
+            .unwrap();
+
+        store.use_time_step(&get_email(), 100).await.unwrap();
+
+        assert_eq!(
+            store.use_time_step(&get_email(), 100).await,
+            Err(TotpSecretStoreError::TimeStepAlreadyUsed)
+        );
+        assert_eq!(
+            store.use_time_step(&get_email(), 99).await,
+            Err(TotpSecretStoreError::TimeStepAlreadyUsed)
+        );
+        assert!(store.use_time_step(&get_email(), 101).await.is_ok());
+    }
+}
@@ -1,68 +1,276 @@
-use crate::domain::{Email, LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError};
-use std::collections::HashMap;
+use crate::{
+    domain::{
+        Email, LoginAttemptId, OrganizationId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError,
+    },
+    utils::{
+        auth::{
+            MAX_TWO_FA_ATTEMPTS, MAX_TWO_FA_RESENDS, TWO_FA_CODE_TTL_SECONDS,
+            TWO_FA_RESEND_COOLDOWN_SECONDS,
+        },
+        constants::MAX_PENDING_LOGIN_ATTEMPTS,
+    },
+};
+use chrono::Utc;
+use std::{
+    collections::{HashMap, VecDeque},
+    time::{Duration, Instant},
+};
+use tokio::sync::Mutex;
 
-#[derive(Default)]
+// Both maps change together, so they share one lock. Login attempts expire after the TTL like the
+// Redis keys do, expired ones are dropped when the next one is added.
 pub struct HashMapTwoFACodeStore {
-    pub codes: HashMap<Email, (LoginAttemptId, TwoFACode)>,
+    logins: Mutex<PendingLogins>,
+    ttl: Duration,
+}
+
+impl HashMapTwoFACodeStore {
+    pub fn with_ttl(ttl: Duration) -> Self {
+        Self {
+            logins: Mutex::default(),
+            ttl,
+        }
+    }
+}
+
+impl Default for HashMapTwoFACodeStore {
+    fn default() -> Self {
+        Self::with_ttl(Duration::from_secs(TWO_FA_CODE_TTL_SECONDS as u64))
+    }
+}
+
+#[derive(Default)]
+struct PendingLogins {
+    codes: HashMap<LoginAttemptId, PendingLogin>,
+    // email -> the user's pending login attempts, oldest first
+    pending: HashMap<Email, VecDeque<LoginAttemptId>>,
+}
+
+struct PendingLogin {
+    email: Email,
+    code: TwoFACode,
+    organization_id: Option<OrganizationId>,
+    failed_attempts: u32,
+    resends: u32,
+    // unix timestamp the current code was sent at
+    sent_at: i64,
+    expires_at: Instant,
+}
+
+impl PendingLogins {
+    // The login attempt, if it was pending
+    fn remove(&mut self, login_attempt_id: &LoginAttemptId) -> Option<PendingLogin> {
+        let pending_login = self.codes.remove(login_attempt_id)?;
+
+        if let Some(pending) = self.pending.get_mut(&pending_login.email) {
+            pending.retain(|id| id != login_attempt_id);
+            if pending.is_empty() {
+                self.pending.remove(&pending_login.email);
+            }
+        }
+        Some(pending_login)
+    }
+
+    // The login attempt, unless it has expired
+    fn get_mut(&mut self, login_attempt_id: &LoginAttemptId) -> Option<&mut PendingLogin> {
+        self.codes
+            .get_mut(login_attempt_id)
+            .filter(|pending_login| pending_login.expires_at > Instant::now())
+    }
+
+    // The login attempt, unless it has expired or has no guesses left
+    fn get_guessable(&mut self, login_attempt_id: &LoginAttemptId) -> Option<&mut PendingLogin> {
+        self.get_mut(login_attempt_id)
+            .filter(|pending_login| pending_login.failed_attempts < MAX_TWO_FA_ATTEMPTS)
+    }
+
+    // Counts a wrong guess and returns how many there have been, removing the login attempt once
+    // there are too many
+    fn add_failed_attempt(
+        &mut self,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<u32, TwoFACodeStoreError> {
+        let pending_login = self
+            .get_guessable(login_attempt_id)
+            .ok_or(TwoFACodeStoreError::LoginAttemptIdNotFound)?;
+        pending_login.failed_attempts += 1;
+        let failed_attempts = pending_login.failed_attempts;
+
+        if failed_attempts >= MAX_TWO_FA_ATTEMPTS {
+            self.remove(login_attempt_id);
+        }
+
+        Ok(failed_attempts)
+    }
+
+    fn remove_expired(&mut self) {
+        let now = Instant::now();
+        let expired: Vec<LoginAttemptId> = self
+            .codes
+            .iter()
+            .filter(|(_, pending_login)| pending_login.expires_at <= now)
+            .map(|(login_attempt_id, _)| login_attempt_id.clone())
+            .collect();
+
+        for login_attempt_id in expired {
+            self.remove(&login_attempt_id);
+        }
+    }
 }
 
 #[async_trait::async_trait]
 impl TwoFACodeStore for HashMapTwoFACodeStore {
     async fn add_code(
-        &mut self,
+        &self,
         email: Email,
         login_attempt_id: LoginAttemptId,
         code: TwoFACode,
+        organization_id: Option<OrganizationId>,
     ) -> Result<(), TwoFACodeStoreError> {
-        self.codes.insert(email, (login_attempt_id, code));
+        let mut logins = self.logins.lock().await;
+        logins.remove_expired();
+        let PendingLogins { codes, pending } = &mut *logins;
+
+        let pending = pending.entry(email.clone()).or_default();
+        pending.push_back(login_attempt_id.clone());
+
+        while pending.len() > *MAX_PENDING_LOGIN_ATTEMPTS {
+            if let Some(oldest) = pending.pop_front() {
+                codes.remove(&oldest);
+            }
+        }
+
+        codes.insert(
+            login_attempt_id,
+            PendingLogin {
+                email,
+                code,
+                organization_id,
+                failed_attempts: 0,
+                resends: 0,
+                sent_at: Utc::now().timestamp(),
+                expires_at: Instant::now() + self.ttl,
+            },
+        );
         Ok(())
     }
 
-    async fn remove_code(&mut self, email: &Email) -> Result<(), TwoFACodeStoreError> {
-        self.codes.remove(email);
-        Ok(())
+    async fn remove_code(
+        &self,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<Option<OrganizationId>, TwoFACodeStoreError> {
+        let mut logins = self.logins.lock().await;
+        if logins.get_mut(login_attempt_id).is_none() {
+            return Err(TwoFACodeStoreError::LoginAttemptIdNotFound);
+        }
+
+        Ok(logins
+            .remove(login_attempt_id)
+            .and_then(|pending_login| pending_login.organization_id))
     }
 
     async fn get_code(
         &self,
-        email: &Email,
-    ) -> Result<(LoginAttemptId, TwoFACode), TwoFACodeStoreError> {
-        match self.codes.get(email) {
-            Some(value) => Ok(value.clone()),
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<(Email, TwoFACode), TwoFACodeStoreError> {
+        match self.logins.lock().await.get_mut(login_attempt_id) {
+            Some(pending_login) => Ok((pending_login.email.clone(), pending_login.code.clone())),
             None => Err(TwoFACodeStoreError::LoginAttemptIdNotFound),
         }
     }
+
+    async fn verify_code(
+        &self,
+        login_attempt_id: &LoginAttemptId,
+        code: &TwoFACode,
+    ) -> Result<(), TwoFACodeStoreError> {
+        let mut logins = self.logins.lock().await;
+        let pending_login = logins
+            .get_guessable(login_attempt_id)
+            .ok_or(TwoFACodeStoreError::LoginAttemptIdNotFound)?;
+
+        if pending_login.code == *code {
+            return Ok(());
+        }
+
+        logins.add_failed_attempt(login_attempt_id)?;
+        Err(TwoFACodeStoreError::IncorrectCode)
+    }
+
+    async fn reserve_attempt(
+        &self,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<u32, TwoFACodeStoreError> {
+        let mut logins = self.logins.lock().await;
+        let pending_login = logins
+            .get_guessable(login_attempt_id)
+            .ok_or(TwoFACodeStoreError::LoginAttemptIdNotFound)?;
+
+        pending_login.failed_attempts += 1;
+        Ok(pending_login.failed_attempts)
+    }
+
+    async fn resend_code(
+        &self,
+        login_attempt_id: &LoginAttemptId,
+        code: TwoFACode,
+    ) -> Result<(), TwoFACodeStoreError> {
+        let mut logins = self.logins.lock().await;
+        let pending_login = logins
+            .get_mut(login_attempt_id)
+            .ok_or(TwoFACodeStoreError::LoginAttemptIdNotFound)?;
+
+        if pending_login.resends >= MAX_TWO_FA_RESENDS {
+            return Err(TwoFACodeStoreError::TooManyResends);
+        }
+
+        let now = Utc::now().timestamp();
+        let cooldown_ends_at = pending_login.sent_at + TWO_FA_RESEND_COOLDOWN_SECONDS;
+        if cooldown_ends_at > now {
+            return Err(TwoFACodeStoreError::ResendCooldown(
+                (cooldown_ends_at - now) as u64,
+            ));
+        }
+
+        pending_login.resends += 1;
+        pending_login.sent_at = now;
+        pending_login.expires_at = Instant::now() + self.ttl;
+        pending_login.code = code;
+
+        Ok(())
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::domain::{Email, LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError};
+
+    fn email() -> Email {
+        Email::parse("user@example.com".to_owned()).unwrap()
+    }
 
     #[tokio::test]
     async fn add_and_get_code_should_succeed() {
-        let mut store = HashMapTwoFACodeStore::default();
+        let store = HashMapTwoFACodeStore::default();
 
-        let email = Email::parse("user@example.com".to_owned()).unwrap();
         let login_attempt_id = LoginAttemptId::default();
         let code = TwoFACode::default();
 
         store
-            .add_code(email.clone(), login_attempt_id.clone(), code.clone())
+            .add_code(email(), login_attempt_id.clone(), code.clone(), None)
             .await
             .unwrap();
 
-        let retrieved = store.get_code(&email).await.unwrap();
-        assert_eq!(retrieved.0, login_attempt_id);
+        let retrieved = store.get_code(&login_attempt_id).await.unwrap();
+        assert_eq!(retrieved.0, email());
         assert_eq!(retrieved.1, code);
     }
 
     #[tokio::test]
-    async fn get_code_should_return_error_for_missing_email() {
+    async fn get_code_should_return_error_for_unknown_login_attempt() {
         let store = HashMapTwoFACodeStore::default();
 
-        let email = Email::parse("user@example.com".to_owned()).unwrap();
-        let result = store.get_code(&email).await;
+        let result = store.get_code(&LoginAttemptId::default()).await;
 
         assert!(matches!(
             result,
@@ -72,54 +280,229 @@ mod tests {
 
     #[tokio::test]
     async fn remove_code_should_delete_existing_entry() {
-        let mut store = HashMapTwoFACodeStore::default();
+        let store = HashMapTwoFACodeStore::default();
 
-        let email = Email::parse("user@example.com".to_owned()).unwrap();
         let login_attempt_id = LoginAttemptId::default();
-        let code = TwoFACode::default();
 
         store
-            .add_code(email.clone(), login_attempt_id, code)
+            .add_code(
+                email(),
+                login_attempt_id.clone(),
+                TwoFACode::default(),
+                None,
+            )
             .await
             .unwrap();
 
         // Verify it's there
-        assert!(store.get_code(&email).await.is_ok());
+        assert!(store.get_code(&login_attempt_id).await.is_ok());
 
         // Remove it
-        store.remove_code(&email).await.unwrap();
+        store.remove_code(&login_attempt_id).await.unwrap();
 
         // Now should not be found
         assert!(matches!(
-            store.get_code(&email).await,
+            store.get_code(&login_attempt_id).await,
             Err(TwoFACodeStoreError::LoginAttemptIdNotFound)
         ));
+        assert!(store.logins.lock().await.pending.is_empty());
     }
 
     #[tokio::test]
-    async fn add_code_should_overwrite_existing_entry() {
-        let mut store = HashMapTwoFACodeStore::default();
+    async fn remove_code_should_succeed_once() {
+        let store = HashMapTwoFACodeStore::default();
+
+        let login_attempt_id = LoginAttemptId::default();
+        store
+            .add_code(
+                email(),
+                login_attempt_id.clone(),
+                TwoFACode::default(),
+                None,
+            )
+            .await
+            .unwrap();
+
+        // of two requests completing the same login attempt, only the first gets through
+        assert_eq!(store.remove_code(&login_attempt_id).await, Ok(None));
+        assert_eq!(
+            store.remove_code(&login_attempt_id).await,
+            Err(TwoFACodeStoreError::LoginAttemptIdNotFound)
+        );
+    }
 
-        let email = Email::parse("user@example.com".to_owned()).unwrap();
+    #[tokio::test]
+    async fn add_code_should_keep_concurrent_login_attempts() {
+        let store = HashMapTwoFACodeStore::default();
 
         let login_attempt_id1 = LoginAttemptId::default();
         let code1 = TwoFACode::default();
-
         store
-            .add_code(email.clone(), login_attempt_id1, code1)
+            .add_code(email(), login_attempt_id1.clone(), code1.clone(), None)
             .await
             .unwrap();
 
         let login_attempt_id2 = LoginAttemptId::default();
         let code2 = TwoFACode::default();
+        store
+            .add_code(email(), login_attempt_id2.clone(), code2.clone(), None)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            store.get_code(&login_attempt_id1).await,
+            Ok((email(), code1))
+        );
+        assert_eq!(
+            store.get_code(&login_attempt_id2).await,
+            Ok((email(), code2))
+        );
+    }
+
+    #[tokio::test]
+    async fn add_code_should_discard_the_oldest_login_attempts_beyond_the_cap() {
+        let store = HashMapTwoFACodeStore::default();
+
+        let mut login_attempt_ids = Vec::new();
+        for _ in 0..=*MAX_PENDING_LOGIN_ATTEMPTS {
+            let login_attempt_id = LoginAttemptId::default();
+            store
+                .add_code(
+                    email(),
+                    login_attempt_id.clone(),
+                    TwoFACode::default(),
+                    None,
+                )
+                .await
+                .unwrap();
+            login_attempt_ids.push(login_attempt_id);
+        }
+
+        assert_eq!(
+            store.get_code(&login_attempt_ids[0]).await,
+            Err(TwoFACodeStoreError::LoginAttemptIdNotFound)
+        );
+        for login_attempt_id in &login_attempt_ids[1..] {
+            assert!(store.get_code(login_attempt_id).await.is_ok());
+        }
+    }
+
+    #[tokio::test]
+    async fn guesses_should_be_refused_once_all_are_reserved() {
+        let store = HashMapTwoFACodeStore::default();
+
+        let login_attempt_id = LoginAttemptId::default();
+        let code = TwoFACode::default();
+        store
+            .add_code(email(), login_attempt_id.clone(), code.clone(), None)
+            .await
+            .unwrap();
+
+        for attempt in 1..=MAX_TWO_FA_ATTEMPTS {
+            assert_eq!(store.reserve_attempt(&login_attempt_id).await, Ok(attempt));
+        }
+
+        assert_eq!(
+            store.reserve_attempt(&login_attempt_id).await,
+            Err(TwoFACodeStoreError::LoginAttemptIdNotFound)
+        );
+        // not even the right code is compared any more
+        assert_eq!(
+            store.verify_code(&login_attempt_id, &code).await,
+            Err(TwoFACodeStoreError::LoginAttemptIdNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn failed_attempts_should_be_counted_per_login_attempt() {
+        let store = HashMapTwoFACodeStore::default();
+
+        let login_attempt_id1 = LoginAttemptId::default();
+        let login_attempt_id2 = LoginAttemptId::default();
+        for login_attempt_id in [&login_attempt_id1, &login_attempt_id2] {
+            store
+                .add_code(
+                    email(),
+                    login_attempt_id.clone(),
+                    TwoFACode::default(),
+                    None,
+                )
+                .await
+                .unwrap();
+        }
+
+        store.reserve_attempt(&login_attempt_id1).await.unwrap();
+
+        assert_eq!(store.reserve_attempt(&login_attempt_id2).await, Ok(1));
+    }
+
+    #[tokio::test]
+    async fn resend_code_should_respect_cooldown_and_limit() {
+        let store = HashMapTwoFACodeStore::default();
+
+        let login_attempt_id = LoginAttemptId::default();
 
         store
-            .add_code(email.clone(), login_attempt_id2.clone(), code2.clone())
+            .add_code(
+                email(),
+                login_attempt_id.clone(),
+                TwoFACode::default(),
+                None,
+            )
             .await
             .unwrap();
 
-        let retrieved = store.get_code(&email).await.unwrap();
-        assert_eq!(retrieved.0, login_attempt_id2);
-        assert_eq!(retrieved.1, code2);
+        // the first code was only just sent
+        assert!(matches!(
+            store
+                .resend_code(&login_attempt_id, TwoFACode::default())
+                .await,
+            Err(TwoFACodeStoreError::ResendCooldown(_))
+        ));
+
+        for _ in 0..MAX_TWO_FA_RESENDS {
+            store
+                .logins
+                .lock()
+                .await
+                .codes
+                .get_mut(&login_attempt_id)
+                .unwrap()
+                .sent_at -= TWO_FA_RESEND_COOLDOWN_SECONDS;
+
+            let code = TwoFACode::default();
+            store
+                .resend_code(&login_attempt_id, code.clone())
+                .await
+                .unwrap();
+            assert_eq!(store.get_code(&login_attempt_id).await, Ok((email(), code)));
+        }
+
+        store
+            .logins
+            .lock()
+            .await
+            .codes
+            .get_mut(&login_attempt_id)
+            .unwrap()
+            .sent_at -= TWO_FA_RESEND_COOLDOWN_SECONDS;
+        assert_eq!(
+            store
+                .resend_code(&login_attempt_id, TwoFACode::default())
+                .await,
+            Err(TwoFACodeStoreError::TooManyResends)
+        );
+    }
+
+    #[tokio::test]
+    async fn resend_code_should_require_a_pending_login_attempt() {
+        let store = HashMapTwoFACodeStore::default();
+
+        assert_eq!(
+            store
+                .resend_code(&LoginAttemptId::default(), TwoFACode::default())
+                .await,
+            Err(TwoFACodeStoreError::LoginAttemptIdNotFound)
+        );
     }
 }
@@ -1,30 +1,42 @@
 use std::collections::HashMap;
 
+use tokio::sync::RwLock;
+
+use super::data_stores::postgres_user_store::{compute_password_hash, verify_password_hash};
 use crate::domain::UserStore;
-use crate::domain::{Email, Password, User, UserStoreError};
+use crate::domain::{Email, Password, TwoFAMethod, User, UserStoreError};
 
 // deriving Default trait ensures we can create new instances of HashMapUserStore that contain an empty HashMap
+// The map is locked only for the lookup or update itself, so the store can be shared without a lock around it
+// Passwords are kept as Argon2 hashes like the Postgres store keeps them, hashing happens outside the lock
 #[derive(Default)]
 pub struct HashMapUserStore {
-    pub users: HashMap<Email, User>,
+    users: RwLock<HashMap<Email, User>>,
 }
 
 #[async_trait::async_trait]
 impl UserStore for HashMapUserStore {
-    async fn add_user(&mut self, user: User) -> Result<(), UserStoreError> {
+    async fn add_user(&self, mut user: User) -> Result<(), UserStoreError> {
+        let password_hash = compute_password_hash(user.password.as_ref().to_owned())
+            .await
+            .map_err(|_| UserStoreError::UnexpectedError)?;
+        user.password =
+            Password::parse(password_hash).map_err(|_| UserStoreError::UnexpectedError)?;
+
+        let mut users = self.users.write().await;
         // If user already exists, return a UserAlreadyExists error
-        if self.users.contains_key(&user.email) {
+        if users.contains_key(&user.email) {
             return Err(UserStoreError::UserAlreadyExists);
         }
         // insert the user into our hashmap and return ok
-        self.users.insert(user.email.clone(), user);
+        users.insert(user.email.clone(), user);
         Ok(())
     }
 
     async fn get_user(&self, email: &Email) -> Result<User, UserStoreError> {
         // This function should return a `Result` type containing either a
         // `User` object or a `UserStoreError::UserNotFound`.
-        if let Some(user) = self.users.get(email) {
+        if let Some(user) = self.users.read().await.get(email) {
             Ok(user.clone())
         } else {
             Err(UserStoreError::UserNotFound)
@@ -36,15 +48,57 @@ impl UserStore for HashMapUserStore {
         email: &Email,
         password: &Password,
     ) -> Result<(), UserStoreError> {
-        if let Some(user) = self.users.get(email) {
-            // check if password matches
-            if !(user.password.eq(password)) {
-                return Err(UserStoreError::InvalidCredentials);
-            } else {
+        let user = self.get_user(email).await?;
+
+        verify_password_hash(
+            user.password.as_ref().to_owned(),
+            password.as_ref().to_owned(),
+        )
+        .await
+        .map_err(|_| UserStoreError::InvalidCredentials)
+    }
+
+    async fn update_password(
+        &self,
+        email: &Email,
+        password: Password,
+    ) -> Result<(), UserStoreError> {
+        let password_hash = compute_password_hash(password.as_ref().to_owned())
+            .await
+            .map_err(|_| UserStoreError::UnexpectedError)?;
+        let password_hash =
+            Password::parse(password_hash).map_err(|_| UserStoreError::UnexpectedError)?;
+
+        match self.users.write().await.get_mut(email) {
+            Some(user) => {
+                user.password = password_hash;
                 Ok(())
             }
-        } else {
-            Err(UserStoreError::UserNotFound)
+            None => Err(UserStoreError::UserNotFound),
+        }
+    }
+
+    async fn set_email_verified(&self, email: &Email) -> Result<(), UserStoreError> {
+        match self.users.write().await.get_mut(email) {
+            Some(user) => {
+                user.email_verified = true;
+                Ok(())
+            }
+            None => Err(UserStoreError::UserNotFound),
+        }
+    }
+
+    async fn set_two_fa_method(
+        &self,
+        email: &Email,
+        two_fa_method: TwoFAMethod,
+    ) -> Result<(), UserStoreError> {
+        match self.users.write().await.get_mut(email) {
+            Some(user) => {
+                user.two_fa_method = two_fa_method;
+                Ok(())
+            }
+            None => Err(UserStoreError::UserNotFound),
         }
     }
 }
@@ -56,15 +110,15 @@ mod tests {
     #[tokio::test]
     async fn test_add_user() {
         // create a user_store_map instance
-        let mut user_store_map: Box<dyn UserStore + Send + Sync> = Box::new(HashMapUserStore {
-            users: HashMap::new(),
-        });
+        let user_store_map: Box<dyn UserStore + Send + Sync> =
+            Box::new(HashMapUserStore::default());
 
         // create a user instance to be added to the storeE)
         let user_to_add = User {
             email: Email::parse("mytestemail@test.com".to_owned()).unwrap(),
             password: Password::parse("Password@12345".to_owned()).unwrap(),
-            requires_2fa: false,
+            two_fa_method: TwoFAMethod::None,
+            email_verified: false,
         };
 
         // add user to the store
@@ -83,15 +137,15 @@ mod tests {
     #[tokio::test]
     async fn test_get_user() {
         // create a user_store_map instance
-        let mut user_store_map: Box<dyn UserStore + Send + Sync> = Box::new(HashMapUserStore {
-            users: HashMap::new(),
-        });
+        let user_store_map: Box<dyn UserStore + Send + Sync> =
+            Box::new(HashMapUserStore::default());
 
         // create a user instance to be added to the store
         let user_to_add = User {
             email: Email::parse("mytestemail@test.com".to_owned()).unwrap(),
             password: Password::parse("Password@12345".to_owned()).unwrap(),
-            requires_2fa: false,
+            two_fa_method: TwoFAMethod::None,
+            email_verified: false,
         };
 
         // assert UserNotFound returned by get_user since we have not yet added user to the store
@@ -103,19 +157,17 @@ mod tests {
         // add the user to the store
         let _ = user_store_map.add_user(user_to_add.clone()).await;
 
-        // assert that we are able to return the newly added user by calling get_user
-        assert_eq!(
-            user_store_map.get_user(&user_to_add.email).await,
-            Ok(user_to_add)
-        );
+        // assert that we are able to return the newly added user by calling get_user, with the password hashed
+        let user = user_store_map.get_user(&user_to_add.email).await.unwrap();
+        assert_eq!(user.email, user_to_add.email);
+        assert_eq!(user.two_fa_method, user_to_add.two_fa_method);
+        assert_ne!(user.password, user_to_add.password);
     }
 
     #[tokio::test]
     async fn test_validate_user() {
         // create a user_store_map instance as HashMapUserStore
-        let mut user_store_map = HashMapUserStore {
-            users: HashMap::new(),
-        };
+        let user_store_map = HashMapUserStore::default();
 
         let test_email: Email = Email::parse("mytestemail@test.com".to_owned()).unwrap();
         let test_password: Password = Password::parse("Password@12345".to_owned()).unwrap();
@@ -129,14 +181,15 @@ mod tests {
             Err(UserStoreError::UserNotFound)
         );
 
-        user_store_map.users.insert(
-            test_email.clone(),
-            User {
+        user_store_map
+            .add_user(User {
                 email: test_email.clone(),
                 password: test_password.clone(),
-                requires_2fa: false,
-            },
-        );
+                two_fa_method: TwoFAMethod::None,
+                email_verified: false,
+            })
+            .await
+            .unwrap();
 
         // Assert validate user returns () with valid email and password
         assert_eq!(
@@ -154,4 +207,125 @@ mod tests {
             Err(UserStoreError::InvalidCredentials)
         );
     }
+
+    #[tokio::test]
+    async fn test_update_password() {
+        let user_store_map = HashMapUserStore::default();
+
+        let test_email: Email = Email::parse("mytestemail@test.com".to_owned()).unwrap();
+        let old_password: Password = Password::parse("Password@12345".to_owned()).unwrap();
+        let new_password: Password = Password::parse("NewPassword@12345".to_owned()).unwrap();
+
+        // Assert we get UserNotFound if user email is not present in user store map
+        assert_eq!(
+            user_store_map
+                .update_password(&test_email, new_password.clone())
+                .await,
+            Err(UserStoreError::UserNotFound)
+        );
+
+        user_store_map
+            .add_user(User {
+                email: test_email.clone(),
+                password: old_password.clone(),
+                two_fa_method: TwoFAMethod::None,
+                email_verified: false,
+            })
+            .await
+            .unwrap();
+
+        user_store_map
+            .update_password(&test_email, new_password.clone())
+            .await
+            .unwrap();
+
+        // Assert only the new password is accepted afterwards
+        assert_eq!(
+            user_store_map
+                .validate_user(&test_email, &new_password)
+                .await,
+            Ok(())
+        );
+        assert_eq!(
+            user_store_map
+                .validate_user(&test_email, &old_password)
+                .await,
+            Err(UserStoreError::InvalidCredentials)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_set_email_verified() {
+        let user_store_map = HashMapUserStore::default();
+
+        let test_email: Email = Email::parse("mytestemail@test.com".to_owned()).unwrap();
+
+        // Assert we get UserNotFound if user email is not present in user store map
+        assert_eq!(
+            user_store_map.set_email_verified(&test_email).await,
+            Err(UserStoreError::UserNotFound)
+        );
+
+        user_store_map
+            .add_user(User {
+                email: test_email.clone(),
+                password: Password::parse("Password@12345".to_owned()).unwrap(),
+                two_fa_method: TwoFAMethod::None,
+                email_verified: false,
+            })
+            .await
+            .unwrap();
+
+        user_store_map
+            .set_email_verified(&test_email)
+            .await
+            .unwrap();
+
+        assert!(
+            user_store_map
+                .get_user(&test_email)
+                .await
+                .unwrap()
+                .email_verified
+        );
+    }
+
+    #[tokio::test]
+    async fn test_set_two_fa_method() {
+        let user_store_map = HashMapUserStore::default();
+
+        let test_email: Email = Email::parse("mytestemail@test.com".to_owned()).unwrap();
+
+        // Assert we get UserNotFound if user email is not present in user store map
+        assert_eq!(
+            user_store_map
+                .set_two_fa_method(&test_email, TwoFAMethod::Totp)
+                .await,
+            Err(UserStoreError::UserNotFound)
+        );
+
+        user_store_map
+            .add_user(User {
+                email: test_email.clone(),
+                password: Password::parse("Password@12345".to_owned()).unwrap(),
+                two_fa_method: TwoFAMethod::None,
+                email_verified: true,
+            })
+            .await
+            .unwrap();
+
+        user_store_map
+            .set_two_fa_method(&test_email, TwoFAMethod::Totp)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            user_store_map
+                .get_user(&test_email)
+                .await
+                .unwrap()
+                .two_fa_method,
+            TwoFAMethod::Totp
+        );
+    }
 }
@@ -0,0 +1,85 @@
+use std::collections::HashMap;
+
+use chrono::Utc;
+
+use crate::{
+    domain::{
+        WebAuthnCeremony, WebAuthnChallenge, WebAuthnChallengeStore, WebAuthnChallengeStoreError,
+    },
+    utils::auth::WEBAUTHN_CHALLENGE_TTL_SECONDS,
+};
+
+#[derive(Default)]
+pub struct HashMapWebAuthnChallengeStore {
+    // challenge -> (ceremony, unix timestamp the challenge expires at)
+    pub challenges: HashMap<String, (WebAuthnCeremony, i64)>,
+}
+
+#[async_trait::async_trait]
+impl WebAuthnChallengeStore for HashMapWebAuthnChallengeStore {
+    async fn add_challenge(
+        &mut self,
+        challenge: &WebAuthnChallenge,
+        ceremony: WebAuthnCeremony,
+    ) -> Result<(), WebAuthnChallengeStoreError> {
+        let expires_at = Utc::now().timestamp() + WEBAUTHN_CHALLENGE_TTL_SECONDS;
+        self.challenges
+            .insert(challenge.as_ref().to_owned(), (ceremony, expires_at));
+        Ok(())
+    }
+
+    async fn consume_challenge(
+        &mut self,
+        challenge: &WebAuthnChallenge,
+    ) -> Result<WebAuthnCeremony, WebAuthnChallengeStoreError> {
+        match self.challenges.remove(challenge.as_ref()) {
+            Some((ceremony, expires_at)) if expires_at > Utc::now().timestamp() => Ok(ceremony),
+            _ => Err(WebAuthnChallengeStoreError::ChallengeNotFound),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::Email;
+
+    #[tokio::test]
+    async fn consume_challenge_should_return_ceremony_once() {
+        let mut store = HashMapWebAuthnChallengeStore::default();
+        let challenge = WebAuthnChallenge::default();
+        let ceremony = WebAuthnCeremony::Registration {
+            email: Email::parse("user@example.com".to_owned()).unwrap(),
+        };
+
+        store
+            .add_challenge(&challenge, ceremony.clone())
+            .await
+            .unwrap();
+
+        assert_eq!(store.consume_challenge(&challenge).await, Ok(ceremony));
+        assert_eq!(
+            store.consume_challenge(&challenge).await,
+            Err(WebAuthnChallengeStoreError::ChallengeNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn consume_challenge_should_reject_expired_challenge() {
+        let mut store = HashMapWebAuthnChallengeStore::default();
+        let challenge = WebAuthnChallenge::default();
+        let ceremony = WebAuthnCeremony::Registration {
+            email: Email::parse("user@example.com".to_owned()).unwrap(),
+        };
+
+        store.challenges.insert(
+            challenge.as_ref().to_owned(),
+            (ceremony, Utc::now().timestamp() - 1),
+        );
+
+        assert_eq!(
+            store.consume_challenge(&challenge).await,
+            Err(WebAuthnChallengeStoreError::ChallengeNotFound)
+        );
+    }
+}
@@ -0,0 +1,145 @@
+use std::collections::HashMap;
+
+use crate::domain::{
+    CredentialId, Email, WebAuthnCredential, WebAuthnCredentialStore, WebAuthnCredentialStoreError,
+};
+
+#[derive(Default)]
+pub struct HashMapWebAuthnCredentialStore {
+    pub credentials: HashMap<CredentialId, WebAuthnCredential>,
+}
+
+#[async_trait::async_trait]
+impl WebAuthnCredentialStore for HashMapWebAuthnCredentialStore {
+    async fn add_credential(
+        &mut self,
+        credential: WebAuthnCredential,
+    ) -> Result<(), WebAuthnCredentialStoreError> {
+        if self.credentials.contains_key(&credential.credential_id) {
+            return Err(WebAuthnCredentialStoreError::CredentialAlreadyExists);
+        }
+        self.credentials
+            .insert(credential.credential_id.clone(), credential);
+        Ok(())
+    }
+
+    async fn get_credential(
+        &self,
+        credential_id: &CredentialId,
+    ) -> Result<WebAuthnCredential, WebAuthnCredentialStoreError> {
+        self.credentials
+            .get(credential_id)
+            .cloned()
+            .ok_or(WebAuthnCredentialStoreError::CredentialNotFound)
+    }
+
+    async fn get_user_credentials(
+        &self,
+        email: &Email,
+    ) -> Result<Vec<WebAuthnCredential>, WebAuthnCredentialStoreError> {
+        Ok(self
+            .credentials
+            .values()
+            .filter(|credential| credential.email == *email)
+            .cloned()
+            .collect())
+    }
+
+    async fn update_sign_count(
+        &mut self,
+        credential_id: &CredentialId,
+        sign_count: u32,
+    ) -> Result<(), WebAuthnCredentialStoreError> {
+        let credential = self
+            .credentials
+            .get_mut(credential_id)
+            .ok_or(WebAuthnCredentialStoreError::CredentialNotFound)?;
+
+        if sign_count <= credential.sign_count && !(sign_count == 0 && credential.sign_count == 0) {
+            return Err(WebAuthnCredentialStoreError::SignCountNotIncreased);
+        }
+
+        credential.sign_count = sign_count;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_credential(id: u8, email: &str) -> WebAuthnCredential {
+        WebAuthnCredential {
+            credential_id: CredentialId::parse(vec![id; 16]).unwrap(),
+            email: Email::parse(email.to_owned()).unwrap(),
+            public_key: vec![1, 2, 3],
+            sign_count: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn add_credential_should_reject_duplicate_id() {
+        let mut store = HashMapWebAuthnCredentialStore::default();
+
+        store
+            .add_credential(get_credential(1, "user@example.com"))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            store
+                .add_credential(get_credential(1, "other@example.com"))
+                .await,
+            Err(WebAuthnCredentialStoreError::CredentialAlreadyExists)
+        );
+    }
+
+    #[tokio::test]
+    async fn get_user_credentials_should_only_return_credentials_of_user() {
+        let mut store = HashMapWebAuthnCredentialStore::default();
+        let email = Email::parse("user@example.com".to_owned()).unwrap();
+
+        store
+            .add_credential(get_credential(1, "user@example.com"))
+            .await
+            .unwrap();
+        store
+            .add_credential(get_credential(2, "user@example.com"))
+            .await
+            .unwrap();
+        store
+            .add_credential(get_credential(3, "other@example.com"))
+            .await
+            .unwrap();
+
+        let credentials = store.get_user_credentials(&email).await.unwrap();
+        assert_eq!(credentials.len(), 2);
+        assert!(credentials
+            .iter()
+            .all(|credential| credential.email == email));
+    }
+
+    #[tokio::test]
+    async fn update_sign_count_should_require_increasing_counter() {
+        let mut store = HashMapWebAuthnCredentialStore::default();
+        let credential = get_credential(1, "user@example.com");
+        let credential_id = credential.credential_id.clone();
+
+        store.add_credential(credential).await.unwrap();
+
+        // authenticators without a counter keep reporting 0
+        assert!(store.update_sign_count(&credential_id, 0).await.is_ok());
+
+        store.update_sign_count(&credential_id, 5).await.unwrap();
+
+        assert_eq!(
+            store.update_sign_count(&credential_id, 5).await,
+            Err(WebAuthnCredentialStoreError::SignCountNotIncreased)
+        );
+        assert_eq!(
+            store.update_sign_count(&credential_id, 0).await,
+            Err(WebAuthnCredentialStoreError::SignCountNotIncreased)
+        );
+        assert!(store.update_sign_count(&credential_id, 6).await.is_ok());
+    }
+}
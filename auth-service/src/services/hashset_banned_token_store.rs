@@ -1,21 +1,85 @@
-use std::collections::HashSet;
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
 
-use crate::domain::{BannedTokenStore, BannedTokenStoreError};
+use tokio::sync::RwLock;
 
-#[derive(Default, Clone)]
+use crate::{
+    domain::{BannedTokenStore, BannedTokenStoreError, Email},
+    utils::auth::banned_token_ttl,
+};
+
+// Bans are forgotten after the TTL like the Redis keys are, expired ones are dropped on the next ban
 pub struct HashsetBannedTokenStore {
-    pub banned_tokens: HashSet<String>,
+    // `jti` claims of revoked tokens -> when their ban expires
+    banned_tokens: RwLock<HashMap<String, Instant>>,
+    // tokens of these users issued before the stored timestamp (in milliseconds) are banned
+    banned_users: RwLock<HashMap<Email, (i64, Instant)>>,
+    ttl: Duration,
+}
+
+impl HashsetBannedTokenStore {
+    pub fn with_ttl(ttl: Duration) -> Self {
+        Self {
+            banned_tokens: RwLock::default(),
+            banned_users: RwLock::default(),
+            ttl,
+        }
+    }
+}
+
+impl Default for HashsetBannedTokenStore {
+    fn default() -> Self {
+        Self::with_ttl(banned_token_ttl())
+    }
 }
 
 #[async_trait::async_trait]
 impl BannedTokenStore for HashsetBannedTokenStore {
-    async fn store_token(&mut self, token: String) -> Result<(), BannedTokenStoreError> {
-        self.banned_tokens.insert(token);
+    async fn store_token(&self, jti: String) -> Result<(), BannedTokenStoreError> {
+        let now = Instant::now();
+        let mut banned_tokens = self.banned_tokens.write().await;
+        banned_tokens.retain(|_, expires_at| *expires_at > now);
+        banned_tokens.insert(jti, now + self.ttl);
         Ok(())
     }
 
-    async fn check_token(&self, token: &str) -> Result<bool, BannedTokenStoreError> {
-        let result = self.banned_tokens.contains(token);
+    async fn check_token(&self, jti: &str) -> Result<bool, BannedTokenStoreError> {
+        let result = self
+            .banned_tokens
+            .read()
+            .await
+            .get(jti)
+            .is_some_and(|expires_at| *expires_at > Instant::now());
+        Ok(result)
+    }
+
+    async fn ban_user_tokens(
+        &self,
+        email: &Email,
+        issued_before: i64,
+    ) -> Result<(), BannedTokenStoreError> {
+        let now = Instant::now();
+        let mut banned_users = self.banned_users.write().await;
+        banned_users.retain(|_, (_, expires_at)| *expires_at > now);
+        banned_users.insert(email.clone(), (issued_before, now + self.ttl));
+        Ok(())
+    }
+
+    async fn check_user_token(
+        &self,
+        email: &Email,
+        issued_at: i64,
+    ) -> Result<bool, BannedTokenStoreError> {
+        let result =
+            self.banned_users
+                .read()
+                .await
+                .get(email)
+                .is_some_and(|(issued_before, expires_at)| {
+                    *expires_at > Instant::now() && issued_at < *issued_before
+                });
         Ok(result)
     }
 }
@@ -26,7 +90,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_store_token() {
-        let mut test_store = HashsetBannedTokenStore::default();
+        let test_store = HashsetBannedTokenStore::default();
         let test_token = "test_token".to_owned();
         let test_result = test_store.store_token(test_token.clone()).await;
         assert!(test_result.is_ok());
@@ -34,7 +98,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_check_token() {
-        let mut test_store = HashsetBannedTokenStore::default();
+        let test_store = HashsetBannedTokenStore::default();
         let test_token = "test_token".to_owned();
         let test_result = test_store.store_token(test_token.clone()).await;
         assert!(test_result.is_ok());
@@ -42,4 +106,24 @@ mod tests {
         let check_result = test_store.check_token(&test_token).await;
         assert!(check_result.is_ok());
     }
+
+    #[tokio::test]
+    async fn test_check_user_token() {
+        let test_store = HashsetBannedTokenStore::default();
+        let test_email = Email::parse("test@example.com".to_owned()).unwrap();
+
+        assert_eq!(
+            test_store.check_user_token(&test_email, 100).await,
+            Ok(false)
+        );
+
+        test_store.ban_user_tokens(&test_email, 100).await.unwrap();
+
+        // tokens issued before the ban are rejected, tokens issued from then on are not
+        assert_eq!(test_store.check_user_token(&test_email, 99).await, Ok(true));
+        assert_eq!(
+            test_store.check_user_token(&test_email, 100).await,
+            Ok(false)
+        );
+    }
 }
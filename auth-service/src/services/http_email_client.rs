@@ -0,0 +1,331 @@
+use std::time::Duration;
+
+use reqwest::{header::RETRY_AFTER, Client, Response, StatusCode};
+use serde::Serialize;
+
+use crate::domain::{Email, EmailClient, EmailMessage};
+
+pub struct HttpEmailConfig {
+    // emails are posted to `{base_url}/email`
+    pub base_url: String,
+    pub api_token: String,
+    pub sender: String,
+    // for the whole request, connecting included
+    pub timeout: Duration,
+    pub connect_timeout: Duration,
+    pub retry_policy: HttpRetryPolicy,
+}
+
+// Requests the API failed with a server error or a rate limit are retried straight away a few
+// times, before the email goes back to the outbox to be retried much later
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HttpRetryPolicy {
+    pub max_retries: u32,
+    // the wait before the first retry, doubled for every further one unless the API asks for a
+    // longer wait with `Retry-After`
+    pub backoff: Duration,
+}
+
+impl Default for HttpRetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 2,
+            backoff: Duration::from_millis(500),
+        }
+    }
+}
+
+// Client for the JSON APIs of transactional email services such as Postmark. The API token is sent
+// as bearer token, and every attempt at one email carries the same `Idempotency-Key` so the API
+// can drop a retry of an email it already accepted.
+pub struct HttpEmailClient {
+    http_client: Client,
+    url: String,
+    api_token: String,
+    sender: String,
+    timeout: Duration,
+    retry_policy: HttpRetryPolicy,
+}
+
+impl HttpEmailClient {
+    pub fn new(config: HttpEmailConfig) -> Result<Self, String> {
+        let http_client = Client::builder()
+            .timeout(config.timeout)
+            .connect_timeout(config.connect_timeout)
+            .build()
+            .map_err(|e| e.to_string())?;
+
+        Ok(Self {
+            http_client,
+            url: format!("{}/email", config.base_url.trim_end_matches('/')),
+            api_token: config.api_token,
+            sender: config.sender,
+            timeout: config.timeout,
+            retry_policy: config.retry_policy,
+        })
+    }
+
+    async fn post(&self, body: &SendEmailRequest<'_>, idempotency_key: &str) -> Attempt {
+        let response = self
+            .http_client
+            .post(&self.url)
+            .bearer_auth(&self.api_token)
+            .header("Idempotency-Key", idempotency_key)
+            .json(body)
+            .send()
+            .await;
+
+        match response {
+            Ok(response) if response.status().is_success() => Attempt::Sent,
+            Ok(response) => {
+                let status = response.status();
+                let retry_after = retry_after(&response);
+                let error = format!(
+                    "Email API responded with {}: {}",
+                    status,
+                    response.text().await.unwrap_or_default()
+                );
+
+                if status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error() {
+                    Attempt::Retry(error, retry_after)
+                } else {
+                    Attempt::Failed(error)
+                }
+            }
+            // the connection failed or timed out, the email may or may not have been accepted
+            Err(e) => Attempt::Retry(format!("Email API request failed: {}", e), None),
+        }
+    }
+}
+
+enum Attempt {
+    Sent,
+    // the error, and how long the API asked to wait
+    Retry(String, Option<Duration>),
+    // the API refused the email, sending it again would not help
+    Failed(String),
+}
+
+fn retry_after(response: &Response) -> Option<Duration> {
+    response
+        .headers()
+        .get(RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .parse()
+        .ok()
+        .map(Duration::from_secs)
+}
+
+#[async_trait::async_trait]
+impl EmailClient for HttpEmailClient {
+    async fn send_email(
+        &self,
+        recipient: &Email,
+        message: &EmailMessage,
+        idempotency_key: &str,
+    ) -> Result<(), String> {
+        let body = SendEmailRequest {
+            from: &self.sender,
+            to: recipient.as_ref(),
+            subject: &message.subject,
+            html_body: &message.html_body,
+            text_body: &message.text_body,
+        };
+
+        let mut backoff = self.retry_policy.backoff;
+        let mut retries = 0;
+
+        loop {
+            match self.post(&body, idempotency_key).await {
+                Attempt::Sent => return Ok(()),
+                Attempt::Failed(error) => return Err(error),
+                Attempt::Retry(error, _) if retries == self.retry_policy.max_retries => {
+                    return Err(error)
+                }
+                // waiting that long is left to the outbox rather than holding up its worker
+                Attempt::Retry(error, Some(retry_after)) if retry_after > self.timeout => {
+                    return Err(error)
+                }
+                Attempt::Retry(_, retry_after) => {
+                    tokio::time::sleep(retry_after.unwrap_or_default().max(backoff)).await;
+                    backoff *= 2;
+                    retries += 1;
+                }
+            }
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct SendEmailRequest<'a> {
+    from: &'a str,
+    to: &'a str,
+    subject: &'a str,
+    html_body: &'a str,
+    text_body: &'a str,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use axum::{
+        extract::State,
+        http::{HeaderMap, StatusCode},
+        routing::post,
+        Json, Router,
+    };
+    use serde_json::Value;
+
+    use super::*;
+
+    // Local stand-in for the email API. It answers with the given statuses in turn, the last one
+    // for every further request, and keeps the requests it received.
+    #[derive(Clone)]
+    struct MockEmailApi {
+        statuses: Arc<Mutex<Vec<StatusCode>>>,
+        requests: Arc<Mutex<Vec<(HeaderMap, Value)>>>,
+    }
+
+    impl MockEmailApi {
+        async fn start(statuses: Vec<StatusCode>) -> (Self, String) {
+            let api = Self {
+                statuses: Arc::new(Mutex::new(statuses)),
+                requests: Arc::new(Mutex::new(Vec::new())),
+            };
+            let router = Router::new()
+                .route("/email", post(receive_email))
+                .with_state(api.clone());
+
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let base_url = format!("http://{}", listener.local_addr().unwrap());
+            tokio::spawn(async move { axum::serve(listener, router).await });
+
+            (api, base_url)
+        }
+
+        fn requests(&self) -> Vec<(HeaderMap, Value)> {
+            self.requests.lock().unwrap().clone()
+        }
+    }
+
+    async fn receive_email(
+        State(api): State<MockEmailApi>,
+        headers: HeaderMap,
+        Json(body): Json<Value>,
+    ) -> StatusCode {
+        api.requests.lock().unwrap().push((headers, body));
+
+        let mut statuses = api.statuses.lock().unwrap();
+        if statuses.len() > 1 {
+            statuses.remove(0)
+        } else {
+            statuses[0]
+        }
+    }
+
+    fn get_client(base_url: String, timeout: Duration) -> HttpEmailClient {
+        HttpEmailClient::new(HttpEmailConfig {
+            base_url,
+            api_token: "api-token".to_owned(),
+            sender: "Auth Service <no-reply@example.com>".to_owned(),
+            timeout,
+            connect_timeout: timeout,
+            retry_policy: HttpRetryPolicy {
+                max_retries: 2,
+                backoff: Duration::from_millis(10),
+            },
+        })
+        .unwrap()
+    }
+
+    async fn send(client: &HttpEmailClient) -> Result<(), String> {
+        let recipient = Email::parse("user@example.com".to_owned()).unwrap();
+        let message = EmailMessage {
+            subject: "2FA Code".to_owned(),
+            html_body: "<p>123456</p>".to_owned(),
+            text_body: "123456".to_owned(),
+        };
+
+        client.send_email(&recipient, &message, "outbox-id").await
+    }
+
+    #[tokio::test]
+    async fn should_post_email_as_json() {
+        let (api, base_url) = MockEmailApi::start(vec![StatusCode::OK]).await;
+        let client = get_client(base_url, Duration::from_secs(5));
+
+        assert_eq!(send(&client).await, Ok(()));
+
+        let requests = api.requests();
+        assert_eq!(requests.len(), 1);
+
+        let (headers, body) = &requests[0];
+        assert_eq!(headers["authorization"], "Bearer api-token");
+        assert_eq!(headers["idempotency-key"], "outbox-id");
+        assert_eq!(
+            *body,
+            serde_json::json!({
+                "From": "Auth Service <no-reply@example.com>",
+                "To": "user@example.com",
+                "Subject": "2FA Code",
+                "HtmlBody": "<p>123456</p>",
+                "TextBody": "123456",
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn should_retry_server_errors_with_same_idempotency_key() {
+        let (api, base_url) = MockEmailApi::start(vec![
+            StatusCode::SERVICE_UNAVAILABLE,
+            StatusCode::TOO_MANY_REQUESTS,
+            StatusCode::OK,
+        ])
+        .await;
+        let client = get_client(base_url, Duration::from_secs(5));
+
+        assert_eq!(send(&client).await, Ok(()));
+
+        let requests = api.requests();
+        assert_eq!(requests.len(), 3);
+        assert!(requests
+            .iter()
+            .all(|(headers, _)| headers["idempotency-key"] == "outbox-id"));
+    }
+
+    #[tokio::test]
+    async fn should_give_up_after_max_retries() {
+        let (api, base_url) = MockEmailApi::start(vec![StatusCode::BAD_GATEWAY]).await;
+        let client = get_client(base_url, Duration::from_secs(5));
+
+        let error = send(&client).await.unwrap_err();
+
+        assert!(error.contains("502"));
+        assert_eq!(api.requests().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn should_not_retry_refused_email() {
+        let (api, base_url) = MockEmailApi::start(vec![StatusCode::UNPROCESSABLE_ENTITY]).await;
+        let client = get_client(base_url, Duration::from_secs(5));
+
+        let error = send(&client).await.unwrap_err();
+
+        assert!(error.contains("422"));
+        assert_eq!(api.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn should_fail_if_api_does_not_respond_in_time() {
+        // accepts connections but never answers
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let client = get_client(base_url, Duration::from_millis(100));
+
+        assert!(send(&client).await.is_err());
+        drop(listener);
+    }
+}
@@ -1,4 +1,4 @@
-use crate::domain::{Email, EmailClient};
+use crate::domain::{Email, EmailClient, EmailMessage};
 
 pub struct MockEmailClient;
 
@@ -7,15 +7,15 @@ impl EmailClient for MockEmailClient {
     async fn send_email(
         &self,
         recipient: &Email,
-        subject: &str,
-        content: &str,
+        message: &EmailMessage,
+        _idempotency_key: &str,
     ) -> Result<(), String> {
-        // For now our mock email client will be simply logging the recipient, subject and content to standard output
+        // Our mock email client simply logs the recipient, subject and plain-text body to standard output
         println!(
             "Sending email to {} with subject {} and content {}",
             recipient.as_ref(),
-            subject,
-            content
+            message.subject,
+            message.text_body
         );
 
         Ok(())
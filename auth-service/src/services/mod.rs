@@ -1,11 +1,39 @@
 mod data_stores;
+mod hashmap_email_outbox_store;
+mod hashmap_failed_login_store;
+mod hashmap_jwt_key_store;
+mod hashmap_one_time_token_store;
+mod hashmap_organization_store;
+mod hashmap_rate_limit_store;
+mod hashmap_recovery_code_store;
+mod hashmap_refresh_token_store;
+mod hashmap_role_store;
+mod hashmap_totp_secret_store;
 mod hashmap_two_fa_code_store;
 mod hashmap_user_store;
+mod hashmap_webauthn_challenge_store;
+mod hashmap_webauthn_credential_store;
 mod hashset_banned_token_store;
+mod http_email_client;
 mod mock_email_client;
+mod smtp_email_client;
 
 pub use data_stores::*;
+pub use hashmap_email_outbox_store::*;
+pub use hashmap_failed_login_store::*;
+pub use hashmap_jwt_key_store::*;
+pub use hashmap_one_time_token_store::*;
+pub use hashmap_organization_store::*;
+pub use hashmap_rate_limit_store::*;
+pub use hashmap_recovery_code_store::*;
+pub use hashmap_refresh_token_store::*;
+pub use hashmap_role_store::*;
+pub use hashmap_totp_secret_store::*;
 pub use hashmap_two_fa_code_store::*;
 pub use hashmap_user_store::*;
+pub use hashmap_webauthn_challenge_store::*;
+pub use hashmap_webauthn_credential_store::*;
 pub use hashset_banned_token_store::*;
+pub use http_email_client::*;
 pub use mock_email_client::*;
+pub use smtp_email_client::*;
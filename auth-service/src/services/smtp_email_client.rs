@@ -0,0 +1,101 @@
+use lettre::{
+    message::{Mailbox, MultiPart},
+    transport::smtp::authentication::Credentials,
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+};
+
+use crate::domain::{Email, EmailClient, EmailMessage};
+
+// How the connection to the SMTP server is secured
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SmtpTls {
+    // Plain connection, only meant for local mail sinks
+    None,
+    // Plain connection upgraded with STARTTLS, port 587 by default
+    StartTls,
+    // TLS from the start, port 465 by default
+    Tls,
+}
+
+impl SmtpTls {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s.to_lowercase().as_str() {
+            "none" => Ok(Self::None),
+            "starttls" => Ok(Self::StartTls),
+            "tls" => Ok(Self::Tls),
+            _ => Err(format!("Unknown SMTP TLS mode: {}", s)),
+        }
+    }
+}
+
+pub struct SmtpEmailClient {
+    mailer: AsyncSmtpTransport<Tokio1Executor>,
+    sender: Mailbox,
+}
+
+impl SmtpEmailClient {
+    // Connections are opened lazily and pooled, a wrong host only shows up once an email is sent
+    pub fn new(
+        host: &str,
+        port: Option<u16>,
+        tls: SmtpTls,
+        credentials: Option<(String, String)>,
+        sender: &str,
+    ) -> Result<Self, String> {
+        let mut builder = match tls {
+            SmtpTls::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host),
+            SmtpTls::StartTls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)
+                .map_err(|e| e.to_string())?,
+            SmtpTls::Tls => {
+                AsyncSmtpTransport::<Tokio1Executor>::relay(host).map_err(|e| e.to_string())?
+            }
+        };
+
+        if let Some(port) = port {
+            builder = builder.port(port);
+        }
+
+        if let Some((username, password)) = credentials {
+            builder = builder.credentials(Credentials::new(username, password));
+        }
+
+        let sender = sender
+            .parse()
+            .map_err(|e| format!("Invalid email sender {}: {}", sender, e))?;
+
+        Ok(Self {
+            mailer: builder.build(),
+            sender,
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailClient for SmtpEmailClient {
+    async fn send_email(
+        &self,
+        recipient: &Email,
+        message: &EmailMessage,
+        idempotency_key: &str,
+    ) -> Result<(), String> {
+        let recipient: Mailbox = recipient.as_ref().parse().map_err(|e| format!("{}", e))?;
+
+        // Every attempt carries the same Message-ID, which lets receiving servers and clients spot duplicates
+        let message_id = format!("<{}@{}>", idempotency_key, self.sender.email.domain());
+
+        let email = Message::builder()
+            .message_id(Some(message_id))
+            .from(self.sender.clone())
+            .to(recipient)
+            .subject(&message.subject)
+            .multipart(MultiPart::alternative_plain_html(
+                message.text_body.clone(),
+                message.html_body.clone(),
+            ))
+            .map_err(|e| e.to_string())?;
+
+        self.mailer.send(email).await.map_err(|e| e.to_string())?;
+
+        Ok(())
+    }
+}
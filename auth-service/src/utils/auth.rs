@@ -1,11 +1,34 @@
-use axum_extra::extract::cookie::{Cookie, SameSite};
+use axum::http::{header::AUTHORIZATION, HeaderMap};
+use axum_extra::extract::{
+    cookie::{Cookie, SameSite},
+    CookieJar,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
 use chrono::Utc;
-use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Validation};
+use jsonwebtoken::{decode, decode_header, encode, Validation};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::time::Duration;
+use uuid::Uuid;
 
-use crate::{app_state::BannedTokenStoreType, domain::Email};
+use crate::{
+    app_state::{
+        AppState, BannedTokenStoreType, KeyRingType, OrganizationStoreType, RefreshTokenStoreType,
+        RoleStoreType,
+    },
+    domain::{
+        AuthAPIError, Email, Membership, OneTimeTokenPurpose, OrganizationId,
+        OrganizationStoreError, RefreshSession, RefreshToken, UserAccess,
+    },
+};
 
-use super::constants::{JWT_COOKIE_NAME, JWT_SECRET};
+use super::{
+    constants::{
+        ADMIN_API_KEY, INTROSPECTION_CLIENTS, JWT_AUDIENCE, JWT_COOKIE_NAME, JWT_ISSUER,
+        JWT_LEEWAY_SECONDS, REFRESH_COOKIE_NAME,
+    },
+    jwt::{refresh_key_ring_for_kid, KeyRing, SigningKey},
+};
 
 #[derive(Debug)]
 pub enum GenerateTokenError {
@@ -17,14 +40,107 @@ pub enum GenerateTokenError {
 pub struct Claims {
     pub sub: String,
     pub exp: usize,
+    pub iat: usize,
+    // `iat` in milliseconds, so a token issued in the same second as a revocation of all the user's
+    // tokens can be told apart from the ones it revoked
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub iat_ms: Option<i64>,
+    pub nbf: usize,
+    // unique per token, so a single token can be revoked
+    pub jti: String,
+    pub iss: String,
+    // the one consumer the token is issued for
+    pub aud: String,
+    pub roles: Vec<String>,
+    // what the roles allow, checked by routes without looking the roles up again
+    pub permissions: Vec<String>,
+    // the organization the user works in and their role there, absent until one is selected
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub org_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub org_role: Option<String>,
 }
 
-// Create cookie with a new JWT auth token
-pub fn generate_auth_cookie(email: &Email) -> Result<Cookie<'static>, GenerateTokenError> {
-    let token = generate_auth_token(email)?;
+impl Claims {
+    pub fn has_permission(&self, permission: &str) -> bool {
+        self.permissions.iter().any(|p| p == permission)
+    }
+
+    // Tokens issued before `iat_ms` was added only have the second they were issued in
+    pub fn issued_at_millis(&self) -> i64 {
+        self.iat_ms.unwrap_or(self.iat as i64 * 1000)
+    }
+}
+
+// Create cookie with a new JWT auth token for the session's organization. The organization is left
+// out of the token once the user no longer belongs to it.
+pub async fn generate_auth_cookie(
+    email: &Email,
+    organization_id: Option<&OrganizationId>,
+    key_ring: KeyRingType,
+    role_store: RoleStoreType,
+    organization_store: OrganizationStoreType,
+) -> Result<Cookie<'static>, GenerateTokenError> {
+    let token = generate_access_token(
+        email,
+        organization_id,
+        session_audience(),
+        key_ring,
+        role_store,
+        organization_store,
+    )
+    .await?;
     Ok(create_auth_cookie(token))
 }
 
+// Create a JWT auth token naming `audience` as its only consumer
+pub async fn generate_access_token(
+    email: &Email,
+    organization_id: Option<&OrganizationId>,
+    audience: &str,
+    key_ring: KeyRingType,
+    role_store: RoleStoreType,
+    organization_store: OrganizationStoreType,
+) -> Result<String, GenerateTokenError> {
+    let access = role_store
+        .read()
+        .await
+        .get_user_access(email)
+        .await
+        .map_err(|_| GenerateTokenError::UnexpectedError)?;
+
+    let membership = match organization_id {
+        Some(organization_id) => match organization_store
+            .read()
+            .await
+            .get_membership(email, organization_id)
+            .await
+        {
+            Ok(membership) => Some(membership),
+            Err(
+                OrganizationStoreError::MembershipNotFound
+                | OrganizationStoreError::OrganizationNotFound,
+            ) => None,
+            Err(_) => return Err(GenerateTokenError::UnexpectedError),
+        },
+        None => None,
+    };
+
+    generate_auth_token(
+        email,
+        &access,
+        membership.as_ref(),
+        audience,
+        &*key_ring.read().await,
+    )
+}
+
+// The consumer the tokens in the auth cookie are issued for. The cookie is shared with that consumer,
+// so this service checks the same audience on the requests it authenticates from the cookie.
+pub fn session_audience() -> &'static str {
+    &JWT_AUDIENCE[0]
+}
+
 // Create cookie and set the value to the passed-in token string
 fn create_auth_cookie(token: String) -> Cookie<'static> {
     let cookie = Cookie::build((JWT_COOKIE_NAME, token))
@@ -39,13 +155,106 @@ fn create_auth_cookie(token: String) -> Cookie<'static> {
 // This value determines how long the JWT auth token is valid for
 pub const TOKEN_TTL_SECONDS: i64 = 600; // 10 minutes
 
-// Create JWT auth token
-fn generate_auth_token(email: &Email) -> Result<String, GenerateTokenError> {
+// This value determines how long a refresh token (and its family) stays valid without being used
+pub const REFRESH_TOKEN_TTL_SECONDS: i64 = 60 * 60 * 24 * 7; // 7 days
+
+// This value determines how long a password reset link can be used for
+pub const PASSWORD_RESET_TOKEN_TTL_SECONDS: i64 = 900; // 15 minutes
+
+// This value determines how long an email verification link can be used for
+pub const EMAIL_VERIFICATION_TOKEN_TTL_SECONDS: i64 = 60 * 60 * 24; // 24 hours
+
+// This value determines how long an organization invitation can be accepted for
+pub const INVITATION_TOKEN_TTL_SECONDS: i64 = 60 * 60 * 24 * 7; // 7 days
+
+// This value determines how long failed logins are remembered after the most recent one
+pub const FAILED_LOGIN_TTL_SECONDS: i64 = 60 * 60 * 24; // 24 hours
+
+// This value caps how long a login lock can last, however often the lock has doubled
+pub const MAX_LOGIN_LOCKOUT_SECONDS: i64 = 60 * 60; // 1 hour
+
+// This value determines how many wrong guesses a 2FA login attempt survives before its code is discarded
+pub const MAX_TWO_FA_ATTEMPTS: u32 = 5;
+
+// This value determines how long a user waits after a 2FA code was sent before they can ask for a new one
+pub const TWO_FA_RESEND_COOLDOWN_SECONDS: i64 = 30;
+
+// This value determines how many new codes can be sent for one 2FA login attempt
+pub const MAX_TWO_FA_RESENDS: u32 = 3;
+
+// This value determines how long a 2FA login attempt can be completed for
+pub const TWO_FA_CODE_TTL_SECONDS: i64 = 600; // 10 minutes
+
+// This value determines how long a WebAuthn registration or login ceremony can take
+pub const WEBAUTHN_CHALLENGE_TTL_SECONDS: i64 = 300; // 5 minutes
+
+// A banned token is accepted for at most TOKEN_TTL_SECONDS plus the clock skew leeway, so its ban
+// can be forgotten after that
+pub fn banned_token_ttl() -> Duration {
+    Duration::from_secs(TOKEN_TTL_SECONDS as u64 + *JWT_LEEWAY_SECONDS)
+}
+
+// How long a single-use token emailed for `purpose` can be used for
+pub fn one_time_token_ttl(purpose: OneTimeTokenPurpose) -> Duration {
+    let seconds = match purpose {
+        OneTimeTokenPurpose::PasswordReset => PASSWORD_RESET_TOKEN_TTL_SECONDS,
+        OneTimeTokenPurpose::EmailVerification => EMAIL_VERIFICATION_TOKEN_TTL_SECONDS,
+    };
+    Duration::from_secs(seconds as u64)
+}
+
+// Start a new refresh token family for the user's session in the organization, if any, and wrap its
+// first token in a cookie
+pub async fn generate_refresh_cookie(
+    email: &Email,
+    organization_id: Option<&OrganizationId>,
+    refresh_token_store: RefreshTokenStoreType,
+) -> Result<Cookie<'static>, GenerateTokenError> {
+    let token = RefreshToken::default();
+    let session = RefreshSession {
+        email: email.clone(),
+        organization_id: organization_id.cloned(),
+    };
+
+    refresh_token_store
+        .write()
+        .await
+        .add_token(session, token.clone())
+        .await
+        .map_err(|_| GenerateTokenError::UnexpectedError)?;
+
+    Ok(create_refresh_cookie(&token))
+}
+
+// Create cookie and set the value to the passed-in refresh token
+pub fn create_refresh_cookie(token: &RefreshToken) -> Cookie<'static> {
+    let cookie = Cookie::build((REFRESH_COOKIE_NAME, token.as_ref().to_owned()))
+        .path("/")
+        .http_only(true)
+        .same_site(SameSite::Lax)
+        // unlike the auth cookie the refresh cookie has to survive browser restarts
+        .max_age(time::Duration::seconds(REFRESH_TOKEN_TTL_SECONDS))
+        .build();
+
+    cookie
+}
+
+// Create JWT auth token carrying the user's roles and permissions and their selected organization,
+// signed with the active key of the key ring
+fn generate_auth_token(
+    email: &Email,
+    access: &UserAccess,
+    membership: Option<&Membership>,
+    audience: &str,
+    key_ring: &KeyRing,
+) -> Result<String, GenerateTokenError> {
     let delta = chrono::Duration::try_seconds(TOKEN_TTL_SECONDS)
         .ok_or(GenerateTokenError::UnexpectedError)?;
 
+    let now = Utc::now();
+
     // Create JWT expiration time
-    let exp = Utc::now()
+    let exp = now
         .checked_add_signed(delta)
         .ok_or(GenerateTokenError::UnexpectedError)?
         .timestamp();
@@ -55,62 +264,192 @@ fn generate_auth_token(email: &Email) -> Result<String, GenerateTokenError> {
         .try_into()
         .map_err(|_| GenerateTokenError::UnexpectedError)?;
 
+    let iat: usize = now
+        .timestamp()
+        .try_into()
+        .map_err(|_| GenerateTokenError::UnexpectedError)?;
+
     let sub = email.as_ref().to_owned();
 
-    let claims = Claims { sub, exp };
+    let claims = Claims {
+        sub,
+        exp,
+        iat,
+        iat_ms: Some(now.timestamp_millis()),
+        nbf: iat,
+        jti: Uuid::new_v4().to_string(),
+        iss: JWT_ISSUER.clone(),
+        aud: audience.to_owned(),
+        roles: access
+            .roles
+            .iter()
+            .map(|role| role.as_ref().to_owned())
+            .collect(),
+        permissions: access
+            .permissions
+            .iter()
+            .map(|permission| permission.as_ref().to_owned())
+            .collect(),
+        org_id: membership.map(|membership| membership.organization.id.to_string()),
+        org_role: membership.map(|membership| membership.role.as_ref().to_owned()),
+    };
+
+    create_token(&claims, key_ring.active()).map_err(GenerateTokenError::TokenError)
+}
+
+// Identify the user making the request from their JWT auth cookie
+pub async fn get_authenticated_email(
+    jar: &CookieJar,
+    state: &AppState,
+) -> Result<Email, AuthAPIError> {
+    let claims = get_authenticated_claims(jar, state).await?;
 
-    create_token(&claims).map_err(GenerateTokenError::TokenError)
+    Email::parse(claims.sub).map_err(|_| AuthAPIError::InvalidToken)
 }
 
-// Check if JWT auth token is valid by decoding it using the JWT secret
+// The claims of the request's JWT auth cookie, e.g. for the organization the session works in
+pub async fn get_authenticated_claims(
+    jar: &CookieJar,
+    state: &AppState,
+) -> Result<Claims, AuthAPIError> {
+    let token = jar
+        .get(JWT_COOKIE_NAME)
+        .ok_or(AuthAPIError::MissingToken)?
+        .value()
+        .to_owned();
+
+    validate_token(
+        &token,
+        session_audience(),
+        state.key_ring.clone(),
+        state.banned_token_store.clone(),
+    )
+    .await
+    .map_err(|_| AuthAPIError::InvalidToken)
+}
+
+// Check if JWT auth token is valid for `audience` by decoding it using the key from the key ring named
+// by its `kid`
 pub async fn validate_token(
     token: &str,
+    audience: &str,
+    key_ring: KeyRingType,
     banned_token_store: BannedTokenStoreType,
 ) -> Result<Claims, jsonwebtoken::errors::Error> {
-    match banned_token_store.read().await.check_token(token).await {
-        Ok(value) => {
-            if value {
-                return Err(jsonwebtoken::errors::Error::from(
-                    jsonwebtoken::errors::ErrorKind::InvalidToken,
-                ));
-            }
-        }
+    let invalid_token =
+        || jsonwebtoken::errors::Error::from(jsonwebtoken::errors::ErrorKind::InvalidToken);
 
-        Err(_) => {
-            return Err(jsonwebtoken::errors::Error::from(
-                jsonwebtoken::errors::ErrorKind::InvalidToken,
-            ));
-        }
+    let header = decode_header(token)?;
+    refresh_key_ring_for_kid(&key_ring, header.kid.as_deref()).await;
+
+    let claims = {
+        let key_ring = key_ring.read().await;
+        let key = key_ring
+            .find(header.kid.as_deref())
+            .ok_or_else(invalid_token)?;
+        decode::<Claims>(token, key.decoding_key(), &validation(key, audience))
+            .map(|data| data.claims)?
+    };
+
+    // Reject tokens revoked one by one (e.g. on logout)
+    match banned_token_store.check_token(&claims.jti).await {
+        Ok(false) => {}
+        _ => return Err(invalid_token()),
+    }
+
+    // Reject tokens issued before all sessions of the user were revoked (e.g. by a password reset)
+    let email = Email::parse(claims.sub.clone()).map_err(|_| invalid_token())?;
+    match banned_token_store
+        .check_user_token(&email, claims.issued_at_millis())
+        .await
+    {
+        Ok(false) => Ok(claims),
+        _ => Err(invalid_token()),
     }
-    decode::<Claims>(
-        token,
-        &DecodingKey::from_secret(JWT_SECRET.as_bytes()),
-        &Validation::default(),
-    )
-    .map(|data| data.claims)
 }
 
-// Create JWT auth token by encoding claims using the JWT secret
-fn create_token(claims: &Claims) -> Result<String, jsonwebtoken::errors::Error> {
-    encode(
-        &jsonwebtoken::Header::default(),
-        &claims,
-        &EncodingKey::from_secret(JWT_SECRET.as_bytes()),
-    )
+// Tokens must come from us, be meant for the consumer checking them and be within their validity window
+fn validation(key: &SigningKey, audience: &str) -> Validation {
+    let mut validation = key.validation();
+    validation.set_issuer(&[JWT_ISSUER.as_str()]);
+    validation.set_audience(&[audience]);
+    validation.set_required_spec_claims(&["exp", "nbf", "iat", "iss", "aud", "sub", "jti"]);
+    validation.validate_nbf = true;
+    validation.leeway = *JWT_LEEWAY_SECONDS;
+    validation
+}
+
+// Create JWT auth token by encoding claims using the given signing key
+fn create_token(
+    claims: &Claims,
+    signing_key: &SigningKey,
+) -> Result<String, jsonwebtoken::errors::Error> {
+    encode(&signing_key.header(), &claims, signing_key.encoding_key())
+}
+
+// Operators and automation call admin routes with the ADMIN_API_KEY as bearer token
+pub fn is_admin_api_key(provided: &str) -> bool {
+    ADMIN_API_KEY
+        .as_ref()
+        .is_some_and(|expected| secrets_match(provided, expected))
+}
+
+// Authenticate a client from its HTTP Basic credentials (RFC 6749 section 2.3.1), returning its id
+pub fn authenticate_client(headers: &HeaderMap) -> Result<String, AuthAPIError> {
+    let credentials = headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Basic "))
+        .and_then(|encoded| STANDARD.decode(encoded.trim()).ok())
+        .and_then(|decoded| String::from_utf8(decoded).ok())
+        .ok_or(AuthAPIError::InvalidClient)?;
+
+    let (client_id, client_secret) = credentials
+        .split_once(':')
+        .ok_or(AuthAPIError::InvalidClient)?;
+
+    match INTROSPECTION_CLIENTS.get(client_id) {
+        Some(expected) if secrets_match(client_secret, expected) => Ok(client_id.to_owned()),
+        _ => Err(AuthAPIError::InvalidClient),
+    }
+}
+
+// Compare digests so the comparison takes the same time wherever the first mismatch is
+fn secrets_match(provided: &str, expected: &str) -> bool {
+    Sha256::digest(provided.as_bytes()) == Sha256::digest(expected.as_bytes())
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::services::HashsetBannedTokenStore;
+    use crate::domain::{BannedTokenStore, OrganizationName, Permission, Role};
+    use crate::services::{HashMapOrganizationStore, HashMapRoleStore, HashsetBannedTokenStore};
     use std::sync::Arc;
     use tokio::sync::RwLock;
 
     use super::*;
 
+    fn role_store() -> RoleStoreType {
+        Arc::new(RwLock::new(HashMapRoleStore::default()))
+    }
+
+    fn organization_store() -> OrganizationStoreType {
+        Arc::new(RwLock::new(HashMapOrganizationStore::default()))
+    }
+
+    fn key_ring() -> KeyRingType {
+        Arc::new(RwLock::new(KeyRing::new(
+            SigningKey::from_secret(b"secret"),
+            vec![],
+        )))
+    }
+
     #[tokio::test]
     async fn test_generate_auth_cookie() {
         let email = Email::parse("test@example.com".to_owned()).unwrap();
-        let cookie = generate_auth_cookie(&email).unwrap();
+        let cookie =
+            generate_auth_cookie(&email, None, key_ring(), role_store(), organization_store())
+                .await
+                .unwrap();
         assert_eq!(cookie.name(), JWT_COOKIE_NAME);
         assert_eq!(cookie.value().split('.').count(), 3);
         assert_eq!(cookie.path(), Some("/"));
@@ -129,19 +468,50 @@ mod tests {
         assert_eq!(cookie.same_site(), Some(SameSite::Lax));
     }
 
+    #[tokio::test]
+    async fn test_create_refresh_cookie() {
+        let token = RefreshToken::default();
+        let cookie = create_refresh_cookie(&token);
+        assert_eq!(cookie.name(), REFRESH_COOKIE_NAME);
+        assert_eq!(cookie.value(), token.as_ref());
+        assert_eq!(cookie.path(), Some("/"));
+        assert_eq!(cookie.http_only(), Some(true));
+        assert_eq!(
+            cookie.max_age(),
+            Some(time::Duration::seconds(REFRESH_TOKEN_TTL_SECONDS))
+        );
+    }
+
     #[tokio::test]
     async fn test_generate_auth_token() {
         let email = Email::parse("test@example.com".to_owned()).unwrap();
-        let result = generate_auth_token(&email).unwrap();
+        let result = generate_auth_token(
+            &email,
+            &UserAccess::default(),
+            None,
+            session_audience(),
+            &*key_ring().read().await,
+        )
+        .unwrap();
         assert_eq!(result.split('.').count(), 3);
     }
 
     #[tokio::test]
     async fn test_validate_token_with_valid_token() {
         let email = Email::parse("test@example.com".to_owned()).unwrap();
-        let token = generate_auth_token(&email).unwrap();
-        let banned_token_store = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
-        let result = validate_token(&token, banned_token_store).await.unwrap();
+        let key_ring = key_ring();
+        let token = generate_auth_token(
+            &email,
+            &UserAccess::default(),
+            None,
+            session_audience(),
+            &*key_ring.read().await,
+        )
+        .unwrap();
+        let banned_token_store = Arc::new(HashsetBannedTokenStore::default());
+        let result = validate_token(&token, session_audience(), key_ring, banned_token_store)
+            .await
+            .unwrap();
         assert_eq!(result.sub, "test@example.com");
 
         let exp = Utc::now()
@@ -152,11 +522,387 @@ mod tests {
         assert!(result.exp > exp as usize);
     }
 
+    #[tokio::test]
+    async fn test_validate_token_with_banned_user() {
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let key_ring = key_ring();
+        let token = generate_auth_token(
+            &email,
+            &UserAccess::default(),
+            None,
+            session_audience(),
+            &*key_ring.read().await,
+        )
+        .unwrap();
+        let banned_token_store = Arc::new(HashsetBannedTokenStore::default());
+
+        // the ban only reaches tokens issued strictly before it
+        tokio::time::sleep(Duration::from_millis(1)).await;
+        banned_token_store
+            .ban_user_tokens(&email, Utc::now().timestamp_millis())
+            .await
+            .unwrap();
+
+        let result = validate_token(&token, session_audience(), key_ring, banned_token_store).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_issued_right_after_user_ban() {
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let key_ring = key_ring();
+        let banned_token_store = Arc::new(HashsetBannedTokenStore::default());
+
+        banned_token_store
+            .ban_user_tokens(&email, Utc::now().timestamp_millis())
+            .await
+            .unwrap();
+
+        // e.g. logging in with the new password straight after a reset, within the same second
+        tokio::time::sleep(Duration::from_millis(1)).await;
+        let token = generate_auth_token(
+            &email,
+            &UserAccess::default(),
+            None,
+            session_audience(),
+            &*key_ring.read().await,
+        )
+        .unwrap();
+
+        let result = validate_token(&token, session_audience(), key_ring, banned_token_store).await;
+        assert!(result.is_ok());
+    }
+
     #[tokio::test]
     async fn test_validate_token_with_invalid_token() {
         let token = "invalid_token".to_owned();
-        let banned_token_store = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
-        let result = validate_token(&token, banned_token_store).await;
+        let key_ring = key_ring();
+        let banned_token_store = Arc::new(HashsetBannedTokenStore::default());
+        let result = validate_token(&token, session_audience(), key_ring, banned_token_store).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_signed_with_rotated_out_key() {
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let key_ring = key_ring();
+        let token = generate_auth_token(
+            &email,
+            &UserAccess::default(),
+            None,
+            session_audience(),
+            &*key_ring.read().await,
+        )
+        .unwrap();
+        let banned_token_store = Arc::new(HashsetBannedTokenStore::default());
+
+        key_ring.write().await.rotate(
+            SigningKey::generate(jsonwebtoken::Algorithm::ES256).unwrap(),
+            Utc::now().timestamp() + TOKEN_TTL_SECONDS,
+        );
+
+        let result = validate_token(&token, session_audience(), key_ring, banned_token_store)
+            .await
+            .unwrap();
+        assert_eq!(result.sub, "test@example.com");
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_signed_with_retired_key() {
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let key_ring = key_ring();
+        let token = generate_auth_token(
+            &email,
+            &UserAccess::default(),
+            None,
+            session_audience(),
+            &*key_ring.read().await,
+        )
+        .unwrap();
+        let banned_token_store = Arc::new(HashsetBannedTokenStore::default());
+
+        key_ring.write().await.rotate(
+            SigningKey::generate(jsonwebtoken::Algorithm::ES256).unwrap(),
+            Utc::now().timestamp() - 1,
+        );
+
+        let result = validate_token(&token, session_audience(), key_ring, banned_token_store).await;
+        assert!(result.is_err());
+    }
+
+    fn valid_claims() -> Claims {
+        let now = Utc::now().timestamp() as usize;
+        Claims {
+            sub: "test@example.com".to_owned(),
+            exp: now + TOKEN_TTL_SECONDS as usize,
+            iat: now,
+            iat_ms: None,
+            nbf: now,
+            jti: Uuid::new_v4().to_string(),
+            iss: JWT_ISSUER.clone(),
+            aud: session_audience().to_owned(),
+            roles: vec![],
+            permissions: vec![],
+            org_id: None,
+            org_role: None,
+        }
+    }
+
+    async fn validate_claims(claims: &Claims) -> Result<Claims, jsonwebtoken::errors::Error> {
+        let key_ring = key_ring();
+        let token = create_token(claims, key_ring.read().await.active()).unwrap();
+        let banned_token_store = Arc::new(HashsetBannedTokenStore::default());
+        validate_token(&token, session_audience(), key_ring, banned_token_store).await
+    }
+
+    #[tokio::test]
+    async fn test_generate_auth_token_claims() {
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let key_ring = key_ring();
+        let banned_token_store = Arc::new(HashsetBannedTokenStore::default());
+
+        let first = generate_auth_token(
+            &email,
+            &UserAccess::default(),
+            None,
+            session_audience(),
+            &*key_ring.read().await,
+        )
+        .unwrap();
+        let second = generate_auth_token(
+            &email,
+            &UserAccess::default(),
+            None,
+            session_audience(),
+            &*key_ring.read().await,
+        )
+        .unwrap();
+
+        let first = validate_token(
+            &first,
+            session_audience(),
+            key_ring.clone(),
+            banned_token_store.clone(),
+        )
+        .await
+        .unwrap();
+        let second = validate_token(&second, session_audience(), key_ring, banned_token_store)
+            .await
+            .unwrap();
+
+        assert_eq!(first.iss, *JWT_ISSUER);
+        assert_eq!(first.aud, session_audience());
+        assert_eq!(first.nbf, first.iat);
+        assert_ne!(first.jti, second.jti);
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_with_wrong_issuer() {
+        let claims = Claims {
+            iss: "https://evil.example.com".to_owned(),
+            ..valid_claims()
+        };
+        assert!(validate_claims(&claims).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_with_wrong_audience() {
+        let claims = Claims {
+            aud: "some-other-service".to_owned(),
+            ..valid_claims()
+        };
+        assert!(validate_claims(&claims).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_only_for_its_audience() {
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let key_ring = key_ring();
+        let banned_token_store = Arc::new(HashsetBannedTokenStore::default());
+
+        let token = generate_access_token(
+            &email,
+            None,
+            "billing-service",
+            key_ring.clone(),
+            role_store(),
+            organization_store(),
+        )
+        .await
+        .unwrap();
+
+        let claims = validate_token(
+            &token,
+            "billing-service",
+            key_ring.clone(),
+            banned_token_store.clone(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(claims.aud, "billing-service");
+
+        // neither another consumer nor this service take it in place of their own tokens
+        let result = validate_token(&token, session_audience(), key_ring, banned_token_store).await;
         assert!(result.is_err());
     }
+
+    #[tokio::test]
+    async fn test_validate_token_not_yet_valid() {
+        let claims = valid_claims();
+        let claims = Claims {
+            nbf: claims.iat + *JWT_LEEWAY_SECONDS as usize + 60,
+            ..claims
+        };
+        assert!(validate_claims(&claims).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_within_leeway() {
+        // a token minted by a server whose clock is slightly ahead, or that just expired
+        let claims = valid_claims();
+        let claims = Claims {
+            nbf: claims.iat + 5,
+            exp: claims.iat - 5,
+            ..claims
+        };
+        assert!(validate_claims(&claims).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_with_banned_jti() {
+        let claims = valid_claims();
+        let key_ring = key_ring();
+        let token = create_token(&claims, key_ring.read().await.active()).unwrap();
+        let banned_token_store = Arc::new(HashsetBannedTokenStore::default());
+
+        banned_token_store
+            .store_token(claims.jti.clone())
+            .await
+            .unwrap();
+
+        let result = validate_token(&token, session_audience(), key_ring, banned_token_store).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_generate_auth_cookie_embeds_roles() {
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let key_ring = key_ring();
+        let role_store = role_store();
+        let banned_token_store = Arc::new(HashsetBannedTokenStore::default());
+
+        role_store
+            .write()
+            .await
+            .assign_role(&email, &Role::parse(Role::ADMIN.to_owned()).unwrap())
+            .await
+            .unwrap();
+
+        let cookie = generate_auth_cookie(
+            &email,
+            None,
+            key_ring.clone(),
+            role_store,
+            organization_store(),
+        )
+        .await
+        .unwrap();
+        let claims = validate_token(
+            cookie.value(),
+            session_audience(),
+            key_ring,
+            banned_token_store,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(claims.roles, vec![Role::ADMIN.to_owned()]);
+        assert!(claims.has_permission(Permission::ROLES_MANAGE));
+        assert!(!claims.has_permission("billing:read"));
+    }
+
+    #[tokio::test]
+    async fn test_generate_auth_cookie_embeds_session_organization() {
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let key_ring = key_ring();
+        let organization_store = organization_store();
+        let banned_token_store = Arc::new(HashsetBannedTokenStore::default());
+
+        let cookie = generate_auth_cookie(
+            &email,
+            None,
+            key_ring.clone(),
+            role_store(),
+            organization_store.clone(),
+        )
+        .await
+        .unwrap();
+        let claims = validate_token(
+            cookie.value(),
+            session_audience(),
+            key_ring.clone(),
+            banned_token_store.clone(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(claims.org_id, None);
+        assert_eq!(claims.org_role, None);
+
+        let organization = organization_store
+            .write()
+            .await
+            .create_organization(OrganizationName::parse("Acme".to_owned()).unwrap(), &email)
+            .await
+            .unwrap();
+
+        let cookie = generate_auth_cookie(
+            &email,
+            Some(&organization.id),
+            key_ring.clone(),
+            role_store(),
+            organization_store,
+        )
+        .await
+        .unwrap();
+        let claims = validate_token(
+            cookie.value(),
+            session_audience(),
+            key_ring,
+            banned_token_store,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(claims.org_id, Some(organization.id.to_string()));
+        assert_eq!(claims.org_role, Some("owner".to_owned()));
+    }
+
+    #[tokio::test]
+    async fn test_generate_auth_cookie_leaves_out_organization_of_non_member() {
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let key_ring = key_ring();
+        let banned_token_store = Arc::new(HashsetBannedTokenStore::default());
+
+        let cookie = generate_auth_cookie(
+            &email,
+            Some(&OrganizationId::default()),
+            key_ring.clone(),
+            role_store(),
+            organization_store(),
+        )
+        .await
+        .unwrap();
+        let claims = validate_token(
+            cookie.value(),
+            session_audience(),
+            key_ring,
+            banned_token_store,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(claims.org_id, None);
+        assert_eq!(claims.org_role, None);
+    }
 }
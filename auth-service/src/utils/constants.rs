@@ -1,12 +1,73 @@
 use dotenvy::dotenv;
 use lazy_static::lazy_static;
-use std::env as std_env;
+use std::{collections::HashMap, env as std_env, time::Duration};
+
+use super::jwt::SigningKey;
+use crate::domain::{EmailProvider, EphemeralStoreBackend, RateLimit};
 
 // Define a lazily evaluated static. lazy_static is needed because std_env::var is not a const function.
 lazy_static! {
     pub static ref JWT_SECRET: String = set_token();
+    pub static ref JWT_SIGNING_KEY: SigningKey = set_signing_key();
+    pub static ref JWT_VERIFICATION_KEYS: Vec<SigningKey> = set_verification_keys();
+    pub static ref ADMIN_API_KEY: Option<String> = set_admin_api_key();
+    pub static ref INTROSPECTION_CLIENTS: HashMap<String, String> = set_introspection_clients();
     pub static ref DATABASE_URL: String = set_db_url();
+    pub static ref SQLITE_DATABASE_URL: Option<String> =
+        set_optional(env::SQLITE_DATABASE_URL_ENV_VAR);
     pub static ref REDIS_HOST_NAME: String = set_redis_host();
+    pub static ref AUTH_SERVICE_URL: String = set_auth_service_url();
+    pub static ref SMTP_HOST: Option<String> = set_optional(env::SMTP_HOST_ENV_VAR);
+    pub static ref SMTP_PORT: Option<u16> = set_smtp_port();
+    pub static ref SMTP_TLS: String = set_smtp_tls();
+    pub static ref SMTP_USERNAME: Option<String> = set_optional(env::SMTP_USERNAME_ENV_VAR);
+    pub static ref SMTP_PASSWORD: Option<String> = set_optional(env::SMTP_PASSWORD_ENV_VAR);
+    pub static ref EMAIL_SENDER: String = set_email_sender();
+    pub static ref EMAIL_PROVIDER: EmailProvider = set_email_provider();
+    pub static ref EMAIL_API_URL: Option<String> = set_optional(env::EMAIL_API_URL_ENV_VAR);
+    pub static ref EMAIL_API_TOKEN: Option<String> = set_optional(env::EMAIL_API_TOKEN_ENV_VAR);
+    pub static ref EMAIL_API_TIMEOUT: Duration = set_seconds(
+        env::EMAIL_API_TIMEOUT_SECONDS_ENV_VAR,
+        DEFAULT_EMAIL_API_TIMEOUT_SECONDS
+    );
+    pub static ref EMAIL_API_CONNECT_TIMEOUT: Duration = set_seconds(
+        env::EMAIL_API_CONNECT_TIMEOUT_SECONDS_ENV_VAR,
+        DEFAULT_EMAIL_API_CONNECT_TIMEOUT_SECONDS
+    );
+    pub static ref EMAIL_API_MAX_RETRIES: u32 = set_email_api_max_retries();
+    pub static ref JWT_ISSUER: String = set_jwt_issuer();
+    pub static ref JWT_AUDIENCE: Vec<String> = set_jwt_audience();
+    pub static ref JWT_LEEWAY_SECONDS: u64 = set_jwt_leeway();
+    pub static ref TOTP_ENCRYPTION_KEY: String = set_totp_encryption_key();
+    pub static ref WEBAUTHN_RP_ID: String = set_webauthn_rp_id();
+    pub static ref WEBAUTHN_ORIGIN: String = set_webauthn_origin();
+    pub static ref LOGIN_LOCKOUT_THRESHOLD: u32 = set_login_lockout_threshold();
+    pub static ref LOGIN_IP_LOCKOUT_THRESHOLD: u32 = set_login_ip_lockout_threshold();
+    pub static ref LOGIN_LOCKOUT_SECONDS: i64 = set_login_lockout_seconds();
+    pub static ref MAX_PENDING_LOGIN_ATTEMPTS: usize = set_max_pending_login_attempts();
+    pub static ref EPHEMERAL_STORE: EphemeralStoreBackend = set_ephemeral_store();
+    pub static ref EXPIRED_ROWS_PURGE_INTERVAL: Duration = set_seconds(
+        env::EXPIRED_ROWS_PURGE_INTERVAL_SECONDS_ENV_VAR,
+        DEFAULT_EXPIRED_ROWS_PURGE_INTERVAL_SECONDS
+    );
+    pub static ref JWT_KEY_REFRESH_INTERVAL: Duration = set_seconds(
+        env::JWT_KEY_REFRESH_INTERVAL_SECONDS_ENV_VAR,
+        DEFAULT_JWT_KEY_REFRESH_INTERVAL_SECONDS
+    );
+    pub static ref GLOBAL_RATE_LIMIT: RateLimit =
+        set_rate_limit(env::GLOBAL_RATE_LIMIT_ENV_VAR, DEFAULT_GLOBAL_RATE_LIMIT);
+    pub static ref SIGNUP_RATE_LIMIT: RateLimit =
+        set_rate_limit(env::SIGNUP_RATE_LIMIT_ENV_VAR, DEFAULT_SIGNUP_RATE_LIMIT);
+    pub static ref LOGIN_RATE_LIMIT: RateLimit =
+        set_rate_limit(env::LOGIN_RATE_LIMIT_ENV_VAR, DEFAULT_LOGIN_RATE_LIMIT);
+    pub static ref VERIFY_2FA_RATE_LIMIT: RateLimit = set_rate_limit(
+        env::VERIFY_2FA_RATE_LIMIT_ENV_VAR,
+        DEFAULT_VERIFY_2FA_RATE_LIMIT
+    );
+    pub static ref PASSWORD_RESET_RATE_LIMIT: RateLimit = set_rate_limit(
+        env::PASSWORD_RESET_RATE_LIMIT_ENV_VAR,
+        DEFAULT_PASSWORD_RESET_RATE_LIMIT
+    );
 }
 
 fn set_token() -> String {
@@ -18,6 +79,69 @@ fn set_token() -> String {
     secret
 }
 
+// Sign with the private key at JWT_SIGNING_KEY_PATH when set, otherwise fall back to HS256 with JWT_SECRET
+fn set_signing_key() -> SigningKey {
+    dotenv().ok();
+    match std_env::var(env::JWT_SIGNING_KEY_PATH_ENV_VAR) {
+        Ok(path) if !path.is_empty() => read_signing_key(&path),
+        _ => SigningKey::from_secret(JWT_SECRET.as_bytes()),
+    }
+}
+
+fn read_signing_key(path: &str) -> SigningKey {
+    let pem = std::fs::read_to_string(path)
+        .unwrap_or_else(|e| panic!("Failed to read JWT signing key {}: {}", path, e));
+    SigningKey::from_pem(&pem)
+        .unwrap_or_else(|e| panic!("Invalid JWT signing key {}: {:?}", path, e))
+}
+
+// Previous keys that still verify tokens but never sign new ones, so a key can be replaced with a
+// restart without logging everyone out. Both variables take a comma separated list.
+fn set_verification_keys() -> Vec<SigningKey> {
+    dotenv().ok();
+    let list = |name: &str| -> Vec<String> {
+        std_env::var(name)
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(str::to_owned)
+            .collect()
+    };
+
+    let pem_keys = list(env::JWT_VERIFICATION_KEY_PATHS_ENV_VAR)
+        .into_iter()
+        .map(|path| read_signing_key(&path));
+    let secret_keys = list(env::JWT_VERIFICATION_SECRETS_ENV_VAR)
+        .into_iter()
+        .map(|secret| SigningKey::from_secret(secret.as_bytes()));
+
+    pem_keys.chain(secret_keys).collect()
+}
+
+// The admin API is disabled unless a key is configured
+fn set_admin_api_key() -> Option<String> {
+    dotenv().ok();
+    std_env::var(env::ADMIN_API_KEY_ENV_VAR)
+        .ok()
+        .filter(|key| !key.is_empty())
+}
+
+// Clients allowed to call the introspection endpoint, configured as `client_id:client_secret`
+// pairs separated by commas
+fn set_introspection_clients() -> HashMap<String, String> {
+    dotenv().ok();
+    std_env::var(env::INTROSPECTION_CLIENTS_ENV_VAR)
+        .unwrap_or_default()
+        .split(',')
+        .filter_map(|client| {
+            let (client_id, client_secret) = client.trim().split_once(':')?;
+            (!client_id.is_empty() && !client_secret.is_empty())
+                .then(|| (client_id.to_owned(), client_secret.to_owned()))
+        })
+        .collect()
+}
+
 fn set_db_url() -> String {
     dotenv().ok(); // Load environment variables
     let secret = std_env::var(env::DATABASE_URL_ENV_VAR).expect("DB URL must be set.");
@@ -32,14 +156,269 @@ fn set_redis_host() -> String {
     std_env::var(env::REDIS_HOST_NAME_ENV_VAR).unwrap_or(DEFAULT_REDIS_HOSTNAME.to_owned())
 }
 
+fn set_auth_service_url() -> String {
+    dotenv().ok();
+    std_env::var(env::AUTH_SERVICE_URL_ENV_VAR).unwrap_or(DEFAULT_AUTH_SERVICE_URL.to_owned())
+}
+
+fn set_optional(name: &str) -> Option<String> {
+    dotenv().ok();
+    std_env::var(name).ok().filter(|value| !value.is_empty())
+}
+
+// Defaults to the usual port of the TLS mode when unset
+fn set_smtp_port() -> Option<u16> {
+    set_optional(env::SMTP_PORT_ENV_VAR)
+        .map(|port| port.parse().expect("SMTP_PORT must be a port number."))
+}
+
+// `starttls` upgrades a plain connection, `tls` connects with TLS right away and `none` is only
+// meant for local mail sinks
+fn set_smtp_tls() -> String {
+    set_optional(env::SMTP_TLS_ENV_VAR).unwrap_or(DEFAULT_SMTP_TLS.to_owned())
+}
+
+// The From address of every email, either `address` or `Name <address>`
+fn set_email_sender() -> String {
+    set_optional(env::EMAIL_SENDER_ENV_VAR).unwrap_or(DEFAULT_EMAIL_SENDER.to_owned())
+}
+
+// `mock`, `smtp` or `http`. Without it emails go out over SMTP once SMTP_HOST is set, and are only
+// printed otherwise.
+fn set_email_provider() -> EmailProvider {
+    match set_optional(env::EMAIL_PROVIDER_ENV_VAR) {
+        Some(provider) => EmailProvider::parse(&provider).expect("Invalid EMAIL_PROVIDER."),
+        None if SMTP_HOST.is_some() => EmailProvider::Smtp,
+        None => EmailProvider::Mock,
+    }
+}
+
+// `redis` (the default) or `postgres`. Only banned tokens and pending 2FA logins move, so Redis is
+// still required with `postgres`.
+fn set_ephemeral_store() -> EphemeralStoreBackend {
+    set_optional(env::EPHEMERAL_STORE_ENV_VAR)
+        .map(|backend| EphemeralStoreBackend::parse(&backend).expect("Invalid EPHEMERAL_STORE."))
+        .unwrap_or(EphemeralStoreBackend::Redis)
+}
+
+fn set_seconds(name: &str, default: u64) -> Duration {
+    let seconds = set_optional(name)
+        .map(|seconds| {
+            seconds
+                .parse()
+                .expect("Timeouts must be a number of seconds.")
+        })
+        .unwrap_or(default);
+    Duration::from_secs(seconds)
+}
+
+// Retries of a request the email API failed with a server error, on top of the outbox's own retries
+fn set_email_api_max_retries() -> u32 {
+    set_optional(env::EMAIL_API_MAX_RETRIES_ENV_VAR)
+        .map(|retries| {
+            retries
+                .parse()
+                .expect("EMAIL_API_MAX_RETRIES must be a number.")
+        })
+        .unwrap_or(DEFAULT_EMAIL_API_MAX_RETRIES)
+}
+
+// Identifies this service in the `iss` claim, the public URL unless configured otherwise
+fn set_jwt_issuer() -> String {
+    dotenv().ok();
+    std_env::var(env::JWT_ISSUER_ENV_VAR)
+        .ok()
+        .filter(|issuer| !issuer.is_empty())
+        .unwrap_or_else(|| AUTH_SERVICE_URL.clone())
+}
+
+// Services tokens can be issued for, comma separated. Each token names one of them in `aud`, tokens
+// in the auth cookie the first one.
+fn set_jwt_audience() -> Vec<String> {
+    dotenv().ok();
+    let audience: Vec<String> = std_env::var(env::JWT_AUDIENCE_ENV_VAR)
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|audience| !audience.is_empty())
+        .map(str::to_owned)
+        .collect();
+
+    if audience.is_empty() {
+        vec![DEFAULT_JWT_AUDIENCE.to_owned()]
+    } else {
+        audience
+    }
+}
+
+// Clock skew tolerated when checking `exp` and `nbf` of tokens
+fn set_jwt_leeway() -> u64 {
+    dotenv().ok();
+    std_env::var(env::JWT_LEEWAY_SECONDS_ENV_VAR)
+        .ok()
+        .and_then(|leeway| leeway.parse().ok())
+        .unwrap_or(DEFAULT_JWT_LEEWAY_SECONDS)
+}
+
+fn set_totp_encryption_key() -> String {
+    dotenv().ok(); // Load environment variables
+    let key =
+        std_env::var(env::TOTP_ENCRYPTION_KEY_ENV_VAR).expect("TOTP_ENCRYPTION_KEY must be set.");
+    if key.is_empty() {
+        panic!("TOTP_ENCRYPTION_KEY must not be empty.");
+    }
+    key
+}
+
+fn set_webauthn_rp_id() -> String {
+    dotenv().ok();
+    std_env::var(env::WEBAUTHN_RP_ID_ENV_VAR).unwrap_or(DEFAULT_WEBAUTHN_RP_ID.to_owned())
+}
+
+// Browsers report the origin of the page that ran the ceremony, by default that is the auth service itself
+fn set_webauthn_origin() -> String {
+    dotenv().ok();
+    std_env::var(env::WEBAUTHN_ORIGIN_ENV_VAR).unwrap_or(AUTH_SERVICE_URL.to_owned())
+}
+
+// Failed logins for one account before it is locked
+fn set_login_lockout_threshold() -> u32 {
+    dotenv().ok();
+    std_env::var(env::LOGIN_LOCKOUT_THRESHOLD_ENV_VAR)
+        .ok()
+        .and_then(|threshold| threshold.parse().ok())
+        .filter(|threshold| *threshold > 0)
+        .unwrap_or(DEFAULT_LOGIN_LOCKOUT_THRESHOLD)
+}
+
+// Failed logins from one client address before it is locked, higher than the account threshold
+// since several users can share an address
+fn set_login_ip_lockout_threshold() -> u32 {
+    dotenv().ok();
+    std_env::var(env::LOGIN_IP_LOCKOUT_THRESHOLD_ENV_VAR)
+        .ok()
+        .and_then(|threshold| threshold.parse().ok())
+        .filter(|threshold| *threshold > 0)
+        .unwrap_or(DEFAULT_LOGIN_IP_LOCKOUT_THRESHOLD)
+}
+
+// Length of the first lock, every further failure doubles it
+fn set_login_lockout_seconds() -> i64 {
+    dotenv().ok();
+    std_env::var(env::LOGIN_LOCKOUT_SECONDS_ENV_VAR)
+        .ok()
+        .and_then(|seconds| seconds.parse().ok())
+        .filter(|seconds| *seconds > 0)
+        .unwrap_or(DEFAULT_LOGIN_LOCKOUT_SECONDS)
+}
+
+// 2FA logins a user can have started but not finished at the same time, e.g. one per device
+fn set_max_pending_login_attempts() -> usize {
+    dotenv().ok();
+    std_env::var(env::MAX_PENDING_LOGIN_ATTEMPTS_ENV_VAR)
+        .ok()
+        .and_then(|max| max.parse().ok())
+        .filter(|max| *max > 0)
+        .unwrap_or(DEFAULT_MAX_PENDING_LOGIN_ATTEMPTS)
+}
+
+// Request limits are written as `capacity/period_seconds`, see `RateLimit::parse`
+fn set_rate_limit(name: &str, default: RateLimit) -> RateLimit {
+    dotenv().ok();
+    std_env::var(name)
+        .ok()
+        .and_then(|limit| RateLimit::parse(&limit).ok())
+        .unwrap_or(default)
+}
+
 pub mod env {
     pub const JWT_SECRET_ENV_VAR: &str = "JWT_SECRET";
+    pub const JWT_SIGNING_KEY_PATH_ENV_VAR: &str = "JWT_SIGNING_KEY_PATH";
+    pub const JWT_VERIFICATION_KEY_PATHS_ENV_VAR: &str = "JWT_VERIFICATION_KEY_PATHS";
+    pub const JWT_VERIFICATION_SECRETS_ENV_VAR: &str = "JWT_VERIFICATION_SECRETS";
+    pub const ADMIN_API_KEY_ENV_VAR: &str = "ADMIN_API_KEY";
+    pub const INTROSPECTION_CLIENTS_ENV_VAR: &str = "INTROSPECTION_CLIENTS";
     pub const DATABASE_URL_ENV_VAR: &str = "DATABASE_URL";
+    pub const SQLITE_DATABASE_URL_ENV_VAR: &str = "SQLITE_DATABASE_URL";
     pub const REDIS_HOST_NAME_ENV_VAR: &str = "REDIS_HOST_NAME";
+    pub const AUTH_SERVICE_URL_ENV_VAR: &str = "AUTH_SERVICE_URL";
+    pub const SMTP_HOST_ENV_VAR: &str = "SMTP_HOST";
+    pub const SMTP_PORT_ENV_VAR: &str = "SMTP_PORT";
+    pub const SMTP_TLS_ENV_VAR: &str = "SMTP_TLS";
+    pub const SMTP_USERNAME_ENV_VAR: &str = "SMTP_USERNAME";
+    pub const SMTP_PASSWORD_ENV_VAR: &str = "SMTP_PASSWORD";
+    pub const EMAIL_SENDER_ENV_VAR: &str = "EMAIL_SENDER";
+    pub const EMAIL_PROVIDER_ENV_VAR: &str = "EMAIL_PROVIDER";
+    pub const EMAIL_API_URL_ENV_VAR: &str = "EMAIL_API_URL";
+    pub const EMAIL_API_TOKEN_ENV_VAR: &str = "EMAIL_API_TOKEN";
+    pub const EMAIL_API_TIMEOUT_SECONDS_ENV_VAR: &str = "EMAIL_API_TIMEOUT_SECONDS";
+    pub const EMAIL_API_CONNECT_TIMEOUT_SECONDS_ENV_VAR: &str = "EMAIL_API_CONNECT_TIMEOUT_SECONDS";
+    pub const EMAIL_API_MAX_RETRIES_ENV_VAR: &str = "EMAIL_API_MAX_RETRIES";
+    pub const JWT_ISSUER_ENV_VAR: &str = "JWT_ISSUER";
+    pub const JWT_AUDIENCE_ENV_VAR: &str = "JWT_AUDIENCE";
+    pub const JWT_LEEWAY_SECONDS_ENV_VAR: &str = "JWT_LEEWAY_SECONDS";
+    pub const TOTP_ENCRYPTION_KEY_ENV_VAR: &str = "TOTP_ENCRYPTION_KEY";
+    pub const WEBAUTHN_RP_ID_ENV_VAR: &str = "WEBAUTHN_RP_ID";
+    pub const WEBAUTHN_ORIGIN_ENV_VAR: &str = "WEBAUTHN_ORIGIN";
+    pub const LOGIN_LOCKOUT_THRESHOLD_ENV_VAR: &str = "LOGIN_LOCKOUT_THRESHOLD";
+    pub const LOGIN_IP_LOCKOUT_THRESHOLD_ENV_VAR: &str = "LOGIN_IP_LOCKOUT_THRESHOLD";
+    pub const LOGIN_LOCKOUT_SECONDS_ENV_VAR: &str = "LOGIN_LOCKOUT_SECONDS";
+    pub const MAX_PENDING_LOGIN_ATTEMPTS_ENV_VAR: &str = "MAX_PENDING_LOGIN_ATTEMPTS";
+    pub const EPHEMERAL_STORE_ENV_VAR: &str = "EPHEMERAL_STORE";
+    pub const EXPIRED_ROWS_PURGE_INTERVAL_SECONDS_ENV_VAR: &str =
+        "EXPIRED_ROWS_PURGE_INTERVAL_SECONDS";
+    pub const JWT_KEY_REFRESH_INTERVAL_SECONDS_ENV_VAR: &str = "JWT_KEY_REFRESH_INTERVAL_SECONDS";
+    pub const GLOBAL_RATE_LIMIT_ENV_VAR: &str = "GLOBAL_RATE_LIMIT";
+    pub const SIGNUP_RATE_LIMIT_ENV_VAR: &str = "SIGNUP_RATE_LIMIT";
+    pub const LOGIN_RATE_LIMIT_ENV_VAR: &str = "LOGIN_RATE_LIMIT";
+    pub const VERIFY_2FA_RATE_LIMIT_ENV_VAR: &str = "VERIFY_2FA_RATE_LIMIT";
+    pub const PASSWORD_RESET_RATE_LIMIT_ENV_VAR: &str = "PASSWORD_RESET_RATE_LIMIT";
 }
 
 pub const JWT_COOKIE_NAME: &str = "jwt";
+pub const REFRESH_COOKIE_NAME: &str = "refresh_token";
 pub const DEFAULT_REDIS_HOSTNAME: &str = "127.0.0.1";
+// Public base URL of the auth service, used to build links sent by email
+pub const DEFAULT_AUTH_SERVICE_URL: &str = "http://localhost:3000";
+pub const DEFAULT_SMTP_TLS: &str = "starttls";
+pub const DEFAULT_EMAIL_SENDER: &str = "Auth Service <no-reply@localhost>";
+pub const DEFAULT_EMAIL_API_TIMEOUT_SECONDS: u64 = 10;
+pub const DEFAULT_EMAIL_API_CONNECT_TIMEOUT_SECONDS: u64 = 5;
+pub const DEFAULT_EMAIL_API_MAX_RETRIES: u32 = 2;
+// WebAuthn relying party ID, the domain credentials are scoped to
+pub const DEFAULT_WEBAUTHN_RP_ID: &str = "localhost";
+pub const DEFAULT_JWT_AUDIENCE: &str = "app-service";
+pub const DEFAULT_JWT_LEEWAY_SECONDS: u64 = 60;
+pub const DEFAULT_LOGIN_LOCKOUT_THRESHOLD: u32 = 5;
+pub const DEFAULT_LOGIN_IP_LOCKOUT_THRESHOLD: u32 = 20;
+pub const DEFAULT_LOGIN_LOCKOUT_SECONDS: i64 = 60;
+pub const DEFAULT_MAX_PENDING_LOGIN_ATTEMPTS: usize = 5;
+// How often the Postgres ephemeral stores delete their expired rows
+pub const DEFAULT_EXPIRED_ROWS_PURGE_INTERVAL_SECONDS: u64 = 300;
+// How often every instance reloads the signing keys rotated in by the others
+pub const DEFAULT_JWT_KEY_REFRESH_INTERVAL_SECONDS: u64 = 30;
+// Every route, per client address
+pub const DEFAULT_GLOBAL_RATE_LIMIT: RateLimit = RateLimit {
+    capacity: 300,
+    period_seconds: 60,
+};
+// The routes below are also limited per email address in the request body
+pub const DEFAULT_SIGNUP_RATE_LIMIT: RateLimit = RateLimit {
+    capacity: 10,
+    period_seconds: 60,
+};
+pub const DEFAULT_LOGIN_RATE_LIMIT: RateLimit = RateLimit {
+    capacity: 30,
+    period_seconds: 60,
+};
+pub const DEFAULT_VERIFY_2FA_RATE_LIMIT: RateLimit = RateLimit {
+    capacity: 30,
+    period_seconds: 60,
+};
+pub const DEFAULT_PASSWORD_RESET_RATE_LIMIT: RateLimit = RateLimit {
+    capacity: 5,
+    period_seconds: 300,
+};
 
 pub mod prod {
     pub const APP_ADDRESS: &str = "0.0.0.0:3000";
@@ -0,0 +1,290 @@
+use std::{sync::Arc, time::Duration};
+
+use tokio::{sync::Notify, task::JoinSet};
+
+use crate::{
+    app_state::{EmailClientType, EmailOutboxStoreType},
+    domain::{Email, EmailIdempotencyKey, EmailMessage, EmailOutboxStoreError, OutboxEmail},
+};
+
+// How the worker retries emails the mail provider refused
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EmailRetryPolicy {
+    // attempts before an email is dead-lettered
+    pub max_attempts: u32,
+    // the wait after the first failure, doubled after every further one up to `max_backoff`
+    pub base_backoff: Duration,
+    pub max_backoff: Duration,
+    // how often the worker looks for due emails when it is not woken up by a new one
+    pub poll_interval: Duration,
+    // a send taking longer counts as failed
+    pub send_timeout: Duration,
+    // emails claimed and sent concurrently in one go
+    pub batch_size: u32,
+}
+
+impl EmailRetryPolicy {
+    // The wait before the next attempt, `None` once the email has used up its attempts
+    pub fn retry_in(&self, attempts: u32) -> Option<Duration> {
+        if attempts >= self.max_attempts {
+            return None;
+        }
+
+        let backoff = self
+            .base_backoff
+            .saturating_mul(2u32.saturating_pow(attempts.saturating_sub(1)));
+        Some(backoff.min(self.max_backoff))
+    }
+
+    // Long enough for every send of a batch to finish or time out before another worker may claim it
+    fn lease(&self) -> Duration {
+        self.send_timeout * 2
+    }
+}
+
+impl Default for EmailRetryPolicy {
+    // About an hour of retries before an email is given up on
+    fn default() -> Self {
+        Self {
+            max_attempts: 8,
+            base_backoff: Duration::from_secs(30),
+            max_backoff: Duration::from_secs(60 * 60),
+            poll_interval: Duration::from_secs(5),
+            send_timeout: Duration::from_secs(30),
+            batch_size: 10,
+        }
+    }
+}
+
+// Requests queue their emails here and respond straight away. A background worker delivers them,
+// so a slow or failing mail provider never holds up or fails a request.
+#[derive(Clone)]
+pub struct EmailOutbox {
+    pub store: EmailOutboxStoreType,
+    policy: EmailRetryPolicy,
+    wake: Arc<Notify>,
+}
+
+impl EmailOutbox {
+    pub fn new(store: EmailOutboxStoreType, policy: EmailRetryPolicy) -> Self {
+        Self {
+            store,
+            policy,
+            wake: Arc::new(Notify::new()),
+        }
+    }
+
+    // Queuing an email again with the same key does nothing, so a retried request sends it once
+    pub async fn enqueue(
+        &self,
+        key: &EmailIdempotencyKey,
+        recipient: &Email,
+        message: &EmailMessage,
+    ) -> Result<(), EmailOutboxStoreError> {
+        let queued = self
+            .store
+            .write()
+            .await
+            .enqueue(key, recipient, message)
+            .await?;
+
+        if queued {
+            self.wake.notify_one();
+        }
+
+        Ok(())
+    }
+
+    // Deliver queued emails until the runtime shuts down. Any number of workers may share a store.
+    pub fn spawn_worker(&self, email_client: EmailClientType) -> tokio::task::JoinHandle<()> {
+        let outbox = self.clone();
+
+        tokio::spawn(async move {
+            loop {
+                // A full batch suggests more emails are due, so look again straight away
+                match outbox.deliver_due(&email_client).await {
+                    Ok(delivered) if delivered == outbox.policy.batch_size as usize => continue,
+                    _ => {}
+                }
+
+                tokio::select! {
+                    _ = outbox.wake.notified() => {}
+                    _ = tokio::time::sleep(outbox.policy.poll_interval) => {}
+                }
+            }
+        })
+    }
+
+    // Send one batch of due emails, returning how many were claimed
+    pub async fn deliver_due(
+        &self,
+        email_client: &EmailClientType,
+    ) -> Result<usize, EmailOutboxStoreError> {
+        let emails = self
+            .store
+            .write()
+            .await
+            .claim_due(self.policy.batch_size, self.policy.lease())
+            .await?;
+        let claimed = emails.len();
+
+        let mut sends = JoinSet::new();
+        for email in emails {
+            let email_client = email_client.clone();
+            let send_timeout = self.policy.send_timeout;
+
+            sends.spawn(async move {
+                let result = send(&email_client, &email, send_timeout).await;
+                (email, result)
+            });
+        }
+
+        while let Some(joined) = sends.join_next().await {
+            let Ok((email, result)) = joined else {
+                continue;
+            };
+
+            let mut store = self.store.write().await;
+            match result {
+                Ok(()) => store.mark_sent(&email.id).await?,
+                Err(error) => {
+                    let retry_in = self.policy.retry_in(email.attempts + 1);
+                    store.mark_failed(&email.id, &error, retry_in).await?
+                }
+            }
+        }
+
+        Ok(claimed)
+    }
+}
+
+async fn send(
+    email_client: &EmailClientType,
+    email: &OutboxEmail,
+    send_timeout: Duration,
+) -> Result<(), String> {
+    let email_client = email_client.read().await;
+    // the outbox id stays the same across retries
+    let idempotency_key = email.id.to_string();
+    let send = email_client.send_email(&email.recipient, &email.message, &idempotency_key);
+
+    tokio::time::timeout(send_timeout, send)
+        .await
+        .map_err(|_| "Timed out sending the email".to_owned())?
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use tokio::sync::RwLock;
+
+    use super::*;
+    use crate::{
+        domain::{EmailClient, OutboxEmailStatus},
+        services::HashMapEmailOutboxStore,
+    };
+
+    // Refuses the first `failures` emails it is asked to send
+    struct FlakyEmailClient {
+        failures: u32,
+        attempts: AtomicU32,
+    }
+
+    #[async_trait::async_trait]
+    impl EmailClient for FlakyEmailClient {
+        async fn send_email(
+            &self,
+            _recipient: &Email,
+            _message: &EmailMessage,
+            _idempotency_key: &str,
+        ) -> Result<(), String> {
+            if self.attempts.fetch_add(1, Ordering::SeqCst) < self.failures {
+                Err("451 Try again later".to_owned())
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    fn get_policy() -> EmailRetryPolicy {
+        EmailRetryPolicy {
+            max_attempts: 3,
+            base_backoff: Duration::ZERO,
+            ..EmailRetryPolicy::default()
+        }
+    }
+
+    async fn enqueue_email(outbox: &EmailOutbox) {
+        let email = Email::parse("user@example.com".to_owned()).unwrap();
+        let message = EmailMessage {
+            subject: "2FA Code".to_owned(),
+            html_body: "<p>123456</p>".to_owned(),
+            text_body: "123456".to_owned(),
+        };
+
+        outbox
+            .enqueue(&EmailIdempotencyKey::new(&["test"]), &email, &message)
+            .await
+            .unwrap();
+    }
+
+    fn get_email_client(failures: u32) -> EmailClientType {
+        Arc::new(RwLock::new(FlakyEmailClient {
+            failures,
+            attempts: AtomicU32::new(0),
+        }))
+    }
+
+    #[test]
+    fn backoff_should_double_up_to_the_maximum() {
+        let policy = EmailRetryPolicy {
+            max_attempts: 10,
+            base_backoff: Duration::from_secs(30),
+            max_backoff: Duration::from_secs(100),
+            ..EmailRetryPolicy::default()
+        };
+
+        assert_eq!(policy.retry_in(1), Some(Duration::from_secs(30)));
+        assert_eq!(policy.retry_in(2), Some(Duration::from_secs(60)));
+        assert_eq!(policy.retry_in(3), Some(Duration::from_secs(100)));
+        assert_eq!(policy.retry_in(9), Some(Duration::from_secs(100)));
+        assert_eq!(policy.retry_in(10), None);
+    }
+
+    #[tokio::test]
+    async fn failed_email_should_be_retried_until_sent() {
+        let store: EmailOutboxStoreType = Arc::new(RwLock::new(HashMapEmailOutboxStore::default()));
+        let outbox = EmailOutbox::new(store.clone(), get_policy());
+        let email_client = get_email_client(2);
+        enqueue_email(&outbox).await;
+
+        for _ in 0..3 {
+            assert_eq!(outbox.deliver_due(&email_client).await, Ok(1));
+        }
+
+        assert_eq!(outbox.deliver_due(&email_client).await, Ok(0));
+        assert_eq!(store.read().await.count_pending().await, Ok(0));
+        assert_eq!(store.read().await.get_stuck().await, Ok(vec![]));
+    }
+
+    #[tokio::test]
+    async fn email_should_be_dead_lettered_after_max_attempts() {
+        let store: EmailOutboxStoreType = Arc::new(RwLock::new(HashMapEmailOutboxStore::default()));
+        let outbox = EmailOutbox::new(store.clone(), get_policy());
+        let email_client = get_email_client(u32::MAX);
+        enqueue_email(&outbox).await;
+
+        for _ in 0..3 {
+            assert_eq!(outbox.deliver_due(&email_client).await, Ok(1));
+        }
+
+        assert_eq!(outbox.deliver_due(&email_client).await, Ok(0));
+
+        let stuck = store.read().await.get_stuck().await.unwrap();
+        assert_eq!(stuck.len(), 1);
+        assert_eq!(stuck[0].status, OutboxEmailStatus::Dead);
+        assert_eq!(stuck[0].attempts, 3);
+        assert_eq!(stuck[0].last_error.as_deref(), Some("451 Try again later"));
+    }
+}
@@ -0,0 +1,179 @@
+use askama::Template;
+
+use crate::domain::{EmailMessage, TwoFACode};
+
+// Every kind of email has an HTML and a plain-text template under `templates/emails`, rendered into
+// one `EmailMessage`. Text templates are not escaped.
+
+pub fn two_fa_code(code: &TwoFACode) -> Result<EmailMessage, askama::Error> {
+    let code = code.as_ref();
+    render("2FA Code", TwoFACodeHtml { code }, TwoFACodeText { code })
+}
+
+pub fn password_reset(link: &str, expires_in_minutes: i64) -> Result<EmailMessage, askama::Error> {
+    render(
+        "Password reset",
+        PasswordResetHtml {
+            link,
+            expires_in_minutes,
+        },
+        PasswordResetText {
+            link,
+            expires_in_minutes,
+        },
+    )
+}
+
+pub fn verify_email(link: &str, expires_in_hours: i64) -> Result<EmailMessage, askama::Error> {
+    render(
+        "Verify your email",
+        VerifyEmailHtml {
+            link,
+            expires_in_hours,
+        },
+        VerifyEmailText {
+            link,
+            expires_in_hours,
+        },
+    )
+}
+
+pub fn account_locked(minutes: i64) -> Result<EmailMessage, askama::Error> {
+    render(
+        "Account locked",
+        AccountLockedHtml { minutes },
+        AccountLockedText { minutes },
+    )
+}
+
+pub fn organization_invitation(
+    organization: &str,
+    role: &str,
+    link: &str,
+    expires_in_days: i64,
+) -> Result<EmailMessage, askama::Error> {
+    render(
+        "Organization invitation",
+        OrganizationInvitationHtml {
+            organization,
+            role,
+            link,
+            expires_in_days,
+        },
+        OrganizationInvitationText {
+            organization,
+            role,
+            link,
+            expires_in_days,
+        },
+    )
+}
+
+fn render(
+    subject: &str,
+    html: impl Template,
+    text: impl Template,
+) -> Result<EmailMessage, askama::Error> {
+    Ok(EmailMessage {
+        subject: subject.to_owned(),
+        html_body: html.render()?,
+        text_body: text.render()?,
+    })
+}
+
+#[derive(Template)]
+#[template(path = "emails/two_fa_code.html")]
+struct TwoFACodeHtml<'a> {
+    code: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "emails/two_fa_code.txt")]
+struct TwoFACodeText<'a> {
+    code: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "emails/password_reset.html")]
+struct PasswordResetHtml<'a> {
+    link: &'a str,
+    expires_in_minutes: i64,
+}
+
+#[derive(Template)]
+#[template(path = "emails/password_reset.txt")]
+struct PasswordResetText<'a> {
+    link: &'a str,
+    expires_in_minutes: i64,
+}
+
+#[derive(Template)]
+#[template(path = "emails/verify_email.html")]
+struct VerifyEmailHtml<'a> {
+    link: &'a str,
+    expires_in_hours: i64,
+}
+
+#[derive(Template)]
+#[template(path = "emails/verify_email.txt")]
+struct VerifyEmailText<'a> {
+    link: &'a str,
+    expires_in_hours: i64,
+}
+
+#[derive(Template)]
+#[template(path = "emails/account_locked.html")]
+struct AccountLockedHtml {
+    minutes: i64,
+}
+
+#[derive(Template)]
+#[template(path = "emails/account_locked.txt")]
+struct AccountLockedText {
+    minutes: i64,
+}
+
+#[derive(Template)]
+#[template(path = "emails/organization_invitation.html")]
+struct OrganizationInvitationHtml<'a> {
+    organization: &'a str,
+    role: &'a str,
+    link: &'a str,
+    expires_in_days: i64,
+}
+
+#[derive(Template)]
+#[template(path = "emails/organization_invitation.txt")]
+struct OrganizationInvitationText<'a> {
+    organization: &'a str,
+    role: &'a str,
+    link: &'a str,
+    expires_in_days: i64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn two_fa_code_should_be_in_both_bodies() {
+        let code = TwoFACode::default();
+
+        let message = two_fa_code(&code).unwrap();
+
+        assert_eq!(message.subject, "2FA Code");
+        assert!(message.html_body.contains(code.as_ref()));
+        assert!(message.text_body.contains(code.as_ref()));
+    }
+
+    #[test]
+    fn html_body_should_be_escaped_but_text_body_should_not() {
+        let link = "https://example.com/password-reset?token=a&b";
+
+        let message = password_reset(link, 15).unwrap();
+
+        assert!(message.html_body.contains("token=a&amp;b"));
+        assert!(message.text_body.contains(link));
+        assert!(message.text_body.contains("15 minutes"));
+    }
+}
@@ -0,0 +1,56 @@
+use aes_gcm::{
+    aead::{Aead, AeadCore, KeyInit, OsRng, Payload},
+    Aes256Gcm, Key, Nonce,
+};
+use sha2::{Digest, Sha256};
+
+use super::constants::TOTP_ENCRYPTION_KEY;
+
+#[derive(Debug, PartialEq)]
+pub struct EncryptionError;
+
+const NONCE_LENGTH: usize = 12;
+
+// Secrets kept in the database (TOTP secrets, rotated JWT signing keys) are encrypted with
+// AES-256-GCM under a key derived from TOTP_ENCRYPTION_KEY. The nonce is prepended to the
+// ciphertext. `associated_data` names what the secret belongs to, so a ciphertext cannot be moved to
+// another row.
+pub fn encrypt(plaintext: &[u8], associated_data: &[u8]) -> Result<Vec<u8>, EncryptionError> {
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+
+    let ciphertext = get_cipher()
+        .encrypt(
+            &nonce,
+            Payload {
+                msg: plaintext,
+                aad: associated_data,
+            },
+        )
+        .map_err(|_| EncryptionError)?;
+
+    Ok([&nonce[..], &ciphertext].concat())
+}
+
+pub fn decrypt(encrypted: &[u8], associated_data: &[u8]) -> Result<Vec<u8>, EncryptionError> {
+    if encrypted.len() < NONCE_LENGTH {
+        return Err(EncryptionError);
+    }
+
+    let (nonce, ciphertext) = encrypted.split_at(NONCE_LENGTH);
+    let nonce: [u8; NONCE_LENGTH] = nonce.try_into().map_err(|_| EncryptionError)?;
+
+    get_cipher()
+        .decrypt(
+            &Nonce::from(nonce),
+            Payload {
+                msg: ciphertext,
+                aad: associated_data,
+            },
+        )
+        .map_err(|_| EncryptionError)
+}
+
+fn get_cipher() -> Aes256Gcm {
+    let key: [u8; 32] = Sha256::digest(TOTP_ENCRYPTION_KEY.as_bytes()).into();
+    Aes256Gcm::new(&Key::<Aes256Gcm>::from(key))
+}
@@ -0,0 +1,700 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::Utc;
+use jsonwebtoken::{
+    jwk::{
+        AlgorithmParameters, CommonParameters, EllipticCurve, EllipticCurveKeyParameters,
+        EllipticCurveKeyType, Jwk, JwkSet, KeyAlgorithm, OctetKeyPairParameters, OctetKeyPairType,
+        PublicKeyUse, RSAKeyParameters, RSAKeyType,
+    },
+    Algorithm, DecodingKey, EncodingKey, Header, Validation,
+};
+use rand::Rng;
+use ring::{
+    rand::SystemRandom,
+    rsa::PublicKeyComponents,
+    signature::{
+        EcdsaKeyPair, Ed25519KeyPair, KeyPair, RsaKeyPair, ECDSA_P256_SHA256_FIXED_SIGNING,
+    },
+};
+use sha2::{Digest, Sha256};
+use tokio::time::{Duration, Instant};
+
+use crate::{
+    app_state::{JwtKeyStoreType, KeyRingType},
+    domain::{JwtKeyStoreError, StoredJwtKey},
+};
+
+#[derive(Debug, PartialEq)]
+pub enum SigningKeyError {
+    InvalidPem,
+    UnsupportedKey,
+}
+
+// A key JWTs are signed with, identified by its `kid`. HS256 keys are a shared secret and are
+// never published, the public half of asymmetric keys is served as a JWK.
+#[derive(Clone)]
+pub struct SigningKey {
+    kid: String,
+    algorithm: Algorithm,
+    encoding_key: EncodingKey,
+    decoding_key: DecodingKey,
+    jwk: Option<Jwk>,
+    // the PEM or shared secret the key was created from, kept to persist rotated keys
+    private_key: Vec<u8>,
+}
+
+// Length of the random secret generated when rotating an HS256 key
+const GENERATED_SECRET_LENGTH: usize = 32;
+
+impl SigningKey {
+    pub fn from_secret(secret: &[u8]) -> Self {
+        // Derived from the secret so every instance sharing it agrees on the kid. Anyone holding a
+        // token can already brute force a weak secret, the hash does not make that any easier.
+        let kid = URL_SAFE_NO_PAD.encode(&Sha256::digest([b"kid:", secret].concat())[..12]);
+
+        Self {
+            kid,
+            algorithm: Algorithm::HS256,
+            encoding_key: EncodingKey::from_secret(secret),
+            decoding_key: DecodingKey::from_secret(secret),
+            jwk: None,
+            private_key: secret.to_vec(),
+        }
+    }
+
+    // Create a fresh key for the same algorithm. RSA keys cannot be generated here and have to be
+    // created offline and passed in as PEM.
+    pub fn generate(algorithm: Algorithm) -> Result<Self, SigningKeyError> {
+        let rng = SystemRandom::new();
+        let pkcs8 = match algorithm {
+            Algorithm::HS256 => {
+                let mut secret = [0u8; GENERATED_SECRET_LENGTH];
+                rand::thread_rng().fill(&mut secret[..]);
+                return Ok(Self::from_secret(&secret));
+            }
+            Algorithm::ES256 => {
+                EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &rng)
+                    .map_err(|_| SigningKeyError::UnsupportedKey)?
+                    .as_ref()
+                    .to_vec()
+            }
+            Algorithm::EdDSA => Ed25519KeyPair::generate_pkcs8(&rng)
+                .map_err(|_| SigningKeyError::UnsupportedKey)?
+                .as_ref()
+                .to_vec(),
+            _ => return Err(SigningKeyError::UnsupportedKey),
+        };
+
+        Self::from_pem(&pem::encode(&pem::Pem::new("PRIVATE KEY", pkcs8)))
+    }
+
+    // Load a private key from PEM, the algorithm follows from the key type:
+    // RSA (PKCS#1 or PKCS#8) signs with RS256, P-256 (PKCS#8) with ES256 and Ed25519 (PKCS#8) with EdDSA
+    pub fn from_pem(pem: &str) -> Result<Self, SigningKeyError> {
+        let parsed = pem::parse(pem).map_err(|_| SigningKeyError::InvalidPem)?;
+        let der = parsed.contents();
+
+        let (algorithm, encoding_key, parameters) = match parsed.tag() {
+            "RSA PRIVATE KEY" => {
+                let key_pair =
+                    RsaKeyPair::from_der(der).map_err(|_| SigningKeyError::UnsupportedKey)?;
+                (
+                    Algorithm::RS256,
+                    EncodingKey::from_rsa_der(der),
+                    rsa_parameters(&key_pair),
+                )
+            }
+            "PRIVATE KEY" => {
+                if let Ok(key_pair) = RsaKeyPair::from_pkcs8(der) {
+                    (
+                        Algorithm::RS256,
+                        EncodingKey::from_rsa_pem(pem.as_bytes())
+                            .map_err(|_| SigningKeyError::InvalidPem)?,
+                        rsa_parameters(&key_pair),
+                    )
+                } else if let Ok(key_pair) = EcdsaKeyPair::from_pkcs8(
+                    &ECDSA_P256_SHA256_FIXED_SIGNING,
+                    der,
+                    &SystemRandom::new(),
+                ) {
+                    // uncompressed point: 0x04 || x || y
+                    let point = key_pair.public_key().as_ref();
+                    (
+                        Algorithm::ES256,
+                        EncodingKey::from_ec_der(der),
+                        AlgorithmParameters::EllipticCurve(EllipticCurveKeyParameters {
+                            key_type: EllipticCurveKeyType::EC,
+                            curve: EllipticCurve::P256,
+                            x: URL_SAFE_NO_PAD.encode(&point[1..33]),
+                            y: URL_SAFE_NO_PAD.encode(&point[33..65]),
+                        }),
+                    )
+                } else if let Ok(key_pair) = Ed25519KeyPair::from_pkcs8_maybe_unchecked(der) {
+                    (
+                        Algorithm::EdDSA,
+                        EncodingKey::from_ed_der(der),
+                        AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+                            key_type: OctetKeyPairType::OctetKeyPair,
+                            curve: EllipticCurve::Ed25519,
+                            x: URL_SAFE_NO_PAD.encode(key_pair.public_key().as_ref()),
+                        }),
+                    )
+                } else {
+                    return Err(SigningKeyError::UnsupportedKey);
+                }
+            }
+            _ => return Err(SigningKeyError::UnsupportedKey),
+        };
+
+        let kid = thumbprint(&parameters);
+        let jwk = Jwk {
+            common: CommonParameters {
+                public_key_use: Some(PublicKeyUse::Signature),
+                key_algorithm: Some(key_algorithm(algorithm)),
+                key_id: Some(kid.clone()),
+                ..Default::default()
+            },
+            algorithm: parameters,
+        };
+
+        let decoding_key =
+            DecodingKey::from_jwk(&jwk).map_err(|_| SigningKeyError::UnsupportedKey)?;
+
+        Ok(Self {
+            kid,
+            algorithm,
+            encoding_key,
+            decoding_key,
+            jwk: Some(jwk),
+            private_key: pem.as_bytes().to_vec(),
+        })
+    }
+
+    // Load a key persisted by `to_stored`, it has to come back with the same kid and algorithm
+    pub fn from_stored(stored: &StoredJwtKey) -> Result<Self, SigningKeyError> {
+        let key = match stored.algorithm.as_str() {
+            "HS256" => Self::from_secret(&stored.private_key),
+            _ => Self::from_pem(
+                std::str::from_utf8(&stored.private_key)
+                    .map_err(|_| SigningKeyError::InvalidPem)?,
+            )?,
+        };
+
+        if key.kid != stored.kid || format!("{:?}", key.algorithm) != stored.algorithm {
+            return Err(SigningKeyError::UnsupportedKey);
+        }
+
+        Ok(key)
+    }
+
+    // The key as kept by a JwtKeyStore, private key included
+    pub fn to_stored(&self, retire_at: Option<i64>) -> StoredJwtKey {
+        StoredJwtKey {
+            kid: self.kid.clone(),
+            algorithm: format!("{:?}", self.algorithm),
+            private_key: self.private_key.clone(),
+            retire_at,
+        }
+    }
+
+    pub fn algorithm(&self) -> Algorithm {
+        self.algorithm
+    }
+
+    pub fn kid(&self) -> &str {
+        &self.kid
+    }
+
+    // Header for newly signed tokens, so verifiers can pick the matching key
+    pub fn header(&self) -> Header {
+        let mut header = Header::new(self.algorithm);
+        header.kid = Some(self.kid.clone());
+        header
+    }
+
+    pub fn validation(&self) -> Validation {
+        Validation::new(self.algorithm)
+    }
+
+    pub fn encoding_key(&self) -> &EncodingKey {
+        &self.encoding_key
+    }
+
+    pub fn decoding_key(&self) -> &DecodingKey {
+        &self.decoding_key
+    }
+
+    // The public key as JWK, none for shared secrets
+    pub fn jwk(&self) -> Option<&Jwk> {
+        self.jwk.as_ref()
+    }
+}
+
+// The keys tokens are signed and verified with. New tokens are always signed with the active key,
+// tokens are accepted if their `kid` names any key in the ring.
+pub struct KeyRing {
+    active: SigningKey,
+    verification_keys: Vec<VerificationKey>,
+    // JWT_SIGNING_KEY and JWT_VERIFICATION_KEYS, the keys from the store are added on top of them
+    configured_key: SigningKey,
+    configured_verification_keys: Vec<SigningKey>,
+    // Where rotated keys are shared with the other instances, without one they only live in memory
+    store: Option<JwtKeyStoreType>,
+    // When the store was last read, or is being read
+    loaded_at: Option<Instant>,
+}
+
+struct VerificationKey {
+    key: SigningKey,
+    // Unix timestamp after which the key is no longer trusted, none for keys from configuration
+    retire_at: Option<i64>,
+}
+
+impl VerificationKey {
+    fn is_trusted(&self, now: i64) -> bool {
+        self.retire_at.is_none_or(|retire_at| now < retire_at)
+    }
+}
+
+// Tokens naming a key the ring does not know reload it at most this often
+const UNKNOWN_KID_REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+
+impl KeyRing {
+    pub fn new(active: SigningKey, verification_keys: Vec<SigningKey>) -> Self {
+        Self {
+            active: active.clone(),
+            verification_keys: verification_keys
+                .iter()
+                .cloned()
+                .map(|key| VerificationKey {
+                    key,
+                    retire_at: None,
+                })
+                .collect(),
+            configured_key: active,
+            configured_verification_keys: verification_keys,
+            store: None,
+            loaded_at: None,
+        }
+    }
+
+    // Share rotations through `store`. Call `refresh_key_ring` to load the keys already in it.
+    pub fn with_store(mut self, store: JwtKeyStoreType) -> Self {
+        self.store = Some(store);
+        self
+    }
+
+    // Rebuild the ring from the stored keys. The newest active one signs, JWT_SIGNING_KEY only does
+    // as long as no key was rotated in.
+    fn load(&mut self, stored: Vec<StoredJwtKey>) -> Result<(), JwtKeyStoreError> {
+        let keys = stored
+            .iter()
+            .map(|stored| {
+                SigningKey::from_stored(stored).map(|key| VerificationKey {
+                    key,
+                    retire_at: stored.retire_at,
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| JwtKeyStoreError::UnexpectedError)?;
+
+        let (active, retired): (Vec<_>, Vec<_>) =
+            keys.into_iter().partition(|v| v.retire_at.is_none());
+        let mut active = active.into_iter();
+
+        self.active = active
+            .next()
+            .map(|v| v.key)
+            .unwrap_or_else(|| self.configured_key.clone());
+        // a store keeps a single active key, any other would still be trusted
+        self.verification_keys = self
+            .configured_verification_keys
+            .iter()
+            .cloned()
+            .map(|key| VerificationKey {
+                key,
+                retire_at: None,
+            })
+            .chain(active)
+            .chain(retired)
+            .collect();
+        self.loaded_at = Some(Instant::now());
+
+        Ok(())
+    }
+
+    pub fn active(&self) -> &SigningKey {
+        &self.active
+    }
+
+    // Find the key a token was signed with. Tokens without a `kid` predate the key ring and were
+    // signed with the key that is active at startup.
+    pub fn find(&self, kid: Option<&str>) -> Option<&SigningKey> {
+        let Some(kid) = kid else {
+            return Some(&self.active);
+        };
+
+        if self.active.kid() == kid {
+            return Some(&self.active);
+        }
+
+        let now = Utc::now().timestamp();
+        self.verification_keys
+            .iter()
+            .find(|v| v.key.kid() == kid && v.is_trusted(now))
+            .map(|v| &v.key)
+    }
+
+    // Start signing with `new_key`. The previous active key keeps verifying the tokens it already
+    // signed until `retire_at`, keys whose time has passed are dropped.
+    pub fn rotate(&mut self, new_key: SigningKey, retire_at: i64) {
+        let now = Utc::now().timestamp();
+        self.verification_keys
+            .retain(|v| v.is_trusted(now) && v.key.kid() != new_key.kid());
+
+        let previous = std::mem::replace(&mut self.active, new_key);
+        self.verification_keys.push(VerificationKey {
+            key: previous,
+            retire_at: Some(retire_at),
+        });
+    }
+
+    // Public keys to verify our tokens with, shared secrets are never included
+    pub fn jwks(&self) -> JwkSet {
+        let now = Utc::now().timestamp();
+        let verification_keys = self
+            .verification_keys
+            .iter()
+            .filter(|v| v.is_trusted(now))
+            .map(|v| &v.key);
+
+        JwkSet {
+            keys: std::iter::once(&self.active)
+                .chain(verification_keys)
+                .filter_map(|key| key.jwk().cloned())
+                .collect(),
+        }
+    }
+}
+
+// Reload the ring from its store, picking up the keys rotated in on other instances
+pub async fn refresh_key_ring(key_ring: &KeyRingType) -> Result<(), JwtKeyStoreError> {
+    let Some(store) = key_ring.read().await.store.clone() else {
+        return Ok(());
+    };
+
+    let stored = store.get_keys().await?;
+    key_ring.write().await.load(stored)
+}
+
+// Reload the ring when a token names a key it does not know, another instance may have just rotated
+// it in. Reloads are spaced out so tokens with made-up kids cannot flood the store.
+pub async fn refresh_key_ring_for_kid(key_ring: &KeyRingType, kid: Option<&str>) {
+    if key_ring.read().await.find(kid).is_some() {
+        return;
+    }
+
+    {
+        let mut key_ring = key_ring.write().await;
+        let recently_loaded = key_ring
+            .loaded_at
+            .is_some_and(|loaded_at| loaded_at.elapsed() < UNKNOWN_KID_REFRESH_INTERVAL);
+        if key_ring.store.is_none() || recently_loaded {
+            return;
+        }
+        key_ring.loaded_at = Some(Instant::now());
+    }
+
+    // the token is rejected if the store cannot be read, the next refresh tries again
+    let _ = refresh_key_ring(key_ring).await;
+}
+
+// Start signing with `new_key` on every instance sharing the ring's store. The key it replaces keeps
+// verifying the tokens it signed until `retire_at`, its kid is returned.
+pub async fn rotate_key_ring(
+    key_ring: &KeyRingType,
+    new_key: SigningKey,
+    retire_at: i64,
+) -> Result<String, JwtKeyStoreError> {
+    let mut key_ring = key_ring.write().await;
+    let retired_kid = key_ring.active().kid().to_owned();
+
+    let Some(store) = key_ring.store.clone() else {
+        key_ring.rotate(new_key, retire_at);
+        return Ok(retired_kid);
+    };
+
+    store
+        .rotate_key(
+            &new_key.to_stored(None),
+            &key_ring.active().to_stored(Some(retire_at)),
+        )
+        .await?;
+
+    let stored = store.get_keys().await?;
+    key_ring.load(stored)?;
+
+    Ok(retired_kid)
+}
+
+fn rsa_parameters(key_pair: &RsaKeyPair) -> AlgorithmParameters {
+    let components: PublicKeyComponents<Vec<u8>> = key_pair.public().into();
+    AlgorithmParameters::RSA(RSAKeyParameters {
+        key_type: RSAKeyType::RSA,
+        n: URL_SAFE_NO_PAD.encode(components.n),
+        e: URL_SAFE_NO_PAD.encode(components.e),
+    })
+}
+
+fn key_algorithm(algorithm: Algorithm) -> KeyAlgorithm {
+    match algorithm {
+        Algorithm::RS256 => KeyAlgorithm::RS256,
+        Algorithm::ES256 => KeyAlgorithm::ES256,
+        _ => KeyAlgorithm::EdDSA,
+    }
+}
+
+// RFC 7638 JWK thumbprint: SHA-256 over the required members in lexicographic order
+fn thumbprint(parameters: &AlgorithmParameters) -> String {
+    let canonical = match parameters {
+        AlgorithmParameters::RSA(p) => format!(r#"{{"e":"{}","kty":"RSA","n":"{}"}}"#, p.e, p.n),
+        AlgorithmParameters::EllipticCurve(p) => {
+            format!(
+                r#"{{"crv":"P-256","kty":"EC","x":"{}","y":"{}"}}"#,
+                p.x, p.y
+            )
+        }
+        AlgorithmParameters::OctetKeyPair(p) => {
+            format!(r#"{{"crv":"Ed25519","kty":"OKP","x":"{}"}}"#, p.x)
+        }
+        AlgorithmParameters::OctetKey(p) => format!(r#"{{"k":"{}","kty":"oct"}}"#, p.value),
+    };
+    URL_SAFE_NO_PAD.encode(Sha256::digest(canonical.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use jsonwebtoken::{decode, encode};
+    use std::sync::Arc;
+
+    use ring::signature::ECDSA_P256_SHA256_ASN1_SIGNING;
+    use serde::{Deserialize, Serialize};
+    use tokio::sync::RwLock;
+
+    use crate::services::HashMapJwtKeyStore;
+
+    use super::*;
+
+    const RSA_PRIVATE_KEY: &str = include_str!("../../tests/fixtures/rsa_private_key.pem");
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct TestClaims {
+        sub: String,
+        exp: usize,
+    }
+
+    fn to_pem(der: &[u8]) -> String {
+        pem::encode(&pem::Pem::new("PRIVATE KEY", der))
+    }
+
+    fn ec_pem() -> String {
+        let pkcs8 =
+            EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, &SystemRandom::new())
+                .unwrap();
+        to_pem(pkcs8.as_ref())
+    }
+
+    fn ed25519_pem() -> String {
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap();
+        to_pem(pkcs8.as_ref())
+    }
+
+    // Sign a token and verify it using only the published JWK, the way a consumer would
+    fn assert_verifiable_from_jwks(key: &SigningKey) {
+        let claims = TestClaims {
+            sub: "test@example.com".to_owned(),
+            exp: 4_102_444_800,
+        };
+        let token = encode(&key.header(), &claims, key.encoding_key()).unwrap();
+
+        let header = jsonwebtoken::decode_header(&token).unwrap();
+        let jwks = KeyRing::new(key.clone(), vec![]).jwks();
+        let jwk = jwks.find(&header.kid.unwrap()).unwrap();
+
+        let decoded = decode::<TestClaims>(
+            &token,
+            &DecodingKey::from_jwk(jwk).unwrap(),
+            &Validation::new(key.algorithm()),
+        )
+        .unwrap();
+
+        assert_eq!(decoded.claims, claims);
+    }
+
+    #[test]
+    fn test_rsa_key_signs_rs256() {
+        let key = SigningKey::from_pem(RSA_PRIVATE_KEY).unwrap();
+        assert_eq!(key.algorithm(), Algorithm::RS256);
+        assert_verifiable_from_jwks(&key);
+    }
+
+    #[test]
+    fn test_p256_key_signs_es256() {
+        let key = SigningKey::from_pem(&ec_pem()).unwrap();
+        assert_eq!(key.algorithm(), Algorithm::ES256);
+        assert_verifiable_from_jwks(&key);
+    }
+
+    #[test]
+    fn test_ed25519_key_signs_eddsa() {
+        let key = SigningKey::from_pem(&ed25519_pem()).unwrap();
+        assert_eq!(key.algorithm(), Algorithm::EdDSA);
+        assert_verifiable_from_jwks(&key);
+    }
+
+    #[test]
+    fn test_kid_is_stable_for_the_same_key() {
+        let pem = ec_pem();
+        let first = SigningKey::from_pem(&pem).unwrap();
+        let second = SigningKey::from_pem(&pem).unwrap();
+        assert_eq!(first.kid(), second.kid());
+        assert_ne!(first.kid(), SigningKey::from_pem(&ec_pem()).unwrap().kid());
+    }
+
+    #[test]
+    fn test_secret_key_is_not_published() {
+        let key = SigningKey::from_secret(b"secret");
+        assert_eq!(key.algorithm(), Algorithm::HS256);
+        assert_eq!(key.kid(), SigningKey::from_secret(b"secret").kid());
+        assert!(!key.kid().contains("secret"));
+        assert!(KeyRing::new(key, vec![]).jwks().keys.is_empty());
+    }
+
+    #[test]
+    fn test_generate_supports_all_but_rsa() {
+        for algorithm in [Algorithm::HS256, Algorithm::ES256, Algorithm::EdDSA] {
+            assert_eq!(
+                SigningKey::generate(algorithm).unwrap().algorithm(),
+                algorithm
+            );
+        }
+        assert_eq!(
+            SigningKey::generate(Algorithm::RS256).err(),
+            Some(SigningKeyError::UnsupportedKey)
+        );
+    }
+
+    #[test]
+    fn test_key_ring_finds_configured_verification_keys() {
+        let active = SigningKey::generate(Algorithm::EdDSA).unwrap();
+        let previous = SigningKey::generate(Algorithm::HS256).unwrap();
+        let key_ring = KeyRing::new(active.clone(), vec![previous.clone()]);
+
+        assert_eq!(
+            key_ring.find(Some(active.kid())).unwrap().kid(),
+            active.kid()
+        );
+        assert_eq!(
+            key_ring.find(Some(previous.kid())).unwrap().kid(),
+            previous.kid()
+        );
+        assert_eq!(key_ring.find(None).unwrap().kid(), active.kid());
+        assert!(key_ring.find(Some("unknown")).is_none());
+        // the shared secret is trusted but not published
+        assert_eq!(key_ring.jwks().keys.len(), 1);
+    }
+
+    #[test]
+    fn test_rotate_keeps_previous_key_until_retired() {
+        let first = SigningKey::generate(Algorithm::ES256).unwrap();
+        let second = SigningKey::generate(Algorithm::ES256).unwrap();
+        let third = SigningKey::generate(Algorithm::ES256).unwrap();
+        let mut key_ring = KeyRing::new(first.clone(), vec![]);
+        let now = Utc::now().timestamp();
+
+        key_ring.rotate(second.clone(), now + 600);
+
+        assert_eq!(key_ring.active().kid(), second.kid());
+        assert!(key_ring.find(Some(first.kid())).is_some());
+        assert_eq!(key_ring.jwks().keys.len(), 2);
+
+        // the second key's retirement time has already passed
+        key_ring.rotate(third.clone(), now - 1);
+
+        assert_eq!(key_ring.active().kid(), third.kid());
+        assert!(key_ring.find(Some(first.kid())).is_some());
+        assert!(key_ring.find(Some(second.kid())).is_none());
+        assert_eq!(key_ring.jwks().keys.len(), 2);
+    }
+
+    #[test]
+    fn test_stored_key_loads_back_as_the_same_key() {
+        let keys = [
+            SigningKey::from_pem(RSA_PRIVATE_KEY).unwrap(),
+            SigningKey::generate(Algorithm::ES256).unwrap(),
+            SigningKey::generate(Algorithm::EdDSA).unwrap(),
+            SigningKey::generate(Algorithm::HS256).unwrap(),
+        ];
+
+        for key in keys {
+            let loaded = SigningKey::from_stored(&key.to_stored(None)).unwrap();
+            assert_eq!(loaded.kid(), key.kid());
+            assert_eq!(loaded.algorithm(), key.algorithm());
+        }
+
+        let mut stored = SigningKey::generate(Algorithm::EdDSA)
+            .unwrap()
+            .to_stored(None);
+        stored.kid = "other".to_owned();
+        assert_eq!(
+            SigningKey::from_stored(&stored).err(),
+            Some(SigningKeyError::UnsupportedKey)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rotation_reaches_key_rings_sharing_the_store() {
+        let configured = SigningKey::generate(Algorithm::EdDSA).unwrap();
+        let new_key = SigningKey::generate(Algorithm::EdDSA).unwrap();
+        let store: JwtKeyStoreType = Arc::new(HashMapJwtKeyStore::default());
+        let key_ring = || {
+            Arc::new(RwLock::new(
+                KeyRing::new(configured.clone(), vec![]).with_store(store.clone()),
+            ))
+        };
+        let (first, second) = (key_ring(), key_ring());
+
+        let retired_kid = rotate_key_ring(&first, new_key.clone(), Utc::now().timestamp() + 600)
+            .await
+            .unwrap();
+
+        assert_eq!(retired_kid, configured.kid());
+        assert_eq!(first.read().await.active().kid(), new_key.kid());
+        assert_eq!(second.read().await.active().kid(), configured.kid());
+
+        // a token signed with the new key makes the other ring reload
+        refresh_key_ring_for_kid(&second, Some(new_key.kid())).await;
+
+        let second = second.read().await;
+        assert_eq!(second.active().kid(), new_key.kid());
+        assert!(second.find(Some(configured.kid())).is_some());
+        assert_eq!(second.jwks().keys.len(), 2);
+
+        // as does starting a new instance
+        let restarted = key_ring();
+        refresh_key_ring(&restarted).await.unwrap();
+        assert_eq!(restarted.read().await.active().kid(), new_key.kid());
+    }
+
+    #[test]
+    fn test_invalid_pem_is_rejected() {
+        assert_eq!(
+            SigningKey::from_pem("not a pem").err(),
+            Some(SigningKeyError::InvalidPem)
+        );
+        assert_eq!(
+            SigningKey::from_pem(&to_pem(b"garbage")).err(),
+            Some(SigningKeyError::UnsupportedKey)
+        );
+    }
+}
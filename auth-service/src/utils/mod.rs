@@ -1,5 +1,13 @@
 pub mod auth;
 pub mod constants;
+pub mod email_outbox;
+pub mod email_templates;
+pub mod encryption;
+pub mod jwt;
+pub mod permissions;
+pub mod rate_limit;
+pub mod totp;
+pub mod webauthn;
 
 // re-export items from submodules
 pub use auth::*;
@@ -0,0 +1,115 @@
+use std::marker::PhantomData;
+
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{header::AUTHORIZATION, request::Parts},
+};
+use axum_extra::extract::CookieJar;
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, Email, Permission},
+};
+
+use super::{
+    auth::{is_admin_api_key, session_audience, validate_token},
+    constants::JWT_COOKIE_NAME,
+};
+
+// A permission a route requires, see `RequirePermission`
+pub trait RequiredPermission {
+    const PERMISSION: &'static str;
+}
+
+pub struct ReadRoles;
+pub struct ManageRoles;
+pub struct RotateJwtKeys;
+pub struct ReadEmailOutbox;
+
+impl RequiredPermission for ReadRoles {
+    const PERMISSION: &'static str = Permission::ROLES_READ;
+}
+
+impl RequiredPermission for ManageRoles {
+    const PERMISSION: &'static str = Permission::ROLES_MANAGE;
+}
+
+impl RequiredPermission for RotateJwtKeys {
+    const PERMISSION: &'static str = Permission::JWT_KEYS_ROTATE;
+}
+
+impl RequiredPermission for ReadEmailOutbox {
+    const PERMISSION: &'static str = Permission::EMAIL_OUTBOX_READ;
+}
+
+// Who is calling a guarded route
+#[derive(Debug, Clone, PartialEq)]
+pub enum Principal {
+    // authenticated with the ADMIN_API_KEY, which holds every permission
+    Operator,
+    User(Email),
+}
+
+// Extractor that rejects the request unless the caller holds permission `P`. Users authenticate
+// with their JWT, as auth cookie or bearer token, and must have been granted the permission
+// through one of their roles when the token was issued.
+pub struct RequirePermission<P> {
+    pub principal: Principal,
+    permission: PhantomData<P>,
+}
+
+#[async_trait]
+impl<P> FromRequestParts<AppState> for RequirePermission<P>
+where
+    P: RequiredPermission,
+{
+    type Rejection = AuthAPIError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let bearer_token = parts
+            .headers
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map(str::to_owned);
+
+        let token = match bearer_token {
+            Some(token) if is_admin_api_key(&token) => {
+                return Ok(Self {
+                    principal: Principal::Operator,
+                    permission: PhantomData,
+                })
+            }
+            Some(token) => token,
+            None => CookieJar::from_headers(&parts.headers)
+                .get(JWT_COOKIE_NAME)
+                .ok_or(AuthAPIError::MissingToken)?
+                .value()
+                .to_owned(),
+        };
+
+        let claims = validate_token(
+            &token,
+            session_audience(),
+            state.key_ring.clone(),
+            state.banned_token_store.clone(),
+        )
+        .await
+        .map_err(|_| AuthAPIError::InvalidToken)?;
+
+        if !claims.has_permission(P::PERMISSION) {
+            return Err(AuthAPIError::MissingPermission);
+        }
+
+        let email = Email::parse(claims.sub).map_err(|_| AuthAPIError::InvalidToken)?;
+
+        Ok(Self {
+            principal: Principal::User(email),
+            permission: PhantomData,
+        })
+    }
+}
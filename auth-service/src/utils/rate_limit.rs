@@ -0,0 +1,144 @@
+use std::net::SocketAddr;
+
+use axum::{
+    body::{to_bytes, Body},
+    extract::{ConnectInfo, Request, State},
+    http::{HeaderMap, HeaderValue, StatusCode},
+    middleware::{from_fn_with_state, Next},
+    response::{IntoResponse, Response},
+    routing::MethodRouter,
+};
+use serde::Deserialize;
+
+use crate::{
+    app_state::RateLimitStoreType,
+    domain::{AuthAPIError, Email, RateLimit, RateLimitDecision, RateLimitKey},
+};
+
+// Bodies are buffered to find the email address in them, larger ones are refused like axum's
+// extractors would
+const MAX_BODY_BYTES: usize = 2 * 1024 * 1024;
+
+pub const RATE_LIMIT_LIMIT: &str = "ratelimit-limit";
+pub const RATE_LIMIT_REMAINING: &str = "ratelimit-remaining";
+pub const RATE_LIMIT_RESET: &str = "ratelimit-reset";
+
+// One limit, applied separately to every client address and, optionally, every email address
+// named in the JSON body of a request. Routes sharing a scope share their buckets.
+#[derive(Clone)]
+pub struct RateLimiter {
+    store: RateLimitStoreType,
+    scope: &'static str,
+    limit: RateLimit,
+    by_email: bool,
+}
+
+impl RateLimiter {
+    pub fn per_ip(store: RateLimitStoreType, scope: &'static str, limit: RateLimit) -> Self {
+        Self {
+            store,
+            scope,
+            limit,
+            by_email: false,
+        }
+    }
+
+    pub fn per_ip_and_email(
+        store: RateLimitStoreType,
+        scope: &'static str,
+        limit: RateLimit,
+    ) -> Self {
+        Self {
+            store,
+            scope,
+            limit,
+            by_email: true,
+        }
+    }
+
+    // Wrap a single route, requests to other routes are not counted
+    pub fn limit<S>(self, route: MethodRouter<S>) -> MethodRouter<S>
+    where
+        S: Clone + Send + Sync + 'static,
+    {
+        route.route_layer(from_fn_with_state(self, rate_limit))
+    }
+}
+
+// Middleware taking a token for every key of the request before it reaches the handler. Responses
+// carry the `RateLimit-*` headers of the bucket closest to running out.
+pub async fn rate_limit(
+    State(limiter): State<RateLimiter>,
+    ConnectInfo(address): ConnectInfo<SocketAddr>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AuthAPIError> {
+    let mut keys = vec![RateLimitKey::ip(limiter.scope, address.ip())];
+
+    let request = if limiter.by_email {
+        let (request, email) = match read_email(request).await {
+            Ok(read) => read,
+            Err(response) => return Ok(response),
+        };
+        keys.extend(email.map(|email| RateLimitKey::email(limiter.scope, &email)));
+        request
+    } else {
+        request
+    };
+
+    let mut tightest: Option<RateLimitDecision> = None;
+
+    for key in &keys {
+        let decision = limiter
+            .store
+            .take_token(key, &limiter.limit)
+            .await
+            .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+        if !decision.allowed {
+            return Err(AuthAPIError::RateLimited(decision));
+        }
+
+        if tightest.is_none_or(|tightest| decision.remaining < tightest.remaining) {
+            tightest = Some(decision);
+        }
+    }
+
+    let mut response = next.run(request).await;
+
+    // A limit on the route itself is more specific than one wrapping the whole router, keep its headers
+    if let Some(decision) = tightest {
+        if !response.headers().contains_key(RATE_LIMIT_LIMIT) {
+            insert_rate_limit_headers(response.headers_mut(), &decision);
+        }
+    }
+
+    Ok(response)
+}
+
+pub fn insert_rate_limit_headers(headers: &mut HeaderMap, decision: &RateLimitDecision) {
+    headers.insert(RATE_LIMIT_LIMIT, HeaderValue::from(decision.limit));
+    headers.insert(RATE_LIMIT_REMAINING, HeaderValue::from(decision.remaining));
+    headers.insert(RATE_LIMIT_RESET, HeaderValue::from(decision.reset_seconds));
+}
+
+#[derive(Deserialize)]
+struct EmailBody {
+    email: String,
+}
+
+// Buffer the body to read the email address from it, then put it back for the handler.
+// Bodies without a valid address are only limited per client address, the handler rejects them.
+async fn read_email(request: Request) -> Result<(Request, Option<Email>), Response> {
+    let (parts, body) = request.into_parts();
+
+    let bytes = to_bytes(body, MAX_BODY_BYTES)
+        .await
+        .map_err(|_| StatusCode::PAYLOAD_TOO_LARGE.into_response())?;
+
+    let email = serde_json::from_slice::<EmailBody>(&bytes)
+        .ok()
+        .and_then(|body| Email::parse(body.email).ok());
+
+    Ok((Request::from_parts(parts, Body::from(bytes)), email))
+}
@@ -0,0 +1,131 @@
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+
+use crate::domain::{Email, TotpSecret, TwoFACode};
+
+// RFC 6238 parameters understood by every common authenticator app
+pub const TOTP_TIME_STEP_SECONDS: u64 = 30;
+const TOTP_DIGITS: u32 = 6;
+// Accept codes from one step before and after the current one to allow for clock drift
+const TOTP_ALLOWED_DRIFT_STEPS: u64 = 1;
+
+pub const TOTP_ISSUER: &str = "Rustgate";
+
+// Compute the code for a time step (RFC 4226 HOTP with the step as counter)
+pub fn generate_code(secret: &TotpSecret, time_step: u64) -> TwoFACode {
+    let mut mac =
+        Hmac::<Sha1>::new_from_slice(secret.as_ref()).expect("HMAC accepts keys of any length");
+    mac.update(&time_step.to_be_bytes());
+    let digest = mac.finalize().into_bytes();
+
+    // dynamic truncation
+    let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([
+        digest[offset] & 0x7f,
+        digest[offset + 1],
+        digest[offset + 2],
+        digest[offset + 3],
+    ]);
+
+    let code = binary % 10u32.pow(TOTP_DIGITS);
+    TwoFACode::parse(format!("{:0width$}", code, width = TOTP_DIGITS as usize))
+        .expect("Generated code is always 6 digits")
+}
+
+// Check `code` against the steps around `unix_time`.
+// Returns the matching time step so the caller can make sure it is not used again.
+pub fn verify_code(secret: &TotpSecret, code: &TwoFACode, unix_time: u64) -> Option<u64> {
+    let current_step = unix_time / TOTP_TIME_STEP_SECONDS;
+
+    (current_step.saturating_sub(TOTP_ALLOWED_DRIFT_STEPS)
+        ..=current_step + TOTP_ALLOWED_DRIFT_STEPS)
+        .find(|time_step| generate_code(secret, *time_step) == *code)
+}
+
+// Build the otpauth:// URI authenticator apps import, usually by scanning it as a QR code
+pub fn provisioning_uri(secret: &TotpSecret, email: &Email) -> String {
+    format!(
+        "otpauth://totp/{issuer}:{account}?secret={secret}&issuer={issuer}&algorithm=SHA1&digits={digits}&period={period}",
+        issuer = encode_uri_component(TOTP_ISSUER),
+        account = encode_uri_component(email.as_ref()),
+        secret = secret.to_base32(),
+        digits = TOTP_DIGITS,
+        period = TOTP_TIME_STEP_SECONDS,
+    )
+}
+
+fn encode_uri_component(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The SHA1 secret from the RFC 6238 test vectors
+    fn get_rfc_secret() -> TotpSecret {
+        TotpSecret::parse(b"12345678901234567890".to_vec()).unwrap()
+    }
+
+    #[test]
+    fn generate_code_matches_rfc_6238_test_vectors() {
+        // RFC 6238 lists 8 digit codes, we use the last 6 of them
+        let test_cases = [
+            (59, "287082"),
+            (1111111109, "081804"),
+            (1111111111, "050471"),
+            (1234567890, "005924"),
+            (2000000000, "279037"),
+        ];
+
+        for (unix_time, expected) in test_cases {
+            assert_eq!(
+                generate_code(&get_rfc_secret(), unix_time / TOTP_TIME_STEP_SECONDS).as_ref(),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn verify_code_accepts_adjacent_time_steps() {
+        let secret = TotpSecret::default();
+        let unix_time = 1_700_000_000;
+        let current_step = unix_time / TOTP_TIME_STEP_SECONDS;
+
+        for time_step in [current_step - 1, current_step, current_step + 1] {
+            let code = generate_code(&secret, time_step);
+            assert_eq!(verify_code(&secret, &code, unix_time), Some(time_step));
+        }
+    }
+
+    #[test]
+    fn verify_code_rejects_codes_outside_window() {
+        let secret = get_rfc_secret();
+        let unix_time = 1_700_000_000;
+        let current_step = unix_time / TOTP_TIME_STEP_SECONDS;
+
+        for time_step in [current_step - 2, current_step + 2] {
+            let code = generate_code(&secret, time_step);
+            assert_eq!(verify_code(&secret, &code, unix_time), None);
+        }
+    }
+
+    #[test]
+    fn provisioning_uri_contains_encoded_account_and_secret() {
+        let secret = get_rfc_secret();
+        let email = Email::parse("user@example.com".to_owned()).unwrap();
+
+        assert_eq!(
+            provisioning_uri(&secret, &email),
+            "otpauth://totp/Rustgate:user%40example.com?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ&issuer=Rustgate&algorithm=SHA1&digits=6&period=30"
+        );
+    }
+}
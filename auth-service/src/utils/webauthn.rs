@@ -0,0 +1,481 @@
+use std::io::Cursor;
+
+use ciborium::Value;
+use ring::signature::{self, RsaPublicKeyComponents, UnparsedPublicKey};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    domain::{CredentialId, WebAuthnChallenge},
+    utils::constants::{WEBAUTHN_ORIGIN, WEBAUTHN_RP_ID},
+};
+
+pub const WEBAUTHN_RP_NAME: &str = "Rustgate";
+
+// COSE algorithm identifiers of the signatures we can verify, in order of preference
+pub const COSE_ALG_ES256: i64 = -7;
+pub const COSE_ALG_EDDSA: i64 = -8;
+pub const COSE_ALG_RS256: i64 = -257;
+pub const SUPPORTED_COSE_ALGORITHMS: [i64; 3] = [COSE_ALG_ES256, COSE_ALG_EDDSA, COSE_ALG_RS256];
+
+// Authenticator data flags
+const FLAG_USER_PRESENT: u8 = 0x01;
+const FLAG_USER_VERIFIED: u8 = 0x04;
+const FLAG_ATTESTED_CREDENTIAL_DATA: u8 = 0x40;
+
+#[derive(Debug, PartialEq)]
+pub enum WebAuthnError {
+    InvalidClientData,
+    InvalidAuthenticatorData,
+    InvalidAttestation,
+    InvalidPublicKey,
+    InvalidSignature,
+    UserNotPresent,
+    UserNotVerified,
+}
+
+#[derive(Deserialize)]
+struct ClientData {
+    #[serde(rename = "type")]
+    ceremony_type: String,
+    challenge: String,
+    origin: String,
+    #[serde(rename = "crossOrigin", default)]
+    cross_origin: bool,
+}
+
+pub const CLIENT_DATA_TYPE_CREATE: &str = "webauthn.create";
+pub const CLIENT_DATA_TYPE_GET: &str = "webauthn.get";
+
+// Check the client data the browser collected and return the challenge it was signed for
+pub fn parse_client_data(
+    client_data_json: &[u8],
+    expected_type: &str,
+) -> Result<WebAuthnChallenge, WebAuthnError> {
+    let client_data: ClientData =
+        serde_json::from_slice(client_data_json).map_err(|_| WebAuthnError::InvalidClientData)?;
+
+    // The origin check is what makes WebAuthn phishing resistant: a look-alike site gets a different origin
+    if client_data.ceremony_type != expected_type
+        || client_data.origin != WEBAUTHN_ORIGIN.as_str()
+        || client_data.cross_origin
+    {
+        return Err(WebAuthnError::InvalidClientData);
+    }
+
+    WebAuthnChallenge::parse(client_data.challenge).map_err(|_| WebAuthnError::InvalidClientData)
+}
+
+#[derive(Debug)]
+pub struct AuthenticatorData {
+    pub rp_id_hash: [u8; 32],
+    pub flags: u8,
+    pub sign_count: u32,
+    pub attested_credential: Option<AttestedCredential>,
+}
+
+#[derive(Debug)]
+pub struct AttestedCredential {
+    pub credential_id: CredentialId,
+    // COSE encoded public key
+    pub public_key: Vec<u8>,
+}
+
+impl AuthenticatorData {
+    pub fn parse(data: &[u8]) -> Result<Self, WebAuthnError> {
+        let invalid = || WebAuthnError::InvalidAuthenticatorData;
+
+        if data.len() < 37 {
+            return Err(invalid());
+        }
+
+        let rp_id_hash: [u8; 32] = data[..32].try_into().map_err(|_| invalid())?;
+        let flags = data[32];
+        let sign_count = u32::from_be_bytes(data[33..37].try_into().map_err(|_| invalid())?);
+
+        let attested_credential = if flags & FLAG_ATTESTED_CREDENTIAL_DATA != 0 {
+            // 16 byte AAGUID followed by the 2 byte length of the credential ID
+            let rest = data.get(37 + 16..).ok_or_else(invalid)?;
+            let id_length = u16::from_be_bytes(
+                rest.get(..2)
+                    .ok_or_else(invalid)?
+                    .try_into()
+                    .map_err(|_| invalid())?,
+            ) as usize;
+
+            let credential_id = rest.get(2..2 + id_length).ok_or_else(invalid)?;
+            let rest = &rest[2 + id_length..];
+
+            // The public key is the CBOR item right after the ID, extensions may follow it
+            let mut cursor = Cursor::new(rest);
+            let _: Value = ciborium::de::from_reader(&mut cursor).map_err(|_| invalid())?;
+            let public_key_length = cursor.position() as usize;
+
+            Some(AttestedCredential {
+                credential_id: CredentialId::parse(credential_id.to_vec())
+                    .map_err(|_| invalid())?,
+                public_key: rest[..public_key_length].to_vec(),
+            })
+        } else {
+            None
+        };
+
+        Ok(Self {
+            rp_id_hash,
+            flags,
+            sign_count,
+            attested_credential,
+        })
+    }
+
+    // Make sure the data was produced for our relying party with the user interacting with the authenticator
+    pub fn check(&self, require_user_verification: bool) -> Result<(), WebAuthnError> {
+        if self.rp_id_hash[..] != Sha256::digest(WEBAUTHN_RP_ID.as_bytes())[..] {
+            return Err(WebAuthnError::InvalidAuthenticatorData);
+        }
+
+        if self.flags & FLAG_USER_PRESENT == 0 {
+            return Err(WebAuthnError::UserNotPresent);
+        }
+
+        if require_user_verification && self.flags & FLAG_USER_VERIFIED == 0 {
+            return Err(WebAuthnError::UserNotVerified);
+        }
+
+        Ok(())
+    }
+}
+
+// Verify the attestation object returned by a registration ceremony and return its authenticator data.
+// We request `attestation: "none"`, so only the "none" format and self attestation in the "packed"
+// format are accepted; attestation certificates are not evaluated.
+pub fn verify_attestation(
+    attestation_object: &[u8],
+    client_data_json: &[u8],
+) -> Result<AuthenticatorData, WebAuthnError> {
+    let invalid = || WebAuthnError::InvalidAttestation;
+
+    let attestation: Value =
+        ciborium::de::from_reader(attestation_object).map_err(|_| invalid())?;
+    let attestation = attestation.as_map().ok_or_else(invalid)?;
+
+    let fmt = get_text_entry(attestation, "fmt").ok_or_else(invalid)?;
+    let statement = get_text_entry_value(attestation, "attStmt")
+        .and_then(Value::as_map)
+        .ok_or_else(invalid)?;
+    let auth_data_bytes = get_text_entry_value(attestation, "authData")
+        .and_then(Value::as_bytes)
+        .ok_or_else(invalid)?;
+
+    let auth_data = AuthenticatorData::parse(auth_data_bytes)?;
+    let credential = auth_data.attested_credential.as_ref().ok_or_else(invalid)?;
+    let public_key = CosePublicKey::parse(&credential.public_key)?;
+
+    match fmt {
+        "none" if statement.is_empty() => {}
+        "packed" => {
+            if get_text_entry_value(statement, "x5c").is_some() {
+                return Err(invalid());
+            }
+
+            let alg = get_text_entry_value(statement, "alg")
+                .and_then(as_i64)
+                .ok_or_else(invalid)?;
+            let sig = get_text_entry_value(statement, "sig")
+                .and_then(Value::as_bytes)
+                .ok_or_else(invalid)?;
+
+            // self attestation is signed by the credential itself
+            if alg != public_key.algorithm() {
+                return Err(invalid());
+            }
+
+            let signed_data = [&auth_data_bytes[..], &Sha256::digest(client_data_json)].concat();
+            public_key
+                .verify(&signed_data, sig)
+                .map_err(|_| invalid())?;
+        }
+        _ => return Err(invalid()),
+    }
+
+    Ok(auth_data)
+}
+
+// Verify an assertion signature, which covers the authenticator data and the hash of the client data
+pub fn verify_assertion(
+    public_key: &[u8],
+    authenticator_data: &[u8],
+    client_data_json: &[u8],
+    signature: &[u8],
+) -> Result<(), WebAuthnError> {
+    let signed_data = [authenticator_data, &Sha256::digest(client_data_json)].concat();
+    CosePublicKey::parse(public_key)?.verify(&signed_data, signature)
+}
+
+pub enum CosePublicKey {
+    Es256 { point: Vec<u8> },
+    EdDsa { x: Vec<u8> },
+    Rs256 { n: Vec<u8>, e: Vec<u8> },
+}
+
+// COSE key map labels
+const COSE_KEY_KTY: i64 = 1;
+const COSE_KEY_ALG: i64 = 3;
+const COSE_KEY_CRV: i64 = -1;
+const COSE_KEY_X: i64 = -2;
+const COSE_KEY_Y: i64 = -3;
+// RSA keys reuse the labels for the modulus and exponent
+const COSE_KEY_N: i64 = -1;
+const COSE_KEY_E: i64 = -2;
+
+const COSE_KTY_OKP: i64 = 1;
+const COSE_KTY_EC2: i64 = 2;
+const COSE_KTY_RSA: i64 = 3;
+const COSE_CRV_P256: i64 = 1;
+const COSE_CRV_ED25519: i64 = 6;
+
+impl CosePublicKey {
+    pub fn parse(public_key: &[u8]) -> Result<Self, WebAuthnError> {
+        let invalid = || WebAuthnError::InvalidPublicKey;
+
+        let key: Value = ciborium::de::from_reader(public_key).map_err(|_| invalid())?;
+        let key = key.as_map().ok_or_else(invalid)?;
+
+        let get_int = |label| get_int_entry(key, label).and_then(as_i64);
+        let get_bytes = |label| {
+            get_int_entry(key, label)
+                .and_then(Value::as_bytes)
+                .cloned()
+                .ok_or_else(invalid)
+        };
+
+        match (get_int(COSE_KEY_KTY), get_int(COSE_KEY_ALG)) {
+            (Some(COSE_KTY_EC2), Some(COSE_ALG_ES256)) => {
+                let (x, y) = (get_bytes(COSE_KEY_X)?, get_bytes(COSE_KEY_Y)?);
+                if get_int(COSE_KEY_CRV) != Some(COSE_CRV_P256) || x.len() != 32 || y.len() != 32 {
+                    return Err(invalid());
+                }
+                // uncompressed SEC1 point
+                Ok(Self::Es256 {
+                    point: [&[0x04], &x[..], &y[..]].concat(),
+                })
+            }
+            (Some(COSE_KTY_OKP), Some(COSE_ALG_EDDSA)) => {
+                let x = get_bytes(COSE_KEY_X)?;
+                if get_int(COSE_KEY_CRV) != Some(COSE_CRV_ED25519) || x.len() != 32 {
+                    return Err(invalid());
+                }
+                Ok(Self::EdDsa { x })
+            }
+            (Some(COSE_KTY_RSA), Some(COSE_ALG_RS256)) => Ok(Self::Rs256 {
+                n: get_bytes(COSE_KEY_N)?,
+                e: get_bytes(COSE_KEY_E)?,
+            }),
+            _ => Err(invalid()),
+        }
+    }
+
+    pub fn algorithm(&self) -> i64 {
+        match self {
+            Self::Es256 { .. } => COSE_ALG_ES256,
+            Self::EdDsa { .. } => COSE_ALG_EDDSA,
+            Self::Rs256 { .. } => COSE_ALG_RS256,
+        }
+    }
+
+    pub fn verify(&self, message: &[u8], signature: &[u8]) -> Result<(), WebAuthnError> {
+        let result = match self {
+            // WebAuthn ECDSA signatures are ASN.1 DER encoded
+            Self::Es256 { point } => {
+                UnparsedPublicKey::new(&signature::ECDSA_P256_SHA256_ASN1, point)
+                    .verify(message, signature)
+            }
+            Self::EdDsa { x } => {
+                UnparsedPublicKey::new(&signature::ED25519, x).verify(message, signature)
+            }
+            Self::Rs256 { n, e } => RsaPublicKeyComponents { n, e }.verify(
+                &signature::RSA_PKCS1_2048_8192_SHA256,
+                message,
+                signature,
+            ),
+        };
+
+        result.map_err(|_| WebAuthnError::InvalidSignature)
+    }
+}
+
+fn get_int_entry(map: &[(Value, Value)], label: i64) -> Option<&Value> {
+    map.iter()
+        .find(|(key, _)| as_i64(key) == Some(label))
+        .map(|(_, value)| value)
+}
+
+fn get_text_entry_value<'a>(map: &'a [(Value, Value)], label: &str) -> Option<&'a Value> {
+    map.iter()
+        .find(|(key, _)| key.as_text() == Some(label))
+        .map(|(_, value)| value)
+}
+
+fn get_text_entry<'a>(map: &'a [(Value, Value)], label: &str) -> Option<&'a str> {
+    get_text_entry_value(map, label).and_then(Value::as_text)
+}
+
+fn as_i64(value: &Value) -> Option<i64> {
+    value
+        .as_integer()
+        .and_then(|integer| i64::try_from(integer).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ring::{
+        rand::SystemRandom,
+        signature::{EcdsaKeyPair, KeyPair, ECDSA_P256_SHA256_ASN1_SIGNING},
+    };
+
+    fn generate_key_pair() -> EcdsaKeyPair {
+        let rng = SystemRandom::new();
+        let pkcs8 = EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, &rng).unwrap();
+        EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, pkcs8.as_ref(), &rng).unwrap()
+    }
+
+    fn to_cose_key(key_pair: &EcdsaKeyPair) -> Vec<u8> {
+        let point = key_pair.public_key().as_ref();
+        let key = Value::Map(vec![
+            (Value::from(COSE_KEY_KTY), Value::from(COSE_KTY_EC2)),
+            (Value::from(COSE_KEY_ALG), Value::from(COSE_ALG_ES256)),
+            (Value::from(COSE_KEY_CRV), Value::from(COSE_CRV_P256)),
+            (Value::from(COSE_KEY_X), Value::Bytes(point[1..33].to_vec())),
+            (Value::from(COSE_KEY_Y), Value::Bytes(point[33..].to_vec())),
+        ]);
+        let mut encoded = Vec::new();
+        ciborium::ser::into_writer(&key, &mut encoded).unwrap();
+        encoded
+    }
+
+    fn get_authenticator_data(flags: u8, sign_count: u32, attested: Option<&[u8]>) -> Vec<u8> {
+        let mut data = Sha256::digest(WEBAUTHN_RP_ID.as_bytes()).to_vec();
+        data.push(flags);
+        data.extend_from_slice(&sign_count.to_be_bytes());
+        if let Some(public_key) = attested {
+            data.extend_from_slice(&[0u8; 16]);
+            data.extend_from_slice(&4u16.to_be_bytes());
+            data.extend_from_slice(&[9, 9, 9, 9]);
+            data.extend_from_slice(public_key);
+        }
+        data
+    }
+
+    #[test]
+    fn authenticator_data_should_parse_attested_credential() {
+        let public_key = to_cose_key(&generate_key_pair());
+        let data = get_authenticator_data(
+            FLAG_USER_PRESENT | FLAG_ATTESTED_CREDENTIAL_DATA,
+            7,
+            Some(&public_key),
+        );
+
+        let auth_data = AuthenticatorData::parse(&data).unwrap();
+        let credential = auth_data.attested_credential.unwrap();
+
+        assert_eq!(auth_data.sign_count, 7);
+        assert_eq!(credential.credential_id.as_ref(), &[9, 9, 9, 9]);
+        assert_eq!(credential.public_key, public_key);
+    }
+
+    #[test]
+    fn authenticator_data_check_should_require_user_presence_and_verification() {
+        let data = AuthenticatorData::parse(&get_authenticator_data(0, 0, None)).unwrap();
+        assert_eq!(data.check(false), Err(WebAuthnError::UserNotPresent));
+
+        let data =
+            AuthenticatorData::parse(&get_authenticator_data(FLAG_USER_PRESENT, 0, None)).unwrap();
+        assert!(data.check(false).is_ok());
+        assert_eq!(data.check(true), Err(WebAuthnError::UserNotVerified));
+
+        let data = AuthenticatorData::parse(&get_authenticator_data(
+            FLAG_USER_PRESENT | FLAG_USER_VERIFIED,
+            0,
+            None,
+        ))
+        .unwrap();
+        assert!(data.check(true).is_ok());
+    }
+
+    #[test]
+    fn authenticator_data_check_should_reject_other_relying_party() {
+        let mut data = get_authenticator_data(FLAG_USER_PRESENT, 0, None);
+        data[..32].copy_from_slice(&Sha256::digest(b"evil.example.com"));
+
+        assert_eq!(
+            AuthenticatorData::parse(&data).unwrap().check(false),
+            Err(WebAuthnError::InvalidAuthenticatorData)
+        );
+    }
+
+    #[test]
+    fn verify_assertion_should_check_es256_signature() {
+        let key_pair = generate_key_pair();
+        let public_key = to_cose_key(&key_pair);
+        let authenticator_data = get_authenticator_data(FLAG_USER_PRESENT, 1, None);
+        let client_data_json = br#"{"type":"webauthn.get"}"#;
+
+        let signed_data = [&authenticator_data[..], &Sha256::digest(client_data_json)].concat();
+        let signature = key_pair.sign(&SystemRandom::new(), &signed_data).unwrap();
+
+        assert!(verify_assertion(
+            &public_key,
+            &authenticator_data,
+            client_data_json,
+            signature.as_ref()
+        )
+        .is_ok());
+
+        // any change to the signed data invalidates the signature
+        assert_eq!(
+            verify_assertion(
+                &public_key,
+                &get_authenticator_data(FLAG_USER_PRESENT, 2, None),
+                client_data_json,
+                signature.as_ref()
+            ),
+            Err(WebAuthnError::InvalidSignature)
+        );
+    }
+
+    #[test]
+    fn parse_client_data_should_check_type_and_origin() {
+        let challenge = WebAuthnChallenge::default();
+        let client_data = |ceremony_type: &str, origin: &str| {
+            serde_json::to_vec(&serde_json::json!({
+                "type": ceremony_type,
+                "challenge": challenge.as_ref(),
+                "origin": origin,
+            }))
+            .unwrap()
+        };
+
+        assert_eq!(
+            parse_client_data(
+                &client_data(CLIENT_DATA_TYPE_GET, &WEBAUTHN_ORIGIN),
+                CLIENT_DATA_TYPE_GET
+            ),
+            Ok(challenge.clone())
+        );
+        assert_eq!(
+            parse_client_data(
+                &client_data(CLIENT_DATA_TYPE_CREATE, &WEBAUTHN_ORIGIN),
+                CLIENT_DATA_TYPE_GET
+            ),
+            Err(WebAuthnError::InvalidClientData)
+        );
+        assert_eq!(
+            parse_client_data(
+                &client_data(CLIENT_DATA_TYPE_GET, "https://evil.example.com"),
+                CLIENT_DATA_TYPE_GET
+            ),
+            Err(WebAuthnError::InvalidClientData)
+        );
+    }
+}
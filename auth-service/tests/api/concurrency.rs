@@ -0,0 +1,140 @@
+use std::{sync::Arc, time::Duration};
+
+use auth_service::{
+    domain::{Email, Password, TwoFAMethod, User, UserStore, UserStoreError},
+    services::postgres_user_store::PostgresUserStore,
+};
+use futures::future::join_all;
+use tokio::sync::Notify;
+
+use crate::helpers::{get_random_email, TestApp};
+
+// Holds the signup of one email inside the user store until it is released, like a database that
+// is slow to answer
+struct StalledSignup {
+    email: Email,
+    entered: Notify,
+    release: Notify,
+}
+
+struct StallingUserStore {
+    inner: PostgresUserStore,
+    stall: Arc<StalledSignup>,
+}
+
+#[async_trait::async_trait]
+impl UserStore for StallingUserStore {
+    async fn add_user(&self, user: User) -> Result<(), UserStoreError> {
+        if user.email == self.stall.email {
+            self.stall.entered.notify_one();
+            self.stall.release.notified().await;
+        }
+        self.inner.add_user(user).await
+    }
+
+    async fn get_user(&self, email: &Email) -> Result<User, UserStoreError> {
+        self.inner.get_user(email).await
+    }
+
+    async fn validate_user(
+        &self,
+        email: &Email,
+        password: &Password,
+    ) -> Result<(), UserStoreError> {
+        self.inner.validate_user(email, password).await
+    }
+
+    async fn update_password(
+        &self,
+        email: &Email,
+        password: Password,
+    ) -> Result<(), UserStoreError> {
+        self.inner.update_password(email, password).await
+    }
+
+    async fn set_email_verified(&self, email: &Email) -> Result<(), UserStoreError> {
+        self.inner.set_email_verified(email).await
+    }
+
+    async fn set_two_fa_method(
+        &self,
+        email: &Email,
+        two_fa_method: TwoFAMethod,
+    ) -> Result<(), UserStoreError> {
+        self.inner.set_two_fa_method(email, two_fa_method).await
+    }
+}
+
+fn signup_body(email: &str) -> serde_json::Value {
+    serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": false
+    })
+}
+
+// Kept below SIGNUP_RATE_LIMIT, which counts every signup of the test against the same address
+const USERS: usize = 4;
+
+#[tokio::test]
+async fn signups_and_logins_should_not_wait_for_a_slow_signup() {
+    let stall = Arc::new(StalledSignup {
+        email: Email::parse(get_random_email()).unwrap(),
+        entered: Notify::new(),
+        release: Notify::new(),
+    });
+    let mut app = TestApp::with_user_store({
+        let stall = stall.clone();
+        |pg_pool| {
+            Arc::new(StallingUserStore {
+                inner: PostgresUserStore::new(pg_pool),
+                stall,
+            })
+        }
+    })
+    .await;
+
+    let existing_users: Vec<String> = (0..USERS).map(|_| get_random_email()).collect();
+    for email in &existing_users {
+        assert_eq!(app.post_signup(&signup_body(email)).await.status(), 201);
+        assert_eq!(app.verify_email(email).await.status(), 200);
+    }
+
+    // Start the slow signup and wait until it is inside the user store
+    let stalled_body = signup_body(stall.email.as_ref());
+    let mut stalled_signup = Box::pin(app.post_signup(&stalled_body));
+    tokio::select! {
+        _ = &mut stalled_signup => panic!("The signup should be held in the user store"),
+        _ = stall.entered.notified() => {}
+    }
+
+    // With the store behind a lock, every one of these would wait for the slow signup to finish
+    let new_users: Vec<String> = (0..USERS).map(|_| get_random_email()).collect();
+    let app_ref = &app;
+    let signups = join_all(
+        new_users
+            .iter()
+            .map(|email| async move { app_ref.post_signup(&signup_body(email)).await.status() }),
+    );
+    let logins = join_all(existing_users.iter().map(|email| async move {
+        let login_body = serde_json::json!({
+            "email": email,
+            "password": "password123",
+        });
+        app_ref.post_login(&login_body).await.status()
+    }));
+
+    let (signups, logins) = tokio::time::timeout(Duration::from_secs(60), async {
+        tokio::join!(signups, logins)
+    })
+    .await
+    .expect("Signups and logins should not wait for the slow signup");
+
+    assert!(signups.iter().all(|status| status.as_u16() == 201));
+    assert!(logins.iter().all(|status| status.as_u16() == 200));
+
+    stall.release.notify_one();
+    assert_eq!(stalled_signup.await.status(), 201);
+
+    app.clean_up().await;
+}
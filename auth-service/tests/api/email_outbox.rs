@@ -0,0 +1,118 @@
+use auth_service::{routes::EmailOutboxResponse, utils::constants::ADMIN_API_KEY, ErrorResponse};
+use test_helpers::api_test;
+
+use crate::helpers::{get_random_email, TestApp};
+
+fn admin_api_key() -> Option<&'static str> {
+    Some(
+        ADMIN_API_KEY
+            .as_deref()
+            .expect("ADMIN_API_KEY must be set for tests"),
+    )
+}
+
+async fn signup(app: &TestApp, email: &str) {
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+            "requires2FA": false
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 201);
+}
+
+async fn get_outbox(app: &TestApp) -> EmailOutboxResponse {
+    let response = app.get_email_outbox(admin_api_key()).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    response
+        .json::<EmailOutboxResponse>()
+        .await
+        .expect("Could not deserialize response body to EmailOutboxResponse")
+}
+
+#[api_test]
+async fn should_deliver_email_after_transient_failures() {
+    let random_email = get_random_email();
+    app.email_server.fail_next(2);
+
+    // the request does not wait for, or fail with, the mail server
+    signup(&app, &random_email).await;
+
+    let sent_email = app
+        .last_email_to(&random_email)
+        .await
+        .expect("No verification email sent");
+
+    assert_eq!(sent_email.subject, "Verify your email");
+
+    let outbox = get_outbox(&app).await;
+
+    assert_eq!(outbox.pending, 0);
+    assert!(outbox.stuck.is_empty());
+}
+
+#[api_test]
+async fn should_dead_letter_email_after_max_attempts() {
+    let random_email = get_random_email();
+    app.email_server.fail_next(3);
+
+    signup(&app, &random_email).await;
+
+    assert!(app.last_email_to(&random_email).await.is_none());
+
+    let outbox = get_outbox(&app).await;
+
+    assert_eq!(outbox.pending, 0);
+    assert_eq!(outbox.stuck.len(), 1);
+
+    let stuck = &outbox.stuck[0];
+    assert_eq!(stuck.recipient, random_email);
+    assert_eq!(stuck.subject, "Verify your email");
+    assert_eq!(stuck.status, "dead");
+    assert_eq!(stuck.attempts, 3);
+    assert!(stuck
+        .last_error
+        .as_deref()
+        .is_some_and(|error| error.contains("451")));
+}
+
+#[api_test]
+async fn should_return_400_if_no_token() {
+    let response = app.get_email_outbox(None).await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[api_test]
+async fn should_return_403_if_missing_permission() {
+    let random_email = get_random_email();
+    signup(&app, &random_email).await;
+
+    let response = app.verify_email(&random_email).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": random_email,
+            "password": "password123",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // the auth cookie is sent along, but the user holds no admin role
+    let response = app.get_email_outbox(None).await;
+
+    assert_eq!(response.status().as_u16(), 403);
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "MissingPermission"
+    );
+}
@@ -1,82 +1,231 @@
-use auth_service::app_state::{BannedTokenStoreType, EmailClientType, TwoFACodeStoreType};
+use auth_service::app_state::EmailOutboxStoreType;
+use auth_service::app_state::{
+    BannedTokenStoreType, EmailClientType, OneTimeTokenStoreType, RefreshTokenStoreType,
+    TwoFACodeStoreType, UserStoreType,
+};
+use auth_service::domain::OneTimeTokenPurpose;
+use auth_service::services::postgres_email_outbox_store::PostgresEmailOutboxStore;
+use auth_service::services::postgres_jwt_key_store::PostgresJwtKeyStore;
+use auth_service::services::postgres_organization_store::PostgresOrganizationStore;
+use auth_service::services::postgres_recovery_code_store::PostgresRecoveryCodeStore;
+use auth_service::services::postgres_role_store::PostgresRoleStore;
+use auth_service::services::postgres_totp_secret_store::PostgresTotpSecretStore;
 use auth_service::services::postgres_user_store::PostgresUserStore;
+use auth_service::services::postgres_webauthn_credential_store::PostgresWebAuthnCredentialStore;
 use auth_service::services::redis_banned_token_store::RedisBannedTokenStore;
+use auth_service::services::redis_one_time_token_store::RedisOneTimeTokenStore;
+use auth_service::services::redis_refresh_token_store::RedisRefreshTokenStore;
 use auth_service::services::redis_two_fa_code_store::RedisTwoFACodeStore;
-use auth_service::services::MockEmailClient;
+use auth_service::services::redis_webauthn_challenge_store::RedisWebAuthnChallengeStore;
+use auth_service::services::{
+    HashMapFailedLoginStore, HashMapRateLimitStore, SmtpEmailClient, SmtpTls,
+};
+use auth_service::utils::constants::{JWT_SIGNING_KEY, JWT_VERIFICATION_KEYS};
+use auth_service::utils::email_outbox::{EmailOutbox, EmailRetryPolicy};
+use auth_service::utils::jwt::KeyRing;
 use auth_service::utils::{DATABASE_URL, DEFAULT_REDIS_HOSTNAME};
-use auth_service::{app_state::AppState, domain::UserStore, utils::constants::test, Application};
+use auth_service::{app_state::AppState, utils::constants::test, Application};
 use auth_service::{get_postgres_pool, get_redis_client};
 
+use redis::aio::ConnectionManager;
 use reqwest::cookie::Jar;
 use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
 use sqlx::{Connection, Executor, PgConnection, PgPool};
+use std::path::PathBuf;
 use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::RwLock;
 use uuid::Uuid;
 
+use crate::smtp_sink::{SentEmail, SmtpSink};
+
 pub struct TestApp {
     pub address: String,
     pub cookie_jar: Arc<Jar>,
     pub banned_token_store: BannedTokenStoreType,
     pub two_fa_code_store: TwoFACodeStoreType, // New!
-    pub email_client: EmailClientType,
+    pub email_server: SmtpSink,
+    pub email_outbox_store: EmailOutboxStoreType,
+    pub refresh_token_store: RefreshTokenStoreType,
+    #[allow(dead_code)]
+    pub password_reset_token_store: OneTimeTokenStoreType,
     pub http_client: reqwest::Client,
     pub db_name: String,
+    // set when the stores live in a SQLite file instead of the database named above
+    pub sqlite_path: Option<PathBuf>,
     pub clean_up_called: bool,
 }
 
 impl TestApp {
     pub async fn new() -> Self {
+        Self::with_user_store(|pg_pool| Arc::new(PostgresUserStore::new(pg_pool))).await
+    }
+
+    // Lets a test wrap the user store, which is otherwise backed by the test database as it is
+    pub async fn with_user_store(user_store: impl FnOnce(PgPool) -> UserStoreType) -> Self {
         let db_name = Uuid::new_v4().to_string();
         let pg_pool = configure_postgresql(&db_name).await;
 
-        let redis_connection = Arc::new(RwLock::new(configure_redis()));
-        let user_store: Box<dyn UserStore + Send + Sync> =
-            Box::new(PostgresUserStore { pool: pg_pool });
-        let user_store = Arc::new(RwLock::new(user_store));
-
-        let banned_token_store = Arc::new(RwLock::new(RedisBannedTokenStore::new(
+        let redis_connection = configure_redis().await;
+        let totp_secret_store =
+            Arc::new(RwLock::new(PostgresTotpSecretStore::new(pg_pool.clone())));
+        let webauthn_credential_store = Arc::new(RwLock::new(
+            PostgresWebAuthnCredentialStore::new(pg_pool.clone()),
+        ));
+        let recovery_code_store = Arc::new(PostgresRecoveryCodeStore::new(pg_pool.clone()));
+        let role_store = Arc::new(RwLock::new(PostgresRoleStore::new(pg_pool.clone())));
+        let organization_store =
+            Arc::new(RwLock::new(PostgresOrganizationStore::new(pg_pool.clone())));
+        let email_outbox_store: EmailOutboxStoreType =
+            Arc::new(RwLock::new(PostgresEmailOutboxStore::new(pg_pool.clone())));
+        let email_outbox = test_email_outbox(email_outbox_store.clone());
+        let jwt_key_store = Arc::new(PostgresJwtKeyStore::new(pg_pool.clone()));
+        let user_store = user_store(pg_pool);
+
+        let banned_token_store = Arc::new(RedisBannedTokenStore::new(redis_connection.clone()));
+        let two_fa_code_store = Arc::new(RedisTwoFACodeStore::new(redis_connection.clone()));
+        let (email_server, email_client) = start_email_server().await;
+        let refresh_token_store = Arc::new(RwLock::new(RedisRefreshTokenStore::new(
+            redis_connection.clone(),
+        )));
+        let password_reset_token_store = Arc::new(RwLock::new(RedisOneTimeTokenStore::new(
+            redis_connection.clone(),
+            OneTimeTokenPurpose::PasswordReset,
+        )));
+        let email_verification_token_store = Arc::new(RwLock::new(RedisOneTimeTokenStore::new(
             redis_connection.clone(),
+            OneTimeTokenPurpose::EmailVerification,
         )));
-        let two_fa_code_store = Arc::new(RwLock::new(RedisTwoFACodeStore::new(redis_connection)));
-        let email_client = Arc::new(RwLock::new(MockEmailClient));
+        let webauthn_challenge_store = Arc::new(RwLock::new(RedisWebAuthnChallengeStore::new(
+            redis_connection,
+        )));
+        // Kept in memory so failed logins of one test cannot lock out another, all tests share an address
+        let failed_login_store = Arc::new(RwLock::new(HashMapFailedLoginStore::default()));
+        // In memory for the same reason, otherwise tests would throttle each other
+        let rate_limit_store = Arc::new(HashMapRateLimitStore::default());
+        let key_ring = Arc::new(RwLock::new(
+            KeyRing::new(JWT_SIGNING_KEY.clone(), JWT_VERIFICATION_KEYS.clone())
+                .with_store(jwt_key_store),
+        ));
 
         let app_state = AppState::new(
             user_store.clone(),
             banned_token_store.clone(),
             two_fa_code_store.clone(),
-            email_client.clone(),
+            email_client,
+            refresh_token_store.clone(),
+            password_reset_token_store.clone(),
+            email_verification_token_store,
+            totp_secret_store,
+            webauthn_credential_store,
+            webauthn_challenge_store,
+            recovery_code_store,
+            key_ring,
+            role_store,
+            organization_store,
+            failed_login_store,
+            rate_limit_store,
+            email_outbox,
         );
 
-        let app = Application::build(app_state, test::APP_ADDRESS)
-            .await
-            .expect("Failed to build the app");
+        let (address, cookie_jar, http_client) = start_app(app_state).await;
 
-        let address = format!("http://{}", app.address.clone());
+        // Create a new TestApp instance and return it
+        Self {
+            address,
+            cookie_jar,
+            banned_token_store,
+            two_fa_code_store,
+            email_server,
+            email_outbox_store,
+            refresh_token_store,
+            password_reset_token_store,
+            http_client,
+            db_name,
+            sqlite_path: None,
+            clean_up_called: false,
+        }
+    }
 
-        // Run the auth service is a separate async task
-        // This will make sure that we do not block the main test thread
+    // The service as it runs with SQLITE_DATABASE_URL, every store in one SQLite file and neither
+    // PostgreSQL nor Redis involved
+    #[cfg(feature = "sqlite")]
+    pub async fn new_sqlite() -> Self {
+        use auth_service::services::{
+            sqlite_banned_token_store::SqliteBannedTokenStore,
+            sqlite_email_outbox_store::SqliteEmailOutboxStore,
+            sqlite_failed_login_store::SqliteFailedLoginStore,
+            sqlite_jwt_key_store::SqliteJwtKeyStore,
+            sqlite_one_time_token_store::SqliteOneTimeTokenStore,
+            sqlite_organization_store::SqliteOrganizationStore,
+            sqlite_rate_limit_store::SqliteRateLimitStore,
+            sqlite_recovery_code_store::SqliteRecoveryCodeStore,
+            sqlite_refresh_token_store::SqliteRefreshTokenStore,
+            sqlite_role_store::SqliteRoleStore, sqlite_totp_secret_store::SqliteTotpSecretStore,
+            sqlite_two_fa_code_store::SqliteTwoFACodeStore, sqlite_user_store::SqliteUserStore,
+            sqlite_webauthn_challenge_store::SqliteWebAuthnChallengeStore,
+            sqlite_webauthn_credential_store::SqliteWebAuthnCredentialStore,
+        };
+
+        let (pool, path) = configure_sqlite().await;
+
+        let email_outbox_store: EmailOutboxStoreType =
+            Arc::new(RwLock::new(SqliteEmailOutboxStore::new(pool.clone())));
+        let email_outbox = test_email_outbox(email_outbox_store.clone());
+        let (email_server, email_client) = start_email_server().await;
+        let banned_token_store = Arc::new(SqliteBannedTokenStore::new(pool.clone()));
+        let two_fa_code_store = Arc::new(SqliteTwoFACodeStore::new(pool.clone()));
+        let refresh_token_store = Arc::new(RwLock::new(SqliteRefreshTokenStore::new(pool.clone())));
+        let password_reset_token_store = Arc::new(RwLock::new(SqliteOneTimeTokenStore::new(
+            pool.clone(),
+            OneTimeTokenPurpose::PasswordReset,
+        )));
+        let key_ring = Arc::new(RwLock::new(
+            KeyRing::new(JWT_SIGNING_KEY.clone(), JWT_VERIFICATION_KEYS.clone())
+                .with_store(Arc::new(SqliteJwtKeyStore::new(pool.clone()))),
+        ));
 
-        #[allow(clippy::let_underscore_future)]
-        let _ = tokio::spawn(app.run());
+        // Every test has a file of its own, so failed logins and rate limits stay in it as well
+        let app_state = AppState::new(
+            Arc::new(SqliteUserStore::new(pool.clone())),
+            banned_token_store.clone(),
+            two_fa_code_store.clone(),
+            email_client,
+            refresh_token_store.clone(),
+            password_reset_token_store.clone(),
+            Arc::new(RwLock::new(SqliteOneTimeTokenStore::new(
+                pool.clone(),
+                OneTimeTokenPurpose::EmailVerification,
+            ))),
+            Arc::new(RwLock::new(SqliteTotpSecretStore::new(pool.clone()))),
+            Arc::new(RwLock::new(SqliteWebAuthnCredentialStore::new(
+                pool.clone(),
+            ))),
+            Arc::new(RwLock::new(SqliteWebAuthnChallengeStore::new(pool.clone()))),
+            Arc::new(SqliteRecoveryCodeStore::new(pool.clone())),
+            key_ring,
+            Arc::new(RwLock::new(SqliteRoleStore::new(pool.clone()))),
+            Arc::new(RwLock::new(SqliteOrganizationStore::new(pool.clone()))),
+            Arc::new(RwLock::new(SqliteFailedLoginStore::new(pool.clone()))),
+            Arc::new(SqliteRateLimitStore::new(pool)),
+            email_outbox,
+        );
 
-        let cookie_jar = Arc::new(Jar::default());
-        // Create a reqwest client backed by the shared cookie jar so tests can set cookies
-        let http_client = reqwest::Client::builder()
-            .cookie_provider(cookie_jar.clone())
-            .build()
-            .expect("Failed to build http client");
+        let (address, cookie_jar, http_client) = start_app(app_state).await;
 
-        // Create a new TestApp instance and return it
         Self {
             address,
             cookie_jar,
-            banned_token_store: banned_token_store,
-            two_fa_code_store: two_fa_code_store,
-            email_client: email_client,
+            banned_token_store,
+            two_fa_code_store,
+            email_server,
+            email_outbox_store,
+            refresh_token_store,
+            password_reset_token_store,
             http_client,
-            db_name,
+            db_name: String::new(),
+            sqlite_path: Some(path),
             clean_up_called: false,
         }
     }
@@ -86,25 +235,240 @@ impl TestApp {
             return;
         }
 
-        delete_database(&self.db_name).await;
+        match &self.sqlite_path {
+            Some(path) => delete_sqlite_files(path),
+            None => delete_database(&self.db_name).await,
+        }
 
         self.clean_up_called = true;
     }
 
+    // Emails are delivered in the background, so wait until the outbox has sent or given up on all
+    // of them before looking at what arrived
+    pub async fn last_email_to(&self, recipient: &str) -> Option<SentEmail> {
+        self.wait_for_outbox().await;
+        self.email_server.last_email_to(recipient)
+    }
+
+    pub async fn wait_for_outbox(&self) {
+        for _ in 0..100 {
+            let pending = self
+                .email_outbox_store
+                .read()
+                .await
+                .count_pending()
+                .await
+                .expect("Failed to count pending emails");
+
+            if pending == 0 {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+
+        panic!("Emails in the outbox were not delivered");
+    }
+
+    pub async fn get_email_outbox(&self, bearer_token: Option<&str>) -> reqwest::Response {
+        let mut request = self
+            .http_client
+            .get(format!("{}/admin/email-outbox", &self.address));
+
+        if let Some(bearer_token) = bearer_token {
+            request = request.bearer_auth(bearer_token);
+        }
+
+        request.send().await.expect("Failed to execute request.")
+    }
+
     pub async fn get_root(&self) -> reqwest::Response {
         self.http_client
-            .get(&format!("{}/", &self.address))
+            .get(format!("{}/", &self.address))
             .send()
             .await
             .expect("Failed to execute a request")
     }
 
+    pub async fn get_jwks(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/.well-known/jwks.json", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_rotate_jwt_keys<Body>(
+        &self,
+        body: &Body,
+        admin_api_key: Option<&str>,
+    ) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        let mut request = self
+            .http_client
+            .post(format!("{}/admin/jwt-keys/rotate", &self.address))
+            .json(body);
+
+        if let Some(admin_api_key) = admin_api_key {
+            request = request.bearer_auth(admin_api_key);
+        }
+
+        request.send().await.expect("Failed to execute request.")
+    }
+
+    pub async fn get_roles(&self, bearer_token: Option<&str>) -> reqwest::Response {
+        let mut request = self
+            .http_client
+            .get(format!("{}/admin/roles", &self.address));
+
+        if let Some(bearer_token) = bearer_token {
+            request = request.bearer_auth(bearer_token);
+        }
+
+        request.send().await.expect("Failed to execute request.")
+    }
+
+    pub async fn get_user_roles(
+        &self,
+        email: &str,
+        bearer_token: Option<&str>,
+    ) -> reqwest::Response {
+        let mut request = self
+            .http_client
+            .get(format!("{}/admin/users/{}/roles", &self.address, email));
+
+        if let Some(bearer_token) = bearer_token {
+            request = request.bearer_auth(bearer_token);
+        }
+
+        request.send().await.expect("Failed to execute request.")
+    }
+
+    pub async fn post_assign_role<Body>(
+        &self,
+        body: &Body,
+        bearer_token: Option<&str>,
+    ) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        let mut request = self
+            .http_client
+            .post(format!("{}/admin/roles/assign", &self.address))
+            .json(body);
+
+        if let Some(bearer_token) = bearer_token {
+            request = request.bearer_auth(bearer_token);
+        }
+
+        request.send().await.expect("Failed to execute request.")
+    }
+
+    pub async fn post_revoke_role<Body>(
+        &self,
+        body: &Body,
+        bearer_token: Option<&str>,
+    ) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        let mut request = self
+            .http_client
+            .post(format!("{}/admin/roles/revoke", &self.address))
+            .json(body);
+
+        if let Some(bearer_token) = bearer_token {
+            request = request.bearer_auth(bearer_token);
+        }
+
+        request.send().await.expect("Failed to execute request.")
+    }
+
+    pub async fn post_create_organization<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/organizations", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_organizations(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/organizations", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_invite_member<Body>(
+        &self,
+        organization_id: &str,
+        body: &Body,
+    ) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!(
+                "{}/organizations/{}/invitations",
+                &self.address, organization_id
+            ))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_invitation_page(&self, token: &str) -> reqwest::Response {
+        self.http_client
+            .get(format!(
+                "{}/organizations/invitations/accept",
+                &self.address
+            ))
+            .query(&[("token", token)])
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_accept_invitation<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!(
+                "{}/organizations/invitations/accept",
+                &self.address
+            ))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_switch_organization<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/organizations/switch", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
     pub async fn post_signup<Body>(&self, body: &Body) -> reqwest::Response
     where
         Body: serde::Serialize,
     {
         self.http_client
-            .post(&format!("{}/signup", &self.address))
+            .post(format!("{}/signup", &self.address))
             .json(body)
             .send()
             .await
@@ -116,7 +480,7 @@ impl TestApp {
         Body: serde::Serialize,
     {
         self.http_client
-            .post(&format!("{}/login", &self.address))
+            .post(format!("{}/login", &self.address))
             .json(body)
             .send()
             .await
@@ -125,7 +489,7 @@ impl TestApp {
 
     pub async fn post_logout(&self) -> reqwest::Response {
         self.http_client
-            .post(&format!("{}/logout", &self.address))
+            .post(format!("{}/logout", &self.address))
             .send()
             .await
             .expect("Failed to execute a request")
@@ -143,12 +507,211 @@ impl TestApp {
             .expect("Failed to execute request.")
     }
 
+    pub async fn post_resend_2fa<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/resend-2fa", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_refresh(&self) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/refresh", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_password_reset_request<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/password-reset/request", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_password_reset_page(&self, token: &str) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/password-reset", &self.address))
+            .query(&[("token", token)])
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_password_reset_confirm<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/password-reset/confirm", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_verify_email(&self, token: &str) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/verify-email", &self.address))
+            .query(&[("token", token)])
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_resend_verification<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/resend-verification", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    // Follow the link from the most recent verification email sent to `email`
+    pub async fn verify_email(&self, email: &str) -> reqwest::Response {
+        let token = self
+            .last_email_to(email)
+            .await
+            .filter(|sent_email| sent_email.subject == "Verify your email")
+            .and_then(|sent_email| get_link_token(&sent_email.content))
+            .expect("No verification email sent");
+
+        self.get_verify_email(&token).await
+    }
+
+    pub async fn post_totp_enroll(&self) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/totp/enroll", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_totp_confirm<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/totp/confirm", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_webauthn_register_start(&self) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/webauthn/register/start", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_webauthn_register_finish<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/webauthn/register/finish", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_webauthn_login_start<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/webauthn/login/start", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_webauthn_login_finish<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/webauthn/login/finish", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_verify_recovery_code<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/verify-recovery-code", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_regenerate_recovery_codes(&self) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/recovery-codes/regenerate", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_introspect(
+        &self,
+        token: &str,
+        client_credentials: Option<(&str, &str)>,
+    ) -> reqwest::Response {
+        let mut request = self
+            .http_client
+            .post(format!("{}/introspect", &self.address))
+            .form(&[("token", token)]);
+
+        if let Some((client_id, client_secret)) = client_credentials {
+            request = request.basic_auth(client_id, Some(client_secret));
+        }
+
+        request.send().await.expect("Failed to execute request.")
+    }
+
+    pub async fn post_token<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/token", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
     pub async fn post_verify_token<Body>(&self, body: &Body) -> reqwest::Response
     where
         Body: serde::Serialize,
     {
         self.http_client
-            .post(&format!("{}/verify-token", &self.address))
+            .post(format!("{}/verify-token", &self.address))
             .json(body)
             .send()
             .await
@@ -162,6 +725,61 @@ impl TestApp {
 // We cannot call clean_up directly in the implementation of the drop fn.
 // This is because clean_up is an async fn and async destructors are not currently supported in Rust.
 // https://rust-lang.github.io/async-fundamentals-initiative/roadmap/async_drop.html
+// Retries come quickly so tests of failing deliveries do not have to wait
+fn test_email_outbox(email_outbox_store: EmailOutboxStoreType) -> EmailOutbox {
+    EmailOutbox::new(
+        email_outbox_store,
+        EmailRetryPolicy {
+            max_attempts: 3,
+            base_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_millis(100),
+            poll_interval: Duration::from_millis(50),
+            ..EmailRetryPolicy::default()
+        },
+    )
+}
+
+// Emails really go out over SMTP, to a sink that keeps them for the test to read
+async fn start_email_server() -> (SmtpSink, EmailClientType) {
+    let email_server = SmtpSink::start().await;
+    let email_client = Arc::new(RwLock::new(
+        SmtpEmailClient::new(
+            "127.0.0.1",
+            Some(email_server.port),
+            SmtpTls::None,
+            None,
+            "Auth Service <no-reply@example.com>",
+        )
+        .expect("Failed to create SMTP email client"),
+    ));
+
+    (email_server, email_client)
+}
+
+// Returns the address the service listens on and a client sharing the returned cookie jar
+async fn start_app(app_state: AppState) -> (String, Arc<Jar>, reqwest::Client) {
+    let app = Application::build(app_state, test::APP_ADDRESS)
+        .await
+        .expect("Failed to build the app");
+
+    let address = format!("http://{}", app.address.clone());
+
+    // Run the auth service is a separate async task
+    // This will make sure that we do not block the main test thread
+
+    #[allow(clippy::let_underscore_future)]
+    let _ = tokio::spawn(app.run());
+
+    let cookie_jar = Arc::new(Jar::default());
+    // Create a reqwest client backed by the shared cookie jar so tests can set cookies
+    let http_client = reqwest::Client::builder()
+        .cookie_provider(cookie_jar.clone())
+        .build()
+        .expect("Failed to build http client");
+
+    (address, cookie_jar, http_client)
+}
+
 impl Drop for TestApp {
     fn drop(&mut self) {
         if !self.clean_up_called {
@@ -169,18 +787,40 @@ impl Drop for TestApp {
         }
     }
 }
+// Pull the token out of the link in an email sent by the auth service
+pub fn get_link_token(content: &str) -> Option<String> {
+    content
+        .split("token=")
+        .nth(1)?
+        .split_whitespace()
+        .next()
+        .map(|token| token.to_owned())
+}
+
+// Pull the code out of a 2FA email sent by the auth service
+pub fn get_two_fa_code(content: &str) -> Option<String> {
+    content
+        .split_whitespace()
+        .find(|word| word.len() == 6 && word.chars().all(|c| c.is_ascii_digit()))
+        .map(|code| code.to_owned())
+}
+
 pub fn get_random_email() -> String {
     format!("{}@example.com", Uuid::new_v4())
 }
 
-async fn configure_postgresql(db_name: &str) -> PgPool {
+pub async fn configure_postgresql(db_name: &str) -> PgPool {
     let postgresql_conn_url = DATABASE_URL.to_owned();
 
-    configure_database(&postgresql_conn_url, &db_name).await;
+    configure_database(&postgresql_conn_url, db_name).await;
+
+    connect_postgresql(db_name).await
+}
 
-    let postgresql_conn_url_with_db = format!("{}/{}", postgresql_conn_url, db_name);
+// A new connection pool to an existing test database, as another instance of the service would have
+pub async fn connect_postgresql(db_name: &str) -> PgPool {
+    let postgresql_conn_url_with_db = format!("{}/{}", DATABASE_URL.as_str(), db_name);
 
-    // Create a new connection pool and return it
     get_postgres_pool(&postgresql_conn_url_with_db)
         .await
         .expect("Failed to create Postgres connection pool!")
@@ -216,7 +856,7 @@ async fn configure_database(db_conn_string: &str, db_name: &str) {
 
 // This helper function connects to our PostgreSQL instance.
 // It kills any active connections to our test database and then deletes the database.
-async fn delete_database(db_name: &str) {
+pub async fn delete_database(db_name: &str) {
     let postgresql_conn_url: String = DATABASE_URL.to_owned();
 
     let connection_options = PgConnectOptions::from_str(&postgresql_conn_url)
@@ -250,11 +890,35 @@ async fn delete_database(db_name: &str) {
         .expect("Failed to drop the database.");
 }
 
-fn configure_redis() -> redis::Connection {
+pub async fn configure_redis() -> ConnectionManager {
     let redis_hostname = DEFAULT_REDIS_HOSTNAME.to_owned();
 
     get_redis_client(redis_hostname)
         .expect("Failed to get Redis client")
-        .get_connection()
+        .get_connection_manager()
+        .await
         .expect("Failed to get Redis connection")
 }
+
+// A fresh database file per test, so the pool's connections share it like they do in production
+#[cfg(feature = "sqlite")]
+pub async fn configure_sqlite() -> (sqlx::SqlitePool, PathBuf) {
+    let path = std::env::temp_dir().join(format!("{}.db", Uuid::new_v4()));
+    let pool = auth_service::get_sqlite_pool(&format!("sqlite://{}", path.display()))
+        .await
+        .expect("Failed to open the SQLite database!");
+
+    sqlx::migrate!("./migrations_sqlite")
+        .run(&pool)
+        .await
+        .expect("Failed to migrate the database");
+
+    (pool, path)
+}
+
+// The service's pool may still be open, SQLite lets the files go anyway
+pub fn delete_sqlite_files(path: &std::path::Path) {
+    for suffix in ["", "-wal", "-shm"] {
+        let _ = std::fs::remove_file(format!("{}{}", path.display(), suffix));
+    }
+}
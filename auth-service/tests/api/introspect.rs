@@ -0,0 +1,184 @@
+use auth_service::{
+    routes::{IntrospectionResponse, TokenResponse},
+    utils::{
+        auth::session_audience,
+        constants::{INTROSPECTION_CLIENTS, JWT_AUDIENCE, JWT_COOKIE_NAME, JWT_ISSUER},
+    },
+    ErrorResponse,
+};
+use test_helpers::api_test;
+
+use crate::helpers::{get_random_email, TestApp};
+
+// The credentials of the consumer the auth cookie's tokens are issued for
+fn client_credentials() -> (&'static str, &'static str) {
+    consumer_credentials(session_audience())
+}
+
+fn consumer_credentials(client_id: &str) -> (&'static str, &'static str) {
+    let (client_id, client_secret) = INTROSPECTION_CLIENTS
+        .get_key_value(client_id)
+        .expect("INTROSPECTION_CLIENTS must list every consumer for tests");
+    (client_id, client_secret)
+}
+
+// Sign up, verify and log in a user without 2FA, returning the JWT from the auth cookie
+async fn login_new_user(app: &TestApp, email: &str) -> String {
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+            "requires2FA": false
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let response = app.verify_email(email).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+
+    auth_cookie.value().to_owned()
+}
+
+async fn introspect(app: &TestApp, token: &str) -> IntrospectionResponse {
+    let response = app.post_introspect(token, Some(client_credentials())).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    response
+        .json::<IntrospectionResponse>()
+        .await
+        .expect("Could not deserialize response body to IntrospectionResponse")
+}
+
+#[api_test]
+async fn should_describe_active_token() {
+    let random_email = get_random_email();
+    let token = login_new_user(&app, &random_email).await;
+
+    let response = introspect(&app, &token).await;
+
+    assert!(response.active);
+    assert_eq!(response.sub, Some(random_email.clone()));
+    assert_eq!(response.username, Some(random_email));
+    assert_eq!(response.token_type, Some("Bearer".to_owned()));
+    assert!(response.exp.unwrap() > response.iat.unwrap());
+    assert_eq!(response.nbf, response.iat);
+    assert_eq!(response.iss, Some(JWT_ISSUER.clone()));
+    assert_eq!(response.aud, Some(session_audience().to_owned()));
+    assert!(response.jti.is_some());
+}
+
+#[api_test]
+async fn should_return_inactive_for_logged_out_token() {
+    let random_email = get_random_email();
+    let token = login_new_user(&app, &random_email).await;
+
+    let response = app.post_logout().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = introspect(&app, &token).await;
+
+    assert_eq!(
+        response,
+        IntrospectionResponse {
+            active: false,
+            ..Default::default()
+        }
+    );
+}
+
+#[api_test]
+async fn should_return_inactive_for_malformed_token() {
+    let response = introspect(&app, "invalid_token").await;
+
+    assert!(!response.active);
+    assert_eq!(response.sub, None);
+}
+
+#[api_test]
+async fn should_return_401_without_client_credentials() {
+    let random_email = get_random_email();
+    let token = login_new_user(&app, &random_email).await;
+
+    let response = app.post_introspect(&token, None).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "InvalidClient".to_owned()
+    );
+}
+
+#[api_test]
+async fn should_return_401_if_client_secret_is_wrong() {
+    let random_email = get_random_email();
+    let token = login_new_user(&app, &random_email).await;
+    let (client_id, _) = client_credentials();
+
+    let response = app
+        .post_introspect(&token, Some((client_id, "wrong-secret")))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[api_test]
+async fn should_describe_token_only_to_its_consumer() {
+    let random_email = get_random_email();
+    login_new_user(&app, &random_email).await;
+
+    let other_consumer = JWT_AUDIENCE
+        .iter()
+        .find(|audience| *audience != session_audience())
+        .expect("JWT_AUDIENCE must name a second consumer for tests");
+
+    let response = app
+        .post_token(&serde_json::json!({ "audience": other_consumer }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let token = response
+        .json::<TokenResponse>()
+        .await
+        .expect("Could not deserialize response body to TokenResponse")
+        .token;
+
+    let response = app
+        .post_introspect(&token, Some(consumer_credentials(other_consumer)))
+        .await
+        .json::<IntrospectionResponse>()
+        .await
+        .expect("Could not deserialize response body to IntrospectionResponse");
+
+    assert!(response.active);
+    assert_eq!(response.sub, Some(random_email));
+    assert_eq!(response.aud, Some(other_consumer.clone()));
+
+    // the consumer of the auth cookie is not told about it
+    let response = introspect(&app, &token).await;
+
+    assert!(!response.active);
+}
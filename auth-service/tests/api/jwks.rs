@@ -0,0 +1,20 @@
+use jsonwebtoken::jwk::JwkSet;
+use test_helpers::api_test;
+
+use crate::helpers::TestApp;
+
+#[api_test]
+async fn should_publish_signing_key_set() {
+    let response = app.get_jwks().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.headers().get("cache-control").is_some());
+
+    let jwks = response
+        .json::<JwkSet>()
+        .await
+        .expect("Could not deserialize response body to JwkSet");
+
+    // the test environment signs with JWT_SECRET, which must never be published
+    assert!(jwks.keys.is_empty());
+}
@@ -0,0 +1,241 @@
+use std::sync::Arc;
+
+use auth_service::{
+    app_state::KeyRingType,
+    routes::RotateJwtKeysResponse,
+    services::postgres_jwt_key_store::PostgresJwtKeyStore,
+    utils::{
+        auth::{session_audience, validate_token, TOKEN_TTL_SECONDS},
+        constants::{
+            ADMIN_API_KEY, JWT_COOKIE_NAME, JWT_LEEWAY_SECONDS, JWT_SIGNING_KEY,
+            JWT_VERIFICATION_KEYS,
+        },
+        jwt::{refresh_key_ring, KeyRing},
+    },
+    ErrorResponse,
+};
+use jsonwebtoken::jwk::JwkSet;
+use ring::{rand::SystemRandom, signature::Ed25519KeyPair};
+use test_helpers::api_test;
+use tokio::sync::RwLock;
+
+use crate::helpers::{connect_postgresql, get_random_email, TestApp};
+
+fn admin_api_key() -> &'static str {
+    ADMIN_API_KEY
+        .as_deref()
+        .expect("ADMIN_API_KEY must be set for tests")
+}
+
+fn ed25519_private_key() -> String {
+    let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap();
+    pem::encode(&pem::Pem::new("PRIVATE KEY", pkcs8.as_ref()))
+}
+
+// Sign up, verify and log in a user without 2FA, returning the JWT from the auth cookie
+async fn login_new_user(app: &TestApp, email: &str) -> String {
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+            "requires2FA": false
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let response = app.verify_email(email).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    login(app, email).await
+}
+
+async fn login(app: &TestApp, email: &str) -> String {
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+
+    auth_cookie.value().to_owned()
+}
+
+async fn rotate(app: &TestApp, body: &serde_json::Value) -> RotateJwtKeysResponse {
+    let response = app.post_rotate_jwt_keys(body, Some(admin_api_key())).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    response
+        .json::<RotateJwtKeysResponse>()
+        .await
+        .expect("Could not deserialize response body to RotateJwtKeysResponse")
+}
+
+#[api_test]
+async fn should_keep_accepting_tokens_signed_with_rotated_out_key() {
+    let random_email = get_random_email();
+    let old_token = login_new_user(&app, &random_email).await;
+    let rotated_at = chrono::Utc::now().timestamp();
+
+    let rotation = rotate(
+        &app,
+        &serde_json::json!({ "privateKey": ed25519_private_key() }),
+    )
+    .await;
+
+    assert_ne!(rotation.kid, rotation.retired_kid);
+    // tokens pass validation for the leeway past their expiry, so their key is trusted that long too
+    assert!(rotation.retire_at >= rotated_at + TOKEN_TTL_SECONDS + *JWT_LEEWAY_SECONDS as i64);
+
+    // the retired key is still trusted for the lifetime of the tokens it signed
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": old_token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    // new tokens are signed with the promoted key
+    let new_token = login(&app, &random_email).await;
+    let header = jsonwebtoken::decode_header(&new_token).unwrap();
+
+    assert_eq!(header.kid, Some(rotation.kid.clone()));
+    assert_eq!(header.alg, jsonwebtoken::Algorithm::EdDSA);
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": new_token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let jwks = app
+        .get_jwks()
+        .await
+        .json::<JwkSet>()
+        .await
+        .expect("Could not deserialize response body to JwkSet");
+
+    assert!(jwks.find(&rotation.kid).is_some());
+}
+
+// Another instance sharing the database, it only knows the configured keys until it loads the store
+async fn other_instance(app: &TestApp) -> KeyRingType {
+    let store = PostgresJwtKeyStore::new(connect_postgresql(&app.db_name).await);
+
+    Arc::new(RwLock::new(
+        KeyRing::new(JWT_SIGNING_KEY.clone(), JWT_VERIFICATION_KEYS.clone())
+            .with_store(Arc::new(store)),
+    ))
+}
+
+#[api_test]
+async fn should_share_rotated_key_with_other_instances() {
+    let random_email = get_random_email();
+    let old_token = login_new_user(&app, &random_email).await;
+    let running = other_instance(&app).await;
+
+    let rotation = rotate(&app, &serde_json::json!({})).await;
+    let new_token = login(&app, &random_email).await;
+
+    // the running instance picks up the new key from the first token signed with it
+    for token in [&new_token, &old_token] {
+        assert!(validate_token(
+            token,
+            session_audience(),
+            running.clone(),
+            app.banned_token_store.clone()
+        )
+        .await
+        .is_ok());
+    }
+    assert_eq!(running.read().await.active().kid(), rotation.kid);
+
+    // an instance started after the rotation signs with the new key straight away
+    let started = other_instance(&app).await;
+    refresh_key_ring(&started).await.unwrap();
+
+    assert_eq!(started.read().await.active().kid(), rotation.kid);
+    assert!(started
+        .read()
+        .await
+        .find(Some(&rotation.retired_kid))
+        .is_some());
+}
+
+#[api_test]
+async fn should_generate_key_when_none_is_given() {
+    let random_email = get_random_email();
+    let old_token = login_new_user(&app, &random_email).await;
+
+    let first = rotate(&app, &serde_json::json!({})).await;
+    let second = rotate(&app, &serde_json::json!({})).await;
+
+    assert_eq!(first.kid, second.retired_kid);
+
+    // both rotated-out keys are still within their retirement window
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": old_token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let new_token = login(&app, &random_email).await;
+    let header = jsonwebtoken::decode_header(&new_token).unwrap();
+
+    assert_eq!(header.kid, Some(second.kid));
+}
+
+#[api_test]
+async fn should_return_400_if_admin_api_key_is_missing() {
+    let response = app.post_rotate_jwt_keys(&serde_json::json!({}), None).await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "MissingToken".to_owned()
+    );
+}
+
+#[api_test]
+async fn should_return_401_if_admin_api_key_is_invalid() {
+    let response = app
+        .post_rotate_jwt_keys(&serde_json::json!({}), Some("not-the-admin-key"))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[api_test]
+async fn should_return_400_if_private_key_is_invalid() {
+    let random_email = get_random_email();
+    let token = login_new_user(&app, &random_email).await;
+
+    let response = app
+        .post_rotate_jwt_keys(
+            &serde_json::json!({ "privateKey": "not a pem" }),
+            Some(admin_api_key()),
+        )
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    // the active key is left in place
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
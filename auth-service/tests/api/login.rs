@@ -1,7 +1,13 @@
-use crate::helpers::{get_random_email, TestApp};
+use crate::helpers::{get_random_email, get_two_fa_code, TestApp};
 use auth_service::{
-    domain::Email, routes::TwoFactorAuthResponse, utils::constants::JWT_COOKIE_NAME, ErrorResponse,
+    domain::{Email, LoginAttemptId},
+    routes::TwoFactorAuthResponse,
+    utils::constants::{
+        JWT_COOKIE_NAME, LOGIN_IP_LOCKOUT_THRESHOLD, LOGIN_LOCKOUT_SECONDS, LOGIN_LOCKOUT_THRESHOLD,
+    },
+    ErrorResponse,
 };
+use reqwest::header::RETRY_AFTER;
 use test_helpers::api_test;
 
 #[api_test]
@@ -138,6 +144,10 @@ async fn should_return_200_if_valid_credentials_and_2fa_disabled() {
 
     assert_eq!(response.status().as_u16(), 201);
 
+    let response = app.verify_email(&random_email).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
     let login_body = serde_json::json!({
         "email": random_email,
         "password": "password123",
@@ -171,6 +181,10 @@ async fn should_return_206_if_valid_credentials_and_2fa_enabled() {
     let response = app.post_signup(&signup_body).await;
     assert_eq!(response.status().as_u16(), 201);
 
+    let response = app.verify_email(&random_email).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
     // call login with the new user and make sure 206 is returned since 2FA is enabled for the user
     let login_body = serde_json::json!({
         "email": random_email,
@@ -188,12 +202,159 @@ async fn should_return_206_if_valid_credentials_and_2fa_enabled() {
     assert_eq!(json_body.message, "2FA required".to_owned());
 
     let email = Email::parse(random_email.clone()).expect("Failed to parse email");
-    let login_attempt_id = json_body.login_attempt_id.clone();
-    let store = app.two_fa_code_store.read().await;
-    let (stored_login_attempt_id, _) = store
-        .get_code(&email)
+    let login_attempt_id =
+        LoginAttemptId::parse(json_body.login_attempt_id.clone()).expect("Failed to parse id");
+    let store = &app.two_fa_code_store;
+    let (stored_email, code) = store
+        .get_code(&login_attempt_id)
         .await
         .expect("Expected login attempt id to be stored for 2FA");
 
-    assert_eq!(stored_login_attempt_id.as_ref(), login_attempt_id.as_str());
+    assert_eq!(stored_email, email);
+
+    // the code arrives by email, in both the HTML and the plain-text body
+    let sent_email = app
+        .last_email_to(&random_email)
+        .await
+        .expect("No 2FA email sent");
+
+    assert_eq!(sent_email.subject, "2FA Code".to_owned());
+    assert_eq!(
+        get_two_fa_code(&sent_email.content),
+        Some(code.as_ref().to_owned())
+    );
+    assert!(sent_email.html.contains(code.as_ref()));
+}
+
+async fn signup_verified_user(app: &TestApp, email: &str) {
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+            "requires2FA": false
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let response = app.verify_email(email).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+async fn post_wrong_password(app: &TestApp, email: &str) -> reqwest::Response {
+    app.post_login(&serde_json::json!({
+        "email": email,
+        "password": "wrongpassword",
+    }))
+    .await
+}
+
+fn retry_after(response: &reqwest::Response) -> u64 {
+    response
+        .headers()
+        .get(RETRY_AFTER)
+        .expect("No Retry-After header")
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap()
+}
+
+#[api_test]
+async fn should_return_429_after_repeated_failed_logins() {
+    let random_email = get_random_email();
+    signup_verified_user(&app, &random_email).await;
+
+    for _ in 1..*LOGIN_LOCKOUT_THRESHOLD {
+        let response = post_wrong_password(&app, &random_email).await;
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    let response = post_wrong_password(&app, &random_email).await;
+
+    assert_eq!(response.status().as_u16(), 429);
+    assert_eq!(retry_after(&response), *LOGIN_LOCKOUT_SECONDS as u64);
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "AccountLocked".to_owned()
+    );
+
+    let lock_email = app
+        .last_email_to(&random_email)
+        .await
+        .expect("No email sent");
+
+    assert_eq!(lock_email.subject, "Account locked".to_owned());
+
+    // while locked even the right password is refused
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": random_email,
+            "password": "password123",
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 429);
+    assert!(retry_after(&response) <= *LOGIN_LOCKOUT_SECONDS as u64);
+}
+
+#[api_test]
+async fn should_lock_unknown_accounts_the_same_way() {
+    let random_email = get_random_email();
+
+    for _ in 1..*LOGIN_LOCKOUT_THRESHOLD {
+        let response = post_wrong_password(&app, &random_email).await;
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    let response = post_wrong_password(&app, &random_email).await;
+
+    assert_eq!(response.status().as_u16(), 429);
+    assert!(app.last_email_to(&random_email).await.is_none());
+}
+
+#[api_test]
+async fn should_forget_failures_after_successful_login() {
+    let random_email = get_random_email();
+    signup_verified_user(&app, &random_email).await;
+
+    for _ in 1..*LOGIN_LOCKOUT_THRESHOLD {
+        let response = post_wrong_password(&app, &random_email).await;
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": random_email,
+            "password": "password123",
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = post_wrong_password(&app, &random_email).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[api_test]
+async fn should_lock_client_address_after_failures_across_accounts() {
+    // one failure each, so no account reaches its own threshold
+    for _ in 1..*LOGIN_IP_LOCKOUT_THRESHOLD {
+        let response = post_wrong_password(&app, &get_random_email()).await;
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    let response = post_wrong_password(&app, &get_random_email()).await;
+
+    assert_eq!(response.status().as_u16(), 429);
+
+    let response = post_wrong_password(&app, &get_random_email()).await;
+
+    assert_eq!(response.status().as_u16(), 429);
 }
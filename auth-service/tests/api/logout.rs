@@ -1,5 +1,6 @@
 use crate::helpers::{get_random_email, TestApp};
 use auth_service::{utils::constants::JWT_COOKIE_NAME, ErrorResponse};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
 use reqwest::Url;
 use test_helpers::api_test;
 
@@ -45,6 +46,10 @@ async fn should_return_200_if_valid_jwt_cookie() {
 
     assert_eq!(response.status().as_u16(), 201);
 
+    let response = app.verify_email(&random_email).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
     let login_body = serde_json::json!({
         "email": random_email,
         "password": "password123",
@@ -74,9 +79,15 @@ async fn should_return_200_if_valid_jwt_cookie() {
 
     assert!(auth_cookie.value().is_empty());
 
-    let banned_token_store = app.banned_token_store.read().await;
+    // tokens are banned by their id rather than the whole token string
+    let payload = token.split('.').nth(1).expect("JWT has no payload");
+    let claims: serde_json::Value =
+        serde_json::from_slice(&URL_SAFE_NO_PAD.decode(payload).unwrap()).unwrap();
+    let jti = claims["jti"].as_str().expect("JWT has no jti claim");
+
+    let banned_token_store = &app.banned_token_store;
     let contains_token = banned_token_store
-        .check_token(token)
+        .check_token(jti)
         .await
         .expect("Failed to check if token is banned");
 
@@ -97,6 +108,10 @@ async fn should_return_400_if_logout_called_twice_in_a_row() {
 
     assert_eq!(response.status().as_u16(), 201);
 
+    let response = app.verify_email(&random_email).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
     let login_body = serde_json::json!({
         "email": random_email,
         "password": "password123",
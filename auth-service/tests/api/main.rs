@@ -1,7 +1,26 @@
+mod concurrency;
+mod email_outbox;
 mod helpers;
+mod introspect;
+mod jwks;
+mod jwt_keys;
 mod login;
 mod logout;
+mod organizations;
+mod password_reset;
+mod rate_limit;
+mod recovery_codes;
+mod refresh;
+mod resend_2fa;
+mod roles;
 mod root;
 mod signup;
+mod smtp_sink;
+mod store_conformance;
+mod stores;
+mod token;
+mod totp;
 mod verify_2fa;
+mod verify_email;
 mod verify_token;
+mod webauthn;
@@ -0,0 +1,495 @@
+use auth_service::{
+    domain::LoginAttemptId,
+    routes::{OrganizationResponse, OrganizationsResponse, TwoFactorAuthResponse},
+    utils::constants::{JWT_COOKIE_NAME, REFRESH_COOKIE_NAME},
+    ErrorResponse,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use reqwest::Url;
+use test_helpers::api_test;
+
+use crate::helpers::{get_link_token, get_random_email, TestApp};
+
+// Sign up, verify and log in a user without 2FA, returning the JWT from the auth cookie
+async fn login_new_user(app: &TestApp, email: &str) -> String {
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+            "requires2FA": false
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let response = app.verify_email(email).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    login(app, email, None).await
+}
+
+async fn login(app: &TestApp, email: &str, organization_id: Option<&str>) -> String {
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+            "organizationId": organization_id,
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    auth_token(&response)
+}
+
+fn auth_token(response: &reqwest::Response) -> String {
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+
+    auth_cookie.value().to_owned()
+}
+
+// Read the claims of a JWT without verifying it
+fn claims(token: &str) -> serde_json::Value {
+    let payload = token.split('.').nth(1).expect("Malformed JWT");
+    serde_json::from_slice(&URL_SAFE_NO_PAD.decode(payload).unwrap()).unwrap()
+}
+
+async fn create_organization(app: &TestApp, name: &str) -> OrganizationResponse {
+    let response = app
+        .post_create_organization(&serde_json::json!({ "name": name }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    response
+        .json::<OrganizationResponse>()
+        .await
+        .expect("Could not deserialize response body to OrganizationResponse")
+}
+
+// The logged-in user invites `email`, returning the token from the invitation email
+async fn invite(app: &TestApp, organization_id: &str, email: &str, role: &str) -> String {
+    let response = app
+        .post_invite_member(
+            organization_id,
+            &serde_json::json!({ "email": email, "role": role }),
+        )
+        .await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    app.last_email_to(email)
+        .await
+        .filter(|sent_email| sent_email.subject == "Organization invitation")
+        .and_then(|sent_email| get_link_token(&sent_email.content))
+        .expect("No invitation email sent")
+}
+
+async fn error(response: reqwest::Response) -> String {
+    response
+        .json::<ErrorResponse>()
+        .await
+        .expect("Could not deserialize response body to ErrorResponse")
+        .error
+}
+
+#[api_test]
+async fn should_return_400_if_not_logged_in() {
+    let response = app
+        .post_create_organization(&serde_json::json!({ "name": "Acme" }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(error(response).await, "MissingToken".to_owned());
+}
+
+#[api_test]
+async fn should_create_and_list_organizations() {
+    login_new_user(&app, &get_random_email()).await;
+
+    let response = app
+        .post_create_organization(&serde_json::json!({ "name": "   " }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    let organization = create_organization(&app, "Acme").await;
+
+    assert_eq!(organization.name, "Acme".to_owned());
+    assert_eq!(organization.role, "owner".to_owned());
+
+    let response = app.get_organizations().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let organizations = response
+        .json::<OrganizationsResponse>()
+        .await
+        .expect("Could not deserialize response body to OrganizationsResponse");
+
+    assert_eq!(organizations.organizations, vec![organization]);
+    // creating an organization does not switch to it
+    assert_eq!(organizations.selected_organization_id, None);
+}
+
+#[api_test]
+async fn should_add_member_through_invitation() {
+    let owner_email = get_random_email();
+    let invitee_email = get_random_email();
+
+    login_new_user(&app, &invitee_email).await;
+    login_new_user(&app, &owner_email).await;
+
+    let organization = create_organization(&app, "Acme").await;
+    let token = invite(&app, &organization.id, &invitee_email, "member").await;
+
+    // inviting a second time while the user is a member is refused
+    let response = app
+        .post_invite_member(
+            &organization.id,
+            &serde_json::json!({ "email": owner_email, "role": "admin" }),
+        )
+        .await;
+
+    assert_eq!(response.status().as_u16(), 409);
+
+    // the emailed link opens a page that posts the token, opening it does not accept the invitation
+    let response = app.get_invitation_page(&token).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let page = response.text().await.expect("Could not read the page");
+
+    assert!(page.contains(&token));
+    assert!(page.contains("/organizations/invitations/accept"));
+
+    let response = app.get_invitation_page("invalid").await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    // the invitation only works for the address it was sent to
+    let response = app
+        .post_accept_invitation(&serde_json::json!({ "token": token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    login(&app, &invitee_email, None).await;
+
+    let response = app
+        .post_accept_invitation(&serde_json::json!({ "token": token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let membership = response
+        .json::<OrganizationResponse>()
+        .await
+        .expect("Could not deserialize response body to OrganizationResponse");
+
+    assert_eq!(membership.id, organization.id);
+    assert_eq!(membership.role, "member".to_owned());
+
+    let response = app
+        .post_accept_invitation(&serde_json::json!({ "token": token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[api_test]
+async fn should_return_403_if_member_invites() {
+    let owner_email = get_random_email();
+    let member_email = get_random_email();
+
+    login_new_user(&app, &member_email).await;
+    login_new_user(&app, &owner_email).await;
+
+    let organization = create_organization(&app, "Acme").await;
+    let token = invite(&app, &organization.id, &member_email, "member").await;
+
+    login(&app, &member_email, None).await;
+
+    let response = app
+        .post_accept_invitation(&serde_json::json!({ "token": token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_invite_member(
+            &organization.id,
+            &serde_json::json!({ "email": get_random_email(), "role": "member" }),
+        )
+        .await;
+
+    assert_eq!(response.status().as_u16(), 403);
+    assert_eq!(error(response).await, "MissingPermission".to_owned());
+}
+
+#[api_test]
+async fn should_return_404_if_inviting_to_organization_of_others() {
+    login_new_user(&app, &get_random_email()).await;
+    let organization = create_organization(&app, "Acme").await;
+
+    login_new_user(&app, &get_random_email()).await;
+
+    let response = app
+        .post_invite_member(
+            &organization.id,
+            &serde_json::json!({ "email": get_random_email(), "role": "member" }),
+        )
+        .await;
+
+    assert_eq!(response.status().as_u16(), 404);
+    assert_eq!(error(response).await, "OrganizationNotFound".to_owned());
+}
+
+#[api_test]
+async fn should_reissue_auth_cookie_on_switch() {
+    let token = login_new_user(&app, &get_random_email()).await;
+
+    assert!(claims(&token).get("org_id").is_none());
+
+    let organization = create_organization(&app, "Acme").await;
+
+    let response = app
+        .post_switch_organization(&serde_json::json!({ "organizationId": organization.id }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let token = auth_token(&response);
+
+    assert_eq!(claims(&token)["org_id"], organization.id.as_str());
+    assert_eq!(claims(&token)["org_role"], "owner");
+
+    // the session keeps the organization when the token is refreshed
+    let response = app.post_refresh().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        claims(&auth_token(&response))["org_id"],
+        organization.id.as_str()
+    );
+
+    let response = app
+        .post_switch_organization(&serde_json::json!({
+            "organizationId": uuid::Uuid::new_v4().to_string()
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+fn refresh_token(response: &reqwest::Response) -> String {
+    response
+        .cookies()
+        .find(|cookie| cookie.name() == REFRESH_COOKIE_NAME)
+        .expect("No refresh cookie found")
+        .value()
+        .to_owned()
+}
+
+// Continue the session of the given refresh token, as if the request came from its browser
+async fn refresh(app: &TestApp, refresh_token: &str) -> reqwest::Response {
+    app.cookie_jar.add_cookie_str(
+        &format!(
+            "{}={}; HttpOnly; SameSite=Lax; Path=/",
+            REFRESH_COOKIE_NAME, refresh_token
+        ),
+        &Url::parse("http://127.0.0.1").expect("Failed to parse URL"),
+    );
+
+    app.post_refresh().await
+}
+
+#[api_test]
+async fn should_keep_organization_per_session() {
+    let email = get_random_email();
+    login_new_user(&app, &email).await;
+
+    let first = create_organization(&app, "Acme").await;
+    let second = create_organization(&app, "Globex").await;
+
+    // one session works in the first organization
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+            "organizationId": first.id,
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let first_session = refresh_token(&response);
+
+    // another one, e.g. in a second browser, switches to the second organization
+    login(&app, &email, None).await;
+
+    let response = app
+        .post_switch_organization(&serde_json::json!({ "organizationId": second.id }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let second_session = refresh_token(&response);
+
+    let response = refresh(&app, &first_session).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(claims(&auth_token(&response))["org_id"], first.id.as_str());
+
+    let response = refresh(&app, &second_session).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(claims(&auth_token(&response))["org_id"], second.id.as_str());
+}
+
+#[api_test]
+async fn should_revoke_refresh_token_of_session_switching_organization() {
+    login_new_user(&app, &get_random_email()).await;
+
+    let organization = create_organization(&app, "Acme").await;
+
+    let response = app.post_refresh().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let before_switch = refresh_token(&response);
+
+    let response = app
+        .post_switch_organization(&serde_json::json!({ "organizationId": organization.id }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    // the old family would refresh into no organization, so it no longer works
+    let response = refresh(&app, &before_switch).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[api_test]
+async fn should_select_organization_at_login() {
+    let email = get_random_email();
+    login_new_user(&app, &email).await;
+
+    let first = create_organization(&app, "Acme").await;
+    let second = create_organization(&app, "Globex").await;
+
+    let token = login(&app, &email, Some(&first.id)).await;
+
+    assert_eq!(claims(&token)["org_id"], first.id.as_str());
+
+    let token = login(&app, &email, Some(&second.id)).await;
+
+    assert_eq!(claims(&token)["org_id"], second.id.as_str());
+
+    // without an organization the new session works in none, whatever earlier sessions selected
+    let token = login(&app, &email, None).await;
+
+    assert!(claims(&token).get("org_id").is_none());
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+            "organizationId": uuid::Uuid::new_v4().to_string(),
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 404);
+    assert_eq!(error(response).await, "OrganizationNotFound".to_owned());
+}
+
+// Log in a user with email 2FA up to the second factor, returning the login attempt id
+async fn start_2fa_login(app: &TestApp, email: &str, organization_id: Option<&str>) -> String {
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+            "organizationId": organization_id,
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 206);
+
+    response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse")
+        .login_attempt_id
+}
+
+// Complete the login with the emailed code, returning the JWT from the auth cookie
+async fn finish_2fa_login(app: &TestApp, email: &str, login_attempt_id: &str) -> String {
+    let (_, code) = app
+        .two_fa_code_store
+        .get_code(&LoginAttemptId::parse(login_attempt_id.to_owned()).unwrap())
+        .await
+        .unwrap();
+
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": login_attempt_id,
+            "2FACode": code.as_ref(),
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    auth_token(&response)
+}
+
+#[api_test]
+async fn should_select_organization_once_2fa_login_completes() {
+    let email = get_random_email();
+
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+            "requires2FA": true
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let response = app.verify_email(&email).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let login_attempt_id = start_2fa_login(&app, &email, None).await;
+    finish_2fa_login(&app, &email, &login_attempt_id).await;
+
+    let first = create_organization(&app, "Acme").await;
+    let second = create_organization(&app, "Globex").await;
+
+    let login_attempt_id = start_2fa_login(&app, &email, Some(&first.id)).await;
+    let token = finish_2fa_login(&app, &email, &login_attempt_id).await;
+
+    assert_eq!(claims(&token)["org_id"], first.id.as_str());
+
+    // a password alone does not change the organization of the session that is logged in
+    let login_attempt_id = start_2fa_login(&app, &email, Some(&second.id)).await;
+
+    let organizations = app
+        .get_organizations()
+        .await
+        .json::<OrganizationsResponse>()
+        .await
+        .expect("Could not deserialize response body to OrganizationsResponse");
+
+    assert_eq!(organizations.selected_organization_id, Some(first.id));
+
+    let token = finish_2fa_login(&app, &email, &login_attempt_id).await;
+
+    assert_eq!(claims(&token)["org_id"], second.id.as_str());
+}
@@ -0,0 +1,321 @@
+use crate::helpers::{configure_redis, get_link_token, get_random_email, TestApp};
+use auth_service::{
+    domain::{Email, OneTimeToken, OneTimeTokenPurpose, OneTimeTokenStore, OneTimeTokenStoreError},
+    services::redis_one_time_token_store::RedisOneTimeTokenStore,
+    utils::constants::JWT_COOKIE_NAME,
+    ErrorResponse,
+};
+use test_helpers::api_test;
+
+// Pull the reset token out of the link in the most recent password reset email
+async fn get_reset_token(app: &TestApp, email: &str) -> String {
+    let sent_email = app
+        .last_email_to(email)
+        .await
+        .expect("No password reset email sent");
+
+    assert_eq!(sent_email.subject, "Password reset");
+
+    get_link_token(&sent_email.content).expect("No token in password reset email")
+}
+
+#[api_test]
+async fn should_return_400_if_invalid_email() {
+    let response = app
+        .post_password_reset_request(&serde_json::json!({ "email": "invalid_email" }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[api_test]
+async fn should_return_200_without_sending_email_if_user_not_found() {
+    let random_email = get_random_email();
+
+    let response = app
+        .post_password_reset_request(&serde_json::json!({ "email": random_email }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    assert!(app.last_email_to(&random_email).await.is_none());
+}
+
+#[api_test]
+async fn should_return_401_if_invalid_reset_token() {
+    let test_cases = [
+        "invalid".to_owned(),
+        // well formed, but never issued
+        OneTimeToken::default().as_ref().to_owned(),
+    ];
+
+    for token in test_cases {
+        let response = app
+            .post_password_reset_confirm(&serde_json::json!({
+                "token": token,
+                "newPassword": "newpassword123"
+            }))
+            .await;
+
+        assert_eq!(response.status().as_u16(), 401);
+
+        assert_eq!(
+            response
+                .json::<ErrorResponse>()
+                .await
+                .expect("Could not deserialize response body to ErrorResponse")
+                .error,
+            "InvalidToken".to_owned()
+        );
+    }
+}
+
+#[api_test]
+async fn should_reset_password_and_invalidate_sessions() {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let response = app.verify_email(&random_email).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+    });
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let auth_token = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found")
+        .value()
+        .to_owned();
+
+    let response = app
+        .post_password_reset_request(&serde_json::json!({ "email": random_email }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let token = get_reset_token(&app, &random_email).await;
+
+    let response = app
+        .post_password_reset_confirm(&serde_json::json!({
+            "token": token,
+            "newPassword": "newpassword123"
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    // the session started before the reset is no longer valid
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": auth_token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app.post_refresh().await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    // only the new password is accepted
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": random_email,
+            "password": "newpassword123",
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    // the session started right after the reset, likely within the same second, is valid
+    let auth_token = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found")
+        .value()
+        .to_owned();
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": auth_token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[api_test]
+async fn should_return_401_if_reset_token_reused() {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let response = app.verify_email(&random_email).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_password_reset_request(&serde_json::json!({ "email": random_email }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let token = get_reset_token(&app, &random_email).await;
+
+    let confirm_body = serde_json::json!({
+        "token": token,
+        "newPassword": "newpassword123"
+    });
+
+    let response = app.post_password_reset_confirm(&confirm_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_password_reset_confirm(&confirm_body).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[api_test]
+async fn should_serve_a_reset_form_at_the_emailed_link() {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let response = app
+        .post_password_reset_request(&serde_json::json!({ "email": random_email }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let sent_email = app
+        .last_email_to(&random_email)
+        .await
+        .expect("No password reset email sent");
+    let token = get_link_token(&sent_email.content).expect("No token in password reset email");
+
+    assert!(sent_email
+        .content
+        .contains(&format!("/password-reset?token={}", token)));
+
+    let response = app.get_password_reset_page(&token).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let page = response.text().await.expect("Could not read the page");
+
+    // the form posts the token to the confirm route, the page itself does not use it up
+    assert!(page.contains(&token));
+    assert!(page.contains("/password-reset/confirm"));
+
+    let response = app.get_password_reset_page("invalid").await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[api_test]
+async fn should_return_400_if_invalid_new_password() {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let response = app.verify_email(&random_email).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_password_reset_request(&serde_json::json!({ "email": random_email }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let token = get_reset_token(&app, &random_email).await;
+
+    let response = app
+        .post_password_reset_confirm(&serde_json::json!({
+            "token": token,
+            "newPassword": "short"
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    // a rejected password does not use up the token
+    let response = app
+        .post_password_reset_confirm(&serde_json::json!({
+            "token": token,
+            "newPassword": "newpassword123"
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+// Two instances of the service share Redis, each with its own store, and both get the same link
+#[tokio::test]
+async fn concurrent_confirms_of_one_token_should_consume_it_once() {
+    let conn = configure_redis().await;
+    let mut first_instance =
+        RedisOneTimeTokenStore::new(conn.clone(), OneTimeTokenPurpose::PasswordReset);
+    let mut second_instance = RedisOneTimeTokenStore::new(conn, OneTimeTokenPurpose::PasswordReset);
+
+    let email = Email::parse(get_random_email()).unwrap();
+    let token = OneTimeToken::default();
+    first_instance
+        .add_token(email.clone(), &token)
+        .await
+        .unwrap();
+
+    let (first, second) = tokio::join!(
+        first_instance.consume_token(&token),
+        second_instance.consume_token(&token)
+    );
+
+    let mut results = [first, second];
+    results.sort_by_key(|result| result.is_err());
+    assert_eq!(
+        results,
+        [Ok(email), Err(OneTimeTokenStoreError::TokenNotFound)]
+    );
+}
@@ -0,0 +1,96 @@
+use auth_service::{
+    utils::{
+        constants::{GLOBAL_RATE_LIMIT, LOGIN_RATE_LIMIT, PASSWORD_RESET_RATE_LIMIT},
+        rate_limit::{RATE_LIMIT_LIMIT, RATE_LIMIT_REMAINING, RATE_LIMIT_RESET},
+    },
+    ErrorResponse,
+};
+use reqwest::header::RETRY_AFTER;
+use test_helpers::api_test;
+
+use crate::helpers::{get_random_email, TestApp};
+
+fn header(response: &reqwest::Response, name: &str) -> u64 {
+    response
+        .headers()
+        .get(name)
+        .unwrap_or_else(|| panic!("No {} header", name))
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap()
+}
+
+#[api_test]
+async fn should_return_429_once_the_route_limit_is_used_up() {
+    let random_email = get_random_email();
+    let body = serde_json::json!({ "email": random_email });
+
+    for remaining in (0..PASSWORD_RESET_RATE_LIMIT.capacity).rev() {
+        let response = app.post_password_reset_request(&body).await;
+
+        assert_eq!(response.status().as_u16(), 200);
+        assert_eq!(
+            header(&response, RATE_LIMIT_LIMIT),
+            PASSWORD_RESET_RATE_LIMIT.capacity as u64
+        );
+        assert_eq!(header(&response, RATE_LIMIT_REMAINING), remaining as u64);
+    }
+
+    let response = app.post_password_reset_request(&body).await;
+
+    assert_eq!(response.status().as_u16(), 429);
+    assert_eq!(header(&response, RATE_LIMIT_REMAINING), 0);
+    assert!(header(&response, RATE_LIMIT_RESET) > 0);
+    assert!(header(&response, RETRY_AFTER.as_str()) > 0);
+
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "RateLimited".to_owned()
+    );
+}
+
+#[api_test]
+async fn should_limit_routes_separately() {
+    let body = serde_json::json!({ "email": get_random_email() });
+
+    for _ in 0..PASSWORD_RESET_RATE_LIMIT.capacity {
+        app.post_password_reset_request(&body).await;
+    }
+
+    let response = app.post_password_reset_request(&body).await;
+    assert_eq!(response.status().as_u16(), 429);
+
+    // the other route's bucket is untouched
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": get_random_email(),
+            "password": "password123"
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(
+        header(&response, RATE_LIMIT_LIMIT),
+        LOGIN_RATE_LIMIT.capacity as u64
+    );
+}
+
+#[api_test]
+async fn should_apply_the_global_limit_to_other_routes() {
+    let response = app.get_root().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        header(&response, RATE_LIMIT_LIMIT),
+        GLOBAL_RATE_LIMIT.capacity as u64
+    );
+    assert_eq!(
+        header(&response, RATE_LIMIT_REMAINING),
+        GLOBAL_RATE_LIMIT.capacity as u64 - 1
+    );
+}
@@ -0,0 +1,359 @@
+use auth_service::{
+    routes::{RecoveryCodesResponse, SignupResponse, TwoFactorAuthResponse, RECOVERY_CODE_COUNT},
+    utils::constants::JWT_COOKIE_NAME,
+    ErrorResponse,
+};
+use futures::future::join_all;
+use test_helpers::api_test;
+
+use crate::helpers::{get_random_email, TestApp};
+
+// Sign up and verify a 2FA user, returning the recovery codes issued at signup
+async fn signup_2fa_user(app: &TestApp, email: &str) -> Vec<String> {
+    let signup_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": true
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let recovery_codes = response
+        .json::<SignupResponse>()
+        .await
+        .expect("Could not deserialize response body to SignupResponse")
+        .recovery_codes
+        .expect("No recovery codes issued");
+
+    let response = app.verify_email(email).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    recovery_codes
+}
+
+// Pass the password step of the login and return the login attempt ID
+async fn start_login(app: &TestApp, email: &str) -> String {
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": email,
+            "password": "password123"
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 206);
+
+    response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse")
+        .login_attempt_id
+}
+
+#[api_test]
+async fn should_issue_recovery_codes_only_when_2fa_is_enabled() {
+    let random_email = get_random_email();
+
+    let recovery_codes = signup_2fa_user(&app, &random_email).await;
+
+    assert_eq!(recovery_codes.len(), RECOVERY_CODE_COUNT);
+
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": get_random_email(),
+            "password": "password123",
+            "requires2FA": false
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let response_body = response
+        .json::<SignupResponse>()
+        .await
+        .expect("Could not deserialize response body to SignupResponse");
+
+    assert!(response_body.recovery_codes.is_none());
+}
+
+#[api_test]
+async fn should_return_200_and_accept_each_code_once() {
+    let random_email = get_random_email();
+    let recovery_codes = signup_2fa_user(&app, &random_email).await;
+
+    let login_attempt_id = start_login(&app, &random_email).await;
+
+    let verify_body = serde_json::json!({
+        "email": random_email,
+        "loginAttemptId": login_attempt_id,
+        "recoveryCode": recovery_codes[0]
+    });
+
+    let response = app.post_verify_recovery_code(&verify_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+
+    assert!(!auth_cookie.value().is_empty());
+
+    let login_attempt_id = start_login(&app, &random_email).await;
+
+    let response = app
+        .post_verify_recovery_code(&serde_json::json!({
+            "email": random_email,
+            "loginAttemptId": login_attempt_id,
+            "recoveryCode": recovery_codes[0]
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    // the remaining codes still work, typed without the dash and in upper case
+    let response = app
+        .post_verify_recovery_code(&serde_json::json!({
+            "email": random_email,
+            "loginAttemptId": login_attempt_id,
+            "recoveryCode": recovery_codes[1].replace('-', "").to_uppercase()
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[api_test]
+async fn should_discard_login_attempt_after_a_burst_of_wrong_codes() {
+    let random_email = get_random_email();
+    let recovery_codes = signup_2fa_user(&app, &random_email).await;
+
+    let login_attempt_id = start_login(&app, &random_email).await;
+
+    // more wrong guesses at once than the login attempt allows, each is counted before it is checked
+    let wrong_body = serde_json::json!({
+        "email": random_email,
+        "loginAttemptId": login_attempt_id,
+        "recoveryCode": "aaaaa-aaaaa"
+    });
+    let responses = join_all((0..8).map(|_| app.post_verify_recovery_code(&wrong_body))).await;
+
+    assert!(responses
+        .iter()
+        .all(|response| response.status().as_u16() == 401));
+
+    let response = app
+        .post_verify_recovery_code(&serde_json::json!({
+            "email": random_email,
+            "loginAttemptId": login_attempt_id,
+            "recoveryCode": recovery_codes[0]
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[api_test]
+async fn should_return_401_if_login_attempt_id_is_incorrect() {
+    let random_email = get_random_email();
+    let recovery_codes = signup_2fa_user(&app, &random_email).await;
+
+    start_login(&app, &random_email).await;
+
+    let response = app
+        .post_verify_recovery_code(&serde_json::json!({
+            "email": random_email,
+            "loginAttemptId": uuid::Uuid::new_v4().to_string(),
+            "recoveryCode": recovery_codes[0]
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[api_test]
+async fn should_return_400_if_invalid_input() {
+    let random_email = get_random_email();
+    signup_2fa_user(&app, &random_email).await;
+
+    let login_attempt_id = start_login(&app, &random_email).await;
+
+    let test_cases = [
+        serde_json::json!({
+            "email": "invalid_email",
+            "loginAttemptId": login_attempt_id,
+            "recoveryCode": "abcde-fghjk"
+        }),
+        serde_json::json!({
+            "email": random_email,
+            "loginAttemptId": "invalid_login_attempt_id",
+            "recoveryCode": "abcde-fghjk"
+        }),
+        serde_json::json!({
+            "email": random_email,
+            "loginAttemptId": login_attempt_id,
+            "recoveryCode": "abc"
+        }),
+    ];
+
+    for test_case in test_cases.iter() {
+        let response = app.post_verify_recovery_code(test_case).await;
+
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "Failed for input: {:?}",
+            test_case
+        );
+    }
+}
+
+#[api_test]
+async fn should_invalidate_old_codes_when_regenerating() {
+    let random_email = get_random_email();
+    let recovery_codes = signup_2fa_user(&app, &random_email).await;
+
+    let login_attempt_id = start_login(&app, &random_email).await;
+
+    let response = app
+        .post_verify_recovery_code(&serde_json::json!({
+            "email": random_email,
+            "loginAttemptId": login_attempt_id,
+            "recoveryCode": recovery_codes[0]
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_regenerate_recovery_codes().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let new_recovery_codes = response
+        .json::<RecoveryCodesResponse>()
+        .await
+        .expect("Could not deserialize response body to RecoveryCodesResponse")
+        .recovery_codes;
+
+    assert_eq!(new_recovery_codes.len(), RECOVERY_CODE_COUNT);
+
+    let login_attempt_id = start_login(&app, &random_email).await;
+
+    let response = app
+        .post_verify_recovery_code(&serde_json::json!({
+            "email": random_email,
+            "loginAttemptId": login_attempt_id,
+            "recoveryCode": recovery_codes[1]
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app
+        .post_verify_recovery_code(&serde_json::json!({
+            "email": random_email,
+            "loginAttemptId": login_attempt_id,
+            "recoveryCode": new_recovery_codes[0]
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[api_test]
+async fn should_return_400_if_regenerating_without_2fa() {
+    let response = app.post_regenerate_recovery_codes().await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    let random_email = get_random_email();
+
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": random_email,
+            "password": "password123",
+            "requires2FA": false
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let response = app.verify_email(&random_email).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": random_email,
+            "password": "password123"
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_regenerate_recovery_codes().await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "TwoFANotEnabled".to_owned()
+    );
+}
+
+// Every store lives in the SQLite file, so recovery codes reference a user in the same database
+#[cfg(feature = "sqlite")]
+#[tokio::test]
+async fn should_sign_up_and_log_in_with_2fa_on_sqlite() {
+    use auth_service::domain::LoginAttemptId;
+
+    let mut app = TestApp::new_sqlite().await;
+
+    let random_email = get_random_email();
+    let recovery_codes = signup_2fa_user(&app, &random_email).await;
+
+    assert_eq!(recovery_codes.len(), RECOVERY_CODE_COUNT);
+
+    let login_attempt_id = start_login(&app, &random_email).await;
+
+    let (_, code) = app
+        .two_fa_code_store
+        .get_code(&LoginAttemptId::parse(login_attempt_id.clone()).unwrap())
+        .await
+        .unwrap();
+
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": random_email,
+            "loginAttemptId": login_attempt_id,
+            "2FACode": code.as_ref()
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    assert!(response
+        .cookies()
+        .any(|cookie| cookie.name() == JWT_COOKIE_NAME && !cookie.value().is_empty()));
+
+    let login_attempt_id = start_login(&app, &random_email).await;
+
+    let response = app
+        .post_verify_recovery_code(&serde_json::json!({
+            "email": random_email,
+            "loginAttemptId": login_attempt_id,
+            "recoveryCode": recovery_codes[0]
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
@@ -0,0 +1,287 @@
+use crate::helpers::{configure_redis, get_random_email, TestApp};
+use auth_service::{
+    domain::{
+        Email, OrganizationId, RefreshSession, RefreshToken, RefreshTokenStore,
+        RefreshTokenStoreError,
+    },
+    services::redis_refresh_token_store::RedisRefreshTokenStore,
+    utils::constants::{JWT_COOKIE_NAME, REFRESH_COOKIE_NAME},
+    ErrorResponse,
+};
+use reqwest::Url;
+use test_helpers::api_test;
+
+#[api_test]
+async fn should_return_400_if_refresh_cookie_missing() {
+    let response = app.post_refresh().await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "MissingToken".to_owned()
+    );
+}
+
+#[api_test]
+async fn should_return_401_if_invalid_refresh_token() {
+    // add invalid cookie
+    app.cookie_jar.add_cookie_str(
+        &format!(
+            "{}=invalid; HttpOnly; SameSite=Lax; Secure; Path=/",
+            REFRESH_COOKIE_NAME
+        ),
+        &Url::parse("http://127.0.0.1").expect("Failed to parse URL"),
+    );
+
+    let response = app.post_refresh().await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME);
+
+    assert!(auth_cookie.is_none());
+
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "InvalidToken".to_owned()
+    );
+}
+
+#[api_test]
+async fn should_return_200_and_rotate_refresh_token() {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let response = app.verify_email(&random_email).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+    });
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let refresh_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == REFRESH_COOKIE_NAME)
+        .expect("No refresh cookie found");
+
+    let old_refresh_token = refresh_cookie.value().to_owned();
+
+    let response = app.post_refresh().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+
+    assert!(!auth_cookie.value().is_empty());
+
+    let refresh_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == REFRESH_COOKIE_NAME)
+        .expect("No refresh cookie found");
+
+    assert_ne!(refresh_cookie.value(), old_refresh_token);
+
+    // the new auth cookie is accepted like one issued by /login
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": auth_cookie.value() }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[api_test]
+async fn should_revoke_token_family_if_refresh_token_reused() {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let response = app.verify_email(&random_email).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+    });
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let old_refresh_token = response
+        .cookies()
+        .find(|cookie| cookie.name() == REFRESH_COOKIE_NAME)
+        .expect("No refresh cookie found")
+        .value()
+        .to_owned();
+
+    let response = app.post_refresh().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let new_refresh_token = response
+        .cookies()
+        .find(|cookie| cookie.name() == REFRESH_COOKIE_NAME)
+        .expect("No refresh cookie found")
+        .value()
+        .to_owned();
+
+    // replay the token that was already rotated
+    app.cookie_jar.add_cookie_str(
+        &format!(
+            "{}={}; HttpOnly; SameSite=Lax; Secure; Path=/",
+            REFRESH_COOKIE_NAME, old_refresh_token
+        ),
+        &Url::parse("http://127.0.0.1").expect("Failed to parse URL"),
+    );
+
+    let response = app.post_refresh().await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    // the legitimate successor has been revoked along with the rest of the family
+    app.cookie_jar.add_cookie_str(
+        &format!(
+            "{}={}; HttpOnly; SameSite=Lax; Secure; Path=/",
+            REFRESH_COOKIE_NAME, new_refresh_token
+        ),
+        &Url::parse("http://127.0.0.1").expect("Failed to parse URL"),
+    );
+
+    let response = app.post_refresh().await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[api_test]
+async fn should_return_401_if_refresh_after_logout() {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let response = app.verify_email(&random_email).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+    });
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let refresh_token = response
+        .cookies()
+        .find(|cookie| cookie.name() == REFRESH_COOKIE_NAME)
+        .expect("No refresh cookie found")
+        .value()
+        .to_owned();
+
+    let response = app.post_logout().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let rotate_result = app
+        .refresh_token_store
+        .write()
+        .await
+        .rotate_token(&RefreshToken::parse(refresh_token.clone()).unwrap())
+        .await;
+
+    assert_eq!(rotate_result, Err(RefreshTokenStoreError::TokenNotFound));
+
+    app.cookie_jar.add_cookie_str(
+        &format!(
+            "{}={}; HttpOnly; SameSite=Lax; Secure; Path=/",
+            REFRESH_COOKIE_NAME, refresh_token
+        ),
+        &Url::parse("http://127.0.0.1").expect("Failed to parse URL"),
+    );
+
+    let response = app.post_refresh().await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+// Two instances of the service share Redis, each with its own store, and both get the same token
+#[tokio::test]
+async fn concurrent_rotations_of_one_token_should_mint_a_single_successor() {
+    let conn = configure_redis().await;
+    let mut first_instance = RedisRefreshTokenStore::new(conn.clone());
+    let mut second_instance = RedisRefreshTokenStore::new(conn);
+
+    let session = RefreshSession {
+        email: Email::parse(get_random_email()).unwrap(),
+        organization_id: Some(OrganizationId::default()),
+    };
+    let token = RefreshToken::default();
+    first_instance
+        .add_token(session.clone(), token.clone())
+        .await
+        .unwrap();
+
+    let (first, second) = tokio::join!(
+        first_instance.rotate_token(&token),
+        second_instance.rotate_token(&token)
+    );
+
+    let (rotated, reused) = match (first, second) {
+        (Ok(rotated), reused) | (reused, Ok(rotated)) => (rotated, reused),
+        results => panic!("Expected one rotation to succeed, got {:?}", results),
+    };
+    assert_eq!(rotated.0, session);
+    assert_eq!(reused, Err(RefreshTokenStoreError::TokenReused));
+
+    // the replay revoked the family, successor included
+    assert_eq!(
+        first_instance.rotate_token(&rotated.1).await,
+        Err(RefreshTokenStoreError::TokenNotFound)
+    );
+}
@@ -0,0 +1,169 @@
+use std::time::Duration;
+
+use auth_service::{
+    domain::LoginAttemptId, routes::TwoFactorAuthResponse,
+    utils::auth::TWO_FA_RESEND_COOLDOWN_SECONDS, ErrorResponse,
+};
+use reqwest::header::RETRY_AFTER;
+use test_helpers::api_test;
+
+use crate::helpers::{get_random_email, get_two_fa_code, TestApp};
+
+// Sign up an email 2FA user and log in, returning the email and the pending login attempt id
+async fn login_new_user(app: &TestApp) -> (String, String) {
+    let random_email = get_random_email();
+
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": random_email,
+            "password": "password123",
+            "requires2FA": true
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    let response = app.verify_email(&random_email).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": random_email,
+            "password": "password123"
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 206);
+
+    let login_attempt_id = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse")
+        .login_attempt_id;
+
+    (random_email, login_attempt_id)
+}
+
+// The code in the most recent 2FA email
+async fn last_code(app: &TestApp, email: &str) -> String {
+    let sent_email = app.last_email_to(email).await.expect("No 2FA email sent");
+
+    assert_eq!(sent_email.subject, "2FA Code");
+    get_two_fa_code(&sent_email.content).expect("No code in 2FA email")
+}
+
+#[api_test]
+async fn should_return_429_during_cooldown() {
+    let (email, login_attempt_id) = login_new_user(&app).await;
+
+    let response = app
+        .post_resend_2fa(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": login_attempt_id
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 429);
+
+    let retry_after: i64 = response
+        .headers()
+        .get(RETRY_AFTER)
+        .expect("No Retry-After header")
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!(retry_after > 0 && retry_after <= TWO_FA_RESEND_COOLDOWN_SECONDS);
+
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "ResendCooldown".to_owned()
+    );
+}
+
+#[api_test]
+async fn should_return_401_if_login_attempt_does_not_match() {
+    let (email, _) = login_new_user(&app).await;
+
+    let response = app
+        .post_resend_2fa(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": LoginAttemptId::default().as_ref()
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    // and for users without a pending login
+    let response = app
+        .post_resend_2fa(&serde_json::json!({
+            "email": get_random_email(),
+            "loginAttemptId": LoginAttemptId::default().as_ref()
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[api_test]
+async fn should_return_400_if_invalid_input() {
+    let response = app
+        .post_resend_2fa(&serde_json::json!({
+            "email": get_random_email(),
+            "loginAttemptId": "not-a-uuid"
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[api_test]
+async fn should_send_a_new_code_for_the_same_attempt() {
+    let (email, login_attempt_id) = login_new_user(&app).await;
+    let first_code = last_code(&app, &email).await;
+
+    tokio::time::sleep(Duration::from_secs(
+        TWO_FA_RESEND_COOLDOWN_SECONDS as u64 + 1,
+    ))
+    .await;
+
+    let response = app
+        .post_resend_2fa(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": login_attempt_id
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response_body = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse");
+    assert_eq!(response_body.login_attempt_id, login_attempt_id);
+
+    let new_code = last_code(&app, &email).await;
+
+    // the previous code no longer works, the new one completes the login
+    if new_code != first_code {
+        let response = app
+            .post_verify_2fa(&serde_json::json!({
+                "email": email,
+                "loginAttemptId": login_attempt_id,
+                "2FACode": first_code
+            }))
+            .await;
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": login_attempt_id,
+            "2FACode": new_code
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+}
@@ -0,0 +1,262 @@
+use auth_service::{
+    domain::{Permission, Role},
+    routes::{RolesResponse, UserRolesResponse},
+    utils::constants::{ADMIN_API_KEY, JWT_COOKIE_NAME},
+    ErrorResponse,
+};
+use test_helpers::api_test;
+
+use crate::helpers::{get_random_email, TestApp};
+
+fn admin_api_key() -> Option<&'static str> {
+    Some(
+        ADMIN_API_KEY
+            .as_deref()
+            .expect("ADMIN_API_KEY must be set for tests"),
+    )
+}
+
+// Sign up, verify and log in a user without 2FA, returning the JWT from the auth cookie
+async fn login_new_user(app: &TestApp, email: &str) -> String {
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+            "requires2FA": false
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let response = app.verify_email(email).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+
+    auth_cookie.value().to_owned()
+}
+
+// The operator makes the user an admin, the user then refreshes to get a token carrying the role
+async fn make_admin(app: &TestApp, email: &str) -> String {
+    let response = app
+        .post_assign_role(
+            &serde_json::json!({ "email": email, "role": Role::ADMIN }),
+            admin_api_key(),
+        )
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_refresh().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+
+    auth_cookie.value().to_owned()
+}
+
+async fn error(response: reqwest::Response) -> String {
+    response
+        .json::<ErrorResponse>()
+        .await
+        .expect("Could not deserialize response body to ErrorResponse")
+        .error
+}
+
+#[api_test]
+async fn should_return_403_if_user_lacks_permission() {
+    let random_email = get_random_email();
+    login_new_user(&app, &random_email).await;
+
+    let response = app.get_roles(None).await;
+
+    assert_eq!(response.status().as_u16(), 403);
+    assert_eq!(error(response).await, "MissingPermission".to_owned());
+
+    let response = app
+        .post_assign_role(
+            &serde_json::json!({ "email": random_email, "role": Role::ADMIN }),
+            None,
+        )
+        .await;
+
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[api_test]
+async fn should_return_400_without_credentials() {
+    let response = app.get_roles(None).await;
+
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(error(response).await, "MissingToken".to_owned());
+}
+
+#[api_test]
+async fn should_grant_permissions_of_assigned_role() {
+    let random_email = get_random_email();
+    login_new_user(&app, &random_email).await;
+
+    let response = app
+        .post_assign_role(
+            &serde_json::json!({ "email": random_email, "role": Role::ADMIN }),
+            admin_api_key(),
+        )
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let user_roles = response
+        .json::<UserRolesResponse>()
+        .await
+        .expect("Could not deserialize response body to UserRolesResponse");
+
+    assert_eq!(user_roles.roles, vec![Role::ADMIN.to_owned()]);
+    assert!(user_roles
+        .permissions
+        .contains(&Permission::ROLES_MANAGE.to_owned()));
+
+    // the token issued before the assignment does not carry the role yet
+    let response = app.get_roles(None).await;
+
+    assert_eq!(response.status().as_u16(), 403);
+
+    let response = app.post_refresh().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.get_roles(None).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let roles = response
+        .json::<RolesResponse>()
+        .await
+        .expect("Could not deserialize response body to RolesResponse");
+
+    let admin = roles
+        .roles
+        .iter()
+        .find(|role| role.name == Role::ADMIN)
+        .expect("No admin role found");
+
+    assert!(admin
+        .permissions
+        .contains(&Permission::JWT_KEYS_ROTATE.to_owned()));
+
+    // the admin can use the rest of the admin API with their auth cookie
+    let response = app.post_rotate_jwt_keys(&serde_json::json!({}), None).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[api_test]
+async fn should_accept_token_as_bearer() {
+    let random_email = get_random_email();
+    login_new_user(&app, &random_email).await;
+    let token = make_admin(&app, &random_email).await;
+
+    let other_email = get_random_email();
+    login_new_user(&app, &other_email).await;
+
+    // the cookie now belongs to the other user, who has no roles
+    let response = app.get_user_roles(&other_email, None).await;
+
+    assert_eq!(response.status().as_u16(), 403);
+
+    let response = app.get_user_roles(&other_email, Some(&token)).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let user_roles = response
+        .json::<UserRolesResponse>()
+        .await
+        .expect("Could not deserialize response body to UserRolesResponse");
+
+    assert_eq!(user_roles.email, other_email);
+    assert!(user_roles.roles.is_empty());
+}
+
+#[api_test]
+async fn should_revoke_permissions_immediately() {
+    let random_email = get_random_email();
+    login_new_user(&app, &random_email).await;
+    let token = make_admin(&app, &random_email).await;
+
+    let response = app.get_roles(Some(&token)).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_revoke_role(
+            &serde_json::json!({ "email": random_email, "role": Role::ADMIN }),
+            admin_api_key(),
+        )
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let user_roles = response
+        .json::<UserRolesResponse>()
+        .await
+        .expect("Could not deserialize response body to UserRolesResponse");
+
+    assert!(user_roles.roles.is_empty());
+    assert!(user_roles.permissions.is_empty());
+
+    // the token carrying the role is no longer accepted
+    let response = app.get_roles(Some(&token)).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[api_test]
+async fn should_return_404_for_unknown_user_or_role() {
+    let random_email = get_random_email();
+    login_new_user(&app, &random_email).await;
+
+    let response = app
+        .post_assign_role(
+            &serde_json::json!({ "email": random_email, "role": "superuser" }),
+            admin_api_key(),
+        )
+        .await;
+
+    assert_eq!(response.status().as_u16(), 404);
+    assert_eq!(error(response).await, "RoleNotFound".to_owned());
+
+    let response = app
+        .post_assign_role(
+            &serde_json::json!({ "email": get_random_email(), "role": Role::ADMIN }),
+            admin_api_key(),
+        )
+        .await;
+
+    assert_eq!(response.status().as_u16(), 404);
+    assert_eq!(error(response).await, "UserNotFound".to_owned());
+
+    let response = app
+        .post_assign_role(
+            &serde_json::json!({ "email": random_email, "role": "Not A Role" }),
+            admin_api_key(),
+        )
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
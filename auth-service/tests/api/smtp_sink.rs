@@ -0,0 +1,272 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use std::sync::{
+    atomic::{AtomicU32, Ordering},
+    Arc, Mutex,
+};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+};
+
+// Local SMTP server the app under test sends its emails to. It accepts every message and keeps
+// it, so tests can read codes and links out of the emails exactly as they would be delivered.
+#[derive(Clone)]
+pub struct SmtpSink {
+    pub port: u16,
+    sent_emails: Arc<Mutex<Vec<SentEmail>>>,
+    // emails still to be refused with a temporary error
+    failures: Arc<AtomicU32>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SentEmail {
+    pub recipient: String,
+    pub subject: String,
+    // the plain-text body
+    pub content: String,
+    pub html: String,
+}
+
+impl SmtpSink {
+    pub async fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("Failed to bind SMTP sink");
+        let port = listener.local_addr().unwrap().port();
+
+        let sink = Self {
+            port,
+            sent_emails: Arc::new(Mutex::new(Vec::new())),
+            failures: Arc::new(AtomicU32::new(0)),
+        };
+        let sessions = sink.clone();
+
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(handle_session(stream, sessions.clone()));
+            }
+        });
+
+        sink
+    }
+
+    // Refuse the next `count` emails the way a busy mail server would
+    pub fn fail_next(&self, count: u32) {
+        self.failures.store(count, Ordering::SeqCst);
+    }
+
+    // Returns the most recent email sent to the recipient
+    pub fn last_email_to(&self, recipient: &str) -> Option<SentEmail> {
+        self.sent_emails
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .find(|email| email.recipient == recipient)
+            .cloned()
+    }
+}
+
+// Just enough of SMTP for a client that does not use TLS or authentication
+async fn handle_session(stream: TcpStream, sink: SmtpSink) -> std::io::Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+    let mut recipients = Vec::new();
+
+    writer.write_all(b"220 localhost ESMTP sink\r\n").await?;
+
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).await? == 0 {
+            return Ok(());
+        }
+        let line = line.trim_end();
+        let command = line.to_uppercase();
+
+        let reply: &[u8] = if command.starts_with("EHLO") || command.starts_with("HELO") {
+            b"250 localhost\r\n"
+        } else if command.starts_with("MAIL FROM:") || command == "NOOP" {
+            b"250 OK\r\n"
+        } else if command.starts_with("RCPT TO:") {
+            let recipient = line["RCPT TO:".len()..].trim();
+            recipients.push(recipient.trim_matches(['<', '>']).to_owned());
+            b"250 OK\r\n"
+        } else if command == "DATA" {
+            writer
+                .write_all(b"354 End data with <CR><LF>.<CR><LF>\r\n")
+                .await?;
+
+            let email = parse_email(&read_data(&mut reader).await?);
+
+            let refused = sink
+                .failures
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |failures| {
+                    failures.checked_sub(1)
+                })
+                .is_ok();
+            if refused {
+                recipients.clear();
+                writer.write_all(b"451 Try again later\r\n").await?;
+                continue;
+            }
+
+            let mut sent_emails = sink.sent_emails.lock().unwrap();
+            for recipient in recipients.drain(..) {
+                sent_emails.push(SentEmail {
+                    recipient,
+                    ..email.clone()
+                });
+            }
+            b"250 OK\r\n"
+        } else if command == "RSET" {
+            recipients.clear();
+            b"250 OK\r\n"
+        } else if command == "QUIT" {
+            writer.write_all(b"221 Bye\r\n").await?;
+            return Ok(());
+        } else {
+            b"502 Command not implemented\r\n"
+        };
+
+        writer.write_all(reply).await?;
+    }
+}
+
+// The message up to the line with a single dot, with dot-stuffing undone and line endings normalized
+async fn read_data<R>(reader: &mut R) -> std::io::Result<String>
+where
+    R: AsyncBufReadExt + Unpin,
+{
+    let mut data = String::new();
+
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).await? == 0 {
+            return Ok(data);
+        }
+        let line = line.trim_end_matches(['\r', '\n']);
+        if line == "." {
+            return Ok(data);
+        }
+        data.push_str(line.strip_prefix('.').unwrap_or(line));
+        data.push('\n');
+    }
+}
+
+fn parse_email(data: &str) -> SentEmail {
+    let (headers, body) = split_headers(data);
+    let mut email = SentEmail {
+        subject: header(&headers, "subject").unwrap_or_default(),
+        ..SentEmail::default()
+    };
+
+    let content_type = header(&headers, "content-type").unwrap_or_default();
+    let parts = match boundary(&content_type) {
+        Some(boundary) => body
+            .split(&format!("--{}", boundary))
+            .skip(1)
+            .filter(|part| !part.starts_with("--"))
+            .map(split_headers)
+            .collect(),
+        None => vec![(headers, body)],
+    };
+
+    for (headers, body) in parts {
+        let body = decode(
+            body.trim_matches('\n'),
+            &header(&headers, "content-transfer-encoding").unwrap_or_default(),
+        );
+        let content_type = header(&headers, "content-type").unwrap_or_default();
+
+        if content_type.starts_with("text/html") {
+            email.html = body;
+        } else if content_type.is_empty() || content_type.starts_with("text/plain") {
+            email.content = body;
+        }
+    }
+
+    email
+}
+
+// Headers with folded lines joined, and the rest of the message
+fn split_headers(data: &str) -> (Vec<(String, String)>, &str) {
+    let data = data.trim_start_matches('\n');
+    let (head, body) = data.split_once("\n\n").unwrap_or((data, ""));
+
+    let mut headers: Vec<(String, String)> = Vec::new();
+    for line in head.lines() {
+        if line.starts_with([' ', '\t']) {
+            if let Some((_, value)) = headers.last_mut() {
+                value.push(' ');
+                value.push_str(line.trim());
+            }
+        } else if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_lowercase(), value.trim().to_owned()));
+        }
+    }
+
+    (headers, body)
+}
+
+fn header(headers: &[(String, String)], name: &str) -> Option<String> {
+    headers
+        .iter()
+        .find(|(header, _)| header == name)
+        .map(|(_, value)| value.clone())
+}
+
+fn boundary(content_type: &str) -> Option<String> {
+    content_type
+        .split(';')
+        .filter_map(|param| param.trim().strip_prefix("boundary="))
+        .map(|boundary| boundary.trim_matches('"').to_owned())
+        .next()
+}
+
+fn decode(body: &str, encoding: &str) -> String {
+    match encoding.to_lowercase().as_str() {
+        "base64" => {
+            let bytes = STANDARD
+                .decode(body.lines().collect::<String>())
+                .expect("Invalid base64 body");
+            String::from_utf8(bytes).expect("Body is not UTF-8")
+        }
+        "quoted-printable" => decode_quoted_printable(body),
+        _ => body.to_owned(),
+    }
+}
+
+fn decode_quoted_printable(body: &str) -> String {
+    let mut bytes = Vec::new();
+    let mut soft_break = true;
+
+    for line in body.split('\n') {
+        // a line ending in `=` continues on the next one
+        if !soft_break {
+            bytes.push(b'\n');
+        }
+        let line = match line.strip_suffix('=') {
+            Some(line) => {
+                soft_break = true;
+                line
+            }
+            None => {
+                soft_break = false;
+                line
+            }
+        };
+
+        let mut line = line.bytes();
+        while let Some(byte) = line.next() {
+            if byte == b'=' {
+                let hex: Vec<u8> = line.by_ref().take(2).collect();
+                let hex = std::str::from_utf8(&hex).expect("Invalid quoted-printable body");
+                bytes.push(u8::from_str_radix(hex, 16).expect("Invalid quoted-printable body"));
+            } else {
+                bytes.push(byte);
+            }
+        }
+    }
+
+    String::from_utf8(bytes).expect("Body is not UTF-8")
+}
@@ -0,0 +1,704 @@
+use std::time::Duration;
+
+use auth_service::{
+    domain::{
+        BannedTokenStore, Email, FailedLoginKey, FailedLoginStore, JwtKeyStore, LoginAttemptId,
+        OneTimeToken, OneTimeTokenStore, OneTimeTokenStoreError, OrganizationId, Password,
+        RateLimit, RateLimitKey, RateLimitStore, RefreshSession, RefreshToken, RefreshTokenStore,
+        RefreshTokenStoreError, StoredJwtKey, TwoFACode, TwoFACodeStore, TwoFACodeStoreError,
+        TwoFAMethod, User, UserStore, UserStoreError, WebAuthnCeremony, WebAuthnChallenge,
+        WebAuthnChallengeStore, WebAuthnChallengeStoreError,
+    },
+    utils::{
+        auth::{MAX_TWO_FA_ATTEMPTS, TWO_FA_RESEND_COOLDOWN_SECONDS},
+        constants::MAX_PENDING_LOGIN_ATTEMPTS,
+    },
+};
+use chrono::Utc;
+use futures::future::join_all;
+use uuid::Uuid;
+
+use crate::helpers::get_random_email;
+
+// What every implementation of a store has to do the same way, whichever backend keeps the data.
+// `stores.rs` runs each implementation against these checks. The expiry checks take a store built
+// with SHORT_TTL.
+pub const SHORT_TTL: Duration = Duration::from_secs(1);
+
+// Long enough for anything stored with SHORT_TTL to be gone, even where expiry has a one second
+// resolution
+const SHORT_TTL_PASSED: Duration = Duration::from_millis(2100);
+
+const CONCURRENT_REQUESTS: usize = 8;
+
+fn random_email() -> Email {
+    Email::parse(get_random_email()).unwrap()
+}
+
+fn password(password: &str) -> Password {
+    Password::parse(password.to_owned()).unwrap()
+}
+
+fn user(email: &Email, password: Password) -> User {
+    User {
+        email: email.clone(),
+        password,
+        two_fa_method: TwoFAMethod::None,
+        email_verified: false,
+    }
+}
+
+pub async fn check_user_store(store: &dyn UserStore) {
+    let email = random_email();
+
+    // not found
+    assert_eq!(
+        store.get_user(&email).await,
+        Err(UserStoreError::UserNotFound)
+    );
+    assert_eq!(
+        store.validate_user(&email, &password("password123")).await,
+        Err(UserStoreError::UserNotFound)
+    );
+    assert_eq!(
+        store.update_password(&email, password("password123")).await,
+        Err(UserStoreError::UserNotFound)
+    );
+    assert_eq!(
+        store.set_email_verified(&email).await,
+        Err(UserStoreError::UserNotFound)
+    );
+    assert_eq!(
+        store.set_two_fa_method(&email, TwoFAMethod::Email).await,
+        Err(UserStoreError::UserNotFound)
+    );
+
+    // duplicates
+    store
+        .add_user(user(&email, password("password123")))
+        .await
+        .unwrap();
+    assert_eq!(
+        store.add_user(user(&email, password("password456"))).await,
+        Err(UserStoreError::UserAlreadyExists)
+    );
+
+    // passwords are checked, and never kept as they were given
+    assert_eq!(
+        store.validate_user(&email, &password("password123")).await,
+        Ok(())
+    );
+    assert_eq!(
+        store.validate_user(&email, &password("password456")).await,
+        Err(UserStoreError::InvalidCredentials)
+    );
+    let stored = store.get_user(&email).await.unwrap();
+    assert_eq!(stored.email, email);
+    assert_ne!(stored.password, password("password123"));
+    assert_eq!(stored.two_fa_method, TwoFAMethod::None);
+    assert!(!stored.email_verified);
+
+    store
+        .update_password(&email, password("password456"))
+        .await
+        .unwrap();
+    assert_eq!(
+        store.validate_user(&email, &password("password456")).await,
+        Ok(())
+    );
+    assert_eq!(
+        store.validate_user(&email, &password("password123")).await,
+        Err(UserStoreError::InvalidCredentials)
+    );
+
+    store.set_email_verified(&email).await.unwrap();
+    store
+        .set_two_fa_method(&email, TwoFAMethod::Totp)
+        .await
+        .unwrap();
+    let stored = store.get_user(&email).await.unwrap();
+    assert!(stored.email_verified);
+    assert_eq!(stored.two_fa_method, TwoFAMethod::Totp);
+
+    // concurrent signups of one email, only one of them creates the user
+    let email = random_email();
+    let results = join_all(
+        (0..CONCURRENT_REQUESTS).map(|_| store.add_user(user(&email, password("password123")))),
+    )
+    .await;
+    assert_eq!(results.iter().filter(|result| result.is_ok()).count(), 1);
+    assert!(results
+        .iter()
+        .all(|result| matches!(result, Ok(()) | Err(UserStoreError::UserAlreadyExists))));
+
+    // concurrent password changes, exactly one of them is kept
+    let passwords: Vec<Password> = (0..CONCURRENT_REQUESTS)
+        .map(|i| password(&format!("password{}", i + 1000)))
+        .collect();
+    join_all(
+        passwords
+            .iter()
+            .map(|password| store.update_password(&email, password.clone())),
+    )
+    .await
+    .into_iter()
+    .for_each(|result| result.unwrap());
+    let valid = join_all(
+        passwords
+            .iter()
+            .map(|password| store.validate_user(&email, password)),
+    )
+    .await;
+    assert_eq!(valid.iter().filter(|result| result.is_ok()).count(), 1);
+}
+
+fn jwt_key(retire_at: Option<i64>) -> StoredJwtKey {
+    StoredJwtKey {
+        kid: Uuid::new_v4().to_string(),
+        algorithm: "HS256".to_owned(),
+        private_key: Uuid::new_v4().as_bytes().to_vec(),
+        retire_at,
+    }
+}
+
+fn kids(keys: &[StoredJwtKey]) -> Vec<(&str, Option<i64>)> {
+    keys.iter()
+        .map(|key| (key.kid.as_str(), key.retire_at))
+        .collect()
+}
+
+pub async fn check_jwt_key_store(store: &dyn JwtKeyStore) {
+    let now = Utc::now().timestamp();
+    assert!(store.get_keys().await.unwrap().is_empty());
+
+    // the first rotation stores the configured key as well
+    let configured = jwt_key(Some(now + 600));
+    let first = jwt_key(None);
+    store.rotate_key(&first, &configured).await.unwrap();
+
+    let keys = store.get_keys().await.unwrap();
+    assert_eq!(
+        kids(&keys),
+        vec![
+            (first.kid.as_str(), None),
+            (configured.kid.as_str(), Some(now + 600))
+        ]
+    );
+    // private keys come back as they went in
+    assert_eq!(keys, vec![first.clone(), configured.clone()]);
+
+    // an instance that has not seen the first rotation yet, both keys it missed are retired
+    let second = jwt_key(None);
+    store
+        .rotate_key(
+            &second,
+            &StoredJwtKey {
+                retire_at: Some(now + 300),
+                ..configured.clone()
+            },
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(
+        kids(&store.get_keys().await.unwrap()),
+        vec![
+            (second.kid.as_str(), None),
+            (first.kid.as_str(), Some(now + 300)),
+            (configured.kid.as_str(), Some(now + 300))
+        ]
+    );
+
+    // retired keys are no longer returned
+    let third = jwt_key(None);
+    store
+        .rotate_key(
+            &third,
+            &StoredJwtKey {
+                retire_at: Some(now - 1),
+                ..second.clone()
+            },
+        )
+        .await
+        .unwrap();
+
+    let keys = store.get_keys().await.unwrap();
+    assert_eq!(
+        keys.first().map(|key| key.kid.as_str()),
+        Some(third.kid.as_str())
+    );
+    assert!(!keys.iter().any(|key| key.kid == second.kid));
+}
+
+pub async fn check_banned_token_store(store: &dyn BannedTokenStore) {
+    // not found
+    let jti = Uuid::new_v4().to_string();
+    let email = random_email();
+    assert!(!store.check_token(&jti).await.unwrap());
+    assert!(!store.check_user_token(&email, 100).await.unwrap());
+
+    store.store_token(jti.clone()).await.unwrap();
+    assert!(store.check_token(&jti).await.unwrap());
+
+    // duplicates, banning a token twice is not an error
+    store.store_token(jti.clone()).await.unwrap();
+    assert!(store.check_token(&jti).await.unwrap());
+
+    // only tokens issued strictly before the ban are banned
+    store.ban_user_tokens(&email, 100).await.unwrap();
+    assert!(store.check_user_token(&email, 99).await.unwrap());
+    assert!(!store.check_user_token(&email, 100).await.unwrap());
+    assert!(!store.check_user_token(&random_email(), 99).await.unwrap());
+
+    // a later ban replaces the earlier one
+    store.ban_user_tokens(&email, 200).await.unwrap();
+    assert!(store.check_user_token(&email, 150).await.unwrap());
+    assert!(!store.check_user_token(&email, 200).await.unwrap());
+
+    // concurrent logouts, none of the bans is lost
+    let jtis: Vec<String> = (0..CONCURRENT_REQUESTS)
+        .map(|_| Uuid::new_v4().to_string())
+        .collect();
+    join_all(jtis.iter().map(|jti| store.store_token(jti.clone())))
+        .await
+        .into_iter()
+        .for_each(|result| result.unwrap());
+    for jti in &jtis {
+        assert!(store.check_token(jti).await.unwrap());
+    }
+}
+
+pub async fn check_banned_token_store_expiry(store: &dyn BannedTokenStore) {
+    let jti = Uuid::new_v4().to_string();
+    let email = random_email();
+    store.store_token(jti.clone()).await.unwrap();
+    store.ban_user_tokens(&email, 100).await.unwrap();
+    assert!(store.check_token(&jti).await.unwrap());
+    assert!(store.check_user_token(&email, 99).await.unwrap());
+
+    tokio::time::sleep(SHORT_TTL_PASSED).await;
+
+    assert!(!store.check_token(&jti).await.unwrap());
+    assert!(!store.check_user_token(&email, 99).await.unwrap());
+
+    // an expired ban can be stored again
+    store.store_token(jti.clone()).await.unwrap();
+    assert!(store.check_token(&jti).await.unwrap());
+}
+
+async fn add_login_attempt(store: &dyn TwoFACodeStore, email: &Email) -> LoginAttemptId {
+    let login_attempt_id = LoginAttemptId::default();
+    store
+        .add_code(
+            email.clone(),
+            login_attempt_id.clone(),
+            TwoFACode::default(),
+            None,
+        )
+        .await
+        .unwrap();
+    login_attempt_id
+}
+
+// Any code but `code`
+fn wrong_code(code: &TwoFACode) -> TwoFACode {
+    let code: u32 = code.as_ref().parse().unwrap();
+    TwoFACode::parse(format!("{:06}", (code + 1) % 1_000_000)).unwrap()
+}
+
+async fn assert_login_attempt_gone(store: &dyn TwoFACodeStore, login_attempt_id: &LoginAttemptId) {
+    assert_eq!(
+        store.get_code(login_attempt_id).await,
+        Err(TwoFACodeStoreError::LoginAttemptIdNotFound)
+    );
+    assert_eq!(
+        store
+            .verify_code(login_attempt_id, &TwoFACode::default())
+            .await,
+        Err(TwoFACodeStoreError::LoginAttemptIdNotFound)
+    );
+    assert_eq!(
+        store.reserve_attempt(login_attempt_id).await,
+        Err(TwoFACodeStoreError::LoginAttemptIdNotFound)
+    );
+    assert_eq!(
+        store
+            .resend_code(login_attempt_id, TwoFACode::default())
+            .await,
+        Err(TwoFACodeStoreError::LoginAttemptIdNotFound)
+    );
+    assert_eq!(
+        store.remove_code(login_attempt_id).await,
+        Err(TwoFACodeStoreError::LoginAttemptIdNotFound)
+    );
+}
+
+pub async fn check_two_fa_code_store(store: &dyn TwoFACodeStore) {
+    let email = random_email();
+
+    // not found
+    assert_login_attempt_gone(store, &LoginAttemptId::default()).await;
+
+    let login_attempt_id = LoginAttemptId::default();
+    let code = TwoFACode::default();
+    store
+        .add_code(email.clone(), login_attempt_id.clone(), code.clone(), None)
+        .await
+        .unwrap();
+    assert_eq!(
+        store.get_code(&login_attempt_id).await,
+        Ok((email.clone(), code))
+    );
+
+    // a code was just sent, so a new one has to wait
+    match store
+        .resend_code(&login_attempt_id, TwoFACode::default())
+        .await
+    {
+        Err(TwoFACodeStoreError::ResendCooldown(seconds)) => {
+            assert!((1..=TWO_FA_RESEND_COOLDOWN_SECONDS as u64).contains(&seconds))
+        }
+        result => panic!("Expected a resend cooldown, got {:?}", result),
+    }
+
+    // completing a login attempt works once
+    assert_eq!(store.remove_code(&login_attempt_id).await, Ok(None));
+    assert_login_attempt_gone(store, &login_attempt_id).await;
+
+    // completing it returns the organization the login asked for
+    let login_attempt_id = LoginAttemptId::default();
+    let organization_id = OrganizationId::default();
+    store
+        .add_code(
+            email.clone(),
+            login_attempt_id.clone(),
+            TwoFACode::default(),
+            Some(organization_id),
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        store.remove_code(&login_attempt_id).await,
+        Ok(Some(organization_id))
+    );
+
+    // guesses checked outside the store are counted before they are checked, up to the limit
+    let login_attempt_id = add_login_attempt(store, &email).await;
+    let (_, code) = store.get_code(&login_attempt_id).await.unwrap();
+    for attempt in 1..=MAX_TWO_FA_ATTEMPTS {
+        assert_eq!(store.reserve_attempt(&login_attempt_id).await, Ok(attempt));
+    }
+    assert_eq!(
+        store.reserve_attempt(&login_attempt_id).await,
+        Err(TwoFACodeStoreError::LoginAttemptIdNotFound)
+    );
+    // once they are used up not even the right code is compared
+    assert_eq!(
+        store.verify_code(&login_attempt_id, &code).await,
+        Err(TwoFACodeStoreError::LoginAttemptIdNotFound)
+    );
+    assert_eq!(store.remove_code(&login_attempt_id).await, Ok(None));
+    assert_login_attempt_gone(store, &login_attempt_id).await;
+
+    // only wrong guesses at the code are counted, the one reaching the limit removes the attempt
+    let login_attempt_id = add_login_attempt(store, &email).await;
+    let (_, code) = store.get_code(&login_attempt_id).await.unwrap();
+    for _ in 1..MAX_TWO_FA_ATTEMPTS {
+        assert_eq!(
+            store
+                .verify_code(&login_attempt_id, &wrong_code(&code))
+                .await,
+            Err(TwoFACodeStoreError::IncorrectCode)
+        );
+    }
+    assert_eq!(store.verify_code(&login_attempt_id, &code).await, Ok(()));
+    assert_eq!(store.verify_code(&login_attempt_id, &code).await, Ok(()));
+    assert_eq!(
+        store
+            .verify_code(&login_attempt_id, &wrong_code(&code))
+            .await,
+        Err(TwoFACodeStoreError::IncorrectCode)
+    );
+    assert_login_attempt_gone(store, &login_attempt_id).await;
+
+    // the oldest pending attempts make room for new ones
+    let mut login_attempt_ids = Vec::new();
+    for _ in 0..=*MAX_PENDING_LOGIN_ATTEMPTS {
+        login_attempt_ids.push(add_login_attempt(store, &email).await);
+    }
+    assert_login_attempt_gone(store, &login_attempt_ids[0]).await;
+    for login_attempt_id in &login_attempt_ids[1..] {
+        assert!(store.get_code(login_attempt_id).await.is_ok());
+    }
+
+    // concurrent completions of one attempt, only one of them succeeds
+    let login_attempt_id = add_login_attempt(store, &random_email()).await;
+    let results =
+        join_all((0..CONCURRENT_REQUESTS).map(|_| store.remove_code(&login_attempt_id))).await;
+    assert_eq!(results.iter().filter(|result| result.is_ok()).count(), 1);
+    assert!(results.iter().all(|result| matches!(
+        result,
+        Ok(None) | Err(TwoFACodeStoreError::LoginAttemptIdNotFound)
+    )));
+
+    // concurrent wrong guesses are compared no more often than the limit allows
+    let login_attempt_id = add_login_attempt(store, &random_email()).await;
+    let (_, code) = store.get_code(&login_attempt_id).await.unwrap();
+    let wrong_code = wrong_code(&code);
+    let results = join_all(
+        (0..CONCURRENT_REQUESTS).map(|_| store.verify_code(&login_attempt_id, &wrong_code)),
+    )
+    .await;
+    assert_eq!(
+        results
+            .iter()
+            .filter(|result| **result == Err(TwoFACodeStoreError::IncorrectCode))
+            .count(),
+        MAX_TWO_FA_ATTEMPTS as usize
+    );
+    assert!(results.iter().all(|result| matches!(
+        result,
+        Err(TwoFACodeStoreError::IncorrectCode | TwoFACodeStoreError::LoginAttemptIdNotFound)
+    )));
+    assert_login_attempt_gone(store, &login_attempt_id).await;
+
+    // concurrent guesses checked outside the store take no more guesses than the limit allows
+    let login_attempt_id = add_login_attempt(store, &random_email()).await;
+    let results =
+        join_all((0..CONCURRENT_REQUESTS).map(|_| store.reserve_attempt(&login_attempt_id))).await;
+    let mut counts: Vec<u32> = results
+        .iter()
+        .filter_map(|result| result.as_ref().ok().copied())
+        .collect();
+    counts.sort();
+    assert_eq!(counts, (1..=MAX_TWO_FA_ATTEMPTS).collect::<Vec<u32>>());
+    assert!(results.iter().all(|result| matches!(
+        result,
+        Ok(_) | Err(TwoFACodeStoreError::LoginAttemptIdNotFound)
+    )));
+
+    // concurrent logins of one user never leave more than the cap pending
+    let email = random_email();
+    let login_attempt_ids: Vec<LoginAttemptId> = (0..*MAX_PENDING_LOGIN_ATTEMPTS * 2)
+        .map(|_| LoginAttemptId::default())
+        .collect();
+    join_all(login_attempt_ids.iter().map(|login_attempt_id| {
+        store.add_code(
+            email.clone(),
+            login_attempt_id.clone(),
+            TwoFACode::default(),
+            None,
+        )
+    }))
+    .await
+    .into_iter()
+    .for_each(|result| result.unwrap());
+    let pending = join_all(
+        login_attempt_ids
+            .iter()
+            .map(|login_attempt_id| store.get_code(login_attempt_id)),
+    )
+    .await;
+    assert_eq!(
+        pending.iter().filter(|result| result.is_ok()).count(),
+        *MAX_PENDING_LOGIN_ATTEMPTS
+    );
+}
+
+pub async fn check_two_fa_code_store_expiry(store: &dyn TwoFACodeStore) {
+    let email = random_email();
+    let login_attempt_id = add_login_attempt(store, &email).await;
+    assert!(store.get_code(&login_attempt_id).await.is_ok());
+
+    tokio::time::sleep(SHORT_TTL_PASSED).await;
+
+    assert_login_attempt_gone(store, &login_attempt_id).await;
+}
+
+pub async fn check_refresh_token_store(store: &mut dyn RefreshTokenStore) {
+    let session = RefreshSession {
+        email: random_email(),
+        organization_id: Some(OrganizationId::default()),
+    };
+    let token = RefreshToken::default();
+    store
+        .add_token(session.clone(), token.clone())
+        .await
+        .unwrap();
+
+    // the new token carries the session on
+    let (rotated_session, new_token) = store.rotate_token(&token).await.unwrap();
+    assert_eq!(rotated_session, session);
+    assert_ne!(new_token, token);
+    let (_, newest_token) = store.rotate_token(&new_token).await.unwrap();
+
+    // replaying a token revokes the family, the latest token included
+    assert_eq!(
+        store.rotate_token(&new_token).await,
+        Err(RefreshTokenStoreError::TokenReused)
+    );
+    assert_eq!(
+        store.rotate_token(&newest_token).await,
+        Err(RefreshTokenStoreError::TokenNotFound)
+    );
+    assert_eq!(
+        store.rotate_token(&RefreshToken::default()).await,
+        Err(RefreshTokenStoreError::TokenNotFound)
+    );
+
+    // revoking a token ends its family only
+    let (revoked, kept) = (RefreshToken::default(), RefreshToken::default());
+    store
+        .add_token(session.clone(), revoked.clone())
+        .await
+        .unwrap();
+    store
+        .add_token(session.clone(), kept.clone())
+        .await
+        .unwrap();
+    store.revoke_token(&revoked).await.unwrap();
+    assert_eq!(
+        store.rotate_token(&revoked).await,
+        Err(RefreshTokenStoreError::TokenNotFound)
+    );
+    let (_, kept) = store.rotate_token(&kept).await.unwrap();
+
+    // revoking the user's tokens ends every family of the user, and no one else's
+    let other_session = RefreshSession {
+        email: random_email(),
+        organization_id: None,
+    };
+    let other = RefreshToken::default();
+    store
+        .add_token(other_session.clone(), other.clone())
+        .await
+        .unwrap();
+    store.revoke_user_tokens(&session.email).await.unwrap();
+    assert_eq!(
+        store.rotate_token(&kept).await,
+        Err(RefreshTokenStoreError::TokenNotFound)
+    );
+    assert_eq!(
+        store.rotate_token(&other).await.map(|(session, _)| session),
+        Ok(other_session)
+    );
+}
+
+pub async fn check_one_time_token_store(store: &mut dyn OneTimeTokenStore) {
+    let email = random_email();
+    let token = OneTimeToken::default();
+    store.add_token(email.clone(), &token).await.unwrap();
+
+    // a token can only be used once
+    assert_eq!(store.consume_token(&token).await, Ok(email));
+    assert_eq!(
+        store.consume_token(&token).await,
+        Err(OneTimeTokenStoreError::TokenNotFound)
+    );
+    assert_eq!(
+        store.consume_token(&OneTimeToken::default()).await,
+        Err(OneTimeTokenStoreError::TokenNotFound)
+    );
+}
+
+pub async fn check_one_time_token_store_expiry(store: &mut dyn OneTimeTokenStore) {
+    let token = OneTimeToken::default();
+    store.add_token(random_email(), &token).await.unwrap();
+
+    tokio::time::sleep(SHORT_TTL_PASSED).await;
+
+    assert_eq!(
+        store.consume_token(&token).await,
+        Err(OneTimeTokenStoreError::TokenNotFound)
+    );
+}
+
+pub async fn check_failed_login_store(store: &mut dyn FailedLoginStore) {
+    let key = FailedLoginKey::email(&random_email());
+    let other_key = FailedLoginKey::email(&random_email());
+
+    // failures are counted per key until they are cleared
+    assert_eq!(store.add_failure(&key).await, Ok(1));
+    assert_eq!(store.add_failure(&key).await, Ok(2));
+    assert_eq!(store.add_failure(&other_key).await, Ok(1));
+    store.clear_failures(&key).await.unwrap();
+    assert_eq!(store.add_failure(&key).await, Ok(1));
+
+    // locks end at the given time, ones that already ended are not kept
+    let until = Utc::now().timestamp() + 60;
+    assert_eq!(store.get_lock(&key).await, Ok(None));
+    store.lock(&key, until).await.unwrap();
+    assert_eq!(store.get_lock(&key).await, Ok(Some(until)));
+    assert_eq!(store.get_lock(&other_key).await, Ok(None));
+    store
+        .lock(&other_key, Utc::now().timestamp() - 1)
+        .await
+        .unwrap();
+    assert_eq!(store.get_lock(&other_key).await, Ok(None));
+}
+
+pub async fn check_rate_limit_store(store: &dyn RateLimitStore) {
+    let limit = RateLimit {
+        capacity: 2,
+        period_seconds: 60,
+    };
+    let key = RateLimitKey::email("conformance", &random_email());
+
+    // a new bucket starts full and refuses requests once empty
+    let decision = store.take_token(&key, &limit).await.unwrap();
+    assert!(decision.allowed);
+    assert_eq!(decision.remaining, 1);
+    assert!(store.take_token(&key, &limit).await.unwrap().allowed);
+    let decision = store.take_token(&key, &limit).await.unwrap();
+    assert!(!decision.allowed);
+    assert!(decision.retry_after_seconds > 0);
+
+    // other keys have buckets of their own
+    let other_key = RateLimitKey::email("conformance", &random_email());
+    assert!(store.take_token(&other_key, &limit).await.unwrap().allowed);
+
+    // concurrent requests cannot take more tokens than the bucket holds
+    let key = RateLimitKey::email("conformance", &random_email());
+    let decisions =
+        join_all((0..CONCURRENT_REQUESTS).map(|_| store.take_token(&key, &limit))).await;
+    assert_eq!(
+        decisions
+            .iter()
+            .filter(|decision| decision.as_ref().unwrap().allowed)
+            .count(),
+        limit.capacity as usize
+    );
+}
+
+pub async fn check_webauthn_challenge_store(store: &mut dyn WebAuthnChallengeStore) {
+    let email = random_email();
+
+    // a challenge can only be used once
+    let challenge = WebAuthnChallenge::default();
+    let ceremony = WebAuthnCeremony::Registration {
+        email: email.clone(),
+    };
+    store
+        .add_challenge(&challenge, ceremony.clone())
+        .await
+        .unwrap();
+    assert_eq!(store.consume_challenge(&challenge).await, Ok(ceremony));
+    assert_eq!(
+        store.consume_challenge(&challenge).await,
+        Err(WebAuthnChallengeStoreError::ChallengeNotFound)
+    );
+
+    // authentications keep the login they complete
+    for login_attempt_id in [Some(LoginAttemptId::default()), None] {
+        let challenge = WebAuthnChallenge::default();
+        let ceremony = WebAuthnCeremony::Authentication {
+            email: email.clone(),
+            login_attempt_id,
+        };
+        store
+            .add_challenge(&challenge, ceremony.clone())
+            .await
+            .unwrap();
+        assert_eq!(store.consume_challenge(&challenge).await, Ok(ceremony));
+    }
+}
@@ -0,0 +1,660 @@
+use auth_service::{
+    domain::{
+        BannedTokenStore, Email, FailedLoginKey, FailedLoginStore, LoginAttemptId, OneTimeToken,
+        OneTimeTokenPurpose, OneTimeTokenStore, OneTimeTokenStoreError, RateLimit, RateLimitKey,
+        RateLimitStore, RefreshSession, RefreshToken, RefreshTokenStore, RefreshTokenStoreError,
+        TwoFACode, TwoFACodeStore, TwoFACodeStoreError, WebAuthnCeremony, WebAuthnChallenge,
+        WebAuthnChallengeStore,
+    },
+    services::{
+        postgres_banned_token_store::PostgresBannedTokenStore,
+        postgres_failed_login_store::PostgresFailedLoginStore,
+        postgres_jwt_key_store::PostgresJwtKeyStore,
+        postgres_one_time_token_store::PostgresOneTimeTokenStore,
+        postgres_rate_limit_store::PostgresRateLimitStore,
+        postgres_refresh_token_store::PostgresRefreshTokenStore,
+        postgres_two_fa_code_store::PostgresTwoFACodeStore, postgres_user_store::PostgresUserStore,
+        postgres_webauthn_challenge_store::PostgresWebAuthnChallengeStore,
+        redis_banned_token_store::RedisBannedTokenStore,
+        redis_failed_login_store::RedisFailedLoginStore,
+        redis_one_time_token_store::RedisOneTimeTokenStore,
+        redis_rate_limit_store::RedisRateLimitStore,
+        redis_refresh_token_store::RedisRefreshTokenStore,
+        redis_two_fa_code_store::RedisTwoFACodeStore,
+        redis_webauthn_challenge_store::RedisWebAuthnChallengeStore, HashMapFailedLoginStore,
+        HashMapJwtKeyStore, HashMapOneTimeTokenStore, HashMapRateLimitStore,
+        HashMapRefreshTokenStore, HashMapTwoFACodeStore, HashMapUserStore,
+        HashMapWebAuthnChallengeStore, HashsetBannedTokenStore,
+    },
+    utils::auth::MAX_TWO_FA_RESENDS,
+};
+use chrono::Utc;
+use uuid::Uuid;
+
+use crate::{
+    helpers::{configure_postgresql, configure_redis, delete_database, get_random_email},
+    store_conformance::{
+        check_banned_token_store, check_banned_token_store_expiry, check_failed_login_store,
+        check_jwt_key_store, check_one_time_token_store, check_one_time_token_store_expiry,
+        check_rate_limit_store, check_refresh_token_store, check_two_fa_code_store,
+        check_two_fa_code_store_expiry, check_user_store, check_webauthn_challenge_store,
+        SHORT_TTL,
+    },
+};
+
+// Every store implementation runs the same conformance checks, followed by whatever only applies to
+// its backend
+
+#[tokio::test]
+async fn hashmap_user_store_should_conform() {
+    check_user_store(&HashMapUserStore::default()).await;
+}
+
+#[tokio::test]
+async fn hashset_banned_token_store_should_conform() {
+    check_banned_token_store(&HashsetBannedTokenStore::default()).await;
+    check_banned_token_store_expiry(&HashsetBannedTokenStore::with_ttl(SHORT_TTL)).await;
+}
+
+#[tokio::test]
+async fn hashmap_two_fa_code_store_should_conform() {
+    check_two_fa_code_store(&HashMapTwoFACodeStore::default()).await;
+    check_two_fa_code_store_expiry(&HashMapTwoFACodeStore::with_ttl(SHORT_TTL)).await;
+}
+
+#[tokio::test]
+async fn hashmap_jwt_key_store_should_conform() {
+    check_jwt_key_store(&HashMapJwtKeyStore::default()).await;
+}
+
+#[tokio::test]
+async fn hashmap_ephemeral_stores_should_conform() {
+    check_refresh_token_store(&mut HashMapRefreshTokenStore::default()).await;
+    check_one_time_token_store(&mut HashMapOneTimeTokenStore::new(
+        OneTimeTokenPurpose::PasswordReset,
+    ))
+    .await;
+    check_one_time_token_store_expiry(&mut HashMapOneTimeTokenStore::with_ttl(SHORT_TTL)).await;
+    check_failed_login_store(&mut HashMapFailedLoginStore::default()).await;
+    check_rate_limit_store(&HashMapRateLimitStore::default()).await;
+    check_webauthn_challenge_store(&mut HashMapWebAuthnChallengeStore::default()).await;
+}
+
+#[tokio::test]
+async fn redis_ephemeral_stores_should_conform() {
+    let conn = configure_redis().await;
+
+    check_refresh_token_store(&mut RedisRefreshTokenStore::new(conn.clone())).await;
+    check_one_time_token_store(&mut RedisOneTimeTokenStore::new(
+        conn.clone(),
+        OneTimeTokenPurpose::PasswordReset,
+    ))
+    .await;
+    check_one_time_token_store_expiry(&mut RedisOneTimeTokenStore::with_ttl(
+        conn.clone(),
+        OneTimeTokenPurpose::EmailVerification,
+        SHORT_TTL,
+    ))
+    .await;
+    check_failed_login_store(&mut RedisFailedLoginStore::new(conn.clone())).await;
+    check_rate_limit_store(&RedisRateLimitStore::new(conn.clone())).await;
+    check_webauthn_challenge_store(&mut RedisWebAuthnChallengeStore::new(conn)).await;
+}
+
+#[tokio::test]
+async fn redis_banned_token_store_should_conform() {
+    let conn = configure_redis().await;
+    check_banned_token_store(&RedisBannedTokenStore::new(conn.clone())).await;
+    check_banned_token_store_expiry(&RedisBannedTokenStore::with_ttl(conn, SHORT_TTL)).await;
+}
+
+#[tokio::test]
+async fn redis_two_fa_code_store_should_conform() {
+    let conn = configure_redis().await;
+    check_two_fa_code_store(&RedisTwoFACodeStore::new(conn.clone())).await;
+    check_two_fa_code_store_expiry(&RedisTwoFACodeStore::with_ttl(conn, SHORT_TTL)).await;
+}
+
+#[tokio::test]
+async fn postgres_user_store_should_conform() {
+    let db_name = Uuid::new_v4().to_string();
+    let pg_pool = configure_postgresql(&db_name).await;
+
+    check_user_store(&PostgresUserStore::new(pg_pool.clone())).await;
+
+    pg_pool.close().await;
+    delete_database(&db_name).await;
+}
+
+#[tokio::test]
+async fn postgres_banned_token_store_should_conform() {
+    let db_name = Uuid::new_v4().to_string();
+    let pg_pool = configure_postgresql(&db_name).await;
+
+    check_banned_token_store(&PostgresBannedTokenStore::new(pg_pool.clone())).await;
+    check_banned_token_store_expiry(&PostgresBannedTokenStore::with_ttl(
+        pg_pool.clone(),
+        SHORT_TTL,
+    ))
+    .await;
+
+    pg_pool.close().await;
+    delete_database(&db_name).await;
+}
+
+#[tokio::test]
+async fn postgres_two_fa_code_store_should_conform() {
+    let db_name = Uuid::new_v4().to_string();
+    let pg_pool = configure_postgresql(&db_name).await;
+
+    check_two_fa_code_store(&PostgresTwoFACodeStore::new(pg_pool.clone())).await;
+    check_two_fa_code_store_expiry(&PostgresTwoFACodeStore::with_ttl(
+        pg_pool.clone(),
+        SHORT_TTL,
+    ))
+    .await;
+
+    pg_pool.close().await;
+    delete_database(&db_name).await;
+}
+
+#[tokio::test]
+async fn postgres_jwt_key_store_should_conform() {
+    let db_name = Uuid::new_v4().to_string();
+    let pg_pool = configure_postgresql(&db_name).await;
+
+    check_jwt_key_store(&PostgresJwtKeyStore::new(pg_pool.clone())).await;
+
+    pg_pool.close().await;
+    delete_database(&db_name).await;
+}
+
+#[tokio::test]
+async fn postgres_ephemeral_stores_should_conform() {
+    let db_name = Uuid::new_v4().to_string();
+    let pg_pool = configure_postgresql(&db_name).await;
+
+    check_refresh_token_store(&mut PostgresRefreshTokenStore::new(pg_pool.clone())).await;
+    check_one_time_token_store(&mut PostgresOneTimeTokenStore::new(
+        pg_pool.clone(),
+        OneTimeTokenPurpose::PasswordReset,
+    ))
+    .await;
+    check_one_time_token_store_expiry(&mut PostgresOneTimeTokenStore::with_ttl(
+        pg_pool.clone(),
+        OneTimeTokenPurpose::EmailVerification,
+        SHORT_TTL,
+    ))
+    .await;
+    check_failed_login_store(&mut PostgresFailedLoginStore::new(pg_pool.clone())).await;
+    check_rate_limit_store(&PostgresRateLimitStore::new(pg_pool.clone())).await;
+    check_webauthn_challenge_store(&mut PostgresWebAuthnChallengeStore::new(pg_pool.clone())).await;
+
+    pg_pool.close().await;
+    delete_database(&db_name).await;
+}
+
+// Tokens of one purpose share the table with the other purpose's, but are never found through it
+#[tokio::test]
+async fn postgres_one_time_token_stores_should_keep_purposes_apart() {
+    let db_name = Uuid::new_v4().to_string();
+    let pg_pool = configure_postgresql(&db_name).await;
+    let mut password_reset_store =
+        PostgresOneTimeTokenStore::new(pg_pool.clone(), OneTimeTokenPurpose::PasswordReset);
+    let mut email_verification_store =
+        PostgresOneTimeTokenStore::new(pg_pool.clone(), OneTimeTokenPurpose::EmailVerification);
+
+    let token = OneTimeToken::default();
+    let email = Email::parse(get_random_email()).unwrap();
+    password_reset_store
+        .add_token(email.clone(), &token)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        email_verification_store.consume_token(&token).await,
+        Err(OneTimeTokenStoreError::TokenNotFound)
+    );
+    assert_eq!(password_reset_store.consume_token(&token).await, Ok(email));
+
+    pg_pool.close().await;
+    delete_database(&db_name).await;
+}
+
+// Postgres keeps expired rows until they are purged, they must not be seen in the meantime
+#[tokio::test]
+async fn postgres_stores_should_ignore_and_purge_expired_rows() {
+    let db_name = Uuid::new_v4().to_string();
+    let pg_pool = configure_postgresql(&db_name).await;
+    let banned_token_store = PostgresBannedTokenStore::new(pg_pool.clone());
+    let two_fa_code_store = PostgresTwoFACodeStore::new(pg_pool.clone());
+
+    let jti = Uuid::new_v4().to_string();
+    let email = Email::parse(get_random_email()).unwrap();
+    let login_attempt_id = LoginAttemptId::default();
+    banned_token_store.store_token(jti.clone()).await.unwrap();
+    banned_token_store
+        .ban_user_tokens(&email, 100)
+        .await
+        .unwrap();
+    two_fa_code_store
+        .add_code(
+            email.clone(),
+            login_attempt_id.clone(),
+            TwoFACode::default(),
+            None,
+        )
+        .await
+        .unwrap();
+
+    for table in ["banned_tokens", "banned_users", "two_fa_codes"] {
+        sqlx::query(&format!(
+            "UPDATE {} SET expires_at = NOW() - INTERVAL '1 second'",
+            table
+        ))
+        .execute(&pg_pool)
+        .await
+        .unwrap();
+    }
+
+    assert!(!banned_token_store.check_token(&jti).await.unwrap());
+    assert!(!banned_token_store
+        .check_user_token(&email, 99)
+        .await
+        .unwrap());
+    assert_eq!(
+        two_fa_code_store.get_code(&login_attempt_id).await,
+        Err(TwoFACodeStoreError::LoginAttemptIdNotFound)
+    );
+
+    assert_eq!(banned_token_store.delete_expired().await, Ok(2));
+    assert_eq!(two_fa_code_store.delete_expired().await, Ok(1));
+    assert_eq!(banned_token_store.delete_expired().await, Ok(0));
+    assert_eq!(two_fa_code_store.delete_expired().await, Ok(0));
+
+    pg_pool.close().await;
+    delete_database(&db_name).await;
+}
+
+#[tokio::test]
+async fn postgres_ephemeral_stores_should_purge_expired_rows() {
+    let db_name = Uuid::new_v4().to_string();
+    let pg_pool = configure_postgresql(&db_name).await;
+    let mut refresh_token_store = PostgresRefreshTokenStore::new(pg_pool.clone());
+    let mut one_time_token_store =
+        PostgresOneTimeTokenStore::new(pg_pool.clone(), OneTimeTokenPurpose::PasswordReset);
+    let mut failed_login_store = PostgresFailedLoginStore::new(pg_pool.clone());
+    let rate_limit_store = PostgresRateLimitStore::new(pg_pool.clone());
+    let mut webauthn_challenge_store = PostgresWebAuthnChallengeStore::new(pg_pool.clone());
+
+    let email = Email::parse(get_random_email()).unwrap();
+    let refresh_token = RefreshToken::default();
+    let one_time_token = OneTimeToken::default();
+    let failed_login_key = FailedLoginKey::email(&email);
+    refresh_token_store
+        .add_token(
+            RefreshSession {
+                email: email.clone(),
+                organization_id: None,
+            },
+            refresh_token.clone(),
+        )
+        .await
+        .unwrap();
+    one_time_token_store
+        .add_token(email.clone(), &one_time_token)
+        .await
+        .unwrap();
+    failed_login_store
+        .add_failure(&failed_login_key)
+        .await
+        .unwrap();
+    failed_login_store
+        .lock(&failed_login_key, Utc::now().timestamp() + 60)
+        .await
+        .unwrap();
+    rate_limit_store
+        .take_token(
+            &RateLimitKey::email("purge", &email),
+            &RateLimit {
+                capacity: 1,
+                period_seconds: 60,
+            },
+        )
+        .await
+        .unwrap();
+    webauthn_challenge_store
+        .add_challenge(
+            &WebAuthnChallenge::default(),
+            WebAuthnCeremony::Registration {
+                email: email.clone(),
+            },
+        )
+        .await
+        .unwrap();
+
+    for table in [
+        "refresh_token_families",
+        "refresh_tokens",
+        "one_time_tokens",
+        "failed_logins",
+        "login_locks",
+        "rate_limit_buckets",
+        "webauthn_challenges",
+    ] {
+        sqlx::query(&format!(
+            "UPDATE {} SET expires_at = NOW() - INTERVAL '1 second'",
+            table
+        ))
+        .execute(&pg_pool)
+        .await
+        .unwrap();
+    }
+
+    assert_eq!(
+        refresh_token_store.rotate_token(&refresh_token).await,
+        Err(RefreshTokenStoreError::TokenNotFound)
+    );
+    assert_eq!(
+        failed_login_store.get_lock(&failed_login_key).await,
+        Ok(None)
+    );
+
+    // a family and its token, a failure count and a lock
+    assert_eq!(refresh_token_store.delete_expired().await, Ok(2));
+    assert_eq!(one_time_token_store.delete_expired().await, Ok(1));
+    assert_eq!(failed_login_store.delete_expired().await, Ok(2));
+    assert_eq!(rate_limit_store.delete_expired().await, Ok(1));
+    assert_eq!(webauthn_challenge_store.delete_expired().await, Ok(1));
+    assert_eq!(refresh_token_store.delete_expired().await, Ok(0));
+    assert_eq!(one_time_token_store.delete_expired().await, Ok(0));
+    assert_eq!(failed_login_store.delete_expired().await, Ok(0));
+    assert_eq!(rate_limit_store.delete_expired().await, Ok(0));
+    assert_eq!(webauthn_challenge_store.delete_expired().await, Ok(0));
+
+    pg_pool.close().await;
+    delete_database(&db_name).await;
+}
+
+// Waiting out the real cooldown would take minutes, so the last send is moved back instead
+#[tokio::test]
+async fn postgres_two_fa_code_store_should_limit_resends() {
+    let db_name = Uuid::new_v4().to_string();
+    let pg_pool = configure_postgresql(&db_name).await;
+    let store = PostgresTwoFACodeStore::new(pg_pool.clone());
+
+    let email = Email::parse(get_random_email()).unwrap();
+    let login_attempt_id = LoginAttemptId::default();
+    store
+        .add_code(
+            email.clone(),
+            login_attempt_id.clone(),
+            TwoFACode::default(),
+            None,
+        )
+        .await
+        .unwrap();
+
+    for _ in 0..MAX_TWO_FA_RESENDS {
+        sqlx::query("UPDATE two_fa_codes SET sent_at = NOW() - INTERVAL '1 hour'")
+            .execute(&pg_pool)
+            .await
+            .unwrap();
+
+        let code = TwoFACode::default();
+        store
+            .resend_code(&login_attempt_id, code.clone())
+            .await
+            .unwrap();
+        assert_eq!(
+            store.get_code(&login_attempt_id).await,
+            Ok((email.clone(), code))
+        );
+    }
+
+    sqlx::query("UPDATE two_fa_codes SET sent_at = NOW() - INTERVAL '1 hour'")
+        .execute(&pg_pool)
+        .await
+        .unwrap();
+    assert_eq!(
+        store
+            .resend_code(&login_attempt_id, TwoFACode::default())
+            .await,
+        Err(TwoFACodeStoreError::TooManyResends)
+    );
+
+    pg_pool.close().await;
+    delete_database(&db_name).await;
+}
+
+#[cfg(feature = "sqlite")]
+mod sqlite {
+    use auth_service::services::{
+        sqlite_banned_token_store::SqliteBannedTokenStore,
+        sqlite_failed_login_store::SqliteFailedLoginStore, sqlite_jwt_key_store::SqliteJwtKeyStore,
+        sqlite_one_time_token_store::SqliteOneTimeTokenStore,
+        sqlite_rate_limit_store::SqliteRateLimitStore,
+        sqlite_refresh_token_store::SqliteRefreshTokenStore,
+        sqlite_two_fa_code_store::SqliteTwoFACodeStore, sqlite_user_store::SqliteUserStore,
+        sqlite_webauthn_challenge_store::SqliteWebAuthnChallengeStore,
+    };
+    use sqlx::SqlitePool;
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::helpers::{configure_sqlite, delete_sqlite_files};
+
+    async fn delete_sqlite(pool: SqlitePool, path: PathBuf) {
+        pool.close().await;
+        delete_sqlite_files(&path);
+    }
+
+    #[tokio::test]
+    async fn sqlite_user_store_should_conform() {
+        let (pool, path) = configure_sqlite().await;
+        check_user_store(&SqliteUserStore::new(pool.clone())).await;
+        delete_sqlite(pool, path).await;
+    }
+
+    #[tokio::test]
+    async fn sqlite_banned_token_store_should_conform() {
+        let (pool, path) = configure_sqlite().await;
+        check_banned_token_store(&SqliteBannedTokenStore::new(pool.clone())).await;
+        check_banned_token_store_expiry(&SqliteBannedTokenStore::with_ttl(pool.clone(), SHORT_TTL))
+            .await;
+        delete_sqlite(pool, path).await;
+    }
+
+    #[tokio::test]
+    async fn sqlite_two_fa_code_store_should_conform() {
+        let (pool, path) = configure_sqlite().await;
+        check_two_fa_code_store(&SqliteTwoFACodeStore::new(pool.clone())).await;
+        check_two_fa_code_store_expiry(&SqliteTwoFACodeStore::with_ttl(pool.clone(), SHORT_TTL))
+            .await;
+        delete_sqlite(pool, path).await;
+    }
+
+    #[tokio::test]
+    async fn sqlite_jwt_key_store_should_conform() {
+        let (pool, path) = configure_sqlite().await;
+        check_jwt_key_store(&SqliteJwtKeyStore::new(pool.clone())).await;
+        delete_sqlite(pool, path).await;
+    }
+
+    #[tokio::test]
+    async fn sqlite_ephemeral_stores_should_conform() {
+        let (pool, path) = configure_sqlite().await;
+
+        check_refresh_token_store(&mut SqliteRefreshTokenStore::new(pool.clone())).await;
+        check_one_time_token_store(&mut SqliteOneTimeTokenStore::new(
+            pool.clone(),
+            OneTimeTokenPurpose::PasswordReset,
+        ))
+        .await;
+        check_one_time_token_store_expiry(&mut SqliteOneTimeTokenStore::with_ttl(
+            pool.clone(),
+            OneTimeTokenPurpose::EmailVerification,
+            SHORT_TTL,
+        ))
+        .await;
+        check_failed_login_store(&mut SqliteFailedLoginStore::new(pool.clone())).await;
+        check_rate_limit_store(&SqliteRateLimitStore::new(pool.clone())).await;
+        check_webauthn_challenge_store(&mut SqliteWebAuthnChallengeStore::new(pool.clone())).await;
+
+        delete_sqlite(pool, path).await;
+    }
+
+    #[tokio::test]
+    async fn sqlite_ephemeral_stores_should_purge_expired_rows() {
+        let (pool, path) = configure_sqlite().await;
+        let mut refresh_token_store = SqliteRefreshTokenStore::new(pool.clone());
+        let mut one_time_token_store =
+            SqliteOneTimeTokenStore::new(pool.clone(), OneTimeTokenPurpose::PasswordReset);
+        let mut failed_login_store = SqliteFailedLoginStore::new(pool.clone());
+        let rate_limit_store = SqliteRateLimitStore::new(pool.clone());
+        let mut webauthn_challenge_store = SqliteWebAuthnChallengeStore::new(pool.clone());
+
+        let email = Email::parse(get_random_email()).unwrap();
+        let refresh_token = RefreshToken::default();
+        let failed_login_key = FailedLoginKey::email(&email);
+        refresh_token_store
+            .add_token(
+                RefreshSession {
+                    email: email.clone(),
+                    organization_id: None,
+                },
+                refresh_token.clone(),
+            )
+            .await
+            .unwrap();
+        one_time_token_store
+            .add_token(email.clone(), &OneTimeToken::default())
+            .await
+            .unwrap();
+        failed_login_store
+            .add_failure(&failed_login_key)
+            .await
+            .unwrap();
+        failed_login_store
+            .lock(&failed_login_key, Utc::now().timestamp() + 60)
+            .await
+            .unwrap();
+        rate_limit_store
+            .take_token(
+                &RateLimitKey::email("purge", &email),
+                &RateLimit {
+                    capacity: 1,
+                    period_seconds: 60,
+                },
+            )
+            .await
+            .unwrap();
+        webauthn_challenge_store
+            .add_challenge(
+                &WebAuthnChallenge::default(),
+                WebAuthnCeremony::Registration {
+                    email: email.clone(),
+                },
+            )
+            .await
+            .unwrap();
+
+        for table in [
+            "refresh_token_families",
+            "refresh_tokens",
+            "one_time_tokens",
+            "failed_logins",
+            "login_locks",
+            "rate_limit_buckets",
+            "webauthn_challenges",
+        ] {
+            sqlx::query(&format!(
+                "UPDATE {} SET expires_at = unixepoch() - 1",
+                table
+            ))
+            .execute(&pool)
+            .await
+            .unwrap();
+        }
+
+        assert_eq!(
+            refresh_token_store.rotate_token(&refresh_token).await,
+            Err(RefreshTokenStoreError::TokenNotFound)
+        );
+        assert_eq!(
+            failed_login_store.get_lock(&failed_login_key).await,
+            Ok(None)
+        );
+
+        // a family and its token, a failure count and a lock
+        assert_eq!(refresh_token_store.delete_expired().await, Ok(2));
+        assert_eq!(one_time_token_store.delete_expired().await, Ok(1));
+        assert_eq!(failed_login_store.delete_expired().await, Ok(2));
+        assert_eq!(rate_limit_store.delete_expired().await, Ok(1));
+        assert_eq!(webauthn_challenge_store.delete_expired().await, Ok(1));
+        assert_eq!(refresh_token_store.delete_expired().await, Ok(0));
+
+        delete_sqlite(pool, path).await;
+    }
+
+    #[tokio::test]
+    async fn sqlite_stores_should_ignore_and_purge_expired_rows() {
+        let (pool, path) = configure_sqlite().await;
+        let banned_token_store = SqliteBannedTokenStore::new(pool.clone());
+        let two_fa_code_store = SqliteTwoFACodeStore::new(pool.clone());
+
+        let jti = Uuid::new_v4().to_string();
+        banned_token_store.store_token(jti.clone()).await.unwrap();
+        sqlx::query("UPDATE banned_tokens SET expires_at = unixepoch() - 1")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        assert!(!banned_token_store.check_token(&jti).await.unwrap());
+        assert_eq!(banned_token_store.delete_expired().await, Ok(1));
+        assert_eq!(two_fa_code_store.delete_expired().await, Ok(0));
+
+        delete_sqlite(pool, path).await;
+    }
+
+    #[tokio::test]
+    async fn sqlite_two_fa_code_store_should_limit_resends() {
+        let (pool, path) = configure_sqlite().await;
+        let store = SqliteTwoFACodeStore::new(pool.clone());
+
+        let login_attempt_id = LoginAttemptId::default();
+        store
+            .add_code(
+                Email::parse(get_random_email()).unwrap(),
+                login_attempt_id.clone(),
+                TwoFACode::default(),
+                None,
+            )
+            .await
+            .unwrap();
+
+        for _ in 0..MAX_TWO_FA_RESENDS {
+            sqlx::query("UPDATE two_fa_codes SET sent_at = unixepoch() - 3600")
+                .execute(&pool)
+                .await
+                .unwrap();
+            store
+                .resend_code(&login_attempt_id, TwoFACode::default())
+                .await
+                .unwrap();
+        }
+
+        sqlx::query("UPDATE two_fa_codes SET sent_at = unixepoch() - 3600")
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert_eq!(
+            store
+                .resend_code(&login_attempt_id, TwoFACode::default())
+                .await,
+            Err(TwoFACodeStoreError::TooManyResends)
+        );
+
+        delete_sqlite(pool, path).await;
+    }
+}
@@ -0,0 +1,125 @@
+use auth_service::{
+    routes::TokenResponse,
+    utils::{
+        auth::{session_audience, TOKEN_TTL_SECONDS},
+        constants::{JWT_AUDIENCE, JWT_COOKIE_NAME},
+    },
+    ErrorResponse,
+};
+use reqwest::Url;
+use test_helpers::api_test;
+
+use crate::helpers::{get_random_email, TestApp};
+
+async fn login_new_user(app: &TestApp, email: &str) {
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+            "requires2FA": false
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let response = app.verify_email(email).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+fn other_consumer() -> &'static String {
+    JWT_AUDIENCE
+        .iter()
+        .find(|audience| *audience != session_audience())
+        .expect("JWT_AUDIENCE must name a second consumer for tests")
+}
+
+async fn error(response: reqwest::Response) -> String {
+    response
+        .json::<ErrorResponse>()
+        .await
+        .expect("Could not deserialize response body to ErrorResponse")
+        .error
+}
+
+#[api_test]
+async fn should_return_400_if_not_logged_in() {
+    let response = app
+        .post_token(&serde_json::json!({ "audience": other_consumer() }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(error(response).await, "MissingToken".to_owned());
+}
+
+#[api_test]
+async fn should_return_400_for_unknown_consumer() {
+    login_new_user(&app, &get_random_email()).await;
+
+    let response = app
+        .post_token(&serde_json::json!({ "audience": "unknown-service" }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(error(response).await, "Invalid Credentials".to_owned());
+}
+
+#[api_test]
+async fn should_issue_token_for_consumer_only() {
+    let random_email = get_random_email();
+    login_new_user(&app, &random_email).await;
+
+    let response = app
+        .post_token(&serde_json::json!({ "audience": other_consumer() }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = response
+        .json::<TokenResponse>()
+        .await
+        .expect("Could not deserialize response body to TokenResponse");
+
+    assert_eq!(response.expires_in, TOKEN_TTL_SECONDS);
+
+    // the consumer accepts it
+    let verify_response = app
+        .post_verify_token(&serde_json::json!({
+            "token": response.token,
+            "audience": other_consumer(),
+        }))
+        .await;
+
+    assert_eq!(verify_response.status().as_u16(), 200);
+
+    // but it does not stand in for the auth cookie
+    let verify_response = app
+        .post_verify_token(&serde_json::json!({ "token": response.token }))
+        .await;
+
+    assert_eq!(verify_response.status().as_u16(), 401);
+
+    app.cookie_jar.add_cookie_str(
+        &format!(
+            "{}={}; HttpOnly; SameSite=Lax; Path=/",
+            JWT_COOKIE_NAME, response.token
+        ),
+        &Url::parse("http://127.0.0.1").expect("Failed to parse URL"),
+    );
+
+    let response = app
+        .post_token(&serde_json::json!({ "audience": other_consumer() }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(error(response).await, "InvalidToken".to_owned());
+}
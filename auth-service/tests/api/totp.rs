@@ -0,0 +1,202 @@
+use auth_service::{
+    domain::{TotpSecret, TwoFACode},
+    routes::{ConfirmTotpResponse, EnrollTotpResponse, TwoFactorAuthResponse, RECOVERY_CODE_COUNT},
+    utils::totp::{generate_code, TOTP_TIME_STEP_SECONDS},
+    ErrorResponse,
+};
+use chrono::Utc;
+use test_helpers::api_test;
+
+use crate::helpers::{get_random_email, TestApp};
+
+// Sign up, verify and log in a user without 2FA so the auth cookie is set
+async fn login_new_user(app: &TestApp, email: &str) {
+    let signup_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let response = app.verify_email(email).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+async fn enroll(app: &TestApp) -> TotpSecret {
+    let response = app.post_totp_enroll().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response_body = response
+        .json::<EnrollTotpResponse>()
+        .await
+        .expect("Could not deserialize response body to EnrollTotpResponse");
+
+    assert!(response_body
+        .otpauth_uri
+        .starts_with("otpauth://totp/Rustgate:"));
+    assert!(response_body
+        .otpauth_uri
+        .contains(&format!("secret={}", response_body.secret)));
+
+    let secret = base32::decode(
+        base32::Alphabet::Rfc4648 { padding: false },
+        &response_body.secret,
+    )
+    .expect("Secret is not valid base32");
+
+    TotpSecret::parse(secret).expect("Invalid TOTP secret")
+}
+
+fn current_time_step() -> u64 {
+    Utc::now().timestamp() as u64 / TOTP_TIME_STEP_SECONDS
+}
+
+fn get_code(secret: &TotpSecret, time_step: u64) -> String {
+    generate_code(secret, time_step).as_ref().to_owned()
+}
+
+#[api_test]
+async fn should_return_400_if_enrolling_without_auth_cookie() {
+    let response = app.post_totp_enroll().await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "MissingToken".to_owned()
+    );
+}
+
+#[api_test]
+async fn should_return_401_if_confirming_with_incorrect_code() {
+    let random_email = get_random_email();
+    login_new_user(&app, &random_email).await;
+
+    let secret = enroll(&app).await;
+
+    // a code from well outside the allowed window
+    let response = app
+        .post_totp_confirm(&serde_json::json!({
+            "code": get_code(&secret, current_time_step() - 10)
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app
+        .post_totp_confirm(&serde_json::json!({ "code": "not-a-code" }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[api_test]
+async fn should_require_totp_code_at_login_once_confirmed() {
+    let random_email = get_random_email();
+    login_new_user(&app, &random_email).await;
+
+    let secret = enroll(&app).await;
+    let time_step = current_time_step();
+
+    let response = app
+        .post_totp_confirm(&serde_json::json!({ "code": get_code(&secret, time_step) }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    // enabling the first second factor comes with a set of recovery codes
+    let response_body = response
+        .json::<ConfirmTotpResponse>()
+        .await
+        .expect("Could not deserialize response body to ConfirmTotpResponse");
+
+    assert_eq!(
+        response_body.recovery_codes.map(|codes| codes.len()),
+        Some(RECOVERY_CODE_COUNT)
+    );
+
+    // enrolling again is rejected while TOTP is enabled
+    let response = app.post_totp_enroll().await;
+
+    assert_eq!(response.status().as_u16(), 409);
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": random_email,
+            "password": "password123",
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 206);
+
+    let response_body = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse");
+
+    assert_eq!(response_body.two_fa_method, "totp".to_owned());
+
+    // no code is emailed to TOTP users
+    assert!(app
+        .last_email_to(&random_email)
+        .await
+        .filter(|sent_email| sent_email.subject == "2FA Code")
+        .is_none());
+
+    let login_attempt_id = response_body.login_attempt_id;
+
+    // the code used for confirmation cannot be replayed
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": random_email,
+            "loginAttemptId": login_attempt_id,
+            "2FACode": get_code(&secret, time_step)
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    // a code the user was never told about is not accepted either
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": random_email,
+            "loginAttemptId": login_attempt_id,
+            "2FACode": TwoFACode::default().as_ref()
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    // the next code from the authenticator app is accepted once
+    let request_body = serde_json::json!({
+        "email": random_email,
+        "loginAttemptId": login_attempt_id,
+        "2FACode": get_code(&secret, time_step + 1)
+    });
+
+    let response = app.post_verify_2fa(&request_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_verify_2fa(&request_body).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
@@ -1,7 +1,10 @@
 use auth_service::{
-    domain::{Email, LoginAttemptId, TwoFACode},
+    domain::{LoginAttemptId, TwoFACode},
     routes::TwoFactorAuthResponse,
-    utils::constants::JWT_COOKIE_NAME,
+    utils::{
+        auth::MAX_TWO_FA_ATTEMPTS,
+        constants::{JWT_COOKIE_NAME, MAX_PENDING_LOGIN_ATTEMPTS},
+    },
     ErrorResponse,
 };
 use test_helpers::api_test;
@@ -24,6 +27,10 @@ async fn should_return_200_if_correct_code() {
 
     assert_eq!(response.status().as_u16(), 201);
 
+    let response = app.verify_email(&random_email).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
     let login_body = serde_json::json!({
         "email": random_email,
         "password": "password123"
@@ -39,15 +46,14 @@ async fn should_return_200_if_correct_code() {
         .expect("Could not deserialize response body to TwoFactorAuthResponse");
 
     assert_eq!(response_body.message, "2FA required".to_owned());
+    assert_eq!(response_body.two_fa_method, "email".to_owned());
     assert!(!response_body.login_attempt_id.is_empty());
 
     let login_attempt_id = response_body.login_attempt_id;
 
     let code_tuple = app
         .two_fa_code_store
-        .read()
-        .await
-        .get_code(&Email::parse(random_email.clone()).unwrap())
+        .get_code(&LoginAttemptId::parse(login_attempt_id.clone()).unwrap())
         .await
         .unwrap();
 
@@ -141,6 +147,10 @@ async fn should_return_401_if_incorrect_credentials() {
 
     assert_eq!(response.status().as_u16(), 201);
 
+    let response = app.verify_email(&random_email).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
     // --------------------------
 
     let login_body = serde_json::json!({
@@ -164,9 +174,7 @@ async fn should_return_401_if_incorrect_credentials() {
 
     let code_tuple = app
         .two_fa_code_store
-        .read()
-        .await
-        .get_code(&Email::parse(random_email.clone()).unwrap())
+        .get_code(&LoginAttemptId::parse(login_attempt_id.clone()).unwrap())
         .await
         .unwrap();
 
@@ -239,6 +247,10 @@ async fn should_return_401_if_old_code() {
 
     assert_eq!(response.status().as_u16(), 201);
 
+    let response = app.verify_email(&random_email).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
     // First login call
 
     let login_body = serde_json::json!({
@@ -262,19 +274,19 @@ async fn should_return_401_if_old_code() {
 
     let code_tuple = app
         .two_fa_code_store
-        .read()
-        .await
-        .get_code(&Email::parse(random_email.clone()).unwrap())
+        .get_code(&LoginAttemptId::parse(login_attempt_id.clone()).unwrap())
         .await
         .unwrap();
 
     let code = code_tuple.1.as_ref();
 
-    // Second login call
+    // Later login calls, until the first attempt is one too many
 
-    let response = app.post_login(&login_body).await;
+    for _ in 0..*MAX_PENDING_LOGIN_ATTEMPTS {
+        let response = app.post_login(&login_body).await;
 
-    assert_eq!(response.status().as_u16(), 206);
+        assert_eq!(response.status().as_u16(), 206);
+    }
 
     // 2FA attempt with old login_attempt_id and code
 
@@ -289,6 +301,122 @@ async fn should_return_401_if_old_code() {
     assert_eq!(response.status().as_u16(), 401);
 }
 
+#[api_test]
+async fn should_return_200_for_concurrent_login_attempts() {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": true
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let response = app.verify_email(&random_email).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123"
+    });
+
+    // e.g. one login from a laptop and one from a phone
+    let mut login_attempts = Vec::new();
+    for _ in 0..2 {
+        let response = app.post_login(&login_body).await;
+
+        assert_eq!(response.status().as_u16(), 206);
+
+        let login_attempt_id = response
+            .json::<TwoFactorAuthResponse>()
+            .await
+            .expect("Could not deserialize response body to TwoFactorAuthResponse")
+            .login_attempt_id;
+
+        let (_, code) = app
+            .two_fa_code_store
+            .get_code(&LoginAttemptId::parse(login_attempt_id.clone()).unwrap())
+            .await
+            .unwrap();
+
+        login_attempts.push((login_attempt_id, code));
+    }
+
+    // both complete, in either order
+    for (login_attempt_id, code) in login_attempts.iter().rev() {
+        let response = app
+            .post_verify_2fa(&serde_json::json!({
+                "email": random_email,
+                "loginAttemptId": login_attempt_id,
+                "2FACode": code.as_ref()
+            }))
+            .await;
+
+        assert_eq!(response.status().as_u16(), 200);
+    }
+}
+
+#[api_test]
+async fn should_return_401_if_login_attempt_belongs_to_another_user() {
+    let mut login_attempts = Vec::new();
+
+    for _ in 0..2 {
+        let random_email = get_random_email();
+
+        let response = app
+            .post_signup(&serde_json::json!({
+                "email": random_email,
+                "password": "password123",
+                "requires2FA": true
+            }))
+            .await;
+
+        assert_eq!(response.status().as_u16(), 201);
+
+        let response = app.verify_email(&random_email).await;
+
+        assert_eq!(response.status().as_u16(), 200);
+
+        let response = app
+            .post_login(&serde_json::json!({
+                "email": random_email,
+                "password": "password123"
+            }))
+            .await;
+
+        assert_eq!(response.status().as_u16(), 206);
+
+        let login_attempt_id = response
+            .json::<TwoFactorAuthResponse>()
+            .await
+            .expect("Could not deserialize response body to TwoFactorAuthResponse")
+            .login_attempt_id;
+
+        login_attempts.push((random_email, login_attempt_id));
+    }
+
+    let (_, code) = app
+        .two_fa_code_store
+        .get_code(&LoginAttemptId::parse(login_attempts[1].1.clone()).unwrap())
+        .await
+        .unwrap();
+
+    // the other user's attempt and code do not log in the first user
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": login_attempts[0].0,
+            "loginAttemptId": login_attempts[1].1,
+            "2FACode": code.as_ref()
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
 #[api_test]
 async fn should_return_401_if_same_code_twice() {
     // remove app creation as it is done in proc attribute macro
@@ -305,6 +433,10 @@ async fn should_return_401_if_same_code_twice() {
 
     assert_eq!(response.status().as_u16(), 201);
 
+    let response = app.verify_email(&random_email).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
     let login_body = serde_json::json!({
         "email": random_email,
         "password": "password123"
@@ -326,9 +458,7 @@ async fn should_return_401_if_same_code_twice() {
 
     let code_tuple = app
         .two_fa_code_store
-        .read()
-        .await
-        .get_code(&Email::parse(random_email.clone()).unwrap())
+        .get_code(&LoginAttemptId::parse(login_attempt_id.clone()).unwrap())
         .await
         .unwrap();
 
@@ -356,6 +486,99 @@ async fn should_return_401_if_same_code_twice() {
     assert_eq!(response.status().as_u16(), 401);
 }
 
+#[api_test]
+async fn should_return_401_after_too_many_wrong_codes() {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": true
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let response = app.verify_email(&random_email).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123"
+    });
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 206);
+
+    let login_attempt_id = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse")
+        .login_attempt_id;
+
+    let code_tuple = app
+        .two_fa_code_store
+        .get_code(&LoginAttemptId::parse(login_attempt_id.clone()).unwrap())
+        .await
+        .unwrap();
+
+    let code = code_tuple.1.as_ref().to_owned();
+    let wrong_code = format!("{:06}", (code.parse::<u32>().unwrap() + 1) % 1_000_000);
+
+    for _ in 0..MAX_TWO_FA_ATTEMPTS {
+        let response = app
+            .post_verify_2fa(&serde_json::json!({
+                "email": random_email,
+                "loginAttemptId": login_attempt_id,
+                "2FACode": wrong_code
+            }))
+            .await;
+
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    // the code was discarded, so even the right one no longer completes the login
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": random_email,
+            "loginAttemptId": login_attempt_id,
+            "2FACode": code
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    // logging in again starts a new attempt with a fresh code
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 206);
+
+    let login_attempt_id = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse")
+        .login_attempt_id;
+
+    let code_tuple = app
+        .two_fa_code_store
+        .get_code(&LoginAttemptId::parse(login_attempt_id.clone()).unwrap())
+        .await
+        .unwrap();
+
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": random_email,
+            "loginAttemptId": login_attempt_id,
+            "2FACode": code_tuple.1.as_ref()
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
 #[api_test]
 async fn should_return_422_if_malformed_input() {
     // remove app creation as it is done in proc attribute macro
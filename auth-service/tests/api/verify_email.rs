@@ -0,0 +1,180 @@
+use crate::helpers::{get_link_token, get_random_email, TestApp};
+use auth_service::{domain::OneTimeToken, ErrorResponse};
+use test_helpers::api_test;
+
+// Pull the verification token out of the link in the most recent verification email
+async fn get_verification_token(app: &TestApp, email: &str) -> String {
+    let sent_email = app
+        .last_email_to(email)
+        .await
+        .expect("No verification email sent");
+
+    assert_eq!(sent_email.subject, "Verify your email");
+
+    get_link_token(&sent_email.content).expect("No token in verification email")
+}
+
+#[api_test]
+async fn should_return_401_if_invalid_token() {
+    let test_cases = [
+        "invalid".to_owned(),
+        // well formed, but never issued
+        OneTimeToken::default().as_ref().to_owned(),
+    ];
+
+    for token in test_cases {
+        let response = app.get_verify_email(&token).await;
+
+        assert_eq!(response.status().as_u16(), 401);
+
+        assert_eq!(
+            response
+                .json::<ErrorResponse>()
+                .await
+                .expect("Could not deserialize response body to ErrorResponse")
+                .error,
+            "InvalidToken".to_owned()
+        );
+    }
+}
+
+#[api_test]
+async fn should_return_403_if_login_before_email_verified() {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+    });
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 403);
+
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "EmailNotVerified".to_owned()
+    );
+}
+
+#[api_test]
+async fn should_verify_email_and_allow_login() {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let token = get_verification_token(&app, &random_email).await;
+
+    let response = app.get_verify_email(&token).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+    });
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[api_test]
+async fn should_return_401_if_verification_token_reused() {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let token = get_verification_token(&app, &random_email).await;
+
+    let response = app.get_verify_email(&token).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.get_verify_email(&token).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[api_test]
+async fn should_resend_verification_email() {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let first_token = get_verification_token(&app, &random_email).await;
+
+    let response = app
+        .post_resend_verification(&serde_json::json!({ "email": random_email }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let second_token = get_verification_token(&app, &random_email).await;
+
+    assert_ne!(first_token, second_token);
+
+    let response = app.get_verify_email(&second_token).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[api_test]
+async fn should_return_200_without_sending_email_if_user_not_found() {
+    let random_email = get_random_email();
+
+    let response = app
+        .post_resend_verification(&serde_json::json!({ "email": random_email }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    assert!(app.last_email_to(&random_email).await.is_none());
+}
+
+#[api_test]
+async fn should_return_400_if_resend_with_invalid_email() {
+    let response = app
+        .post_resend_verification(&serde_json::json!({ "email": "invalid_email" }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
@@ -1,4 +1,7 @@
-use auth_service::{utils::constants::JWT_COOKIE_NAME, ErrorResponse};
+use auth_service::{
+    utils::{auth::session_audience, constants::JWT_COOKIE_NAME},
+    ErrorResponse,
+};
 use test_helpers::api_test;
 
 use crate::helpers::{get_random_email, TestApp};
@@ -19,6 +22,10 @@ async fn should_return_200_valid_token() {
 
     assert_eq!(response.status().as_u16(), 201);
 
+    let response = app.verify_email(&random_email).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
     let login_body = serde_json::json!({
         "email": random_email,
         "password": "password123",
@@ -44,6 +51,25 @@ async fn should_return_200_valid_token() {
     let response = app.post_verify_token(&verify_token_body).await;
 
     assert_eq!(response.status().as_u16(), 200);
+
+    // a consumer naming itself only accepts tokens issued for it
+    let verify_token_body = serde_json::json!({
+        "token": &token,
+        "audience": session_audience(),
+    });
+
+    let response = app.post_verify_token(&verify_token_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let verify_token_body = serde_json::json!({
+        "token": &token,
+        "audience": "some-other-service",
+    });
+
+    let response = app.post_verify_token(&verify_token_body).await;
+
+    assert_eq!(response.status().as_u16(), 401);
 }
 
 #[api_test]
@@ -88,6 +114,10 @@ async fn should_return_401_if_banned_token() {
 
     assert_eq!(response.status().as_u16(), 201);
 
+    let response = app.verify_email(&random_email).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
     let login_body = serde_json::json!({
         "email": random_email,
         "password": "password123",
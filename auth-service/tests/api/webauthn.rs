@@ -0,0 +1,366 @@
+use auth_service::{
+    domain::LoginAttemptId,
+    routes::{CreationOptions, RequestOptions, TwoFactorAuthResponse},
+    utils::constants::{JWT_COOKIE_NAME, WEBAUTHN_ORIGIN, WEBAUTHN_RP_ID},
+    ErrorResponse,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use ciborium::Value;
+use ring::{
+    rand::SystemRandom,
+    signature::{EcdsaKeyPair, KeyPair, ECDSA_P256_SHA256_ASN1_SIGNING},
+};
+use sha2::{Digest, Sha256};
+use test_helpers::api_test;
+
+use crate::helpers::{get_random_email, TestApp};
+
+// A software authenticator holding a single ES256 credential
+struct TestAuthenticator {
+    key_pair: EcdsaKeyPair,
+    credential_id: Vec<u8>,
+    sign_count: u32,
+    origin: String,
+}
+
+// user present and user verified
+const FLAGS_UP_UV: u8 = 0x01 | 0x04;
+const FLAG_ATTESTED_CREDENTIAL_DATA: u8 = 0x40;
+
+impl TestAuthenticator {
+    fn new() -> Self {
+        let rng = SystemRandom::new();
+        let pkcs8 = EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, &rng).unwrap();
+        let key_pair =
+            EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, pkcs8.as_ref(), &rng)
+                .unwrap();
+
+        Self {
+            key_pair,
+            credential_id: uuid::Uuid::new_v4().as_bytes().to_vec(),
+            sign_count: 0,
+            origin: WEBAUTHN_ORIGIN.to_owned(),
+        }
+    }
+
+    fn credential_id(&self) -> String {
+        URL_SAFE_NO_PAD.encode(&self.credential_id)
+    }
+
+    fn client_data(&self, ceremony_type: &str, challenge: &str) -> Vec<u8> {
+        serde_json::to_vec(&serde_json::json!({
+            "type": ceremony_type,
+            "challenge": challenge,
+            "origin": self.origin,
+        }))
+        .unwrap()
+    }
+
+    fn authenticator_data(&self, flags: u8) -> Vec<u8> {
+        let mut data = Sha256::digest(WEBAUTHN_RP_ID.as_bytes()).to_vec();
+        data.push(flags);
+        data.extend_from_slice(&self.sign_count.to_be_bytes());
+        data
+    }
+
+    fn cose_key(&self) -> Vec<u8> {
+        let point = self.key_pair.public_key().as_ref();
+        let key = Value::Map(vec![
+            (Value::from(1), Value::from(2)),
+            (Value::from(3), Value::from(-7)),
+            (Value::from(-1), Value::from(1)),
+            (Value::from(-2), Value::Bytes(point[1..33].to_vec())),
+            (Value::from(-3), Value::Bytes(point[33..].to_vec())),
+        ]);
+        let mut encoded = Vec::new();
+        ciborium::ser::into_writer(&key, &mut encoded).unwrap();
+        encoded
+    }
+
+    // The JSON `navigator.credentials.create()` would produce
+    fn register(&self, options: &CreationOptions) -> serde_json::Value {
+        let mut auth_data = self.authenticator_data(FLAGS_UP_UV | FLAG_ATTESTED_CREDENTIAL_DATA);
+        auth_data.extend_from_slice(&[0u8; 16]);
+        auth_data.extend_from_slice(&(self.credential_id.len() as u16).to_be_bytes());
+        auth_data.extend_from_slice(&self.credential_id);
+        auth_data.extend_from_slice(&self.cose_key());
+
+        let attestation = Value::Map(vec![
+            (Value::from("fmt"), Value::from("none")),
+            (Value::from("attStmt"), Value::Map(vec![])),
+            (Value::from("authData"), Value::Bytes(auth_data)),
+        ]);
+        let mut attestation_object = Vec::new();
+        ciborium::ser::into_writer(&attestation, &mut attestation_object).unwrap();
+
+        serde_json::json!({
+            "id": self.credential_id(),
+            "rawId": self.credential_id(),
+            "type": "public-key",
+            "response": {
+                "clientDataJSON": URL_SAFE_NO_PAD.encode(self.client_data("webauthn.create", &options.challenge)),
+                "attestationObject": URL_SAFE_NO_PAD.encode(attestation_object),
+            }
+        })
+    }
+
+    // The JSON `navigator.credentials.get()` would produce
+    fn sign_in(&mut self, options: &RequestOptions) -> serde_json::Value {
+        self.sign_count += 1;
+
+        let client_data = self.client_data("webauthn.get", &options.challenge);
+        let auth_data = self.authenticator_data(FLAGS_UP_UV);
+
+        let signed_data = [&auth_data[..], &Sha256::digest(&client_data)].concat();
+        let signature = self
+            .key_pair
+            .sign(&SystemRandom::new(), &signed_data)
+            .unwrap();
+
+        serde_json::json!({
+            "id": self.credential_id(),
+            "rawId": self.credential_id(),
+            "type": "public-key",
+            "response": {
+                "clientDataJSON": URL_SAFE_NO_PAD.encode(client_data),
+                "authenticatorData": URL_SAFE_NO_PAD.encode(auth_data),
+                "signature": URL_SAFE_NO_PAD.encode(signature.as_ref()),
+            }
+        })
+    }
+}
+
+// Sign up, verify and log in a user without 2FA so the auth cookie is set
+async fn login_new_user(app: &TestApp, email: &str) {
+    let signup_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let response = app.verify_email(email).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+async fn register(app: &TestApp, authenticator: &TestAuthenticator) {
+    let response = app.post_webauthn_register_start().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let options = response
+        .json::<CreationOptions>()
+        .await
+        .expect("Could not deserialize response body to CreationOptions");
+
+    assert_eq!(options.rp.id, WEBAUTHN_RP_ID.to_owned());
+
+    let response = app
+        .post_webauthn_register_finish(&authenticator.register(&options))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 201);
+}
+
+async fn start_login<Body: serde::Serialize>(app: &TestApp, body: &Body) -> RequestOptions {
+    let response = app.post_webauthn_login_start(body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    response
+        .json::<RequestOptions>()
+        .await
+        .expect("Could not deserialize response body to RequestOptions")
+}
+
+#[api_test]
+async fn should_return_400_if_registering_without_auth_cookie() {
+    let response = app.post_webauthn_register_start().await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "MissingToken".to_owned()
+    );
+}
+
+#[api_test]
+async fn should_use_registered_credential_as_second_factor() {
+    let random_email = get_random_email();
+    login_new_user(&app, &random_email).await;
+
+    let mut authenticator = TestAuthenticator::new();
+    register(&app, &authenticator).await;
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": random_email,
+            "password": "password123",
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 206);
+
+    let response_body = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse");
+
+    assert_eq!(response_body.two_fa_method, "webauthn".to_owned());
+
+    let login_attempt_id = response_body.login_attempt_id;
+
+    // a generated code is never accepted in place of the credential
+    let (_, code) = app
+        .two_fa_code_store
+        .get_code(&LoginAttemptId::parse(login_attempt_id.clone()).unwrap())
+        .await
+        .unwrap();
+
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": random_email,
+            "loginAttemptId": login_attempt_id,
+            "2FACode": code.as_ref()
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    let options = start_login(
+        &app,
+        &serde_json::json!({
+            "email": random_email,
+            "loginAttemptId": login_attempt_id,
+        }),
+    )
+    .await;
+
+    assert_eq!(options.allow_credentials.len(), 1);
+    assert_eq!(
+        options.allow_credentials[0].id,
+        authenticator.credential_id()
+    );
+
+    let response = app
+        .post_webauthn_login_finish(&authenticator.sign_in(&options))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+
+    assert!(!auth_cookie.value().is_empty());
+}
+
+#[api_test]
+async fn should_sign_in_without_password() {
+    let random_email = get_random_email();
+    login_new_user(&app, &random_email).await;
+
+    let mut authenticator = TestAuthenticator::new();
+    register(&app, &authenticator).await;
+
+    let options = start_login(&app, &serde_json::json!({ "email": random_email })).await;
+
+    assert_eq!(options.user_verification, "required".to_owned());
+
+    let credential = authenticator.sign_in(&options);
+
+    let response = app.post_webauthn_login_finish(&credential).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    // the challenge was used up, the same assertion cannot be replayed
+    let response = app.post_webauthn_login_finish(&credential).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[api_test]
+async fn should_return_401_if_sign_count_does_not_increase() {
+    let random_email = get_random_email();
+    login_new_user(&app, &random_email).await;
+
+    let mut authenticator = TestAuthenticator::new();
+    register(&app, &authenticator).await;
+
+    authenticator.sign_count = 10;
+
+    let options = start_login(&app, &serde_json::json!({ "email": random_email })).await;
+    let response = app
+        .post_webauthn_login_finish(&authenticator.sign_in(&options))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    // a clone of the authenticator is behind on the counter
+    authenticator.sign_count = 5;
+
+    let options = start_login(&app, &serde_json::json!({ "email": random_email })).await;
+    let response = app
+        .post_webauthn_login_finish(&authenticator.sign_in(&options))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[api_test]
+async fn should_return_401_if_origin_does_not_match() {
+    let random_email = get_random_email();
+    login_new_user(&app, &random_email).await;
+
+    let mut authenticator = TestAuthenticator::new();
+    register(&app, &authenticator).await;
+
+    // the ceremony ran on a phishing site
+    authenticator.origin = "https://evil.example.com".to_owned();
+
+    let options = start_login(&app, &serde_json::json!({ "email": random_email })).await;
+    let response = app
+        .post_webauthn_login_finish(&authenticator.sign_in(&options))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[api_test]
+async fn should_return_401_if_credential_belongs_to_other_user() {
+    let random_email = get_random_email();
+    login_new_user(&app, &random_email).await;
+
+    let mut authenticator = TestAuthenticator::new();
+    register(&app, &authenticator).await;
+
+    let other_email = get_random_email();
+    login_new_user(&app, &other_email).await;
+
+    // the challenge is issued for the other user, but signed with the first user's credential
+    let options = start_login(&app, &serde_json::json!({ "email": other_email })).await;
+    let response = app
+        .post_webauthn_login_finish(&authenticator.sign_in(&options))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}